@@ -0,0 +1,243 @@
+//! ユースケース層 – 管理者向け入出力 DTO
+
+use crate::domain::{
+  clock_skew::ClockSkew,
+  entity::{session::Session, user_auth::UserAuth},
+  value_obj::public_id::PublicId,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 認証情報のメタデータ (外部 I/F へ返す)
+/// ハッシュ値を保持するフィールドは定義しない。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AuthMetaView {
+  pub login_fail_times: u16,
+  pub is_locked: bool,
+  pub locked_until: Option<DateTime<Utc>>,
+  pub password_history_depth: u8,
+  pub must_change_password: bool,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl From<&UserAuth> for AuthMetaView {
+  fn from(a: &UserAuth) -> Self {
+    Self {
+      login_fail_times: a.login_fail_times,
+      is_locked: a.is_locked(Utc::now()),
+      locked_until: a.locked_until,
+      password_history_depth: a.password_history_depth(),
+      must_change_password: a.must_change_password,
+      created_at: a.created_at,
+      updated_at: a.updated_at,
+    }
+  }
+}
+
+/// ログイン失敗回数リセット結果 (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct UnlockResponse {
+  /// リセット前のログイン失敗回数
+  pub previous_login_fail_times: u16,
+}
+
+/// パスワード変更の要求結果 (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RequirePasswordChangeResponse {
+  /// 無効化したセッションの件数
+  pub sessions_revoked: u64,
+}
+
+/// ランダムアートの照合対象 (外部 I/F から受け取る)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct RandomartVerifyEntry {
+  pub public_id: String,
+  pub expected_art: String,
+}
+
+/// ランダムアートの照合結果 (外部 I/F へ返す)
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RandomartVerifyResult {
+  pub public_id: String,
+  /// 公開IDから再計算した値と一致した場合はtrue
+  pub matches: bool,
+  /// 公開IDが不正な場合の理由
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+/// ステータスの一括変更 (外部 I/F から受け取る)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct BulkStatusRequest {
+  pub public_ids: Vec<String>,
+  /// 変更後のステータス（"active", "suspended"など）
+  pub status: String,
+  /// true := 変更できたユーザーのみ反映する，
+  /// false := 1件でも失敗した場合は全て反映しない（既定）
+  #[serde(default)]
+  pub best_effort: bool,
+}
+
+/// ユーザーごとのステータス変更の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkStatusOutcome {
+  /// 変更した
+  Updated,
+  /// 既に変更後のステータスだった
+  Unchanged,
+  /// 変更できたが，他のユーザーの失敗により反映しなかった
+  RolledBack,
+  /// 変更できなかった
+  Failed,
+}
+
+/// ユーザーごとのステータス変更の結果 (外部 I/F へ返す)
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BulkStatusResult {
+  pub public_id: String,
+  pub outcome: BulkStatusOutcome,
+  /// 変更前のステータス
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub previous_status: Option<&'static str>,
+  /// 無効化したセッションの数
+  pub sessions_revoked: u64,
+  /// 変更できなかった理由
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+/// ステータスの一括変更の結果 (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BulkStatusReport {
+  /// 反映した変更の件数
+  pub updated: usize,
+  /// 変更できなかった件数
+  pub failed: usize,
+  /// 入力と同じ順序の結果
+  pub results: Vec<BulkStatusResult>,
+}
+
+/// セッション一覧の絞り込み条件 (外部 I/F から受け取る)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct SessionListQuery {
+  /// ユーザーの公開ID
+  pub user: Option<String>,
+  /// true := 有効期限内のみ，false := 有効期限切れのみ
+  pub active: Option<bool>,
+  pub limit: Option<u32>,
+  /// 前ページの`next_cursor`
+  pub cursor: Option<String>,
+  /// 次ページの`prev_cursor`（`cursor`とは同時に指定できない）
+  pub before: Option<String>,
+}
+
+/// 一括メンテナンスの実行条件 (外部 I/F から受け取る)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct MaintenanceBatchQuery {
+  /// 1回に走査する件数（省略時は既定値）
+  pub batch_size: Option<u32>,
+}
+
+impl MaintenanceBatchQuery {
+  /// 走査する件数の既定値
+  pub const DEFAULT_BATCH_SIZE: u32 = 500;
+
+  pub fn batch_size(&self) -> u32 {
+    self.batch_size.unwrap_or(Self::DEFAULT_BATCH_SIZE)
+  }
+}
+
+/// セッション (外部 I/F へ返す)
+/// セッションIDは認証に使用できるため，マスクした値のみを返す。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SessionView {
+  pub session_id: String,
+  pub user_public_id: String,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: DateTime<Utc>,
+  pub active: bool,
+  pub user_agent: Option<String>,
+  pub ip: Option<String>,
+}
+
+impl SessionView {
+  /// `now`時点の有効期限で`active`を判定する。(許容する時刻のずれを含める)
+  pub fn new(s: &Session, public_id: &PublicId, now: DateTime<Utc>) -> Self {
+    Self {
+      session_id: s.session_id.masked(),
+      user_public_id: public_id.as_str().to_owned(),
+      created_at: s.created_at,
+      expires_at: s.expires_at,
+      active: !ClockSkew::current().is_expired(s.expires_at, now),
+      user_agent: s.user_agent.clone(),
+      ip: s.ip.map(|ip| ip.to_string()),
+    }
+  }
+}
+
+/// セッション一覧 (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SessionPage {
+  pub sessions: Vec<SessionView>,
+  /// 次のページが存在する場合のカーソル
+  pub next_cursor: Option<String>,
+  /// 前のページが存在する場合のカーソル
+  pub prev_cursor: Option<String>,
+  /// 絞り込み条件に一致する全件数（`X-Total-Count`ヘッダで返す）
+  #[serde(skip)]
+  pub total: i64,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::domain::value_obj::{user_id::UserId, user_password::UserPassword};
+  use crate::utils::hashing::hashing;
+
+  fn user_auth() -> UserAuth {
+    let now = Utc::now();
+    UserAuth {
+      user_id: UserId::from_db(1).unwrap(),
+      current_hash: UserPassword::from_hash(hashing("current").unwrap()).unwrap(),
+      prev_hash1: Some(UserPassword::from_hash(hashing("prev1").unwrap()).unwrap()),
+      prev_hash2: None,
+      login_fail_times: 2,
+      locked_until: Some(now + UserAuth::LOCK_DURATION),
+      must_change_password: false,
+      created_at: now,
+      updated_at: now,
+    }
+  }
+
+  #[test]
+  fn auth_meta_view_excludes_hashes() {
+    let auth = user_auth();
+    let json = serde_json::to_value(AuthMetaView::from(&auth)).unwrap();
+    let obj = json.as_object().unwrap();
+
+    // ハッシュ関連のキー・値が含まれないこと
+    assert!(obj.keys().all(|k| !k.contains("hash")));
+    let raw = json.to_string();
+    assert!(!raw.contains("$argon2"));
+    assert!(!raw.contains(auth.current_hash.as_hash()));
+
+    assert_eq!(obj["login_fail_times"], 2);
+    assert_eq!(obj["is_locked"], true);
+    assert!(obj["locked_until"].is_string());
+    assert_eq!(obj["password_history_depth"], 1);
+  }
+}
@@ -0,0 +1,1061 @@
+//! AdminService
+
+use crate::{
+  application::{
+    admin::dto::{
+      AuthMetaView, BulkStatusOutcome, BulkStatusReport, BulkStatusRequest, BulkStatusResult,
+      RandomartVerifyEntry, RandomartVerifyResult, RequirePasswordChangeResponse, SessionListQuery,
+      SessionPage, SessionView, UnlockResponse,
+    },
+    user::throttle::LoginThrottle,
+  },
+  domain::{
+    entity::{
+      audit_log::{AuditAction, AuditLog},
+      session::Session,
+      user::{User, UserStatus},
+      user_auth::UserAuth,
+    },
+    repository::UserAuthRepository,
+    value_obj::{public_id::PublicId, user_id::UserId},
+  },
+  infra::pg::{
+    audit_log_repo::PgAuditLogRepository,
+    deadline::Deadline,
+    session_repo::{PgSessionRepository, SessionFilter},
+    user_auth_repo::PgUserAuthRepository,
+    user_repo::{PgTx, PgUserRepository},
+  },
+  interfaces::http::error::{AppError, AppResult},
+  utils::randomart::generate_randomart,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::{num::NonZeroUsize, thread};
+
+/// サポート・管理者向けの操作を提供するサービス
+#[derive(Clone)]
+pub struct AdminService {
+  pool: PgPool,
+  user_repo: PgUserRepository,
+  auth_repo: PgUserAuthRepository,
+  audit_repo: PgAuditLogRepository,
+  session_repo: PgSessionRepository,
+  /// ロック解除時にユーザー名単位のカウンタをリセットするログインスロットル
+  login_throttle: Option<LoginThrottle>,
+  /// リクエストの処理期限（トランザクション内のクエリに適用する）
+  deadline: Deadline,
+}
+
+impl AdminService {
+  /// ランダムアートの一括照合で受け付ける最大件数
+  pub const MAX_RANDOMART_VERIFY: usize = 1000;
+  /// ステータスの一括変更で受け付ける最大件数
+  pub const MAX_BULK_STATUS: usize = 500;
+  /// セッション一覧の既定の件数
+  pub const DEFAULT_SESSION_PAGE: u32 = 50;
+  /// セッション一覧で1ページに返す最大件数
+  pub const MAX_SESSION_PAGE: u32 = 200;
+
+  /// コンストラクタ
+  pub fn new(pool: PgPool) -> Self {
+    Self {
+      pool: pool.clone(),
+      user_repo: PgUserRepository::new(pool.clone()),
+      auth_repo: PgUserAuthRepository::new(pool.clone()),
+      audit_repo: PgAuditLogRepository::new(pool.clone()),
+      session_repo: PgSessionRepository::new(pool),
+      login_throttle: None,
+      deadline: Deadline::NONE,
+    }
+  }
+
+  /// ロック解除時にリセットするログインスロットルを設定する
+  /// (ログインで使用するものと同じストアを共有する)
+  pub fn with_login_throttle(mut self, throttle: LoginThrottle) -> Self {
+    self.login_throttle = Some(throttle);
+    self
+  }
+
+  /// リクエストの処理期限を設定する（リクエストごとに複製したサービスに設定する）
+  pub fn with_deadline(mut self, deadline: Deadline) -> Self {
+    self.deadline = deadline;
+    self
+  }
+
+  /// トランザクションを開始し，処理期限までの残り時間を`statement_timeout`に設定する
+  async fn begin(&self) -> AppResult<PgTx<'static>> {
+    let mut tx = self.pool.begin().await.map_err(AppError::from)?;
+    self.deadline.apply_tx(&mut tx).await?;
+    Ok(tx)
+  }
+
+  /// 公開IDで指定したユーザーの認証メタデータを返す
+  /// ハッシュ値は返却しない
+  pub async fn auth_meta(&self, public_id: &PublicId) -> AppResult<AuthMetaView> {
+    let user = self.find_user(public_id).await?;
+    let auth = self.find_auth(user.user_id).await?;
+    Ok(AuthMetaView::from(&auth))
+  }
+
+  /// 公開IDごとにランダムアートを再計算し，期待値と一致するか照合する
+  /// 再計算はCPU負荷が高いため，ブロッキングスレッド上で並列に行う
+  pub async fn verify_randomart(
+    &self,
+    entries: Vec<RandomartVerifyEntry>,
+  ) -> AppResult<Vec<RandomartVerifyResult>> {
+    if entries.len() > Self::MAX_RANDOMART_VERIFY {
+      return Err(AppError::BadRequest(Some(format!(
+        "一度に照合できるのは{}件までです。",
+        Self::MAX_RANDOMART_VERIFY
+      ))));
+    }
+    tokio::task::spawn_blocking(move || verify_randomart_parallel(&entries))
+      .await
+      .map_err(|e| AppError::InternalServerError(Some(format!("Randomart verify failed: {e}"))))
+  }
+
+  /// ユーザー・有効期限の状態で絞り込んだセッションを，作成日時の降順に返す
+  /// 次のページは`next_cursor`を`cursor`に，前のページは`prev_cursor`を`before`に指定して取得する
+  pub async fn sessions(&self, query: SessionListQuery) -> AppResult<SessionPage> {
+    let limit = query.limit.unwrap_or(Self::DEFAULT_SESSION_PAGE);
+    if !(1..=Self::MAX_SESSION_PAGE).contains(&limit) {
+      return Err(AppError::BadRequest(Some(format!(
+        "limitは1以上{}以下で指定してください。",
+        Self::MAX_SESSION_PAGE
+      ))));
+    }
+    let user_id = match query.user {
+      Some(public_id) => {
+        let public_id = PublicId::from_string(&public_id, true)?.ok_or_else(|| {
+          AppError::UnprocessableContent(Some("公開ID(public_id)は必須です。".into()))
+        })?;
+        Some(self.find_user(&public_id).await?.user_id)
+      }
+      None => None,
+    };
+    if query.cursor.is_some() && query.before.is_some() {
+      return Err(AppError::BadRequest(Some(
+        "cursorとbeforeは同時に指定できません。".into(),
+      )));
+    }
+    let after = query.cursor.as_deref().map(decode_cursor).transpose()?;
+    let before = query.before.as_deref().map(decode_cursor).transpose()?;
+    let (forward, backward) = (after.is_some(), before.is_some());
+
+    // 前後のページの有無を判定するため1件多く取得する
+    let now = Utc::now();
+    let filter = SessionFilter {
+      user_id,
+      active: query.active,
+    };
+    let mut rows = self
+      .session_repo
+      .page(&filter, now, after, before, i64::from(limit) + 1)
+      .await?;
+    let has_more = rows.len() > limit as usize;
+    rows.truncate(limit as usize);
+    // beforeを指定した場合は昇順で取得されるため，降順に戻す
+    if backward {
+      rows.reverse();
+    }
+    let cursor_of = |row: Option<&(Session, PublicId, i64)>| {
+      row.map(|(s, _, seq)| encode_cursor(s.created_at, *seq))
+    };
+    let (next_cursor, prev_cursor) = if backward {
+      (
+        cursor_of(rows.last()),
+        cursor_of(rows.first().filter(|_| has_more)),
+      )
+    } else {
+      (
+        cursor_of(rows.last().filter(|_| has_more)),
+        cursor_of(rows.first().filter(|_| forward)),
+      )
+    };
+    let total = self.session_repo.count(&filter, now).await?;
+
+    Ok(SessionPage {
+      sessions: rows
+        .iter()
+        .map(|(s, public_id, _)| SessionView::new(s, public_id, now))
+        .collect(),
+      next_cursor,
+      prev_cursor,
+      total,
+    })
+  }
+
+  /// 公開IDで指定したユーザーのログイン失敗回数をリセットし，ロックを解除する
+  /// 操作は監査ログに記録し，リセット前の失敗回数を返す
+  /// 操作者より権限が強いか同じロールのユーザーのロックは解除できない。
+  /// 認証情報は呼び出し元のトランザクションで行をロックして読み出し，
+  /// 失敗回数の更新と監査ログの記録も同じトランザクションで行う。
+  /// ユーザー名単位のログインスロットルのカウンタもリセットする。
+  pub async fn unlock<'a>(
+    &self,
+    tx: &mut PgTx<'a>,
+    actor: &User,
+    public_id: &PublicId,
+  ) -> AppResult<UnlockResponse> {
+    let user = self
+      .user_repo
+      .find_by_public_id_for_update_tx(tx, public_id)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("ユーザーが見つかりません。".into())))?;
+    if !actor.role.outranks(user.role) {
+      return Err(AppError::Forbidden(Some(
+        "このユーザーのロックを解除する権限がありません。".into(),
+      )));
+    }
+    let mut auth = self
+      .auth_repo
+      .find_for_update_tx(tx, user.user_id)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("認証情報が見つかりません。".into())))?;
+
+    let previous = auth.login_fail_times;
+    auth.clear_login_failures();
+    self.auth_repo.update_tx(tx, &auth).await?;
+
+    self
+      .audit_repo
+      .insert_tx(
+        tx,
+        &AuditLog {
+          actor_user_id: Some(actor.user_id),
+          target_user_id: Some(user.user_id),
+          action: AuditAction::UnlockLogin,
+          detail: Some(format!("login_fail_times: {previous} -> 0")),
+          created_at: Utc::now(),
+        },
+      )
+      .await?;
+    if let Some(throttle) = &self.login_throttle {
+      throttle.reset_user(user.user_name.as_str()).await?;
+    }
+
+    Ok(UnlockResponse {
+      previous_login_fail_times: previous,
+    })
+  }
+
+  /// 公開IDで指定したユーザーに次回ログイン時のパスワード変更を要求する
+  /// 操作者より権限が強いか同じロールのユーザーには要求できない。
+  /// 既存のセッションは全て無効化し，以降のログインではパスワード変更のみに使用できるセッションを発行する。
+  /// 更新と監査ログの記録は，呼び出し元のトランザクションで行う。
+  pub async fn require_password_change<'a>(
+    &self,
+    tx: &mut PgTx<'a>,
+    actor: &User,
+    public_id: &PublicId,
+  ) -> AppResult<RequirePasswordChangeResponse> {
+    let user = self.find_user(public_id).await?;
+    if !actor.role.outranks(user.role) {
+      return Err(AppError::Forbidden(Some(
+        "このユーザーにパスワード変更を要求する権限がありません。".into(),
+      )));
+    }
+    let mut auth = self.find_auth(user.user_id).await?;
+
+    auth.must_change_password = true;
+    self.auth_repo.update_tx(tx, &auth).await?;
+    let sessions_revoked = self
+      .session_repo
+      .delete_by_user_id_tx(tx, user.user_id)
+      .await?;
+
+    self
+      .audit_repo
+      .insert_tx(
+        tx,
+        &AuditLog {
+          actor_user_id: Some(actor.user_id),
+          target_user_id: Some(user.user_id),
+          action: AuditAction::RequirePasswordChange,
+          detail: Some(format!("sessions_revoked: {sessions_revoked}")),
+          created_at: Utc::now(),
+        },
+      )
+      .await?;
+
+    Ok(RequirePasswordChangeResponse { sessions_revoked })
+  }
+
+  /// 公開IDで指定したユーザーのステータスを一括で変更し，ユーザーごとの結果を入力順に返す
+  /// 遷移できないステータスのユーザー・操作者より権限が強いか同じロールのユーザーは変更しない。
+  /// `best_effort`がfalseの場合は1件でも失敗すると全ての変更を反映しない。
+  /// ログインできないステータスにしたユーザーのセッションは全て無効化する。
+  pub async fn bulk_status(
+    &self,
+    actor: &User,
+    request: BulkStatusRequest,
+  ) -> AppResult<BulkStatusReport> {
+    if request.public_ids.len() > Self::MAX_BULK_STATUS {
+      return Err(AppError::BadRequest(Some(format!(
+        "一度に変更できるのは{}件までです。",
+        Self::MAX_BULK_STATUS
+      ))));
+    }
+    let status = UserStatus::parse(&request.status).ok_or_else(|| {
+      AppError::UnprocessableContent(Some(format!(
+        "ステータス(status)の値が正しくありません: {}",
+        request.status
+      )))
+    })?;
+
+    let mut results = Vec::with_capacity(request.public_ids.len());
+    let mut tx = self.begin().await?;
+    for public_id in &request.public_ids {
+      results.push(self.apply_status(&mut tx, actor, public_id, status).await?);
+      // best_effortの場合は1件ごとに反映する
+      if request.best_effort {
+        let done = std::mem::replace(&mut tx, self.begin().await?);
+        done.commit().await.map_err(AppError::from)?;
+      }
+    }
+
+    let failed = results
+      .iter()
+      .filter(|r| r.outcome == BulkStatusOutcome::Failed)
+      .count();
+    if failed > 0 && !request.best_effort {
+      tx.rollback().await.map_err(AppError::from)?;
+      for r in results
+        .iter_mut()
+        .filter(|r| r.outcome == BulkStatusOutcome::Updated)
+      {
+        r.outcome = BulkStatusOutcome::RolledBack;
+        r.sessions_revoked = 0;
+      }
+    } else {
+      tx.commit().await.map_err(AppError::from)?;
+    }
+
+    Ok(BulkStatusReport {
+      updated: results
+        .iter()
+        .filter(|r| r.outcome == BulkStatusOutcome::Updated)
+        .count(),
+      failed,
+      results,
+    })
+  }
+
+  /* 内部関数  */
+
+  /// トランザクション内で1件のステータスを変更する
+  /// 変更できない場合はFailedの結果を返し，DBのエラーのみErrを返す
+  async fn apply_status(
+    &self,
+    tx: &mut PgTx<'static>,
+    actor: &User,
+    raw_public_id: &str,
+    status: UserStatus,
+  ) -> AppResult<BulkStatusResult> {
+    let mut result = BulkStatusResult {
+      public_id: raw_public_id.to_owned(),
+      outcome: BulkStatusOutcome::Failed,
+      previous_status: None,
+      sessions_revoked: 0,
+      error: None,
+    };
+    let public_id = match PublicId::from_string(raw_public_id, true) {
+      Ok(Some(public_id)) => public_id,
+      Ok(None) => {
+        result.error = Some("公開IDは必須です。".to_owned());
+        return Ok(result);
+      }
+      Err(_) => {
+        result.error = Some("公開IDの形式が正しくありません。".to_owned());
+        return Ok(result);
+      }
+    };
+    let Some(mut user) = self
+      .user_repo
+      .find_by_public_id_for_update_tx(tx, &public_id)
+      .await?
+    else {
+      result.error = Some("ユーザーが見つかりません。".to_owned());
+      return Ok(result);
+    };
+    let previous = user.status;
+    result.previous_status = Some(previous.as_str());
+
+    if !actor.role.outranks(user.role) {
+      result.error = Some("このユーザーのステータスを変更する権限がありません。".to_owned());
+      return Ok(result);
+    }
+    if previous == status {
+      result.outcome = BulkStatusOutcome::Unchanged;
+      return Ok(result);
+    }
+    if !previous.can_transition_to(status) {
+      result.error = Some(format!(
+        "ステータスを{}から{}に変更することはできません。",
+        previous.as_str(),
+        status.as_str()
+      ));
+      return Ok(result);
+    }
+
+    user.status = status;
+    self.user_repo.update_status_tx(tx, &user).await?;
+    if status.can_login().is_err() {
+      result.sessions_revoked = self
+        .session_repo
+        .delete_by_user_id_tx(tx, user.user_id)
+        .await?;
+    }
+    self
+      .audit_repo
+      .insert_tx(
+        tx,
+        &AuditLog {
+          actor_user_id: Some(actor.user_id),
+          target_user_id: Some(user.user_id),
+          action: AuditAction::ChangeStatus,
+          detail: Some(format!(
+            "status: {} -> {}",
+            previous.as_str(),
+            status.as_str()
+          )),
+          created_at: Utc::now(),
+        },
+      )
+      .await?;
+    result.outcome = BulkStatusOutcome::Updated;
+    Ok(result)
+  }
+
+  /// 公開IDでユーザーを取得する。存在しない場合は404
+  async fn find_user(&self, public_id: &PublicId) -> AppResult<User> {
+    self
+      .user_repo
+      .find_by_public_id(public_id)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("ユーザーが見つかりません。".into())))
+  }
+
+  /// ユーザーIDで認証情報を取得する。存在しない場合は404
+  async fn find_auth(&self, user_id: UserId) -> AppResult<UserAuth> {
+    self
+      .auth_repo
+      .find(user_id)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("認証情報が見つかりません。".into())))
+  }
+}
+
+/// セッション一覧のカーソルを生成する。(`<created_atのUNIXマイクロ秒>_<seq>`)
+/// session_idはBearerトークンのため，カーソルには含めない。
+fn encode_cursor(created_at: DateTime<Utc>, seq: i64) -> String {
+  format!("{}_{}", created_at.timestamp_micros(), seq)
+}
+
+/// セッション一覧のカーソルを解析する。不正な場合は400
+fn decode_cursor(cursor: &str) -> AppResult<(DateTime<Utc>, i64)> {
+  let invalid = || AppError::BadRequest(Some("cursorの形式が正しくありません。".into()));
+  let (micros, seq) = cursor.split_once('_').ok_or_else(invalid)?;
+  let created_at = micros
+    .parse()
+    .ok()
+    .and_then(DateTime::from_timestamp_micros)
+    .ok_or_else(invalid)?;
+  let seq = seq.parse().map_err(|_| invalid())?;
+  Ok((created_at, seq))
+}
+
+/// 照合対象を利用可能なスレッド数に分割して照合する。(結果は入力と同じ順序)
+fn verify_randomart_parallel(entries: &[RandomartVerifyEntry]) -> Vec<RandomartVerifyResult> {
+  let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+  let chunk = entries.len().div_ceil(threads).max(1);
+  thread::scope(|scope| {
+    let handles: Vec<_> = entries
+      .chunks(chunk)
+      .map(|c| scope.spawn(|| c.iter().map(verify_one).collect::<Vec<_>>()))
+      .collect();
+    handles
+      .into_iter()
+      // ワーカーのパニックはspawn_blockingのエラーとして呼び出し元に伝える
+      .flat_map(|h| h.join().expect("randomart worker panicked"))
+      .collect()
+  })
+}
+
+fn verify_one(entry: &RandomartVerifyEntry) -> RandomartVerifyResult {
+  let (matches, error) = match PublicId::from_string(&entry.public_id, true) {
+    Ok(Some(public_id)) => (generate_randomart(&public_id) == entry.expected_art, None),
+    Ok(None) => (false, Some("公開IDは必須です。".to_owned())),
+    Err(_) => (false, Some("公開IDの形式が正しくありません。".to_owned())),
+  };
+  RandomartVerifyResult {
+    public_id: entry.public_id.clone(),
+    matches,
+    error,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    config::AppConfig,
+    domain::{
+      clock_skew::ClockSkew,
+      entity::{
+        session::{Session, SessionScope},
+        user::{UserRole, UserStatus},
+      },
+      repository::AuditLogRepository,
+      value_obj::session_id::SessionId,
+    },
+    infra::memory::rate_limit_store::MemoryRateLimitStore,
+    test_support::seed_user,
+  };
+  use chrono::Duration;
+  use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+  };
+
+  /// `age_min`分前に作成し，`ttl_min`分後に失効する(負の場合は失効済み)セッションを登録する
+  async fn seed_session(pool: &PgPool, user_id: UserId, age_min: i64, ttl_min: i64) -> Session {
+    let now = Utc::now();
+    let session = Session {
+      session_id: SessionId::new(),
+      user_id,
+      created_at: now - Duration::minutes(age_min),
+      expires_at: now + Duration::minutes(ttl_min),
+      user_agent: None,
+      ip: None,
+      scope: SessionScope::Full,
+    };
+    PgSessionRepository::new(pool.clone())
+      .insert(&session)
+      .await
+      .unwrap();
+    session
+  }
+
+  fn query(
+    user: &User,
+    active: Option<bool>,
+    limit: u32,
+    cursor: Option<String>,
+  ) -> SessionListQuery {
+    SessionListQuery {
+      user: Some(user.public_id.as_str().to_owned()),
+      active,
+      limit: Some(limit),
+      cursor,
+      before: None,
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn sessions_filter_by_active_state(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "session_user", UserStatus::Active, UserRole::User).await;
+    let (other, _) = seed_user(&pool, "other_user", UserStatus::Active, UserRole::User).await;
+    let active = seed_session(&pool, user.user_id, 1, 60).await;
+    let expired = seed_session(&pool, user.user_id, 120, -60).await;
+    seed_session(&pool, other.user_id, 1, 60).await;
+    let svc = AdminService::new(pool);
+
+    let page = svc
+      .sessions(query(&user, Some(true), 50, None))
+      .await
+      .unwrap();
+    assert_eq!(page.sessions.len(), 1);
+    assert_eq!(page.sessions[0].session_id, active.session_id.masked());
+    assert!(page.sessions[0].active);
+    assert_eq!(page.sessions[0].user_public_id, user.public_id.as_str());
+
+    let page = svc
+      .sessions(query(&user, Some(false), 50, None))
+      .await
+      .unwrap();
+    assert_eq!(page.sessions.len(), 1);
+    assert_eq!(page.sessions[0].session_id, expired.session_id.masked());
+    assert!(!page.sessions[0].active);
+
+    let page = svc.sessions(query(&user, None, 50, None)).await.unwrap();
+    assert_eq!(page.sessions.len(), 2);
+    // トークンの全体は返さない
+    let raw = serde_json::to_string(&page).unwrap();
+    assert!(!raw.contains(&active.session_id.to_string()));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn sessions_within_clock_skew_are_active(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "skew_user", UserStatus::Active, UserRole::User).await;
+    // 有効期限を過ぎていても，許容する時刻のずれの範囲内のセッションは参照時に有効と扱われる
+    let now = Utc::now();
+    let session = Session {
+      session_id: SessionId::new(),
+      user_id: user.user_id,
+      created_at: now - Duration::minutes(1),
+      expires_at: now - ClockSkew::current().leeway / 2,
+      user_agent: None,
+      ip: None,
+      scope: SessionScope::Full,
+    };
+    PgSessionRepository::new(pool.clone())
+      .insert(&session)
+      .await
+      .unwrap();
+    let svc = AdminService::new(pool);
+
+    let page = svc
+      .sessions(query(&user, Some(true), 50, None))
+      .await
+      .unwrap();
+    assert_eq!(page.total, 1);
+    assert!(page.sessions[0].active);
+    let page = svc
+      .sessions(query(&user, Some(false), 50, None))
+      .await
+      .unwrap();
+    assert_eq!(page.total, 0);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn sessions_paginate_with_cursor(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "paged_user", UserStatus::Active, UserRole::User).await;
+    let mut seeded = Vec::new();
+    for age in 0..5 {
+      seeded.push(seed_session(&pool, user.user_id, age, 60).await);
+    }
+    let svc = AdminService::new(pool);
+
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    loop {
+      let page = svc.sessions(query(&user, None, 2, cursor)).await.unwrap();
+      assert!(page.sessions.len() <= 2);
+      seen.extend(page.sessions.into_iter().map(|s| s.session_id));
+      cursor = page.next_cursor;
+      if cursor.is_none() {
+        break;
+      }
+    }
+    // 作成日時の降順に，重複・欠落なく全件を返す
+    let expected: Vec<_> = seeded.iter().map(|s| s.session_id.masked()).collect();
+    assert_eq!(seen, expected);
+
+    let err = svc
+      .sessions(query(&user, None, 2, Some("invalid".into())))
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::BadRequest(_)));
+    let err = svc.sessions(query(&user, None, 0, None)).await.unwrap_err();
+    assert!(matches!(err, AppError::BadRequest(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn sessions_paginate_backward_with_before(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "prev_user", UserStatus::Active, UserRole::User).await;
+    for age in 0..5 {
+      seed_session(&pool, user.user_id, age, 60).await;
+    }
+    let svc = AdminService::new(pool);
+    let ids = |page: &SessionPage| -> Vec<String> {
+      page.sessions.iter().map(|s| s.session_id.clone()).collect()
+    };
+
+    let first = svc.sessions(query(&user, None, 2, None)).await.unwrap();
+    assert_eq!(first.total, 5);
+    assert!(first.prev_cursor.is_none());
+    let second = svc
+      .sessions(query(&user, None, 2, first.next_cursor.clone()))
+      .await
+      .unwrap();
+    assert_eq!(second.total, 5);
+
+    // 前のページに戻ると，同じ順序で同じ内容を返す
+    let mut back = query(&user, None, 2, None);
+    back.before = second.prev_cursor.clone();
+    let back = svc.sessions(back).await.unwrap();
+    assert_eq!(ids(&back), ids(&first));
+    assert!(back.prev_cursor.is_none());
+    assert_eq!(back.next_cursor, first.next_cursor);
+
+    let mut both = query(&user, None, 2, first.next_cursor);
+    both.before = second.prev_cursor;
+    let err = svc.sessions(both).await.unwrap_err();
+    assert!(matches!(err, AppError::BadRequest(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn unlock_resets_fail_count_and_writes_audit(pool: PgPool) {
+    let (support, _) = seed_user(&pool, "support", UserStatus::Active, UserRole::Support).await;
+    let (user, mut auth) =
+      seed_user(&pool, "locked_user", UserStatus::Active, UserRole::User).await;
+
+    // ロック状態にする
+    let auth_repo = PgUserAuthRepository::new(pool.clone());
+    auth.login_fail_times = 7;
+    auth_repo.update(&auth).await.unwrap();
+
+    let svc = AdminService::new(pool.clone());
+    let mut tx = pool.begin().await.unwrap();
+    let res = svc
+      .unlock(&mut tx, &support, &user.public_id)
+      .await
+      .unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!(res.previous_login_fail_times, 7);
+
+    // 失敗回数がリセットされていること
+    let auth = auth_repo.find(user.user_id).await.unwrap().unwrap();
+    assert_eq!(auth.login_fail_times, 0);
+    assert!(!auth.is_locked(Utc::now()));
+
+    // 監査ログが記録されていること
+    let logs = PgAuditLogRepository::new(pool)
+      .find_by_target(user.user_id)
+      .await
+      .unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].action, AuditAction::UnlockLogin);
+    assert_eq!(logs[0].actor_user_id, Some(support.user_id));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn unlock_requires_outranking_actor(pool: PgPool) {
+    let (support, _) = seed_user(
+      &pool,
+      "unlock_support",
+      UserStatus::Active,
+      UserRole::Support,
+    )
+    .await;
+    let (peer, _) = seed_user(&pool, "unlock_peer", UserStatus::Active, UserRole::Support).await;
+    let (admin, _) = seed_user(&pool, "unlock_admin", UserStatus::Active, UserRole::Admin).await;
+    let svc = AdminService::new(pool.clone());
+
+    for target in [&peer, &admin] {
+      let mut tx = pool.begin().await.unwrap();
+      let err = svc
+        .unlock(&mut tx, &support, &target.public_id)
+        .await
+        .unwrap_err();
+      assert!(matches!(err, AppError::Forbidden(_)), "{err:?}");
+    }
+    let logs = PgAuditLogRepository::new(pool)
+      .find_by_target(admin.user_id)
+      .await
+      .unwrap();
+    assert!(logs.is_empty());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn unlock_holds_auth_row_lock_until_commit(pool: PgPool) {
+    let (support, _) = seed_user(&pool, "row_support", UserStatus::Active, UserRole::Support).await;
+    let (user, _) = seed_user(&pool, "row_user", UserStatus::Active, UserRole::User).await;
+    let svc = AdminService::new(pool.clone());
+    // 並行するログイン失敗の記録は，ロック解除のコミットまで待機させる
+    let try_lock = || {
+      sqlx::query("SELECT 1 FROM user_auths WHERE user_id = $1 FOR UPDATE NOWAIT")
+        .bind(user.user_id.as_i64())
+        .execute(&pool)
+    };
+
+    let mut tx = pool.begin().await.unwrap();
+    svc
+      .unlock(&mut tx, &support, &user.public_id)
+      .await
+      .unwrap();
+    assert!(try_lock().await.is_err());
+    tx.commit().await.unwrap();
+    assert!(try_lock().await.is_ok());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn unlock_resets_login_throttle_for_user(pool: PgPool) {
+    let (support, _) = seed_user(
+      &pool,
+      "throttle_support",
+      UserStatus::Active,
+      UserRole::Support,
+    )
+    .await;
+    let (user, _) = seed_user(&pool, "throttled_user", UserStatus::Active, UserRole::User).await;
+    let mut config = AppConfig::new().unwrap().rate_limit;
+    config.login_user_max_attempts = 2;
+    let throttle = LoginThrottle::new(&config, Arc::new(MemoryRateLimitStore::new()));
+    let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10));
+    for _ in 0..config.login_user_max_attempts {
+      throttle.record_failure(ip, "throttled_user").await.unwrap();
+    }
+    let other_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 11));
+    let err = throttle
+      .check(other_ip, "throttled_user")
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::TooManyRequests(_)));
+
+    let svc = AdminService::new(pool.clone()).with_login_throttle(throttle.clone());
+    let mut tx = pool.begin().await.unwrap();
+    svc
+      .unlock(&mut tx, &support, &user.public_id)
+      .await
+      .unwrap();
+    tx.commit().await.unwrap();
+    throttle.check(other_ip, "throttled_user").await.unwrap();
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn require_password_change_sets_flag_and_revokes_sessions(pool: PgPool) {
+    let (support, _) = seed_user(&pool, "pwd_support", UserStatus::Active, UserRole::Support).await;
+    let (peer, _) = seed_user(&pool, "pwd_peer", UserStatus::Active, UserRole::Support).await;
+    let (user, _) = seed_user(&pool, "pwd_user", UserStatus::Active, UserRole::User).await;
+    seed_session(&pool, user.user_id, 10, 60).await;
+    seed_session(&pool, user.user_id, 5, 60).await;
+    let svc = AdminService::new(pool.clone());
+    let auth_repo = PgUserAuthRepository::new(pool.clone());
+
+    // 同じロールのユーザーには要求できない
+    let mut tx = pool.begin().await.unwrap();
+    let err = svc
+      .require_password_change(&mut tx, &support, &peer.public_id)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::Forbidden(_)));
+    tx.rollback().await.unwrap();
+    let peer_auth = auth_repo.find(peer.user_id).await.unwrap().unwrap();
+    assert!(!peer_auth.must_change_password);
+
+    let mut tx = pool.begin().await.unwrap();
+    let res = svc
+      .require_password_change(&mut tx, &support, &user.public_id)
+      .await
+      .unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!(res.sessions_revoked, 2);
+
+    let auth = auth_repo.find(user.user_id).await.unwrap().unwrap();
+    assert!(auth.must_change_password);
+    let sessions = PgSessionRepository::new(pool.clone())
+      .find_by_user_id(user.user_id)
+      .await
+      .unwrap();
+    assert!(sessions.is_empty());
+    let logs = PgAuditLogRepository::new(pool)
+      .find_by_target(user.user_id)
+      .await
+      .unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].action, AuditAction::RequirePasswordChange);
+    assert_eq!(logs[0].actor_user_id, Some(support.user_id));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn verify_randomart_reports_each_entry(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "art_owner", UserStatus::Active, UserRole::User).await;
+    let other = PublicId::new();
+    let entry = |public_id: &str, expected_art: &str| RandomartVerifyEntry {
+      public_id: public_id.into(),
+      expected_art: expected_art.into(),
+    };
+    let entries = vec![
+      entry(user.public_id.as_str(), &user.randomart),
+      entry(user.public_id.as_str(), &generate_randomart(&other)),
+      entry("", ""),
+    ];
+
+    let results = AdminService::new(pool)
+      .verify_randomart(entries)
+      .await
+      .unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results[0].matches);
+    assert_eq!(results[0].public_id, user.public_id.as_str());
+    // 改ざんされた値は一致しない
+    assert!(!results[1].matches);
+    assert_eq!(results[1].error, None);
+    assert!(!results[2].matches && results[2].error.is_some());
+  }
+
+  #[test]
+  fn parallel_verify_keeps_input_order() {
+    let ids: Vec<PublicId> = (0..50).map(|_| PublicId::new()).collect();
+    let entries: Vec<_> = ids
+      .iter()
+      .enumerate()
+      .map(|(i, id)| RandomartVerifyEntry {
+        public_id: id.as_str().into(),
+        // 偶数番目のみ正しい値
+        expected_art: if i % 2 == 0 {
+          generate_randomart(id)
+        } else {
+          String::new()
+        },
+      })
+      .collect();
+    let results = verify_randomart_parallel(&entries);
+    for (i, (r, id)) in results.iter().zip(&ids).enumerate() {
+      assert_eq!(r.public_id, id.as_str());
+      assert_eq!(r.matches, i % 2 == 0);
+    }
+  }
+
+  /// 一括変更の対象(変更可能2件・遷移不可1件・権限不足1件)を登録する
+  async fn seed_bulk_targets(pool: &PgPool) -> (User, Vec<User>) {
+    let (actor, _) = seed_user(pool, "bulk_mod", UserStatus::Active, UserRole::Moderator).await;
+    let mut targets = Vec::new();
+    for (name, status, role) in [
+      ("bulk_active", UserStatus::Active, UserRole::User),
+      ("bulk_deact", UserStatus::Deactivated, UserRole::User),
+      ("bulk_pending", UserStatus::Pending, UserRole::User),
+      ("bulk_peer", UserStatus::Active, UserRole::Moderator),
+    ] {
+      let (user, _) = seed_user(pool, name, status, role).await;
+      seed_session(pool, user.user_id, 1, 60).await;
+      targets.push(user);
+    }
+    (actor, targets)
+  }
+
+  fn bulk_request(targets: &[User], best_effort: bool) -> BulkStatusRequest {
+    BulkStatusRequest {
+      public_ids: targets
+        .iter()
+        .map(|u| u.public_id.as_str().to_owned())
+        .chain(["not-a-public-id".to_owned()])
+        .collect(),
+      status: "suspended".into(),
+      best_effort,
+    }
+  }
+
+  async fn status_of(pool: &PgPool, user: &User) -> UserStatus {
+    PgUserRepository::new(pool.clone())
+      .find_by_public_id(&user.public_id)
+      .await
+      .unwrap()
+      .unwrap()
+      .status
+  }
+
+  async fn session_count(pool: &PgPool, user: &User) -> usize {
+    PgSessionRepository::new(pool.clone())
+      .find_by_user_id(user.user_id)
+      .await
+      .unwrap()
+      .len()
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn bulk_status_rolls_back_mixed_batch(pool: PgPool) {
+    let (actor, targets) = seed_bulk_targets(&pool).await;
+    let svc = AdminService::new(pool.clone());
+
+    let report = svc
+      .bulk_status(&actor, bulk_request(&targets, false))
+      .await
+      .unwrap();
+    let outcomes: Vec<_> = report.results.iter().map(|r| r.outcome).collect();
+    assert_eq!(
+      outcomes,
+      [
+        BulkStatusOutcome::RolledBack,
+        BulkStatusOutcome::RolledBack,
+        BulkStatusOutcome::Failed,
+        BulkStatusOutcome::Failed,
+        BulkStatusOutcome::Failed,
+      ]
+    );
+    assert_eq!((report.updated, report.failed), (0, 3));
+    assert_eq!(report.results[2].previous_status, Some("pending"));
+    assert!(report.results[2].error.is_some());
+
+    // 全ての変更・セッションの無効化が取り消される
+    for (user, status) in targets.iter().zip([
+      UserStatus::Active,
+      UserStatus::Deactivated,
+      UserStatus::Pending,
+      UserStatus::Active,
+    ]) {
+      assert_eq!(status_of(&pool, user).await, status);
+      assert_eq!(session_count(&pool, user).await, 1);
+    }
+    let logs = svc
+      .audit_repo
+      .find_by_target(targets[0].user_id)
+      .await
+      .unwrap();
+    assert!(logs.is_empty());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn bulk_status_best_effort_applies_valid_rows(pool: PgPool) {
+    let (actor, targets) = seed_bulk_targets(&pool).await;
+    let svc = AdminService::new(pool.clone());
+
+    let report = svc
+      .bulk_status(&actor, bulk_request(&targets, true))
+      .await
+      .unwrap();
+    assert_eq!((report.updated, report.failed), (2, 3));
+    assert_eq!(report.results[0].outcome, BulkStatusOutcome::Updated);
+    assert_eq!(report.results[0].previous_status, Some("active"));
+    assert_eq!(report.results[0].sessions_revoked, 1);
+    // 同じロールのユーザーは変更できない
+    assert_eq!(report.results[3].outcome, BulkStatusOutcome::Failed);
+
+    for user in &targets[..2] {
+      assert_eq!(status_of(&pool, user).await, UserStatus::Suspended);
+      assert_eq!(session_count(&pool, user).await, 0);
+    }
+    for user in &targets[2..] {
+      assert_eq!(session_count(&pool, user).await, 1);
+    }
+    let logs = svc
+      .audit_repo
+      .find_by_target(targets[0].user_id)
+      .await
+      .unwrap();
+    assert_eq!(logs[0].action, AuditAction::ChangeStatus);
+    assert_eq!(
+      logs[0].detail.as_deref(),
+      Some("status: active -> suspended")
+    );
+
+    // 既に変更後のステータスの場合はUnchanged
+    let report = svc
+      .bulk_status(&actor, bulk_request(&targets[..1], true))
+      .await
+      .unwrap();
+    assert_eq!(report.results[0].outcome, BulkStatusOutcome::Unchanged);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn bulk_status_rejects_unknown_status(pool: PgPool) {
+    let (actor, _) = seed_user(&pool, "bulk_mod", UserStatus::Active, UserRole::Moderator).await;
+    let request = BulkStatusRequest {
+      public_ids: vec![],
+      status: "banned".into(),
+      best_effort: false,
+    };
+    let err = AdminService::new(pool)
+      .bulk_status(&actor, request)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::UnprocessableContent(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn bulk_status_respects_request_deadline(pool: PgPool) {
+    let (actor, targets) = seed_bulk_targets(&pool).await;
+    let svc =
+      AdminService::new(pool.clone()).with_deadline(Deadline::after(std::time::Duration::ZERO));
+
+    let err = svc
+      .bulk_status(&actor, bulk_request(&targets, false))
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::RequestTimeout(_)));
+    assert_eq!(status_of(&pool, &targets[0]).await, UserStatus::Active);
+  }
+}
@@ -0,0 +1,522 @@
+//! MaintenanceService
+//! --------------------------------------------------------------
+//! ・定期的に実行する後片付け処理をまとめる
+//! ・保持期間を過ぎたセッション・監査ログの個人情報を消去する（行は残し，件数の集計は維持する）
+//! ・期限切れのレート制限カウンタ・登録フォームのトークン・登録のクールダウン・
+//!   メールアドレス確認のトークンを削除する
+//! ・期限切れのセッションは[maintenance].purge_expired_sessionsがtrueの場合のみ削除する
+//! ・ランダムアートを現在のアルゴリズムで一括再生成する（管理者のAPIから手動で実行する）
+//! ・メールアドレスの正規形を現在の方式で一括再計算する（strip_plus_addressingの変更後に管理者のAPIから実行する）
+//! --------------------------------------------------------------
+
+use crate::{
+  config::Maintenance,
+  infra::pg::{
+    audit_log_repo::PgAuditLogRepository, form_nonce_store::PgFormNonceStore,
+    rate_limit_store::PgRateLimitStore, registration_cooldown_store::PgRegistrationCooldownStore,
+    session_repo::PgSessionRepository, user_repo::PgUserRepository,
+    verification_token_store::PgVerificationTokenStore,
+  },
+  interfaces::http::error::{AppError, AppResult},
+  utils::randomart::generate_randomart,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tracing as log;
+
+/// 1回の後片付けで処理した件数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CleanupReport {
+  pub sessions_scrubbed: u64,
+  pub audit_logs_scrubbed: u64,
+  pub sessions_purged: u64,
+  pub rate_limit_buckets_purged: u64,
+  pub form_nonces_purged: u64,
+  pub registration_cooldowns_purged: u64,
+  pub verification_tokens_purged: u64,
+}
+
+/// ランダムアートの一括再生成で処理した件数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RandomartReport {
+  /// 走査したユーザー数
+  pub scanned: u64,
+  /// 保存値が古く，更新したユーザー数
+  pub updated: u64,
+}
+
+/// メールアドレスの正規形の一括再計算で処理した件数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct EmailCanonicalReport {
+  /// 走査したユーザー数
+  pub scanned: u64,
+  /// 正規形が古く，更新したユーザー数
+  pub updated: u64,
+  /// 他のユーザーと正規形が重複するため，更新しなかったユーザー数
+  pub conflicts: u64,
+}
+
+/// 定期メンテナンスを提供するサービス
+#[derive(Clone)]
+pub struct MaintenanceService {
+  pool: PgPool,
+  user_repo: PgUserRepository,
+  session_repo: PgSessionRepository,
+  audit_repo: PgAuditLogRepository,
+  rate_limit_store: PgRateLimitStore,
+  nonce_store: PgFormNonceStore,
+  cooldown_store: PgRegistrationCooldownStore,
+  verification_store: PgVerificationTokenStore,
+  /// 個人情報の保持期間（Noneの場合は消去しない）
+  pii_retention: Option<Duration>,
+  /// 期限切れのセッションの行を削除するか
+  purge_expired_sessions: bool,
+  interval: std::time::Duration,
+}
+
+impl MaintenanceService {
+  /// Configの[maintenance]から生成する。
+  pub fn new(pool: PgPool, config: &Maintenance) -> Self {
+    Self {
+      pool: pool.clone(),
+      user_repo: PgUserRepository::new(pool.clone()),
+      session_repo: PgSessionRepository::new(pool.clone()),
+      audit_repo: PgAuditLogRepository::new(pool.clone()),
+      rate_limit_store: PgRateLimitStore::new(pool.clone()),
+      nonce_store: PgFormNonceStore::new(pool.clone()),
+      cooldown_store: PgRegistrationCooldownStore::new(pool.clone()),
+      verification_store: PgVerificationTokenStore::new(pool),
+      pii_retention: (config.pii_retention_days > 0)
+        .then(|| Duration::days(i64::from(config.pii_retention_days))),
+      purge_expired_sessions: config.purge_expired_sessions,
+      interval: std::time::Duration::from_secs(config.interval_secs.max(1)),
+    }
+  }
+
+  /// `now`を基準に後片付けを1回実行する。
+  pub async fn cleanup(&self, now: DateTime<Utc>) -> AppResult<CleanupReport> {
+    let mut report = CleanupReport::default();
+    if let Some(retention) = self.pii_retention {
+      let cutoff = now - retention;
+      report.sessions_scrubbed = self.session_repo.scrub_metadata_before(cutoff).await?;
+      report.audit_logs_scrubbed = self.audit_repo.scrub_detail_before(cutoff).await?;
+    }
+    if self.purge_expired_sessions {
+      report.sessions_purged = self.session_repo.purge_expired().await?;
+    }
+    report.rate_limit_buckets_purged = self.rate_limit_store.purge_expired(now).await?;
+    report.form_nonces_purged = self.nonce_store.purge_expired(now).await?;
+    report.registration_cooldowns_purged = self.cooldown_store.purge_expired(now).await?;
+    report.verification_tokens_purged = self.verification_store.purge_expired(now).await?;
+    Ok(report)
+  }
+
+  /// 保存済みのランダムアートを現在のアルゴリズムの出力で置き換える。
+  /// `batch_size`件ずつuser_idの順に走査し，バッチごとに1つのトランザクションで更新する。
+  /// (途中で失敗した場合も，完了したバッチの更新は残る)
+  pub async fn regenerate_all_randomart(&self, batch_size: u32) -> AppResult<RandomartReport> {
+    if batch_size == 0 {
+      return Err(AppError::BadRequest(Some(
+        "batch_sizeは1以上である必要があります。".into(),
+      )));
+    }
+
+    let mut report = RandomartReport::default();
+    let mut after = 0;
+    loop {
+      let page = self
+        .user_repo
+        .page_randomart(after, i64::from(batch_size))
+        .await?;
+      let Some((last, _, _)) = page.last() else {
+        break;
+      };
+      after = last.as_i64();
+
+      let mut tx = self.pool.begin().await.map_err(AppError::from)?;
+      for (user_id, public_id, stored) in &page {
+        let current = generate_randomart(public_id);
+        // 走査後に公開IDが再発行された場合は，再発行時に生成済みのため更新しない
+        if *stored != current
+          && self
+            .user_repo
+            .update_randomart_tx(&mut tx, *user_id, public_id, &current)
+            .await?
+        {
+          report.updated += 1;
+        }
+      }
+      tx.commit().await.map_err(AppError::from)?;
+
+      report.scanned += page.len() as u64;
+      log::info!(
+        scanned = report.scanned,
+        updated = report.updated,
+        "Randomart regeneration in progress"
+      );
+    }
+    Ok(report)
+  }
+
+  /// 保存済みのメールアドレスの正規形を，現在の方式([validation].strip_plus_addressing)で
+  /// 再計算する。`batch_size`件ずつuser_idの順に走査し，1件ずつ更新する。
+  /// 他のユーザーと重複する場合は更新せずに件数を数え，ログに出力する。(重複の解消は手動で行う)
+  pub async fn recanonicalize_emails(&self, batch_size: u32) -> AppResult<EmailCanonicalReport> {
+    if batch_size == 0 {
+      return Err(AppError::BadRequest(Some(
+        "batch_sizeは1以上である必要があります。".into(),
+      )));
+    }
+
+    let mut report = EmailCanonicalReport::default();
+    let mut after = 0;
+    loop {
+      let page = self
+        .user_repo
+        .page_emails(after, i64::from(batch_size))
+        .await?;
+      let Some((last, _, _)) = page.last() else {
+        break;
+      };
+      after = last.as_i64();
+
+      for (user_id, email, stored) in &page {
+        let current = email.canonical();
+        if stored.as_deref() == Some(current.as_str()) {
+          continue;
+        }
+        match self
+          .user_repo
+          .update_email_canonical(*user_id, email, &current)
+          .await
+        {
+          Ok(true) => report.updated += 1,
+          // 走査後にメールアドレスが変更された場合は，更新時に再計算済み
+          Ok(false) => {}
+          Err(AppError::Conflict(_)) => {
+            log::warn!(
+              user_id = user_id.as_i64(),
+              "Canonical email conflicts with another user"
+            );
+            report.conflicts += 1;
+          }
+          Err(e) => return Err(e),
+        }
+      }
+
+      report.scanned += page.len() as u64;
+      log::info!(
+        scanned = report.scanned,
+        updated = report.updated,
+        conflicts = report.conflicts,
+        "Email canonicalization in progress"
+      );
+    }
+    Ok(report)
+  }
+
+  /// 後片付けを[maintenance].interval_secsごとに実行するタスクを起動する。
+  /// 失敗した場合はログを出力し，次の周期で再試行する。
+  pub fn spawn(self) -> JoinHandle<()> {
+    tokio::spawn(async move {
+      let mut ticker = tokio::time::interval(self.interval);
+      loop {
+        ticker.tick().await;
+        match self.cleanup(Utc::now()).await {
+          Ok(report) => log::info!(?report, "Maintenance cleanup finished"),
+          Err(e) => log::warn!(error = ?e, "Maintenance cleanup failed"),
+        }
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    domain::{
+      entity::{
+        audit_log::{AuditAction, AuditLog},
+        session::{Session, SessionScope},
+        user::{UserRole, UserStatus},
+      },
+      repository::AuditLogRepository,
+      value_obj::{public_id::PublicId, session_id::SessionId, user_id::UserId},
+    },
+    test_support::seed_user,
+  };
+  use std::collections::HashMap;
+
+  fn config(pii_retention_days: u32) -> Maintenance {
+    Maintenance {
+      interval_secs: 3600,
+      pii_retention_days,
+      purge_expired_sessions: false,
+    }
+  }
+
+  fn session(user_id: UserId, created_at: DateTime<Utc>) -> Session {
+    Session {
+      session_id: SessionId::new(),
+      user_id,
+      created_at,
+      expires_at: created_at + Duration::hours(1),
+      user_agent: Some("Mozilla/5.0".into()),
+      ip: Some("192.0.2.1".parse().unwrap()),
+      scope: SessionScope::Full,
+    }
+  }
+
+  /// 期限切れのセッションも参照できるよう，行を直接読み出す
+  async fn session_metadata(pool: &PgPool, s: &Session) -> (Option<String>, bool) {
+    let row = sqlx::query!(
+      r#"SELECT user_agent, ip IS NOT NULL AS "has_ip!" FROM sessions WHERE session_id = $1"#,
+      s.session_id.as_uuid()
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap();
+    (row.user_agent, row.has_ip)
+  }
+
+  async fn session_count(pool: &PgPool) -> i64 {
+    sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM sessions"#)
+      .fetch_one(pool)
+      .await
+      .unwrap()
+  }
+
+  fn audit(user_id: UserId, created_at: DateTime<Utc>) -> AuditLog {
+    AuditLog {
+      actor_user_id: Some(user_id),
+      target_user_id: Some(user_id),
+      action: AuditAction::UnlockLogin,
+      detail: Some("previous_login_fail_times=3".into()),
+      created_at,
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn scrubs_pii_beyond_retention_and_keeps_rows(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "pii_user", UserStatus::Active, UserRole::User).await;
+    let now = Utc::now();
+    let old = now - Duration::days(31);
+    let recent = now - Duration::days(29);
+
+    let sessions = PgSessionRepository::new(pool.clone());
+    let (old_s, recent_s) = (session(user.user_id, old), session(user.user_id, recent));
+    sessions.insert(&old_s).await.unwrap();
+    sessions.insert(&recent_s).await.unwrap();
+    let audits = PgAuditLogRepository::new(pool.clone());
+    audits.insert(&audit(user.user_id, old)).await.unwrap();
+    audits.insert(&audit(user.user_id, recent)).await.unwrap();
+
+    let service = MaintenanceService::new(pool.clone(), &config(30));
+    let report = service.cleanup(now).await.unwrap();
+    assert_eq!(report.sessions_scrubbed, 1);
+    assert_eq!(report.audit_logs_scrubbed, 1);
+
+    // 保持期間を過ぎた行のみ個人情報が消去される
+    assert_eq!(session_metadata(&pool, &old_s).await, (None, false));
+    let (user_agent, has_ip) = session_metadata(&pool, &recent_s).await;
+    assert!(user_agent.is_some() && has_ip);
+
+    // 行は削除されない（新しい順）
+    let logs = audits.find_by_target(user.user_id).await.unwrap();
+    assert_eq!(logs.len(), 2);
+    assert!(logs[0].detail.is_some());
+    assert_eq!(logs[1].detail, None);
+    assert_eq!(session_count(&pool).await, 2);
+    assert_eq!(report.sessions_purged, 0);
+
+    // 2回目は対象が無い
+    let report = service.cleanup(now).await.unwrap();
+    assert_eq!(
+      (report.sessions_scrubbed, report.audit_logs_scrubbed),
+      (0, 0)
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn zero_retention_disables_pii_scrub(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "keep_user", UserStatus::Active, UserRole::User).await;
+    let now = Utc::now();
+    let sessions = PgSessionRepository::new(pool.clone());
+    let s = session(user.user_id, now - Duration::days(3650));
+    sessions.insert(&s).await.unwrap();
+
+    let report = MaintenanceService::new(pool.clone(), &config(0))
+      .cleanup(now)
+      .await
+      .unwrap();
+    assert_eq!(report.sessions_scrubbed, 0);
+    assert!(session_metadata(&pool, &s).await.0.is_some());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn expired_sessions_are_purged_only_when_enabled(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "purge_user", UserStatus::Active, UserRole::User).await;
+    let now = Utc::now();
+    let sessions = PgSessionRepository::new(pool.clone());
+    sessions
+      .insert(&session(user.user_id, now - Duration::days(1)))
+      .await
+      .unwrap();
+    sessions.insert(&session(user.user_id, now)).await.unwrap();
+
+    let report = MaintenanceService::new(pool.clone(), &config(30))
+      .cleanup(now)
+      .await
+      .unwrap();
+    assert_eq!(report.sessions_purged, 0);
+    assert_eq!(session_count(&pool).await, 2);
+
+    let config = Maintenance {
+      purge_expired_sessions: true,
+      ..config(30)
+    };
+    let report = MaintenanceService::new(pool.clone(), &config)
+      .cleanup(now)
+      .await
+      .unwrap();
+    assert_eq!(report.sessions_purged, 1);
+    assert_eq!(session_count(&pool).await, 1);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn regenerates_stale_randomart_in_batches(pool: PgPool) {
+    let mut users = Vec::new();
+    for i in 0..5 {
+      let (user, _) = seed_user(
+        &pool,
+        &format!("art_user{i}"),
+        UserStatus::Active,
+        UserRole::User,
+      )
+      .await;
+      users.push(user);
+    }
+    // 旧アルゴリズムで保存された状態を再現する（1件は最新のまま）
+    for user in &users[1..] {
+      sqlx::query!(
+        "UPDATE users SET randomart = 'stale' WHERE user_id = $1",
+        user.user_id.as_i64()
+      )
+      .execute(&pool)
+      .await
+      .unwrap();
+    }
+
+    let service = MaintenanceService::new(pool.clone(), &config(30));
+    let report = service.regenerate_all_randomart(2).await.unwrap();
+    assert_eq!(
+      report,
+      RandomartReport {
+        scanned: 5,
+        updated: 4
+      }
+    );
+
+    let stored: HashMap<i64, String> = sqlx::query!("SELECT user_id, randomart FROM users")
+      .fetch_all(&pool)
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|r| (r.user_id, r.randomart))
+      .collect();
+    for user in &users {
+      assert_eq!(
+        stored[&user.user_id.as_i64()],
+        generate_randomart(&user.public_id)
+      );
+    }
+
+    // 2回目は更新対象が無い
+    let report = service.regenerate_all_randomart(10).await.unwrap();
+    assert_eq!(
+      report,
+      RandomartReport {
+        scanned: 5,
+        updated: 0
+      }
+    );
+    assert!(service.regenerate_all_randomart(0).await.is_err());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn randomart_update_skips_rotated_public_id(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "rotated_art", UserStatus::Active, UserRole::User).await;
+    let repo = PgUserRepository::new(pool.clone());
+    // 走査した時点の公開IDが再発行された状態を再現する
+    let scanned = PublicId::new();
+
+    let mut tx = pool.begin().await.unwrap();
+    let updated = repo
+      .update_randomart_tx(&mut tx, user.user_id, &scanned, "outdated")
+      .await
+      .unwrap();
+    tx.commit().await.unwrap();
+    assert!(!updated);
+    let stored = sqlx::query_scalar!(
+      "SELECT randomart FROM users WHERE user_id = $1",
+      user.user_id.as_i64()
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(stored, user.randomart);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn recanonicalizes_stale_emails_and_counts_conflicts(pool: PgPool) {
+    let mut users = Vec::new();
+    for (name, email) in [
+      ("canon_a", "a@example.com"),
+      ("canon_b", "b@example.com"),
+      ("canon_c", "c@example.com"),
+    ] {
+      let (user, _) = seed_user(&pool, name, UserStatus::Active, UserRole::User).await;
+      sqlx::query!(
+        "UPDATE users SET email = $1, email_canonical = $1 WHERE user_id = $2",
+        email,
+        user.user_id.as_i64()
+      )
+      .execute(&pool)
+      .await
+      .unwrap();
+      users.push(user);
+    }
+    seed_user(&pool, "canon_none", UserStatus::Active, UserRole::User).await;
+    // 以前の方式で生成された正規形を再現する（cの古い正規形はaの現在の正規形と重複する）
+    for (user, canonical) in [(&users[0], "A@EXAMPLE.COM"), (&users[2], "a@example.com")] {
+      sqlx::query!(
+        "UPDATE users SET email_canonical = $1 WHERE user_id = $2",
+        canonical,
+        user.user_id.as_i64()
+      )
+      .execute(&pool)
+      .await
+      .unwrap();
+    }
+
+    let service = MaintenanceService::new(pool.clone(), &config(30));
+    let report = service.recanonicalize_emails(2).await.unwrap();
+    assert_eq!(
+      report,
+      EmailCanonicalReport {
+        scanned: 3,
+        updated: 1,
+        conflicts: 1
+      }
+    );
+    // cの更新により重複が解消されたため，2回目はaも更新される
+    let report = service.recanonicalize_emails(10).await.unwrap();
+    assert_eq!((report.updated, report.conflicts), (1, 0));
+    let report = service.recanonicalize_emails(10).await.unwrap();
+    assert_eq!((report.updated, report.conflicts), (0, 0));
+    assert!(service.recanonicalize_emails(0).await.is_err());
+  }
+}
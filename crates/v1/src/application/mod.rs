@@ -1 +1,4 @@
+pub mod admin;
+pub mod maintenance;
+pub mod patch;
 pub mod user;
@@ -0,0 +1,89 @@
+//! 部分更新の入力値
+//! --------------------------------------------------------------
+//! ・省略(Undefined)：値を変更しない
+//! ・null(Null)：値を消去する
+//! ・値(Value)：値を設定する
+//! フィールドには`#[serde(default)]`を指定し，省略時にUndefinedとなるようにする。
+//! --------------------------------------------------------------
+
+use serde::{Deserialize, Deserializer};
+
+/// 部分更新の入力値
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Patch<T> {
+  /// フィールドが省略された
+  #[default]
+  Undefined,
+  /// nullが指定された
+  Null,
+  /// 値が指定された
+  Value(T),
+}
+
+impl<T> Patch<T> {
+  /// 現在値に適用した結果を返す。
+  pub fn apply(self, current: Option<T>) -> Option<T> {
+    match self {
+      Self::Undefined => current,
+      Self::Null => None,
+      Self::Value(v) => Some(v),
+    }
+  }
+
+  /// 指定された値を変換する。(Undefined・Nullはそのまま)
+  pub fn try_map<U, E>(self, f: impl FnOnce(T) -> Result<U, E>) -> Result<Patch<U>, E> {
+    Ok(match self {
+      Self::Undefined => Patch::Undefined,
+      Self::Null => Patch::Null,
+      Self::Value(v) => Patch::Value(f(v)?),
+    })
+  }
+
+  /// フィールドが省略された場合はtrueを返す。
+  pub fn is_undefined(&self) -> bool {
+    matches!(self, Self::Undefined)
+  }
+}
+
+/// フィールドが存在する場合のみ呼び出されるため，nullはNull，それ以外はValueとなる。
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    Ok(match Option::<T>::deserialize(deserializer)? {
+      None => Self::Null,
+      Some(v) => Self::Value(v),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Debug, Deserialize)]
+  struct Req {
+    #[serde(default)]
+    phone: Patch<String>,
+  }
+
+  fn parse(json: &str) -> Patch<String> {
+    serde_json::from_str::<Req>(json).unwrap().phone
+  }
+
+  #[test]
+  fn distinguishes_omitted_null_and_value() {
+    assert_eq!(parse("{}"), Patch::Undefined);
+    assert_eq!(parse(r#"{"phone":null}"#), Patch::Null);
+    assert_eq!(parse(r#"{"phone":"0901"}"#), Patch::Value("0901".into()));
+  }
+
+  #[test]
+  fn apply_to_current_value() {
+    let current = || Some("old".to_string());
+    assert_eq!(Patch::Undefined.apply(current()), current());
+    assert_eq!(Patch::<String>::Null.apply(current()), None);
+    assert_eq!(
+      Patch::Value("new".into()).apply(current()),
+      Some("new".into())
+    );
+  }
+}
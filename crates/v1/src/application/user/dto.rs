@@ -1,11 +1,31 @@
 //! ユースケース層 – 入出力 DTO
 
-use chrono::NaiveDate;
+use crate::{
+  application::patch::Patch,
+  config,
+  domain::{
+    entity::{session::Session, user::User},
+    password_policy::{PasswordPolicy, PolicyViolation},
+    value_obj::{
+      birth_date::BirthDate,
+      email_address::EmailAddress,
+      phone_number::{PhoneFormat, PhoneNumber, PhonePolicy},
+      user_full_name::NamePolicy,
+      user_name::{UserName, UserNamePolicy},
+      user_password::PasswordStrength,
+    },
+  },
+  interfaces::http::error::AppResult,
+  utils::randomart::RandomartBody,
+};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// ユーザー登録リクエスト (外部 I/F から受け取る)
+/// 不明なフィールド（`passwrod`などの誤記）は拒否する
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub struct RegisterRequest {
   pub user_name: String,
   pub password: String,
@@ -14,12 +34,501 @@ pub struct RegisterRequest {
   pub email: Option<String>,
   pub phone: Option<String>,
   pub birth_date: Option<NaiveDate>,
+  /// `GET /register/nonce`で発行したワンタイムトークン（二重送信の防止）
+  pub form_nonce: Option<String>,
+}
+
+/// 登録フォームのワンタイムトークン (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct FormNonceResponse {
+  pub form_nonce: String,
+  pub expires_at: DateTime<Utc>,
 }
 
 /// ユーザー登録結果 (外部 I/F へ返す)
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub struct RegisterResponse {
+  pub public_id: String,
+  /// [randomart].response_formatの形式で返す
+  pub randomart: RandomartBody,
+}
+
+/// ログインリクエスト (外部 I/F から受け取る)
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct LoginRequest {
+  pub user_name: String,
+  pub password: String,
+  /// trueの場合は有効期間の長いセッションを発行する
+  #[serde(default)]
+  pub remember_me: bool,
+}
+
+/// パスワードをログに出力しないよう，Debugではマスクする
+impl fmt::Debug for LoginRequest {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("LoginRequest")
+      .field("user_name", &self.user_name)
+      .field("password", &"********")
+      .field("remember_me", &self.remember_me)
+      .finish()
+  }
+}
+
+/// ログイン結果 (外部 I/F へ返す)
+/// session_idは`Authorization: Bearer <session_id>`として以降のリクエストに付与する
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct LoginResponse {
+  pub session_id: String,
+  pub public_id: String,
+  pub expires_at: DateTime<Utc>,
+}
+
+/// ログインの結果
+#[derive(Debug)]
+pub enum LoginOutcome {
+  /// 通常のセッションを発行した
+  Authenticated(LoginResponse),
+  /// パスワードの変更が必要なため，パスワード変更のみに使用できるセッションを発行した
+  PasswordChangeRequired(LoginResponse),
+}
+
+/// パスワード変更リクエスト (外部 I/F から受け取る)
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct ChangePasswordRequest {
+  pub current_password: String,
+  pub new_password: String,
+}
+
+/// パスワードをログに出力しないよう，Debugではマスクする
+impl fmt::Debug for ChangePasswordRequest {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ChangePasswordRequest")
+      .field("current_password", &"********")
+      .field("new_password", &"********")
+      .finish()
+  }
+}
+
+/// プロフィール更新リクエスト (外部 I/F から受け取る)
+/// 省略した項目は変更せず，nullを指定した項目は消去する
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct UpdateProfileRequest {
+  #[serde(default)]
+  pub first_name: Patch<String>,
+  #[serde(default)]
+  pub last_name: Patch<String>,
+  #[serde(default)]
+  pub email: Patch<String>,
+  #[serde(default)]
+  pub phone: Patch<String>,
+  #[serde(default)]
+  pub birth_date: Patch<NaiveDate>,
+  /// 予備のメールアドレス（emailとは異なる値）
+  #[serde(default)]
+  pub recovery_email: Patch<String>,
+}
+
+/// プロフィール (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ProfileResponse {
+  pub public_id: String,
+  pub user_name: String,
+  pub first_name: Option<String>,
+  pub last_name: Option<String>,
+  pub email: Option<String>,
+  pub phone: Option<String>,
+  pub birth_date: Option<NaiveDate>,
+  /// 予備のメールアドレス（本人にのみマスクして返す）
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub recovery_email: Option<String>,
+  /// 更新日時（本文には含めず，ETagとして返す）
+  #[serde(skip)]
+  pub updated_at: DateTime<Utc>,
+}
+
+impl ProfileResponse {
+  /// ユーザーのプロフィールを生成する。
+  /// 予備のメールアドレスは，本人(`is_owner`)の場合のみマスクして含める。
+  pub fn new(u: &User, is_owner: bool) -> Self {
+    Self {
+      public_id: u.public_id.as_str().to_owned(),
+      user_name: u.user_name.as_str().to_owned(),
+      first_name: u.full_name.as_ref().map(|n| n.first().to_owned()),
+      last_name: u
+        .full_name
+        .as_ref()
+        .and_then(|n| n.last())
+        .map(str::to_owned),
+      email: u.email.as_ref().map(|e| e.as_str().to_owned()),
+      phone: u.phone.as_ref().map(|p| p.as_str().to_owned()),
+      birth_date: u.birth_date.as_ref().map(|b| *b.as_naive_date()),
+      recovery_email: u
+        .recovery_email
+        .as_ref()
+        .filter(|_| is_owner)
+        .map(EmailAddress::redacted),
+      updated_at: u.updated_at,
+    }
+  }
+}
+
+/// ステータス変更リクエスト (外部 I/F から受け取る)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct ChangeStatusRequest {
+  /// 変更後のステータス（"deactivated"または"deleted"）
+  pub status: String,
+}
+
+/// メールアドレス確認のトークン発行結果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct VerificationTokenResponse {
+  pub token: String,
+  pub expires_at: DateTime<Utc>,
+}
+
+/// メールアドレス確認リクエスト (外部 I/F から受け取る)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct VerifyEmailRequest {
+  pub token: String,
+}
+
+/// メールアドレス確認結果 (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct VerifyEmailResponse {
+  pub public_id: String,
+  /// "verified"または"already_verified"
+  pub result: &'static str,
+}
+
+/// ステータス変更結果 (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct StatusResponse {
+  pub public_id: String,
+  pub status: &'static str,
+  /// 無効にしたセッションの件数
+  pub sessions_revoked: u64,
+}
+
+/// 公開ID再発行結果 (外部 I/F へ返す)
+/// 旧公開IDへの外部からの参照は無効になる
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RotateIdResponse {
   pub public_id: String,
   pub randomart: String,
+  pub previous_public_id: String,
+}
+
+/// 本人のセッション (外部 I/F へ返す)
+/// 端末を識別できるよう，作成時の端末情報を返す。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct OwnSessionView {
+  /// 先頭8文字以外をマスクしたセッションID
+  pub session_id: String,
+  /// このリクエストで使用しているセッションか
+  pub current: bool,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: DateTime<Utc>,
+  pub user_agent: Option<String>,
+  pub ip: Option<String>,
+}
+
+impl OwnSessionView {
+  pub fn new(s: &Session, current: bool) -> Self {
+    Self {
+      session_id: s.session_id.masked(),
+      current,
+      created_at: s.created_at,
+      expires_at: s.expires_at,
+      user_agent: s.user_agent.clone(),
+      ip: s.ip.map(|ip| ip.to_string()),
+    }
+  }
+}
+
+/// 本人のセッション一覧 (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct OwnSessionList {
+  pub sessions: Vec<OwnSessionView>,
+}
+
+/// ランダムアート取得結果 (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RandomartResponse {
+  pub public_id: String,
+  pub randomart: String,
+}
+
+/// ユーザー名の利用可否確認クエリ (外部 I/F から受け取る)
+#[derive(Debug, Deserialize)]
+pub struct UsernameAvailabilityQuery {
+  /// 未指定の場合は空文字列（形式不正）として扱う
+  #[serde(default)]
+  pub q: String,
+}
+
+/// ユーザー名を利用できない理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnavailableReason {
+  /// ユーザー名の形式を満たさない
+  InvalidFormat,
+  /// 既に使用されている
+  Taken,
+}
+
+/// ユーザー名の利用可否 (外部 I/F へ返す)
+/// 厳格モードでは，列挙を防ぐため理由を返さない
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct UsernameAvailabilityResponse {
+  pub available: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub reason: Option<UnavailableReason>,
+}
+
+/// パスワード強度評価リクエスト (外部 I/F から受け取る)
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct PasswordStrengthRequest {
+  pub password: String,
+  pub user_name: Option<String>,
+}
+
+/// パスワードをログに出力しないよう，Debugではマスクする
+impl fmt::Debug for PasswordStrengthRequest {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PasswordStrengthRequest")
+      .field("password", &"********")
+      .field("user_name", &self.user_name)
+      .finish()
+  }
+}
+
+/// パスワード強度評価結果 (外部 I/F へ返す)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PasswordStrengthResponse {
+  pub score: u8,
+  pub guesses_log10: f64,
+  pub crack_time: String,
+  pub warning: Option<String>,
+  pub suggestions: Vec<String>,
+  /// パスワードポリシーの違反（登録時に拒否される理由）
+  pub violations: Vec<String>,
+}
+
+impl PasswordStrengthResponse {
+  /// 強度の評価結果とパスワードポリシーの違反から生成する
+  pub fn new(s: PasswordStrength, violations: &[PolicyViolation]) -> Self {
+    Self {
+      score: s.score,
+      guesses_log10: s.guesses_log10,
+      crack_time: s.crack_time,
+      warning: s.warning,
+      suggestions: s.suggestions,
+      violations: violations.iter().map(ToString::to_string).collect(),
+    }
+  }
+}
+
+/// 登録フォームの入力仕様 (外部 I/F へ返す)
+/// 設定済みの検証ポリシーとVOの定数から生成するため，フロントエンドはこれを基にフォームを構築できる。
+#[derive(Debug, Serialize)]
+pub struct RegisterSchema {
+  pub fields: Vec<FieldSchema>,
+}
+
+/// 入力項目の仕様
+#[derive(Debug, Serialize)]
+pub struct FieldSchema {
+  pub name: &'static str,
+  #[serde(rename = "type")]
+  pub kind: FieldType,
+  pub required: bool,
+  pub constraints: FieldConstraints,
+  /// 入力ルールの説明
+  pub hint: String,
+}
+
+/// 入力項目の型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+  String,
+  Password,
+  Email,
+  Phone,
+  Date,
+}
+
+/// 入力項目の制約（該当しない制約は出力しない）
+#[derive(Debug, Default, Serialize)]
+pub struct FieldConstraints {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub min_length: Option<usize>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_length: Option<usize>,
+  /// 形式の説明
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub pattern: Option<&'static str>,
+  /// 選択できる値
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub allowed_values: Option<Vec<String>>,
+  /// zxcvbnの強度スコアの下限(0〜4)
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub min_score: Option<u8>,
+  /// エントロピー（ビット）の下限
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub min_bits: Option<u32>,
+  /// 日付の下限(YYYY-MM-DD)
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub min_date: Option<String>,
+  /// 年齢の下限
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub min_age: Option<u32>,
+}
+
+impl RegisterSchema {
+  /// Configの[validation]と[password]から生成する。
+  pub fn from_config(
+    validation: &config::Validation,
+    password: &config::Password,
+  ) -> AppResult<Self> {
+    Ok(Self::new(
+      &UserNamePolicy::from_config(validation)?,
+      &NamePolicy::from_config(validation)?,
+      &PhonePolicy::from_config(validation)?,
+      &PasswordPolicy::from_config(password, validation)?,
+    ))
+  }
+
+  /// 各検証ポリシーから生成する。
+  pub fn new(
+    user_name: &UserNamePolicy,
+    names: &NamePolicy,
+    phones: &PhonePolicy,
+    password: &PasswordPolicy,
+  ) -> Self {
+    let (phone_min, phone_max) = PhoneNumber::length_limits(phones.format);
+    let (phone_pattern, phone_example) = match phones.format {
+      PhoneFormat::Jp => ("数字のみ，先頭は0", "09012345678"),
+      PhoneFormat::E164 => ("E.164形式（+と国番号から始まる数字）", "+819012345678"),
+    };
+    let countries = (phones.format == PhoneFormat::E164 && !phones.allowed_countries.is_empty())
+      .then(|| phones.allowed_countries.clone());
+
+    let fields = vec![
+      FieldSchema {
+        name: "user_name",
+        kind: FieldType::String,
+        required: true,
+        constraints: FieldConstraints {
+          min_length: Some(user_name.min_len),
+          max_length: Some(user_name.max_len),
+          pattern: Some("英数字と _ . - + （先頭末尾は英数字か_，ドットの連続不可）"),
+          ..Default::default()
+        },
+        hint: format!(
+          "{}文字以上{}文字以下\n{}",
+          user_name.min_len,
+          user_name.max_len,
+          UserName::RULES
+        ),
+      },
+      FieldSchema {
+        name: "password",
+        kind: FieldType::Password,
+        required: true,
+        constraints: FieldConstraints {
+          min_length: Some(password.min_len),
+          max_length: Some(password.max_len),
+          min_score: Some(password.min_score.into()),
+          min_bits: password.min_bits,
+          ..Default::default()
+        },
+        hint: format!(
+          "{}文字以上{}文字以下（前後の空白は除く）\n・ユーザー名や誕生日を含めない\n・推測されにくい文字列にする",
+          password.min_len, password.max_len
+        ),
+      },
+      FieldSchema {
+        name: "first_name",
+        kind: FieldType::String,
+        required: false,
+        constraints: FieldConstraints {
+          min_length: names.first_min,
+          max_length: Some(names.first_max),
+          ..Default::default()
+        },
+        hint: "姓を入力する場合は必須".into(),
+      },
+      FieldSchema {
+        name: "last_name",
+        kind: FieldType::String,
+        required: false,
+        constraints: FieldConstraints {
+          min_length: names.last_min,
+          max_length: Some(names.last_max),
+          ..Default::default()
+        },
+        hint: "任意".into(),
+      },
+      FieldSchema {
+        name: "email",
+        kind: FieldType::Email,
+        required: false,
+        constraints: FieldConstraints {
+          min_length: Some(EmailAddress::MIN_LEN),
+          max_length: Some(EmailAddress::MAX_LEN),
+          ..Default::default()
+        },
+        hint: "任意（例：user@example.com）".into(),
+      },
+      FieldSchema {
+        name: "phone",
+        kind: FieldType::Phone,
+        required: false,
+        constraints: FieldConstraints {
+          min_length: Some(phone_min),
+          max_length: Some(phone_max),
+          pattern: Some(phone_pattern),
+          allowed_values: countries,
+          ..Default::default()
+        },
+        hint: format!("任意，{phone_pattern}（例：{phone_example}）"),
+      },
+      FieldSchema {
+        name: "birth_date",
+        kind: FieldType::Date,
+        required: false,
+        constraints: FieldConstraints {
+          pattern: Some("YYYY-MM-DD"),
+          min_date: Some(format!("{}-01-01", BirthDate::MIN_YEAR)),
+          min_age: Some(BirthDate::MIN_REGISTRATION_AGE),
+          ..Default::default()
+        },
+        hint: format!(
+          "任意，未来日は不可，{}歳未満は登録できない",
+          BirthDate::MIN_REGISTRATION_AGE
+        ),
+      },
+    ];
+    Self { fields }
+  }
 }
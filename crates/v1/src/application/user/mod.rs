@@ -1,2 +1,3 @@
 pub mod dto;
 pub mod service;
+pub mod throttle;
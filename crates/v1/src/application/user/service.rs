@@ -1,22 +1,62 @@
 //! UserService
 
 use crate::{
-  application::user::dto::{RegisterRequest, RegisterResponse},
+  application::user::{
+    dto::{
+      FormNonceResponse, LoginOutcome, LoginRequest, LoginResponse, OwnSessionList, OwnSessionView,
+      ProfileResponse, RandomartResponse, RegisterRequest, RegisterResponse, RotateIdResponse,
+      StatusResponse, UnavailableReason, UpdateProfileRequest, UsernameAvailabilityResponse,
+      VerificationTokenResponse, VerifyEmailResponse,
+    },
+    throttle::{LoginThrottle, RegistrationThrottle},
+  },
+  config::{ProfileField, RandomartFormat, RandomartSource, UniquenessStrategy},
   domain::{
-    entity::user::{UserRole, UserStatus},
-    entity::{user::User, user_auth::UserAuth},
+    entity::user::{EmailVerification, UserRole, UserStatus},
+    entity::{
+      audit_log::{AuditAction, AuditLog},
+      session::{Session, SessionPolicy, SessionScope},
+      user::User,
+      user_auth::UserAuth,
+    },
+    repository::UserAuthRepository,
     value_obj::{
       birth_date::BirthDate, email_address::EmailAddress, phone_number::PhoneNumber,
-      public_id::PublicId, user_full_name::UserFullName, user_id::UserId, user_name::UserName,
-      user_password::UserPassword,
+      public_id::PublicId, session_id::SessionId, user_full_name::UserFullName, user_id::UserId,
+      user_name::UserName, user_password::UserPassword,
     },
   },
-  infra::pg::{user_auth_repo::PgUserAuthRepository, user_repo::PgUserRepository},
-  interfaces::http::error::{AppError, AppResult},
-  utils::randomart::generate_randomart,
+  infra::pg::{
+    audit_log_repo::PgAuditLogRepository,
+    deadline::Deadline,
+    form_nonce_store::{NonceOutcome, PgFormNonceStore},
+    session_repo::PgSessionRepository,
+    user_auth_repo::PgUserAuthRepository,
+    user_repo::{PgTx, PgUserRepository, user_name_taken},
+    verification_token_store::{PgVerificationTokenStore, TokenOutcome},
+  },
+  interfaces::http::{
+    client::ClientInfo,
+    error::{AppError, AppResult},
+    precondition::{Precondition, precondition_failed},
+  },
+  utils::{
+    hashing::{hashing, verify_hashed},
+    randomart::{RandomartBody, generate_randomart},
+  },
 };
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use sqlx::PgPool;
+use std::{
+  net::{IpAddr, Ipv4Addr},
+  sync::LazyLock,
+};
+use uuid::Uuid;
+
+/// 存在しないユーザーのログインでも検証を行うためのハッシュ
+/// (応答時間の差からユーザー名の存在を推測されないようにする)
+static DUMMY_HASH: LazyLock<String> =
+  LazyLock::new(|| hashing("dummy-password").expect("ダミーハッシュの生成に失敗。"));
 
 /// `PgPool` を受け取り、ユーザー関連のリポジトリを初期化するサービス
 #[derive(Clone)]
@@ -24,6 +64,24 @@ pub struct UserService {
   pool: PgPool,
   user_repo: PgUserRepository,
   auth_repo: PgUserAuthRepository,
+  audit_repo: PgAuditLogRepository,
+  session_repo: PgSessionRepository,
+  session_policy: SessionPolicy,
+  login_throttle: Option<LoginThrottle>,
+  uniqueness: UniquenessStrategy,
+  randomart_source: RandomartSource,
+  randomart_format: RandomartFormat,
+  registration_throttle: Option<RegistrationThrottle>,
+  immutable_fields: Vec<ProfileField>,
+  strict_username_check: bool,
+  nonce_store: PgFormNonceStore,
+  /// form_nonceの有効期間（Noneの場合はform_nonceを使用しない）
+  form_nonce_ttl: Option<Duration>,
+  verification_store: PgVerificationTokenStore,
+  /// メールアドレス確認のトークンの有効期間
+  verification_token_ttl: Duration,
+  /// リクエストの処理期限（トランザクション内のクエリに適用する）
+  deadline: Deadline,
 }
 
 impl UserService {
@@ -33,10 +91,130 @@ impl UserService {
     Self {
       user_repo: PgUserRepository::new(pool.clone()),
       auth_repo: PgUserAuthRepository::new(pool.clone()),
+      audit_repo: PgAuditLogRepository::new(pool.clone()),
+      session_repo: PgSessionRepository::new(pool.clone()),
+      session_policy: SessionPolicy::default(),
+      login_throttle: None,
+      uniqueness: UniquenessStrategy::default(),
+      randomart_source: RandomartSource::default(),
+      randomart_format: RandomartFormat::default(),
+      registration_throttle: None,
+      immutable_fields: Vec::new(),
+      strict_username_check: false,
+      nonce_store: PgFormNonceStore::new(pool.clone()),
+      form_nonce_ttl: None,
+      verification_store: PgVerificationTokenStore::new(pool.clone()),
+      verification_token_ttl: Duration::hours(24),
+      deadline: Deadline::NONE,
       pool,
     }
   }
 
+  /// ユーザー名の重複チェック方式を設定する
+  pub fn with_uniqueness_strategy(mut self, strategy: UniquenessStrategy) -> Self {
+    self.uniqueness = strategy;
+    self
+  }
+
+  /// ランダムアートの取得元を設定する
+  pub fn with_randomart_source(mut self, source: RandomartSource) -> Self {
+    self.randomart_source = source;
+    self
+  }
+
+  /// ログイン時に発行するセッションの方針を設定する
+  pub fn with_session_policy(mut self, policy: SessionPolicy) -> Self {
+    self.session_policy = policy;
+    self
+  }
+
+  /// IP単位・ユーザー名単位のログインスロットルを設定する
+  pub fn with_login_throttle(mut self, throttle: LoginThrottle) -> Self {
+    self.login_throttle = Some(throttle);
+    self
+  }
+
+  /// メールアドレスのドメイン単位の登録スロットルを設定する
+  pub fn with_registration_throttle(mut self, throttle: RegistrationThrottle) -> Self {
+    self.registration_throttle = Some(throttle);
+    self
+  }
+
+  /// 登録結果で返すランダムアートの形式を設定する
+  pub fn with_randomart_format(mut self, format: RandomartFormat) -> Self {
+    self.randomart_format = format;
+    self
+  }
+
+  /// 登録後に変更できないプロフィールの項目を設定する
+  pub fn with_immutable_fields(mut self, fields: Vec<ProfileField>) -> Self {
+    self.immutable_fields = fields;
+    self
+  }
+
+  /// ユーザー名の利用可否確認で，利用できない理由を返さないかを設定する
+  pub fn with_strict_username_check(mut self, strict: bool) -> Self {
+    self.strict_username_check = strict;
+    self
+  }
+
+  /// 登録にform_nonceを必須とし，その有効期間を設定する（Noneの場合は使用しない）
+  pub fn with_form_nonce_ttl(mut self, ttl: Option<Duration>) -> Self {
+    self.form_nonce_ttl = ttl;
+    self
+  }
+
+  /// メールアドレス確認のトークンの有効期間を設定する
+  pub fn with_verification_token_ttl(mut self, ttl: Duration) -> Self {
+    self.verification_token_ttl = ttl;
+    self
+  }
+
+  /// リクエストの処理期限を設定する（リクエストごとに複製したサービスに設定する）
+  pub fn with_deadline(mut self, deadline: Deadline) -> Self {
+    self.deadline = deadline;
+    self
+  }
+
+  /// form_nonce発行サービス
+  /// 登録フォームの表示ごとに発行し，登録時に1回だけ使用できる。
+  pub async fn issue_form_nonce(&self) -> AppResult<FormNonceResponse> {
+    let Some(ttl) = self.form_nonce_ttl else {
+      return Err(AppError::NotFound(Some(
+        "form_nonceは無効に設定されています。".into(),
+      )));
+    };
+    let form_nonce = Uuid::new_v4().simple().to_string();
+    let expires_at = Utc::now() + ttl;
+    self.nonce_store.issue(&form_nonce, expires_at).await?;
+    Ok(FormNonceResponse {
+      form_nonce,
+      expires_at,
+    })
+  }
+
+  /// form_nonceを使用済みにする。
+  /// 登録と同じトランザクションで行い，登録に失敗した場合は未使用のまま残す。
+  async fn consume_form_nonce(&self, tx: &mut PgTx<'_>, nonce: Option<&str>) -> AppResult<()> {
+    if self.form_nonce_ttl.is_none() {
+      return Ok(());
+    }
+    let Some(nonce) = nonce.filter(|n| !n.is_empty()) else {
+      return Err(AppError::UnprocessableContent(Some(
+        "form_nonceは必須のパラメータです。".into(),
+      )));
+    };
+    match self.nonce_store.consume_tx(tx, nonce, Utc::now()).await? {
+      NonceOutcome::Consumed => Ok(()),
+      NonceOutcome::Reused => Err(AppError::Conflict(Some(
+        "この登録フォームは既に送信されています。".into(),
+      ))),
+      NonceOutcome::Expired | NonceOutcome::Unknown => Err(AppError::UnprocessableContent(Some(
+        "form_nonceが無効か有効期限切れです。再取得してください。".into(),
+      ))),
+    }
+  }
+
   /// ユーザー登録サービス
   /// ユーザー名とパスワードを受け取り、ユーザーと認証情報をデータベースに登録する
   pub async fn register(&self, request: RegisterRequest) -> AppResult<RegisterResponse> {
@@ -44,12 +222,22 @@ impl UserService {
     // リクエスト→ `VO` → `Entity`へと変換をする。`
     let (mut user, mut auth) = Self::build_entities(&request)?;
 
+    // メールアドレスのドメイン単位の登録数を確認する
+    if let Some(throttle) = &self.registration_throttle {
+      throttle.check(user.email.as_ref()).await?;
+    }
+
     // トランザクションを開始する
-    let mut tx = self.pool.begin().await.map_err(AppError::from)?;
+    let mut tx = self.begin().await?;
+
+    // 二重送信を防ぐため，form_nonceを使用済みにする
+    self
+      .consume_form_nonce(&mut tx, request.form_nonce.as_deref())
+      .await?;
 
     // ユーザーを，users テーブルに INSERT する
-    let new_id = self.user_repo.insert_tx(&mut tx, &user).await?;
-    user.user_id = UserId::new(new_id)?; // 自動採番をセット
+    let new_id = self.insert_user(&mut tx, &user).await?;
+    user.user_id = UserId::from_db(new_id)?; // 自動採番をセット
 
     // ユーザー認証情報を，user_auths テーブルに INSERT する
     auth.user_id = user.user_id;
@@ -58,15 +246,645 @@ impl UserService {
     // トランザクションをコミットする
     tx.commit().await.map_err(AppError::from)?;
 
+    // 登録が完了した場合のみドメインの登録数を加算する
+    if let Some(throttle) = &self.registration_throttle {
+      throttle.record(user.email.as_ref()).await?;
+    }
+
     // 4. レスポンス DTO
     Ok(RegisterResponse {
+      public_id: user.public_id.as_str().to_owned(),
+      randomart: RandomartBody::format(user.randomart, self.randomart_format),
+    })
+  }
+
+  /// メールアドレス確認のトークン発行サービス
+  /// 確認メールの送信元から呼び出し，発行したトークンを確認リンクに含める。
+  pub async fn issue_verification_token(
+    &self,
+    user_id: UserId,
+  ) -> AppResult<VerificationTokenResponse> {
+    let token = Uuid::new_v4().simple().to_string();
+    let expires_at = Utc::now() + self.verification_token_ttl;
+    self
+      .verification_store
+      .issue(&token, user_id, expires_at)
+      .await?;
+    Ok(VerificationTokenResponse { token, expires_at })
+  }
+
+  /// メールアドレス確認サービス
+  /// 有効なトークンの場合は，Pendingのユーザーを`update_status`でActiveにする。
+  /// 使用済みのトークン（確認リンクの二重クリック）は，確認済みとして成功を返す。
+  /// 不正・期限切れのトークンは400を返す。
+  pub async fn verify_email(&self, token: &str) -> AppResult<VerifyEmailResponse> {
+    let mut tx = self.begin().await?;
+    let user_id = match self
+      .verification_store
+      .consume_tx(&mut tx, token, Utc::now())
+      .await?
+    {
+      TokenOutcome::Consumed(user_id) | TokenOutcome::Reused(user_id) => user_id,
+      TokenOutcome::Expired | TokenOutcome::Unknown => {
+        return Err(AppError::BadRequest(Some(
+          "確認トークンが無効か有効期限切れです。".into(),
+        )));
+      }
+    };
+
+    let mut user = self
+      .user_repo
+      .find_by_user_id_for_update_tx(&mut tx, user_id)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("ユーザーが見つかりません。".into())))?;
+    let Some((next, result)) = user.status.verify_email() else {
+      return Err(AppError::Conflict(Some(format!(
+        "ステータスが{}のユーザーはメールアドレスを確認できません。",
+        user.status.as_str()
+      ))));
+    };
+    if result == EmailVerification::Verified {
+      user.status = next;
+      self.user_repo.update_status_tx(&mut tx, &user).await?;
+    }
+    tx.commit().await.map_err(AppError::from)?;
+
+    Ok(VerifyEmailResponse {
+      public_id: user.public_id.as_str().to_owned(),
+      result: match result {
+        EmailVerification::Verified => "verified",
+        EmailVerification::AlreadyVerified => "already_verified",
+      },
+    })
+  }
+
+  /// ログインサービス
+  /// ユーザー名とパスワードを検証し，新しいセッションを発行する。
+  /// ユーザーが存在しない場合・パスワードが一致しない場合・ロック中の場合は，区別せずに401を返す。
+  /// ロックはログイン失敗回数が上限に達してから一定時間で解除される。
+  /// ステータスによる拒否は，パスワードが一致した場合のみ返す。
+  /// パスワードの変更が必要な場合は，パスワード変更のみに使用できるセッションを発行する。
+  pub async fn login(&self, request: LoginRequest, client: ClientInfo) -> AppResult<LoginOutcome> {
+    let ip = throttle_ip(&client);
+    // 全角など表記の異なる同じユーザー名を同じカウンタで数えるため，正規化した名前で制限する
+    // (ユーザー名として不正な入力はどのユーザーにも一致しないため，入力のまま数える)
+    let name = UserName::for_lookup(&request.user_name).ok().flatten();
+    let throttle_name = name
+      .as_ref()
+      .map_or(request.user_name.as_str(), UserName::as_str);
+    if let Some(throttle) = &self.login_throttle {
+      throttle.check(ip, throttle_name).await?;
+    }
+
+    let credentials = match &name {
+      Some(name) => self.find_credentials(name).await?,
+      None => None,
+    };
+    let Some((mut user, mut auth)) = credentials else {
+      Self::verify_password(&request.password, &DUMMY_HASH)?;
+      self.record_login_failure(ip, throttle_name, None).await?;
+      return Err(invalid_credentials());
+    };
+    // ロック中はパスワードの正誤・ユーザーの存在を判別させないため，
+    // 検証せずに同じ時間をかけて同じ応答で拒否する（ロックの期限は延長しない）
+    if auth.is_locked(Utc::now()) {
+      Self::verify_password(&request.password, &DUMMY_HASH)?;
+      self.record_login_failure(ip, throttle_name, None).await?;
+      return Err(invalid_credentials());
+    }
+    if !Self::verify_password(&request.password, auth.current_hash.as_hash())? {
+      self
+        .record_login_failure(ip, throttle_name, Some(&mut auth))
+        .await?;
+      return Err(invalid_credentials());
+    }
+
+    user.status.can_login()?;
+
+    // ログイン失敗回数・期限切れのロックとユーザー名単位のカウンタをリセットする
+    // 移行元のハッシュ方式の場合は，検証済みの平文でArgon2へ再ハッシュ化する
+    let rehash = auth.current_hash.scheme().needs_rehash();
+    if rehash {
+      auth.current_hash = UserPassword::from_hash(hashing(&request.password)?)?;
+    }
+    if rehash || auth.login_fail_times > 0 || auth.locked_until.is_some() {
+      auth.clear_login_failures();
+      self.auth_repo.update(&auth).await?;
+    }
+    if let Some(throttle) = &self.login_throttle {
+      throttle.record_success(throttle_name).await?;
+    }
+    user.last_login_at = Some(Utc::now());
+    self.user_repo.update_last_login_at(&user).await?;
+
+    let mut session = Session::issue(
+      user.user_id,
+      Utc::now(),
+      &self.session_policy,
+      request.remember_me,
+      client.user_agent,
+      client.ip,
+    );
+    if auth.must_change_password {
+      session = session.restrict_to_password_change();
+    }
+    self.session_repo.insert(&session).await?;
+
+    let response = LoginResponse {
+      session_id: session.session_id.to_string(),
+      public_id: user.public_id.as_str().to_owned(),
+      expires_at: session.expires_at,
+    };
+    Ok(match session.scope {
+      SessionScope::Full => LoginOutcome::Authenticated(response),
+      SessionScope::PasswordChange => LoginOutcome::PasswordChangeRequired(response),
+    })
+  }
+
+  /// 本人のセッション一覧サービス
+  /// 有効期限内のセッションを作成日時の降順に返す。`current`に一致するセッションには印を付ける。
+  pub async fn own_sessions(
+    &self,
+    user_id: UserId,
+    current: Option<&SessionId>,
+  ) -> AppResult<OwnSessionList> {
+    let sessions = self.session_repo.find_by_user_id(user_id).await?;
+    Ok(OwnSessionList {
+      sessions: sessions
+        .iter()
+        .map(|s| OwnSessionView::new(s, current == Some(&s.session_id)))
+        .collect(),
+    })
+  }
+
+  /// ログアウトサービス
+  /// セッションを削除する。存在しないセッションの場合は404を返す。
+  pub async fn logout(&self, sid: SessionId) -> AppResult<()> {
+    if self.session_repo.find(sid.clone()).await?.is_none() {
+      return Err(AppError::NotFound(Some(
+        "セッションが見つかりません。".into(),
+      )));
+    }
+    self.session_repo.delete(sid).await
+  }
+
+  /// パスワード変更サービス
+  /// 現在のパスワードを確認し，新しいパスワードを登録時と同じポリシーで検証する。
+  /// 現在及び直近2件のパスワードと同じ場合は変更できない。
+  /// 現在のパスワードの誤りはログインの失敗と同じく数え，ロック・スロットルの対象とする。
+  /// 変更に成功した場合は，パスワード変更の要求を解除する。
+  pub async fn change_password(
+    &self,
+    user_id: UserId,
+    old: String,
+    new: String,
+    client: ClientInfo,
+  ) -> AppResult<()> {
+    let user = self
+      .user_repo
+      .find_by_user_id(user_id)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("ユーザーが見つかりません。".into())))?;
+    let ip = throttle_ip(&client);
+    if let Some(throttle) = &self.login_throttle {
+      throttle.check(ip, user.user_name.as_str()).await?;
+    }
+    let mut auth = self.auth_repo.find(user_id).await?.ok_or_else(|| {
+      AppError::InternalServerError(Some(format!("user_auth not found: {}", user_id.as_i64())))
+    })?;
+
+    let wrong_password =
+      || AppError::Unauthorized(Some("現在のパスワードが正しくありません。".into()));
+    // ロック中は，ログインと同じく検証せずに同じ時間をかけて拒否する
+    if auth.is_locked(Utc::now()) {
+      Self::verify_password(&old, &DUMMY_HASH)?;
+      self
+        .record_login_failure(ip, user.user_name.as_str(), None)
+        .await?;
+      return Err(wrong_password());
+    }
+    if !Self::verify_password(&old, auth.current_hash.as_hash())? {
+      self
+        .record_login_failure(ip, user.user_name.as_str(), Some(&mut auth))
+        .await?;
+      return Err(wrong_password());
+    }
+
+    let birth_date = user.birth_date.as_ref().map(|b| *b.as_naive_date());
+    let password = UserPassword::new(new.as_str(), true, user.user_name.as_str(), birth_date)?
+      .ok_or_else(|| AppError::UnprocessableContent(Some("新しいパスワードは必須です。".into())))?;
+
+    // 保存するハッシュは前後の空白を除いた値から生成されるため，同じ正規化で比較する
+    for hash in auth.recent_hashes() {
+      if Self::verify_password(new.trim(), hash.as_hash())? {
+        return Err(AppError::UnprocessableContent(Some(
+          "直近に使用したパスワードは使用できません。".into(),
+        )));
+      }
+    }
+
+    // 現在のパスワードの確認はログインの成功と同じく，失敗回数とユーザー名単位のカウンタをリセットする
+    auth.rotate_password(password);
+    auth.must_change_password = false;
+    auth.clear_login_failures();
+    self.auth_repo.update(&auth).await?;
+    if let Some(throttle) = &self.login_throttle {
+      throttle.record_success(user.user_name.as_str()).await?;
+    }
+    Ok(())
+  }
+
+  /// 公開IDの再発行サービス
+  /// 本人またはAdmin以上のみが実行でき，公開IDとランダムアートを再生成する。
+  /// user_idは維持されるが，旧公開IDへの外部からの参照は無効になる。
+  pub async fn rotate_public_id(
+    &self,
+    actor: &User,
+    public_id: &PublicId,
+  ) -> AppResult<RotateIdResponse> {
+    if actor.public_id != *public_id && actor.role < UserRole::Admin {
+      return Err(AppError::Forbidden(Some(
+        "この操作を行う権限がありません。".into(),
+      )));
+    }
+
+    let mut user = self
+      .user_repo
+      .find_by_public_id(public_id)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("ユーザーが見つかりません。".into())))?;
+
+    let previous = user.public_id.clone();
+    user.public_id = PublicId::new();
+    user.randomart = generate_randomart(&user.public_id);
+
+    // 公開IDの更新と監査ログの記録は同じトランザクションで行う
+    let mut tx = self.begin().await?;
+    self.user_repo.update_public_id_tx(&mut tx, &user).await?;
+    self
+      .audit_repo
+      .insert_tx(
+        &mut tx,
+        &AuditLog {
+          actor_user_id: Some(actor.user_id),
+          target_user_id: Some(user.user_id),
+          action: AuditAction::RotatePublicId,
+          detail: Some(format!(
+            "public_id: {} -> {}",
+            previous.as_str(),
+            user.public_id.as_str()
+          )),
+          created_at: Utc::now(),
+        },
+      )
+      .await?;
+    tx.commit().await.map_err(AppError::from)?;
+
+    Ok(RotateIdResponse {
       public_id: user.public_id.as_str().to_owned(),
       randomart: user.randomart,
+      previous_public_id: previous.as_str().to_owned(),
+    })
+  }
+
+  /// プロフィール更新サービス
+  /// 本人またはAdmin以上のみが実行できる。
+  /// 省略した項目は変更せず，nullを指定した項目は消去する。
+  pub async fn update_profile(
+    &self,
+    actor: &User,
+    public_id: &PublicId,
+    request: UpdateProfileRequest,
+    precondition: &Precondition,
+  ) -> AppResult<ProfileResponse> {
+    if actor.public_id != *public_id && actor.role < UserRole::Admin {
+      return Err(AppError::Forbidden(Some(
+        "この操作を行う権限がありません。".into(),
+      )));
+    }
+
+    let mut user = self
+      .user_repo
+      .find_by_public_id(public_id)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("ユーザーが見つかりません。".into())))?;
+
+    // 取得から更新までの間に他の更新があった場合も，上書きせずに412を返す
+    precondition.check(user.updated_at)?;
+    let before = user.clone();
+    Self::apply_profile(&mut user, request)?;
+    self.ensure_immutable(&before, &user)?;
+    user.updated_at = self
+      .user_repo
+      .update_profile_if_unmodified(&user, before.updated_at)
+      .await?
+      .ok_or_else(precondition_failed)?;
+    Ok(ProfileResponse::new(&user, actor.user_id == user.user_id))
+  }
+
+  /// ユーザー取得サービス
+  /// 本人またはSupport以上のみが，ステータスを問わず公開IDでユーザーを取得できる。
+  /// 権限を確認してから対象を検索し，権限の無い利用者には存在の有無を返さない。
+  pub async fn get_by_public_id(&self, actor: &User, pid: PublicId) -> AppResult<User> {
+    if actor.public_id != pid && actor.role < UserRole::Support {
+      return Err(AppError::Forbidden(Some(
+        "この操作を行う権限がありません。".into(),
+      )));
+    }
+    self.find_by_public_id(&pid).await
+  }
+
+  /// 公開IDでユーザーを取得する。存在しない場合は404を返す。(権限は呼び出し元で確認する)
+  async fn find_by_public_id(&self, pid: &PublicId) -> AppResult<User> {
+    self
+      .user_repo
+      .find_by_public_id(pid)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("ユーザーが見つかりません。".into())))
+  }
+
+  /// 公開IDを指定したステータス変更サービス
+  /// 権限を確認してから対象を検索し，権限の無い利用者には存在の有無も現在のステータスも返さない。
+  pub async fn change_status_by_public_id(
+    &self,
+    actor: &User,
+    pid: PublicId,
+    next: UserStatus,
+  ) -> AppResult<StatusResponse> {
+    let is_owner = actor.public_id == pid;
+    if !is_owner && actor.role < UserRole::Admin {
+      return Err(AppError::Forbidden(Some(
+        "この操作を行う権限がありません。".into(),
+      )));
+    }
+    // 休止・退会以外は本人の操作では遷移できない
+    if !matches!(next, UserStatus::Deactivated | UserStatus::Deleted) {
+      return Err(AppError::Conflict(Some(format!(
+        "ステータスを{}に変更することはできません。",
+        next.as_str()
+      ))));
+    }
+    let user = self.find_by_public_id(&pid).await?;
+    self.change_status(actor, user.user_id, next).await
+  }
+
+  /// 休止サービス
+  /// 本人またはAdmin以上が，ユーザーをDeactivatedにする。
+  pub async fn deactivate(&self, actor: &User, user_id: UserId) -> AppResult<StatusResponse> {
+    self
+      .change_status(actor, user_id, UserStatus::Deactivated)
+      .await
+  }
+
+  /// 退会サービス（論理削除）
+  /// 本人またはAdmin以上が，ユーザーをDeletedにする。
+  pub async fn delete(&self, actor: &User, user_id: UserId) -> AppResult<StatusResponse> {
+    self
+      .change_status(actor, user_id, UserStatus::Deleted)
+      .await
+  }
+
+  /// ユーザー名の利用可否確認サービス
+  /// 登録時と同じ検証を行い，ステータスを問わず既存のユーザー名と重複しないかを確認する。
+  pub async fn username_availability(&self, q: &str) -> AppResult<UsernameAvailabilityResponse> {
+    let reason = match UserName::new(q, true) {
+      Ok(Some(name)) if self.user_repo.exists_by_username(&name).await? => {
+        Some(UnavailableReason::Taken)
+      }
+      Ok(_) => None,
+      Err(_) => Some(UnavailableReason::InvalidFormat),
+    };
+    Ok(UsernameAvailabilityResponse {
+      available: reason.is_none(),
+      reason: reason.filter(|_| !self.strict_username_check),
+    })
+  }
+
+  /// ランダムアート取得サービス
+  /// 設定に従い，保存済みの値または公開IDから再生成した値を返す。
+  pub async fn randomart(&self, public_id: &PublicId) -> AppResult<RandomartResponse> {
+    let user = self
+      .user_repo
+      .find_by_public_id(public_id)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("ユーザーが見つかりません。".into())))?;
+
+    let randomart = match self.randomart_source {
+      RandomartSource::Stored => user.randomart,
+      RandomartSource::Recompute => generate_randomart(&user.public_id),
+    };
+    Ok(RandomartResponse {
+      public_id: user.public_id.as_str().to_owned(),
+      randomart,
     })
   }
 
   /* 内部関数  */
 
+  /// 本人の操作としてステータスを遷移させ，ログインできなくなるためセッションを無効にする。
+  /// 遷移表（`UserStatus::can_owner_transition_to`）にない遷移は409を返す。
+  async fn change_status(
+    &self,
+    actor: &User,
+    user_id: UserId,
+    next: UserStatus,
+  ) -> AppResult<StatusResponse> {
+    let is_owner = actor.user_id == user_id;
+    if !is_owner && actor.role < UserRole::Admin {
+      return Err(AppError::Forbidden(Some(
+        "この操作を行う権限がありません。".into(),
+      )));
+    }
+
+    let mut tx = self.begin().await?;
+    let mut user = self
+      .user_repo
+      .find_by_user_id_for_update_tx(&mut tx, user_id)
+      .await?
+      .ok_or_else(|| AppError::NotFound(Some("ユーザーが見つかりません。".into())))?;
+    if !is_owner && !actor.role.outranks(user.role) {
+      return Err(AppError::Forbidden(Some(
+        "このユーザーのステータスを変更する権限がありません。".into(),
+      )));
+    }
+    let previous = user.status;
+    if !previous.can_owner_transition_to(next) {
+      return Err(AppError::Conflict(Some(format!(
+        "ステータスを{}から{}に変更することはできません。",
+        previous.as_str(),
+        next.as_str()
+      ))));
+    }
+
+    user.status = next;
+    self.user_repo.update_status_tx(&mut tx, &user).await?;
+    let sessions_revoked = self
+      .session_repo
+      .delete_by_user_id_tx(&mut tx, user.user_id)
+      .await?;
+    self
+      .audit_repo
+      .insert_tx(
+        &mut tx,
+        &AuditLog {
+          actor_user_id: Some(actor.user_id),
+          target_user_id: Some(user.user_id),
+          action: AuditAction::ChangeStatus,
+          detail: Some(format!(
+            "status: {} -> {}",
+            previous.as_str(),
+            next.as_str()
+          )),
+          created_at: Utc::now(),
+        },
+      )
+      .await?;
+    tx.commit().await.map_err(AppError::from)?;
+
+    Ok(StatusResponse {
+      public_id: user.public_id.as_str().to_owned(),
+      status: next.as_str(),
+      sessions_revoked,
+    })
+  }
+
+  /// トランザクションを開始し，処理期限までの残り時間を`statement_timeout`に設定する
+  async fn begin(&self) -> AppResult<PgTx<'static>> {
+    let mut tx = self.pool.begin().await.map_err(AppError::from)?;
+    self.deadline.apply_tx(&mut tx).await?;
+    Ok(tx)
+  }
+
+  /// 設定された重複チェック方式に従って，ユーザーを users テーブルに INSERT する
+  /// いずれの方式でも，ユーザー名が重複している場合は409を返す
+  async fn insert_user(&self, tx: &mut PgTx<'_>, user: &User) -> AppResult<i64> {
+    if self.uniqueness == UniquenessStrategy::Pessimistic
+      && self
+        .user_repo
+        .exists_by_username_tx(tx, &user.user_name)
+        .await?
+    {
+      return Err(user_name_taken());
+    }
+    // 楽観的方式の場合は，一意制約違反をそのまま409として返す
+    self.user_repo.insert_tx(tx, user).await
+  }
+
+  /// ログイン対象のユーザーと認証情報を取得する
+  /// ユーザー名の形式を満たさない場合も，存在しないユーザーとして扱う
+  async fn find_credentials(&self, name: &UserName) -> AppResult<Option<(User, UserAuth)>> {
+    let Some(user) = self.user_repo.find_by_username_any_status(name).await? else {
+      return Ok(None);
+    };
+    Ok(
+      self
+        .auth_repo
+        .find(user.user_id)
+        .await?
+        .map(|auth| (user, auth)),
+    )
+  }
+
+  /// パスワードを検証し，一致したかどうかを返す
+  fn verify_password(plain: &str, hashed: &str) -> AppResult<bool> {
+    match verify_hashed(plain, hashed) {
+      Ok(()) => Ok(true),
+      Err(AppError::Unauthorized(_)) => Ok(false),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// ログイン失敗を記録する
+  /// 認証情報がある場合はログイン失敗回数を加算し，上限に達した場合はロックする
+  async fn record_login_failure(
+    &self,
+    ip: IpAddr,
+    user_name: &str,
+    auth: Option<&mut UserAuth>,
+  ) -> AppResult<()> {
+    if let Some(auth) = auth {
+      auth.record_login_failure(Utc::now());
+      self.auth_repo.update(auth).await?;
+    }
+    if let Some(throttle) = &self.login_throttle {
+      throttle.record_failure(ip, user_name).await?;
+    }
+    Ok(())
+  }
+
+  /// プロフィール更新リクエストをユーザーに適用する
+  /// 氏名は姓・名のそれぞれに適用した後，まとめて検証する
+  fn apply_profile(user: &mut User, req: UpdateProfileRequest) -> AppResult<()> {
+    if !(req.first_name.is_undefined() && req.last_name.is_undefined()) {
+      let current = user.full_name.take();
+      let first = req
+        .first_name
+        .apply(current.as_ref().map(|n| n.first().to_owned()));
+      let last = req
+        .last_name
+        .apply(current.as_ref().and_then(|n| n.last()).map(str::to_owned));
+      user.full_name = UserFullName::new(first.unwrap_or_default(), last.unwrap_or_default())?;
+    }
+
+    // 空文字列は消去として扱う（登録時と同じ）
+    user.email = req
+      .email
+      .try_map(|e| EmailAddress::new(e, false))?
+      .apply(user.email.take().map(Some))
+      .flatten();
+    user.phone = req
+      .phone
+      .try_map(|p| PhoneNumber::new(p, false))?
+      .apply(user.phone.take().map(Some))
+      .flatten();
+    user.recovery_email = req
+      .recovery_email
+      .try_map(|e| EmailAddress::new(e, false))?
+      .apply(user.recovery_email.take().map(Some))
+      .flatten();
+    user.ensure_distinct_contacts()?;
+    user.birth_date = req
+      .birth_date
+      .try_map(BirthDate::try_from_naive_date)?
+      .apply(user.birth_date.take());
+    Ok(())
+  }
+
+  /// 変更できない項目が変更されていないことを確認する
+  /// 現在と同じ値の指定は変更として扱わない
+  fn ensure_immutable(&self, before: &User, after: &User) -> AppResult<()> {
+    match self
+      .immutable_fields
+      .iter()
+      .find(|&&f| Self::profile_value(before, f) != Self::profile_value(after, f))
+    {
+      Some(field) => Err(AppError::Forbidden(Some(format!(
+        "{}は変更できません。",
+        field.as_str()
+      )))),
+      None => Ok(()),
+    }
+  }
+
+  /// 比較用にプロフィールの項目の値を返す
+  fn profile_value(user: &User, field: ProfileField) -> Option<String> {
+    match field {
+      ProfileField::FirstName => user.full_name.as_ref().map(|n| n.first().to_owned()),
+      ProfileField::LastName => user
+        .full_name
+        .as_ref()
+        .and_then(|n| n.last())
+        .map(str::to_owned),
+      ProfileField::Email => user.email.as_ref().map(|e| e.as_str().to_owned()),
+      ProfileField::Phone => user.phone.as_ref().map(|p| p.as_str().to_owned()),
+      ProfileField::BirthDate => user
+        .birth_date
+        .as_ref()
+        .map(|b| b.as_naive_date().to_string()),
+      ProfileField::RecoveryEmail => user.recovery_email.as_ref().map(|e| e.as_str().to_owned()),
+    }
+  }
+
   /// Requestデータを受け取り、`User` と `UserAuth` のエンティティを生成する
   fn build_entities(req: &RegisterRequest) -> AppResult<(User, UserAuth)> {
     // ユーザー名とパスワードが空でないことをチェックする
@@ -101,21 +919,25 @@ impl UserService {
       .transpose()?
       .flatten();
 
-    let birth_date = req.birth_date.map(BirthDate::from_naive_date);
+    let birth_date = req
+      .birth_date
+      .map(BirthDate::try_from_naive_date)
+      .transpose()?;
 
     // Entityの生成
     let now = Utc::now();
     let public_id = PublicId::new();
     let randomart = generate_randomart(&public_id);
 
-    // user_id は 0 でダミー。INSERT 後に上書きする
+    // user_id は仮の値。INSERT 後に上書きする
     let user = User {
-      user_id: UserId::new(0)?,
+      user_id: UserId::unsaved(),
       public_id: public_id.clone(),
       randomart: randomart.clone(),
       user_name,
       full_name,
       email,
+      recovery_email: None,
       phone,
       birth_date,
       status: UserStatus::Pending,
@@ -125,12 +947,16 @@ impl UserService {
       updated_at: now,
     };
 
+    user.ensure_distinct_contacts()?;
+
     let auth = UserAuth {
       user_id: user.user_id,
       current_hash: password,
       prev_hash1: None,
       prev_hash2: None,
       login_fail_times: 0,
+      locked_until: None,
+      must_change_password: false,
       created_at: now,
       updated_at: now,
     };
@@ -139,3 +965,1236 @@ impl UserService {
     Ok((user, auth))
   }
 }
+
+/// ユーザー名・パスワードの不一致（どちらが誤っているかは返さない）
+fn invalid_credentials() -> AppError {
+  AppError::Unauthorized(Some(
+    "ユーザー名またはパスワードが正しくありません。".into(),
+  ))
+}
+
+/// スロットルでIP単位に数える接続元
+/// (接続元が不明な場合は，IP単位のカウンタを1つにまとめる)
+fn throttle_ip(client: &ClientInfo) -> IpAddr {
+  client.ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    config::AppConfig,
+    domain::{
+      clock_skew::ClockSkew, repository::AuditLogRepository, value_obj::email_address::EmailPolicy,
+    },
+    infra::memory::rate_limit_store::MemoryRateLimitStore,
+    interfaces::http::precondition::etag,
+    test_support::{PASSWORD, new_user, seed_user},
+    utils::hashing::HashScheme,
+  };
+  use std::sync::Arc;
+
+  async fn insert_duplicate(pool: &PgPool, strategy: UniquenessStrategy) -> AppError {
+    seed_user(pool, "taken_name", UserStatus::Active, UserRole::User).await;
+    let svc = UserService::new(pool.clone()).with_uniqueness_strategy(strategy);
+    let user = new_user("taken_name", UserStatus::Pending, UserRole::User);
+
+    let mut tx = pool.begin().await.unwrap();
+    svc.insert_user(&mut tx, &user).await.unwrap_err()
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn optimistic_strategy_rejects_duplicate_username(pool: PgPool) {
+    let err = insert_duplicate(&pool, UniquenessStrategy::Optimistic).await;
+    assert!(matches!(err, AppError::Conflict(Some(m)) if m.contains("user_name")));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn pessimistic_strategy_rejects_duplicate_username(pool: PgPool) {
+    let err = insert_duplicate(&pool, UniquenessStrategy::Pessimistic).await;
+    assert!(matches!(err, AppError::Conflict(Some(m)) if m.contains("user_name")));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn both_strategies_insert_unique_username(pool: PgPool) {
+    for (name, strategy) in [
+      ("fresh_optimistic", UniquenessStrategy::Optimistic),
+      ("fresh_pessimistic", UniquenessStrategy::Pessimistic),
+    ] {
+      let svc = UserService::new(pool.clone()).with_uniqueness_strategy(strategy);
+      let mut tx = pool.begin().await.unwrap();
+      let id = svc
+        .insert_user(
+          &mut tx,
+          &new_user(name, UserStatus::Pending, UserRole::User),
+        )
+        .await
+        .unwrap();
+      tx.commit().await.unwrap();
+      assert!(id > 0);
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn register_assigns_user_id_from_db(pool: PgPool) {
+    let request = RegisterRequest {
+      user_name: "new_member".into(),
+      password: PASSWORD.into(),
+      first_name: None,
+      last_name: None,
+      email: None,
+      phone: None,
+      birth_date: None,
+      form_nonce: None,
+    };
+    let res = UserService::new(pool.clone())
+      .register(request)
+      .await
+      .unwrap();
+
+    let user = PgUserRepository::new(pool.clone())
+      .find_by_username_any_status(&UserName::new("new_member", true).unwrap().unwrap())
+      .await
+      .unwrap()
+      .unwrap();
+    assert!(!user.user_id.is_unsaved());
+    assert_eq!(user.public_id.as_str(), res.public_id);
+    let auth = PgUserAuthRepository::new(pool)
+      .find(user.user_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert!(auth.current_hash.verify(PASSWORD));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn tagged_address_conflicts_with_untagged_when_stripping(pool: PgPool) {
+    EmailPolicy {
+      strip_plus_addressing: true,
+    }
+    .install();
+    assert!(EmailPolicy::current().strip_plus_addressing);
+    let request = |user_name: &str, email: &str| RegisterRequest {
+      email: Some(email.into()),
+      ..nonce_request(user_name, None)
+    };
+    let svc = UserService::new(pool);
+
+    svc
+      .register(request("plain_gmail", "user@gmail.com"))
+      .await
+      .unwrap();
+    let err = svc
+      .register(request("tagged_gmail", "user+tag@gmail.com"))
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::Conflict(Some(m)) if m.contains("email_address")));
+  }
+
+  fn nonce_request(user_name: &str, form_nonce: Option<&str>) -> RegisterRequest {
+    RegisterRequest {
+      user_name: user_name.into(),
+      password: PASSWORD.into(),
+      first_name: None,
+      last_name: None,
+      email: None,
+      phone: None,
+      birth_date: None,
+      form_nonce: form_nonce.map(str::to_owned),
+    }
+  }
+
+  fn nonce_service(pool: PgPool) -> UserService {
+    UserService::new(pool).with_form_nonce_ttl(Some(Duration::minutes(10)))
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn register_consumes_valid_form_nonce(pool: PgPool) {
+    seed_user(&pool, "taken_name", UserStatus::Active, UserRole::User).await;
+    let svc = nonce_service(pool);
+    let nonce = svc.issue_form_nonce().await.unwrap().form_nonce;
+
+    // 登録に失敗した場合は使用済みにならない
+    let err = svc
+      .register(nonce_request("taken_name", Some(&nonce)))
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::Conflict(Some(m)) if m.contains("user_name")));
+    svc
+      .register(nonce_request("nonce_user", Some(&nonce)))
+      .await
+      .unwrap();
+
+    // 必須の設定では省略できない
+    let err = svc
+      .register(nonce_request("nonce_user2", None))
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::UnprocessableContent(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn register_rejects_reused_form_nonce(pool: PgPool) {
+    let svc = nonce_service(pool);
+    let nonce = svc.issue_form_nonce().await.unwrap().form_nonce;
+    svc
+      .register(nonce_request("double_click", Some(&nonce)))
+      .await
+      .unwrap();
+    let err = svc
+      .register(nonce_request("double_click", Some(&nonce)))
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::Conflict(Some(m)) if m.contains("既に送信")));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn register_rejects_expired_form_nonce(pool: PgPool) {
+    PgFormNonceStore::new(pool.clone())
+      .issue("expired_nonce", Utc::now() - Duration::minutes(1))
+      .await
+      .unwrap();
+    let err = nonce_service(pool.clone())
+      .register(nonce_request("late_user", Some("expired_nonce")))
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::UnprocessableContent(Some(m)) if m.contains("有効期限")));
+
+    // 無効に設定した場合は発行しない
+    let err = UserService::new(pool).issue_form_nonce().await.unwrap_err();
+    assert!(matches!(err, AppError::NotFound(_)));
+  }
+
+  async fn stored_status(pool: &PgPool, user_id: UserId) -> UserStatus {
+    let status = sqlx::query_scalar!(
+      "SELECT status FROM users WHERE user_id = $1",
+      user_id.as_i64()
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap();
+    UserStatus::try_from(status).unwrap()
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn verify_email_activates_pending_user_once(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "verify_me", UserStatus::Pending, UserRole::User).await;
+    let svc = UserService::new(pool.clone());
+    let token = svc
+      .issue_verification_token(user.user_id)
+      .await
+      .unwrap()
+      .token;
+
+    let res = svc.verify_email(&token).await.unwrap();
+    assert_eq!(res.result, "verified");
+    assert_eq!(res.public_id, user.public_id.as_str());
+    assert_eq!(stored_status(&pool, user.user_id).await, UserStatus::Active);
+
+    // 確認リンクの二重クリックは確認済みとして成功する
+    let res = svc.verify_email(&token).await.unwrap();
+    assert_eq!(res.result, "already_verified");
+    assert_eq!(stored_status(&pool, user.user_id).await, UserStatus::Active);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn verify_email_accepts_new_token_for_verified_user(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "verified", UserStatus::Active, UserRole::User).await;
+    let svc = UserService::new(pool.clone());
+    let token = svc
+      .issue_verification_token(user.user_id)
+      .await
+      .unwrap()
+      .token;
+    let res = svc.verify_email(&token).await.unwrap();
+    assert_eq!(res.result, "already_verified");
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn verify_email_rejects_expired_and_unknown_tokens(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "late_verify", UserStatus::Pending, UserRole::User).await;
+    // 時刻のずれの許容範囲を超えて失効したトークン
+    let ttl = -ClockSkew::current().leeway - Duration::seconds(1);
+    let svc = UserService::new(pool.clone()).with_verification_token_ttl(ttl);
+    let token = svc
+      .issue_verification_token(user.user_id)
+      .await
+      .unwrap()
+      .token;
+
+    let err = svc.verify_email(&token).await.unwrap_err();
+    assert!(matches!(err, AppError::BadRequest(Some(m)) if m.contains("有効期限")));
+    let err = svc.verify_email("unknown").await.unwrap_err();
+    assert!(matches!(err, AppError::BadRequest(_)));
+    assert_eq!(
+      stored_status(&pool, user.user_id).await,
+      UserStatus::Pending
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn verify_email_rejects_suspended_user(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "suspended", UserStatus::Suspended, UserRole::User).await;
+    let svc = UserService::new(pool.clone());
+    let token = svc
+      .issue_verification_token(user.user_id)
+      .await
+      .unwrap()
+      .token;
+
+    let err = svc.verify_email(&token).await.unwrap_err();
+    assert!(matches!(err, AppError::Conflict(_)));
+    // ロールバックされ，トークンは未使用のまま残る
+    let err = svc.verify_email(&token).await.unwrap_err();
+    assert!(matches!(err, AppError::Conflict(_)));
+    assert_eq!(
+      stored_status(&pool, user.user_id).await,
+      UserStatus::Suspended
+    );
+  }
+
+  fn login_request(user_name: &str, password: &str) -> LoginRequest {
+    LoginRequest {
+      user_name: user_name.to_owned(),
+      password: password.to_owned(),
+      remember_me: false,
+    }
+  }
+
+  async fn fail_times(pool: &PgPool, user: &User) -> u16 {
+    PgUserAuthRepository::new(pool.clone())
+      .find(user.user_id)
+      .await
+      .unwrap()
+      .unwrap()
+      .login_fail_times
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn login_issues_session_and_resets_fail_count(pool: PgPool) {
+    let (user, mut auth) = seed_user(&pool, "login_user", UserStatus::Active, UserRole::User).await;
+    auth.login_fail_times = 2;
+    PgUserAuthRepository::new(pool.clone())
+      .update(&auth)
+      .await
+      .unwrap();
+    let client = ClientInfo {
+      user_agent: Some("curl/8.0".into()),
+      ip: Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))),
+    };
+
+    let LoginOutcome::Authenticated(res) = UserService::new(pool.clone())
+      .login(login_request("login_user", PASSWORD), client.clone())
+      .await
+      .unwrap()
+    else {
+      panic!("password change should not be required");
+    };
+    assert_eq!(res.public_id, user.public_id.as_str());
+
+    let sid = SessionId::from_string(&res.session_id, true)
+      .unwrap()
+      .unwrap();
+    let session = PgSessionRepository::new(pool.clone())
+      .find(sid)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(session.user_id, user.user_id);
+    assert_eq!(session.user_agent, client.user_agent);
+    assert_eq!(session.ip, client.ip);
+    assert_eq!(fail_times(&pool, &user).await, 0);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn login_with_wrong_password_or_unknown_user_is_unauthorized(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "login_user", UserStatus::Active, UserRole::User).await;
+    let svc = UserService::new(pool.clone());
+
+    let mut messages = Vec::new();
+    for (name, password) in [("login_user", "wrong-password"), ("nobody", PASSWORD)] {
+      let err = svc
+        .login(login_request(name, password), ClientInfo::default())
+        .await
+        .unwrap_err();
+      let AppError::Unauthorized(Some(m)) = err else {
+        panic!("unexpected error: {err:?}");
+      };
+      messages.push(m);
+    }
+    // どちらが誤っているかを区別しない
+    assert_eq!(messages[0], messages[1]);
+    assert_eq!(fail_times(&pool, &user).await, 1);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn login_denied_by_status_only_after_password_matches(pool: PgPool) {
+    seed_user(&pool, "pending_user", UserStatus::Pending, UserRole::User).await;
+    let svc = UserService::new(pool);
+    let login = |password: &'static str| {
+      svc.login(
+        login_request("pending_user", password),
+        ClientInfo::default(),
+      )
+    };
+
+    let err = login(PASSWORD).await.unwrap_err();
+    assert!(matches!(err, AppError::Forbidden(Some(m)) if m == "EMAIL_UNVERIFIED"));
+    let err = login("wrong-password").await.unwrap_err();
+    assert!(matches!(err, AppError::Unauthorized(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn locked_account_is_rejected_like_invalid_credentials(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "locked_user", UserStatus::Active, UserRole::User).await;
+    let svc = UserService::new(pool.clone());
+    let login = |password: &'static str| {
+      svc.login(
+        login_request("locked_user", password),
+        ClientInfo::default(),
+      )
+    };
+
+    for _ in 0..UserAuth::MAX_LOGIN_FAIL_TIMES {
+      login("wrong-password").await.unwrap_err();
+    }
+    let auth = PgUserAuthRepository::new(pool.clone())
+      .find(user.user_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert!(auth.is_locked(Utc::now()));
+    let locked_until = auth.locked_until;
+
+    // パスワードの正誤・ユーザーの存在によらず同じ応答を返し，推測の成否を判別させない
+    let AppError::Unauthorized(unknown) = svc
+      .login(login_request("nobody", PASSWORD), ClientInfo::default())
+      .await
+      .unwrap_err()
+    else {
+      panic!("unknown user should be unauthorized");
+    };
+    for password in [PASSWORD, "wrong-password"] {
+      let err = login(password).await.unwrap_err();
+      assert!(matches!(err, AppError::Unauthorized(m) if m == unknown));
+    }
+    // ロック中の試行では期限を延長しない
+    let auth = PgUserAuthRepository::new(pool.clone())
+      .find(user.user_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(auth.locked_until, locked_until);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn lock_expires_after_lock_duration(pool: PgPool) {
+    let (user, mut auth) =
+      seed_user(&pool, "expired_lock", UserStatus::Active, UserRole::User).await;
+    let repo = PgUserAuthRepository::new(pool.clone());
+    auth.login_fail_times = 3;
+    auth.locked_until = Some(Utc::now() - Duration::seconds(1));
+    repo.update(&auth).await.unwrap();
+
+    let res = UserService::new(pool.clone())
+      .login(
+        login_request("expired_lock", PASSWORD),
+        ClientInfo::default(),
+      )
+      .await;
+    assert!(matches!(res, Ok(LoginOutcome::Authenticated(_))));
+    let auth = repo.find(user.user_id).await.unwrap().unwrap();
+    assert_eq!(auth.login_fail_times, 0);
+    assert_eq!(auth.locked_until, None);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn login_records_last_login_at(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "last_login", UserStatus::Active, UserRole::User).await;
+    let repo = PgUserRepository::new(pool.clone());
+    let seeded = repo.find_by_user_id(user.user_id).await.unwrap().unwrap();
+    assert_eq!(seeded.last_login_at, None);
+    let before = Utc::now();
+    UserService::new(pool.clone())
+      .login(login_request("last_login", PASSWORD), ClientInfo::default())
+      .await
+      .unwrap();
+
+    let stored = repo.find_by_user_id(user.user_id).await.unwrap().unwrap();
+    assert!(stored.last_login_at.is_some_and(|at| at >= before));
+    // ログインはプロフィールの変更として扱わない
+    assert_eq!(stored.updated_at, seeded.updated_at);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn login_throttle_counts_normalized_user_name(pool: PgPool) {
+    seed_user(&pool, "admin_user", UserStatus::Active, UserRole::User).await;
+    let mut config = AppConfig::new().unwrap().rate_limit;
+    config.login_user_max_attempts = 2;
+    let svc = UserService::new(pool.clone()).with_login_throttle(LoginThrottle::new(
+      &config,
+      Arc::new(MemoryRateLimitStore::new()),
+    ));
+
+    // 全角の表記は正規化後に同じユーザー名になるため，同じカウンタで数える
+    for name in ["admin_user", "ａｄｍｉｎ_user"] {
+      let err = svc
+        .login(login_request(name, "wrong-password"), ClientInfo::default())
+        .await
+        .unwrap_err();
+      assert!(matches!(err, AppError::Unauthorized(_)));
+    }
+    let err = svc
+      .login(
+        login_request("ａdmin_user", PASSWORD),
+        ClientInfo::default(),
+      )
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::TooManyRequests(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn login_throttle_rejects_repeated_failures(pool: PgPool) {
+    seed_user(&pool, "login_user", UserStatus::Active, UserRole::User).await;
+    let mut config = AppConfig::new().unwrap().rate_limit;
+    config.login_user_max_attempts = 2;
+    let svc = UserService::new(pool.clone()).with_login_throttle(LoginThrottle::new(
+      &config,
+      Arc::new(MemoryRateLimitStore::new()),
+    ));
+
+    for _ in 0..2 {
+      let err = svc
+        .login(
+          login_request("login_user", "wrong-password"),
+          ClientInfo::default(),
+        )
+        .await
+        .unwrap_err();
+      assert!(matches!(err, AppError::Unauthorized(_)));
+    }
+    // 上限に達した後は正しいパスワードでも429を返す
+    let err = svc
+      .login(login_request("Login_User", PASSWORD), ClientInfo::default())
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::TooManyRequests(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn login_rehashes_bcrypt_password_to_argon2(pool: PgPool) {
+    let (user, mut auth) =
+      seed_user(&pool, "bcrypt_user", UserStatus::Active, UserRole::User).await;
+    auth.current_hash = UserPassword::from_hash(bcrypt::hash(PASSWORD, 4).unwrap()).unwrap();
+    let auth_repo = PgUserAuthRepository::new(pool.clone());
+    auth_repo.update(&auth).await.unwrap();
+
+    UserService::new(pool)
+      .login(
+        login_request("bcrypt_user", PASSWORD),
+        ClientInfo::default(),
+      )
+      .await
+      .unwrap();
+    let stored = auth_repo.find(user.user_id).await.unwrap().unwrap();
+    assert_eq!(stored.current_hash.scheme(), HashScheme::Argon2);
+    assert!(stored.current_hash.verify(PASSWORD));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn change_password_rotates_history_and_rejects_reuse(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "rotate_user", UserStatus::Active, UserRole::User).await;
+    let svc = UserService::new(pool.clone());
+    let change = |old: &str, new: &str| {
+      svc.change_password(user.user_id, old.into(), new.into(), ClientInfo::default())
+    };
+    let passwords = [
+      PASSWORD,
+      "Vq8#tR2!mZ5@kW9$",
+      "Hn4%pX7&cJ3*bL6^",
+      "Ds2(fG9)yU5!eA8#",
+    ];
+
+    change(passwords[0], passwords[1]).await.unwrap();
+    change(passwords[1], passwords[2]).await.unwrap();
+    let auth = PgUserAuthRepository::new(pool.clone())
+      .find(user.user_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert!(auth.current_hash.verify(passwords[2]));
+    assert!(auth.prev_hash1.as_ref().unwrap().verify(passwords[1]));
+    assert!(auth.prev_hash2.as_ref().unwrap().verify(passwords[0]));
+
+    // current・prev_hash1・prev_hash2のいずれとも同じパスワードは使用できない
+    for reused in &passwords[..3] {
+      let err = change(passwords[2], reused).await.unwrap_err();
+      assert!(matches!(err, AppError::UnprocessableContent(Some(m)) if m.contains("直近")));
+    }
+
+    // 最も古い履歴は破棄される
+    change(passwords[2], passwords[3]).await.unwrap();
+    let auth = PgUserAuthRepository::new(pool)
+      .find(user.user_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(auth.password_history_depth(), 2);
+    assert!(auth.recent_hashes().all(|h| !h.verify(passwords[0])));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn change_password_requires_current_password(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "wrong_old_user", UserStatus::Active, UserRole::User).await;
+    let err = UserService::new(pool.clone())
+      .change_password(
+        user.user_id,
+        "wrong-password".into(),
+        "Vq8#tR2!mZ5@kW9$".into(),
+        ClientInfo::default(),
+      )
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::Unauthorized(_)));
+    // ログインの失敗と同じく数える
+    assert_eq!(fail_times(&pool, &user).await, 1);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn change_password_failures_are_throttled(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "guessed_user", UserStatus::Active, UserRole::User).await;
+    let mut config = AppConfig::new().unwrap().rate_limit;
+    config.login_user_max_attempts = 2;
+    let svc = UserService::new(pool.clone()).with_login_throttle(LoginThrottle::new(
+      &config,
+      Arc::new(MemoryRateLimitStore::new()),
+    ));
+    let change = |old: &'static str| {
+      svc.change_password(
+        user.user_id,
+        old.into(),
+        "Vq8#tR2!mZ5@kW9$".into(),
+        ClientInfo::default(),
+      )
+    };
+
+    for _ in 0..config.login_user_max_attempts {
+      let err = change("wrong-password").await.unwrap_err();
+      assert!(matches!(err, AppError::Unauthorized(_)));
+    }
+    // 上限に達した後は，正しいパスワードでも検証せずに拒否する
+    let err = change(PASSWORD).await.unwrap_err();
+    assert!(matches!(err, AppError::TooManyRequests(_)));
+    // 同じユーザー名のログインも制限される
+    let err = svc
+      .login(
+        login_request("guessed_user", PASSWORD),
+        ClientInfo::default(),
+      )
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::TooManyRequests(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn forced_password_change_issues_limited_session_until_changed(pool: PgPool) {
+    let (user, mut auth) =
+      seed_user(&pool, "forced_user", UserStatus::Active, UserRole::User).await;
+    auth.must_change_password = true;
+    let auth_repo = PgUserAuthRepository::new(pool.clone());
+    auth_repo.update(&auth).await.unwrap();
+    let svc = UserService::new(pool.clone());
+
+    let outcome = svc
+      .login(
+        login_request("forced_user", PASSWORD),
+        ClientInfo::default(),
+      )
+      .await
+      .unwrap();
+    let LoginOutcome::PasswordChangeRequired(res) = outcome else {
+      panic!("unexpected outcome: {outcome:?}");
+    };
+    let sid = SessionId::from_string(&res.session_id, true)
+      .unwrap()
+      .unwrap();
+    let session = PgSessionRepository::new(pool.clone())
+      .find(sid)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(session.scope, SessionScope::PasswordChange);
+    assert_eq!(
+      session.expires_at - session.created_at,
+      Session::PASSWORD_CHANGE_TTL
+    );
+
+    // 変更に成功すると要求が解除され，通常のセッションが発行される
+    let new_password = "Vq8#tR2!mZ5@kW9$";
+    svc
+      .change_password(
+        user.user_id,
+        PASSWORD.into(),
+        new_password.into(),
+        ClientInfo::default(),
+      )
+      .await
+      .unwrap();
+    assert!(
+      !auth_repo
+        .find(user.user_id)
+        .await
+        .unwrap()
+        .unwrap()
+        .must_change_password
+    );
+    let outcome = svc
+      .login(
+        login_request("forced_user", new_password),
+        ClientInfo::default(),
+      )
+      .await
+      .unwrap();
+    assert!(matches!(outcome, LoginOutcome::Authenticated(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn logout_deletes_session(pool: PgPool) {
+    seed_user(&pool, "logout_user", UserStatus::Active, UserRole::User).await;
+    let svc = UserService::new(pool.clone());
+    let LoginOutcome::Authenticated(res) = svc
+      .login(
+        login_request("logout_user", PASSWORD),
+        ClientInfo::default(),
+      )
+      .await
+      .unwrap()
+    else {
+      panic!("password change should not be required");
+    };
+    let sid = SessionId::from_string(&res.session_id, true)
+      .unwrap()
+      .unwrap();
+
+    svc.logout(sid.clone()).await.unwrap();
+    let repo = PgSessionRepository::new(pool);
+    assert!(repo.find(sid.clone()).await.unwrap().is_none());
+
+    // 削除済みのセッションは404
+    let err = svc.logout(sid).await.unwrap_err();
+    assert!(matches!(err, AppError::NotFound(_)));
+  }
+
+  async fn status_of(pool: &PgPool, user: &User) -> UserStatus {
+    PgUserRepository::new(pool.clone())
+      .find_by_public_id(&user.public_id)
+      .await
+      .unwrap()
+      .unwrap()
+      .status
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn owner_deactivates_and_admin_deletes(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "leaving_user", UserStatus::Active, UserRole::User).await;
+    let (admin, _) = seed_user(&pool, "status_admin", UserStatus::Active, UserRole::Admin).await;
+    let session = Session::issue(
+      user.user_id,
+      Utc::now(),
+      &SessionPolicy::default(),
+      false,
+      None,
+      None,
+    );
+    PgSessionRepository::new(pool.clone())
+      .insert(&session)
+      .await
+      .unwrap();
+    let svc = UserService::new(pool.clone());
+
+    let res = svc.deactivate(&user, user.user_id).await.unwrap();
+    assert_eq!((res.status, res.sessions_revoked), ("deactivated", 1));
+    assert_eq!(status_of(&pool, &user).await, UserStatus::Deactivated);
+
+    let res = svc.delete(&admin, user.user_id).await.unwrap();
+    assert_eq!(res.status, "deleted");
+    assert_eq!(status_of(&pool, &user).await, UserStatus::Deleted);
+    let logs = svc.audit_repo.find_by_target(user.user_id).await.unwrap();
+    assert_eq!(logs.len(), 2);
+    assert!(logs.iter().all(|l| l.action == AuditAction::ChangeStatus));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn illegal_owner_transitions_are_conflicts(pool: PgPool) {
+    let (admin, _) = seed_user(&pool, "status_admin", UserStatus::Active, UserRole::Admin).await;
+    let svc = UserService::new(pool.clone());
+    for (name, status, deactivate) in [
+      ("already_deleted", UserStatus::Deleted, true),
+      ("suspended_user", UserStatus::Suspended, false),
+      ("already_deactivated", UserStatus::Deactivated, true),
+    ] {
+      let (user, _) = seed_user(&pool, name, status, UserRole::User).await;
+      let result = if deactivate {
+        svc.deactivate(&admin, user.user_id).await
+      } else {
+        svc.delete(&admin, user.user_id).await
+      };
+      assert!(matches!(result, Err(AppError::Conflict(_))), "{name}");
+      assert_eq!(status_of(&pool, &user).await, status);
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn status_change_requires_owner_or_outranking_admin(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "target_user", UserStatus::Active, UserRole::User).await;
+    let (other, _) = seed_user(&pool, "other_user", UserStatus::Active, UserRole::User).await;
+    let (admin, _) = seed_user(&pool, "status_admin", UserStatus::Active, UserRole::Admin).await;
+    let (peer, _) = seed_user(&pool, "peer_admin", UserStatus::Active, UserRole::Admin).await;
+    let svc = UserService::new(pool.clone());
+
+    let err = svc.delete(&other, user.user_id).await.unwrap_err();
+    assert!(matches!(err, AppError::Forbidden(_)));
+    let err = svc.delete(&admin, peer.user_id).await.unwrap_err();
+    assert!(matches!(err, AppError::Forbidden(_)));
+    assert_eq!(status_of(&pool, &peer).await, UserStatus::Active);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn get_by_public_id_allows_only_owner_and_staff(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "get_target", UserStatus::Suspended, UserRole::User).await;
+    let (other, _) = seed_user(&pool, "get_other", UserStatus::Active, UserRole::User).await;
+    let (support, _) = seed_user(&pool, "get_support", UserStatus::Active, UserRole::Support).await;
+    let svc = UserService::new(pool.clone());
+
+    // 権限が無い場合は，存在しない公開IDでも既存の公開IDでも同じ403を返す
+    for pid in [user.public_id.clone(), PublicId::new()] {
+      let err = svc.get_by_public_id(&other, pid).await.unwrap_err();
+      assert!(matches!(err, AppError::Forbidden(_)));
+    }
+    for actor in [&user, &support] {
+      let found = svc
+        .get_by_public_id(actor, user.public_id.clone())
+        .await
+        .unwrap();
+      assert_eq!(found.user_id, user.user_id);
+    }
+    let err = svc
+      .get_by_public_id(&support, PublicId::new())
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::NotFound(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn status_change_by_public_id_checks_permission_before_lookup(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "target_user", UserStatus::Suspended, UserRole::User).await;
+    let (other, _) = seed_user(&pool, "other_user", UserStatus::Active, UserRole::User).await;
+    let (admin, _) = seed_user(&pool, "status_admin", UserStatus::Active, UserRole::Admin).await;
+    let svc = UserService::new(pool.clone());
+
+    // 権限が無い場合は，存在しない公開IDでも既存の公開IDでも同じ403を返す
+    for pid in [user.public_id.clone(), PublicId::new()] {
+      let err = svc
+        .change_status_by_public_id(&other, pid, UserStatus::Deleted)
+        .await
+        .unwrap_err();
+      assert!(matches!(err, AppError::Forbidden(_)));
+    }
+    // 遷移できないステータスの指定も，権限を確認してから現在のステータスを含めずに拒否する
+    let err = svc
+      .change_status_by_public_id(&other, user.public_id.clone(), UserStatus::Active)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::Forbidden(_)));
+    let err = svc
+      .change_status_by_public_id(&admin, user.public_id.clone(), UserStatus::Active)
+      .await
+      .unwrap_err();
+    assert!(
+      matches!(err, AppError::Conflict(Some(m)) if m == "ステータスをactiveに変更することはできません。")
+    );
+
+    let err = svc
+      .change_status_by_public_id(&admin, PublicId::new(), UserStatus::Deleted)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::NotFound(_)));
+    let res = svc
+      .change_status_by_public_id(&admin, user.public_id.clone(), UserStatus::Deleted)
+      .await;
+    assert!(matches!(res, Err(AppError::Conflict(_))));
+    assert_eq!(status_of(&pool, &user).await, UserStatus::Suspended);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn rotate_public_id_changes_id_and_randomart(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "rotator", UserStatus::Active, UserRole::User).await;
+    let svc = UserService::new(pool.clone());
+
+    let res = svc.rotate_public_id(&user, &user.public_id).await.unwrap();
+    assert_eq!(res.previous_public_id, user.public_id.as_str());
+    assert_ne!(res.public_id, user.public_id.as_str());
+    assert_ne!(res.randomart, user.randomart);
+
+    // user_idは維持され，新しい公開IDで取得できること
+    let repo = PgUserRepository::new(pool.clone());
+    let new_id = PublicId::from_string(&res.public_id, true)
+      .unwrap()
+      .unwrap();
+    let rotated = repo.find_by_public_id(&new_id).await.unwrap().unwrap();
+    assert_eq!(rotated.user_id, user.user_id);
+    assert_eq!(rotated.randomart, res.randomart);
+    assert!(
+      repo
+        .find_by_public_id(&user.public_id)
+        .await
+        .unwrap()
+        .is_none()
+    );
+
+    // 監査ログが記録されていること
+    let logs = PgAuditLogRepository::new(pool)
+      .find_by_target(user.user_id)
+      .await
+      .unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].action, AuditAction::RotatePublicId);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn rotate_public_id_rolls_back_when_audit_fails(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "rotate_tx", UserStatus::Active, UserRole::User).await;
+    sqlx::query("ALTER TABLE audit_logs ADD CONSTRAINT reject_insert CHECK (false) NOT VALID")
+      .execute(&pool)
+      .await
+      .unwrap();
+    let svc = UserService::new(pool.clone());
+
+    assert!(svc.rotate_public_id(&user, &user.public_id).await.is_err());
+    let stored = PgUserRepository::new(pool)
+      .find_by_user_id(user.user_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(stored.public_id, user.public_id);
+    assert_eq!(stored.randomart, user.randomart);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn rotate_public_id_respects_request_deadline(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "rotate_late", UserStatus::Active, UserRole::User).await;
+    let svc =
+      UserService::new(pool.clone()).with_deadline(Deadline::after(std::time::Duration::ZERO));
+
+    let err = svc
+      .rotate_public_id(&user, &user.public_id)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::RequestTimeout(_)));
+    let stored = PgUserRepository::new(pool)
+      .find_by_user_id(user.user_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(stored.public_id, user.public_id);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn rotate_public_id_requires_self_or_admin(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "owner", UserStatus::Active, UserRole::User).await;
+    let (other, _) = seed_user(&pool, "other", UserStatus::Active, UserRole::Moderator).await;
+    let (admin, _) = seed_user(&pool, "admin", UserStatus::Active, UserRole::Admin).await;
+    let svc = UserService::new(pool);
+
+    let err = svc
+      .rotate_public_id(&other, &user.public_id)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::Forbidden(_)));
+    assert!(svc.rotate_public_id(&admin, &user.public_id).await.is_ok());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn stored_and_recomputed_randomart_agree(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "art_user", UserStatus::Active, UserRole::User).await;
+
+    let mut arts = Vec::new();
+    for source in [RandomartSource::Stored, RandomartSource::Recompute] {
+      let svc = UserService::new(pool.clone()).with_randomart_source(source);
+      let res = svc.randomart(&user.public_id).await.unwrap();
+      assert_eq!(res.public_id, user.public_id.as_str());
+      arts.push(res.randomart);
+    }
+    assert_eq!(arts[0], arts[1]);
+    assert_eq!(arts[0], user.randomart);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn username_availability_reports_reason(pool: PgPool) {
+    seed_user(&pool, "taken_name", UserStatus::Pending, UserRole::User).await;
+    let svc = UserService::new(pool);
+
+    let res = svc.username_availability("free_name").await.unwrap();
+    assert!(res.available);
+    assert_eq!(res.reason, None);
+
+    let res = svc.username_availability("taken_name").await.unwrap();
+    assert!(!res.available);
+    assert_eq!(res.reason, Some(UnavailableReason::Taken));
+
+    for q in ["", "a b!"] {
+      let res = svc.username_availability(q).await.unwrap();
+      assert!(!res.available);
+      assert_eq!(res.reason, Some(UnavailableReason::InvalidFormat));
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn strict_username_check_hides_reason(pool: PgPool) {
+    seed_user(&pool, "taken_name", UserStatus::Active, UserRole::User).await;
+    let svc = UserService::new(pool).with_strict_username_check(true);
+    for q in ["taken_name", "a b!"] {
+      let res = svc.username_availability(q).await.unwrap();
+      assert!(!res.available);
+      assert_eq!(res.reason, None);
+    }
+    assert!(
+      svc
+        .username_availability("free_name")
+        .await
+        .unwrap()
+        .available
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn randomart_of_unknown_user_is_not_found(pool: PgPool) {
+    let err = UserService::new(pool)
+      .randomart(&PublicId::new())
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::NotFound(_)));
+  }
+
+  /// 電話番号を設定したユーザーを登録する
+  async fn seed_with_phone(pool: &PgPool, name: &str) -> User {
+    let (mut user, _) = seed_user(pool, name, UserStatus::Active, UserRole::User).await;
+    user.full_name = UserFullName::new("Taro", "Yamada").unwrap();
+    user.phone = PhoneNumber::new("09012345678", true).unwrap();
+    PgUserRepository::new(pool.clone())
+      .update_profile(&user)
+      .await
+      .unwrap();
+    user
+  }
+
+  async fn patch(pool: &PgPool, user: &User, json: &str) -> ProfileResponse {
+    let request: UpdateProfileRequest = serde_json::from_str(json).unwrap();
+    UserService::new(pool.clone())
+      .update_profile(user, &user.public_id, request, &Precondition::None)
+      .await
+      .unwrap()
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn update_profile_omitted_field_is_unchanged(pool: PgPool) {
+    let user = seed_with_phone(&pool, "omit_user").await;
+    let res = patch(&pool, &user, r#"{"email":"omit@example.com"}"#).await;
+    assert_eq!(res.phone.as_deref(), Some("09012345678"));
+    assert_eq!(res.email.as_deref(), Some("omit@example.com"));
+    assert_eq!(res.first_name.as_deref(), Some("Taro"));
+
+    let stored = PgUserRepository::new(pool)
+      .find_by_public_id(&user.public_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(stored.phone, user.phone);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn update_profile_explicit_null_clears(pool: PgPool) {
+    let user = seed_with_phone(&pool, "null_user").await;
+    let res = patch(&pool, &user, r#"{"phone":null,"last_name":null}"#).await;
+    assert_eq!(res.phone, None);
+    assert_eq!(res.last_name, None);
+    assert_eq!(res.first_name.as_deref(), Some("Taro"));
+
+    let stored = PgUserRepository::new(pool)
+      .find_by_public_id(&user.public_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(stored.phone, None);
+    assert_eq!(stored.full_name.unwrap().last(), None);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn update_profile_value_sets(pool: PgPool) {
+    let user = seed_with_phone(&pool, "set_user").await;
+    let res = patch(
+      &pool,
+      &user,
+      r#"{"phone":"08011112222","first_name":"Hanako","birth_date":"1990-01-02"}"#,
+    )
+    .await;
+    assert_eq!(res.phone.as_deref(), Some("08011112222"));
+    assert_eq!(res.first_name.as_deref(), Some("Hanako"));
+    assert_eq!(res.last_name.as_deref(), Some("Yamada"));
+    assert_eq!(
+      res.birth_date.map(|d| d.to_string()).as_deref(),
+      Some("1990-01-02")
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn stale_version_update_is_rejected(pool: PgPool) {
+    let user = seed_with_phone(&pool, "stale_user").await;
+    let svc = UserService::new(pool.clone());
+    let fetched = svc
+      .get_by_public_id(&user, user.public_id.clone())
+      .await
+      .unwrap()
+      .updated_at;
+    let update = |json: &str, precondition: Precondition| {
+      let request: UpdateProfileRequest = serde_json::from_str(json).unwrap();
+      let svc = svc.clone();
+      let user = user.clone();
+      async move {
+        svc
+          .update_profile(&user, &user.public_id, request, &precondition)
+          .await
+      }
+    };
+
+    let current = Precondition::IfMatch(vec![etag(fetched)]);
+    let res = update(r#"{"phone":"09000000001"}"#, current.clone())
+      .await
+      .unwrap();
+    assert!(res.updated_at > fetched);
+
+    // 取得時の版を指定した2件目の更新は，1件目を上書きしない
+    let err = update(r#"{"phone":"09000000002"}"#, current)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::PreconditionFailed(_)));
+    let stored = svc
+      .get_by_public_id(&user, user.public_id.clone())
+      .await
+      .unwrap();
+    assert_eq!(stored.phone.as_ref().unwrap().as_str(), "09000000001");
+
+    // 取得後に他の更新があった場合は，事前条件の指定がなくても上書きしない
+    let repo = PgUserRepository::new(pool);
+    assert_eq!(
+      repo
+        .update_profile_if_unmodified(&stored, fetched)
+        .await
+        .unwrap(),
+      None
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn immutable_field_cannot_be_changed(pool: PgPool) {
+    let mut user = seed_with_phone(&pool, "immutable_user").await;
+    user.birth_date = Some(BirthDate::from_naive_date("1990-01-02".parse().unwrap()));
+    PgUserRepository::new(pool.clone())
+      .update_profile(&user)
+      .await
+      .unwrap();
+    let svc = UserService::new(pool.clone()).with_immutable_fields(vec![ProfileField::BirthDate]);
+    let update = |json: &str| {
+      let request: UpdateProfileRequest = serde_json::from_str(json).unwrap();
+      svc.update_profile(&user, &user.public_id, request, &Precondition::None)
+    };
+
+    for json in [r#"{"birth_date":"2000-01-01"}"#, r#"{"birth_date":null}"#] {
+      let err = update(json).await.unwrap_err();
+      assert!(matches!(err, AppError::Forbidden(Some(m)) if m.contains("birth_date")));
+    }
+    let stored = PgUserRepository::new(pool.clone())
+      .find_by_public_id(&user.public_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(stored.birth_date, user.birth_date);
+
+    // 他の項目，及び現在と同じ値の指定は変更できる
+    let res = update(r#"{"phone":"08011112222","birth_date":"1990-01-02"}"#)
+      .await
+      .unwrap();
+    assert_eq!(res.phone.as_deref(), Some("08011112222"));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn update_profile_requires_self_or_admin(pool: PgPool) {
+    let user = seed_with_phone(&pool, "profile_owner").await;
+    let (other, _) = seed_user(&pool, "profile_other", UserStatus::Active, UserRole::User).await;
+    let err = UserService::new(pool)
+      .update_profile(
+        &other,
+        &user.public_id,
+        UpdateProfileRequest::default(),
+        &Precondition::None,
+      )
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::Forbidden(_)));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn recovery_email_is_masked_for_owner_only(pool: PgPool) {
+    let user = seed_with_phone(&pool, "recovery_owner").await;
+    let res = patch(
+      &pool,
+      &user,
+      r#"{"email":"main@example.com","recovery_email":"backup@example.com"}"#,
+    )
+    .await;
+    assert_eq!(res.recovery_email.as_deref(), Some("b***@example.com"));
+
+    let stored = PgUserRepository::new(pool.clone())
+      .find_by_public_id(&user.public_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      stored.recovery_email.unwrap().as_str(),
+      "backup@example.com"
+    );
+
+    // 本人以外(Admin)には返さない
+    let (admin, _) = seed_user(&pool, "recovery_admin", UserStatus::Active, UserRole::Admin).await;
+    let res = UserService::new(pool)
+      .update_profile(
+        &admin,
+        &user.public_id,
+        UpdateProfileRequest::default(),
+        &Precondition::None,
+      )
+      .await
+      .unwrap();
+    assert_eq!(res.recovery_email, None);
+    assert!(
+      !serde_json::to_string(&res)
+        .unwrap()
+        .contains("recovery_email")
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn recovery_email_must_differ_from_primary(pool: PgPool) {
+    let user = seed_with_phone(&pool, "recovery_same").await;
+    patch(&pool, &user, r#"{"email":"same@example.com"}"#).await;
+
+    let request: UpdateProfileRequest =
+      serde_json::from_str(r#"{"recovery_email":"SAME@example.com"}"#).unwrap();
+    let err = UserService::new(pool)
+      .update_profile(&user, &user.public_id, request, &Precondition::None)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::UnprocessableContent(Some(m)) if m.contains("recovery_email")));
+  }
+}
@@ -0,0 +1,387 @@
+//! ログイン試行・ユーザー登録のスロットリング
+//! --------------------------------------------------------------
+//! ・ログインはIP単位とユーザー名単位の2つのリミッタを独立して持つ
+//! ・どちらか一方でも上限に達した場合は429を返す
+//! ・ログイン成功時はユーザー名側のみリセットし，IP側は時間経過でのみ減衰する
+//! ・ユーザー登録はメールアドレスのドメイン単位で登録数を制限する
+//! ・カウンタは[rate_limit].backendで選択したストアに保持する
+//! ・キーの可変部分（ユーザー名・ドメイン）はハッシュ化し，ストアのキー長を超えないようにする
+//! --------------------------------------------------------------
+
+use crate::{
+  config::{RateLimit, RateLimitBackend},
+  domain::{repository::RateLimitStore, value_obj::email_address::EmailAddress},
+  infra::{memory::rate_limit_store::MemoryRateLimitStore, pg::rate_limit_store::PgRateLimitStore},
+  interfaces::http::error::{AppError, AppResult},
+};
+use chrono::{DateTime, Utc};
+use sha3::{Digest, Sha3_256};
+use sqlx::PgPool;
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+/// キーごとの上限回数とウィンドウ
+#[derive(Debug, Clone, Copy)]
+struct Limit {
+  max: u32,
+  window: Duration,
+}
+
+/// [rate_limit].backendの設定に従ってカウンタのストアを生成する。
+pub fn store_from_config(config: &RateLimit, pool: PgPool) -> Arc<dyn RateLimitStore> {
+  match config.backend {
+    RateLimitBackend::Memory => Arc::new(MemoryRateLimitStore::new()),
+    RateLimitBackend::Postgres => Arc::new(PgRateLimitStore::new(pool)),
+  }
+}
+
+/// IP単位とユーザー名単位を組み合わせたログインスロットル
+#[derive(Clone)]
+pub struct LoginThrottle {
+  store: Arc<dyn RateLimitStore>,
+  by_ip: Limit,
+  by_user: Limit,
+}
+
+impl LoginThrottle {
+  /// Configの[rate_limit]と任意のストアから生成する。
+  pub fn new(config: &RateLimit, store: Arc<dyn RateLimitStore>) -> Self {
+    Self {
+      store,
+      by_ip: Limit {
+        max: config.login_ip_max_attempts,
+        window: Duration::from_secs(config.login_ip_window_secs),
+      },
+      by_user: Limit {
+        max: config.login_user_max_attempts,
+        window: Duration::from_secs(config.login_user_window_secs),
+      },
+    }
+  }
+
+  /// Configの[rate_limit]から生成する。ストアはbackendの設定に従う。
+  pub fn from_config(config: &RateLimit, pool: PgPool) -> Self {
+    Self::new(config, store_from_config(config, pool))
+  }
+
+  /// ログイン試行前に呼び出す。どちらかが上限に達していれば429を返す。
+  pub async fn check(&self, ip: IpAddr, user_name: &str) -> AppResult<()> {
+    self.check_at(ip, user_name, Utc::now()).await
+  }
+
+  /// ログイン失敗時に呼び出す。両方のカウンタを加算する。
+  pub async fn record_failure(&self, ip: IpAddr, user_name: &str) -> AppResult<()> {
+    let now = Utc::now();
+    self
+      .store
+      .hit(&Self::ip_key(ip), self.by_ip.window, now)
+      .await?;
+    self
+      .store
+      .hit(&Self::user_key(user_name), self.by_user.window, now)
+      .await?;
+    Ok(())
+  }
+
+  /// ログイン成功時に呼び出す。ユーザー名側のカウンタのみリセットする。
+  pub async fn record_success(&self, user_name: &str) -> AppResult<()> {
+    self.reset_user(user_name).await
+  }
+
+  /// ユーザー名側のカウンタをリセットする。(管理者によるロック解除で使用する)
+  pub async fn reset_user(&self, user_name: &str) -> AppResult<()> {
+    self.store.reset(&Self::user_key(user_name)).await
+  }
+
+  async fn check_at(&self, ip: IpAddr, user_name: &str, now: DateTime<Utc>) -> AppResult<()> {
+    let ip_hits = self
+      .store
+      .hits(&Self::ip_key(ip), self.by_ip.window, now)
+      .await?;
+    let user_hits = self
+      .store
+      .hits(&Self::user_key(user_name), self.by_user.window, now)
+      .await?;
+    if ip_hits >= self.by_ip.max || user_hits >= self.by_user.max {
+      return Err(AppError::TooManyRequests(Some(
+        "ログイン試行回数が上限に達しました。しばらくしてから再度お試しください。".into(),
+      )));
+    }
+    Ok(())
+  }
+
+  fn ip_key(ip: IpAddr) -> String {
+    format!("login_ip:{ip}")
+  }
+
+  /// ユーザー名の大文字小文字の違いでカウンタを分散させない。
+  /// (ユーザー名は任意長の入力のため，ハッシュ化して長さを固定する)
+  fn user_key(user_name: &str) -> String {
+    format!(
+      "login_user:{}",
+      digest_hex(&user_name.trim().to_lowercase())
+    )
+  }
+}
+
+/// キーの可変部分をSHA3-256の16進表記（64文字）にする。
+fn digest_hex(value: &str) -> String {
+  Sha3_256::digest(value.as_bytes())
+    .iter()
+    .map(|b| format!("{b:02x}"))
+    .collect()
+}
+
+/// メールアドレスのドメイン単位の登録スロットル
+/// IPを変えながら同じドメインで大量に登録されることを防ぐ。
+#[derive(Clone)]
+pub struct RegistrationThrottle {
+  store: Arc<dyn RateLimitStore>,
+  by_domain: Limit,
+}
+
+impl RegistrationThrottle {
+  /// Configの[rate_limit]と任意のストアから生成する。
+  pub fn new(config: &RateLimit, store: Arc<dyn RateLimitStore>) -> Self {
+    Self {
+      store,
+      by_domain: Limit {
+        max: config.per_email_domain,
+        window: Duration::from_secs(config.per_email_domain_window_secs),
+      },
+    }
+  }
+
+  /// Configの[rate_limit]から生成する。ストアはbackendの設定に従う。
+  pub fn from_config(config: &RateLimit, pool: PgPool) -> Self {
+    Self::new(config, store_from_config(config, pool))
+  }
+
+  /// 登録前に呼び出す。ドメインの登録数が上限に達していれば429を返す。
+  /// (メールアドレスが無い場合，上限が0の場合は制限しない)
+  pub async fn check(&self, email: Option<&EmailAddress>) -> AppResult<()> {
+    let Some(key) = self.key(email) else {
+      return Ok(());
+    };
+    let hits = self
+      .store
+      .hits(&key, self.by_domain.window, Utc::now())
+      .await?;
+    if hits >= self.by_domain.max {
+      return Err(AppError::TooManyRequests(Some(
+        "このドメインのメールアドレスでの登録が上限に達しました。しばらくしてから再度お試しください。"
+          .into(),
+      )));
+    }
+    Ok(())
+  }
+
+  /// 登録の完了時に呼び出す。ドメインのカウンタを加算する。
+  pub async fn record(&self, email: Option<&EmailAddress>) -> AppResult<()> {
+    if let Some(key) = self.key(email) {
+      self
+        .store
+        .hit(&key, self.by_domain.window, Utc::now())
+        .await?;
+    }
+    Ok(())
+  }
+
+  /// ドメインの大文字小文字の違いでカウンタを分散させない。
+  /// (ドメインはメールアドレスの最大長に近い長さになり得るため，ハッシュ化して長さを固定する)
+  fn key(&self, email: Option<&EmailAddress>) -> Option<String> {
+    if self.by_domain.max == 0 {
+      return None;
+    }
+    Some(format!(
+      "register_domain:{}",
+      digest_hex(&email?.domain().to_lowercase())
+    ))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::AppConfig;
+  use std::net::Ipv4Addr;
+
+  fn config() -> RateLimit {
+    RateLimit {
+      login_ip_max_attempts: 3,
+      login_ip_window_secs: 60,
+      login_user_max_attempts: 2,
+      login_user_window_secs: 60,
+      backend: RateLimitBackend::Memory,
+      request_window_secs: 60,
+      request_anonymous_max: 0,
+      by_role: AppConfig::new().unwrap().rate_limit.by_role,
+      per_email_domain: 2,
+      per_email_domain_window_secs: 60,
+    }
+  }
+
+  fn throttle() -> LoginThrottle {
+    LoginThrottle::new(&config(), Arc::new(MemoryRateLimitStore::new()))
+  }
+
+  fn ip(n: u8) -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(10, 0, 0, n))
+  }
+
+  #[tokio::test]
+  async fn user_limit_trips_independently_of_ip() {
+    let throttle = throttle();
+    // 異なるIPから同一ユーザー名を狙う
+    throttle.record_failure(ip(1), "victim").await.unwrap();
+    throttle.record_failure(ip(2), "victim").await.unwrap();
+    assert!(matches!(
+      throttle.check(ip(3), "victim").await,
+      Err(AppError::TooManyRequests(_))
+    ));
+    // 他のユーザー名はIP3から試行可能
+    assert!(throttle.check(ip(3), "other").await.is_ok());
+  }
+
+  #[tokio::test]
+  async fn ip_limit_trips_independently_of_user() {
+    let throttle = throttle();
+    // 同一IPから異なるユーザー名を狙う
+    for name in ["a_user", "b_user", "c_user"] {
+      throttle.record_failure(ip(1), name).await.unwrap();
+    }
+    assert!(matches!(
+      throttle.check(ip(1), "d_user").await,
+      Err(AppError::TooManyRequests(_))
+    ));
+    // 他のIPからは試行可能
+    assert!(throttle.check(ip(2), "d_user").await.is_ok());
+  }
+
+  #[tokio::test]
+  async fn success_resets_user_counter_but_not_ip() {
+    let throttle = throttle();
+    throttle.record_failure(ip(1), "alice").await.unwrap();
+    throttle.record_failure(ip(1), "Alice").await.unwrap();
+    assert!(throttle.check(ip(2), "alice").await.is_err());
+
+    throttle.record_success("alice").await.unwrap();
+    assert!(throttle.check(ip(2), "alice").await.is_ok());
+
+    // IP側は2回分残っているため，あと1回で上限に達する
+    throttle.record_failure(ip(1), "bob").await.unwrap();
+    assert!(throttle.check(ip(1), "carol").await.is_err());
+
+    // IP側は時間経過でのみ減衰する
+    let later = Utc::now() + chrono::Duration::seconds(60);
+    assert!(throttle.check_at(ip(1), "carol", later).await.is_ok());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn postgres_backend_shares_counters_between_instances(pool: PgPool) {
+    let config = RateLimit {
+      backend: RateLimitBackend::Postgres,
+      ..config()
+    };
+    // 別インスタンスを想定し，同じDBを参照するスロットルを2つ生成する
+    let a = LoginThrottle::from_config(&config, pool.clone());
+    let b = LoginThrottle::from_config(&config, pool);
+    a.record_failure(ip(1), "victim").await.unwrap();
+    b.record_failure(ip(2), "victim").await.unwrap();
+    assert!(matches!(
+      a.check(ip(3), "victim").await,
+      Err(AppError::TooManyRequests(_))
+    ));
+
+    b.record_success("victim").await.unwrap();
+    assert!(a.check(ip(3), "victim").await.is_ok());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn postgres_backend_accepts_long_user_name(pool: PgPool) {
+    let config = RateLimit {
+      backend: RateLimitBackend::Postgres,
+      ..config()
+    };
+    let throttle = LoginThrottle::from_config(&config, pool);
+    // キーの長さ(VARCHAR(255))を超えるユーザー名でもDBエラーにしない
+    let long_name = "x".repeat(1000);
+    throttle.record_failure(ip(1), &long_name).await.unwrap();
+    throttle.record_failure(ip(2), &long_name).await.unwrap();
+    assert!(matches!(
+      throttle.check(ip(3), &long_name).await,
+      Err(AppError::TooManyRequests(_))
+    ));
+    assert!(LoginThrottle::user_key(&long_name).len() < 255);
+  }
+
+  fn email(s: &str) -> EmailAddress {
+    EmailAddress::new(s, true).unwrap().unwrap()
+  }
+
+  fn registration() -> RegistrationThrottle {
+    RegistrationThrottle::new(&config(), Arc::new(MemoryRateLimitStore::new()))
+  }
+
+  #[tokio::test]
+  async fn shared_domain_trips_registration_limit() {
+    let throttle = registration();
+    for addr in ["a@spam.example", "b@SPAM.example"] {
+      let e = email(addr);
+      throttle.check(Some(&e)).await.unwrap();
+      throttle.record(Some(&e)).await.unwrap();
+    }
+    assert!(matches!(
+      throttle.check(Some(&email("c@spam.example"))).await,
+      Err(AppError::TooManyRequests(_))
+    ));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn postgres_backend_accepts_long_email_domain(pool: PgPool) {
+    let config = RateLimit {
+      backend: RateLimitBackend::Postgres,
+      ..config()
+    };
+    let throttle = RegistrationThrottle::from_config(&config, pool);
+    // キーの長さ(VARCHAR(255))を超えるドメインでもDBエラーにしない
+    let domain = format!("{}.example", vec!["x".repeat(60); 4].join("."));
+    let long = email(&format!("a@{domain}"));
+    for _ in 0..2 {
+      throttle.check(Some(&long)).await.unwrap();
+      throttle.record(Some(&long)).await.unwrap();
+    }
+    assert!(matches!(
+      throttle.check(Some(&long)).await,
+      Err(AppError::TooManyRequests(_))
+    ));
+    assert!(throttle.key(Some(&long)).unwrap().len() < 255);
+  }
+
+  #[tokio::test]
+  async fn diverse_domains_and_missing_email_are_not_limited() {
+    let throttle = registration();
+    for n in 0..5 {
+      let e = email(&format!("user{n}@domain{n}.example"));
+      throttle.check(Some(&e)).await.unwrap();
+      throttle.record(Some(&e)).await.unwrap();
+    }
+    for _ in 0..5 {
+      throttle.check(None).await.unwrap();
+      throttle.record(None).await.unwrap();
+    }
+  }
+
+  #[tokio::test]
+  async fn zero_disables_registration_limit() {
+    let config = RateLimit {
+      per_email_domain: 0,
+      ..config()
+    };
+    let throttle = RegistrationThrottle::new(&config, Arc::new(MemoryRateLimitStore::new()));
+    let e = email("a@spam.example");
+    for _ in 0..5 {
+      throttle.check(Some(&e)).await.unwrap();
+      throttle.record(Some(&e)).await.unwrap();
+    }
+  }
+}
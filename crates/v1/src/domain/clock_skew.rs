@@ -0,0 +1,114 @@
+//! 有効期限の判定における時刻のずれの許容
+//! --------------------------------------------------------------
+//! ・ノード間の時刻のずれで有効なセッションを誤って拒否しないよう，期限に猶予を設ける
+//! ・有効期限（expires_at）は猶予の分だけ遅れて失効したものとして扱う
+//! ・有効期限を判定する全ての箇所で同じ猶予を使用する
+//! --------------------------------------------------------------
+
+use crate::{
+  config,
+  interfaces::http::error::{AppError, AppResult},
+};
+use chrono::{DateTime, Duration, Utc};
+use std::sync::OnceLock;
+
+/// 有効期限の判定に適用する時刻のずれの許容範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSkew {
+  pub leeway: Duration,
+}
+
+impl Default for ClockSkew {
+  fn default() -> Self {
+    Self::DEFAULT
+  }
+}
+
+/// 起動時に設定した許容範囲
+static CLOCK_SKEW: OnceLock<ClockSkew> = OnceLock::new();
+
+impl ClockSkew {
+  /// 既定値(30秒)
+  const DEFAULT: ClockSkew = ClockSkew {
+    leeway: Duration::seconds(30),
+  };
+
+  /// 設定できる許容範囲の上限（これを超えるずれは時刻同期の不備として扱う）
+  pub const MAX_SECS: u64 = 300;
+
+  /// Configの[security]から生成する。
+  pub fn from_config(config: &config::Security) -> AppResult<Self> {
+    if config.clock_skew_secs > Self::MAX_SECS {
+      return Err(AppError::InternalServerError(Some(format!(
+        "Invalid [security] config: clock_skew_secs={} (expected 0-{})",
+        config.clock_skew_secs,
+        Self::MAX_SECS
+      ))));
+    }
+    Ok(Self {
+      leeway: Duration::seconds(config.clock_skew_secs as i64),
+    })
+  }
+
+  /// アプリケーション全体の許容範囲として設定する。
+  /// (2回目以降の呼び出しは無視される)
+  pub fn install(self) {
+    let _ = CLOCK_SKEW.set(self);
+  }
+
+  /// 設定済みの許容範囲を返す。(未設定の場合は既定値)
+  pub fn current() -> &'static ClockSkew {
+    CLOCK_SKEW.get().unwrap_or(&Self::DEFAULT)
+  }
+
+  /// `now`時点で失効したものとして扱う有効期限の上限を返す。
+  /// (有効期限がこの値以下の場合は失効している)
+  pub fn expiry_cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+    now - self.leeway
+  }
+
+  /// 有効期限が`now`時点で失効しているか判定する。
+  pub fn is_expired(&self, expires_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    expires_at <= self.expiry_cutoff(now)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn skew(clock_skew_secs: u64) -> AppResult<ClockSkew> {
+    ClockSkew::from_config(&config::Security { clock_skew_secs })
+  }
+
+  #[test]
+  fn from_config_matches_defaults() {
+    let config = crate::config::AppConfig::new().unwrap().security;
+    assert_eq!(
+      ClockSkew::from_config(&config).unwrap(),
+      ClockSkew::default()
+    );
+    assert!(skew(ClockSkew::MAX_SECS + 1).is_err());
+  }
+
+  #[test]
+  fn expiry_boundary_with_leeway() {
+    let skew = skew(30).unwrap();
+    let now = Utc::now();
+    let second = Duration::seconds(1);
+    // 期限を過ぎても許容範囲内は有効
+    assert!(!skew.is_expired(now - skew.leeway + second, now));
+    assert!(skew.is_expired(now - skew.leeway, now));
+    assert!(skew.is_expired(now - skew.leeway - second, now));
+    // 期限前は当然有効
+    assert!(!skew.is_expired(now + second, now));
+  }
+
+  #[test]
+  fn zero_leeway_expires_at_the_exact_time() {
+    let skew = skew(0).unwrap();
+    let now = Utc::now();
+    assert!(skew.is_expired(now, now));
+    assert!(!skew.is_expired(now + Duration::milliseconds(1), now));
+  }
+}
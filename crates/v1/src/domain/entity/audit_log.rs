@@ -0,0 +1,42 @@
+use crate::domain::value_obj::user_id::UserId;
+use chrono::{DateTime, Utc};
+
+/// 監査ログに記録する操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+  UnlockLogin,
+  RotatePublicId,
+  ChangeStatus,
+  RequirePasswordChange,
+}
+impl AuditAction {
+  /// DBに保存する文字列表現を返す。
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::UnlockLogin => "unlock_login",
+      Self::RotatePublicId => "rotate_public_id",
+      Self::ChangeStatus => "change_status",
+      Self::RequirePasswordChange => "require_password_change",
+    }
+  }
+
+  /// DBの文字列表現から変換する。未知の値の場合はNoneを返す。
+  pub fn parse(s: &str) -> Option<Self> {
+    match s {
+      "unlock_login" => Some(Self::UnlockLogin),
+      "rotate_public_id" => Some(Self::RotatePublicId),
+      "change_status" => Some(Self::ChangeStatus),
+      "require_password_change" => Some(Self::RequirePasswordChange),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+  pub actor_user_id: Option<UserId>,
+  pub target_user_id: Option<UserId>,
+  pub action: AuditAction,
+  pub detail: Option<String>,
+  pub created_at: DateTime<Utc>,
+}
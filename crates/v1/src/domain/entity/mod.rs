@@ -1,3 +1,4 @@
+pub mod audit_log;
 pub mod session;
 pub mod user;
 pub mod user_auth;
@@ -1,5 +1,13 @@
-use crate::domain::value_obj::{session_id::SessionId, user_id::UserId};
-use chrono::{DateTime, Utc};
+use crate::{
+  config,
+  domain::value_obj::{
+    session_id::{IdStrategy, SessionId},
+    user_id::UserId,
+  },
+  interfaces::http::error::AppError,
+};
+use chrono::{DateTime, Duration, Utc};
+use std::net::IpAddr;
 
 #[derive(Debug, Clone)]
 pub struct Session {
@@ -7,4 +15,215 @@ pub struct Session {
   pub user_id: UserId,
   pub created_at: DateTime<Utc>,
   pub expires_at: DateTime<Utc>,
+  /// セッション作成時のUser-Agent（端末の識別用）
+  pub user_agent: Option<String>,
+  /// セッション作成時の接続元IPアドレス
+  pub ip: Option<IpAddr>,
+  /// セッションの利用範囲
+  pub scope: SessionScope,
+}
+
+/// セッションの利用範囲
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionScope {
+  /// 制限なし
+  #[default]
+  Full,
+  /// パスワード変更のみ（パスワードの変更が必要なユーザーのログイン時に発行する）
+  PasswordChange,
+}
+
+impl From<SessionScope> for i16 {
+  fn from(s: SessionScope) -> Self {
+    match s {
+      SessionScope::Full => 0,
+      SessionScope::PasswordChange => 1,
+    }
+  }
+}
+
+impl TryFrom<i16> for SessionScope {
+  type Error = AppError;
+  fn try_from(v: i16) -> Result<Self, Self::Error> {
+    match v {
+      0 => Ok(Self::Full),
+      1 => Ok(Self::PasswordChange),
+      _ => Err(AppError::InternalServerError(Some(format!(
+        "Invalid session scope in DB: {v}"
+      )))),
+    }
+  }
+}
+
+/// セッションの発行方針
+/// 有効期間はremember_meの有無によらず，上限(max)を超えない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionPolicy {
+  pub default: Duration,
+  pub remember: Duration,
+  pub max: Duration,
+  /// セッションIDの生成方式
+  pub id_strategy: IdStrategy,
+}
+
+impl SessionPolicy {
+  /// Configの[session]から生成する。
+  pub fn from_config(config: &config::Session) -> Self {
+    let secs =
+      |s: u64| Duration::seconds(i64::try_from(s).unwrap_or(i64::MAX).min(i64::MAX / 1000));
+    Self {
+      default: secs(config.ttl_secs),
+      remember: secs(config.remember_ttl_secs),
+      max: secs(config.max_ttl_secs),
+      id_strategy: config.id_strategy,
+    }
+  }
+
+  /// remember_meの有無に応じた有効期間を返す。
+  pub fn for_login(&self, remember_me: bool) -> Duration {
+    let ttl = if remember_me {
+      self.remember
+    } else {
+      self.default
+    };
+    ttl.min(self.max)
+  }
+}
+
+/// 設定ファイルの既定値と同じ発行方針
+impl Default for SessionPolicy {
+  fn default() -> Self {
+    Self {
+      default: Duration::days(1),
+      remember: Duration::days(30),
+      max: Duration::days(90),
+      id_strategy: IdStrategy::default(),
+    }
+  }
+}
+
+impl Session {
+  /// 保存するUser-Agentの最大文字数
+  pub const MAX_USER_AGENT_LEN: usize = 512;
+  /// パスワード変更のみに使用できるセッションの有効期間(15分)
+  pub const PASSWORD_CHANGE_TTL: Duration = Duration::seconds(15 * 60);
+
+  /// ログイン時に新しいセッションを発行する。
+  pub fn issue(
+    user_id: UserId,
+    now: DateTime<Utc>,
+    policy: &SessionPolicy,
+    remember_me: bool,
+    user_agent: Option<String>,
+    ip: Option<IpAddr>,
+  ) -> Self {
+    Self {
+      session_id: SessionId::generate(policy.id_strategy),
+      user_id,
+      created_at: now,
+      expires_at: now + policy.for_login(remember_me),
+      user_agent,
+      ip,
+      scope: SessionScope::Full,
+    }
+  }
+
+  /// パスワード変更のみに使用できるセッションに制限する。
+  /// 有効期間はremember_meによらず`PASSWORD_CHANGE_TTL`に短縮する。(元の期限を延ばすことはない)
+  pub fn restrict_to_password_change(mut self) -> Self {
+    self.scope = SessionScope::PasswordChange;
+    self.expires_at = self
+      .expires_at
+      .min(self.created_at + Self::PASSWORD_CHANGE_TTL);
+    self
+  }
+
+  /// User-Agentを保存用に整形する。
+  /// 制御文字を除去・trimし，最大文字数で切り詰める。空の場合はNoneを返す。
+  pub fn sanitize_user_agent(raw: &str) -> Option<String> {
+    let ua: String = raw
+      .chars()
+      .filter(|c| !c.is_control())
+      .collect::<String>()
+      .trim()
+      .chars()
+      .take(Self::MAX_USER_AGENT_LEN)
+      .collect();
+    (!ua.is_empty()).then_some(ua)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ttl(ttl_secs: u64, remember_ttl_secs: u64, max_ttl_secs: u64) -> SessionPolicy {
+    SessionPolicy::from_config(&config::Session {
+      ttl_secs,
+      remember_ttl_secs,
+      max_ttl_secs,
+      id_strategy: IdStrategy::UuidV7,
+    })
+  }
+
+  #[test]
+  fn remember_me_extends_expires_at() {
+    let ttl = ttl(3600, 86400, 604800);
+    let now = Utc::now();
+    let user_id = UserId::from_db(1).unwrap();
+
+    let normal = Session::issue(user_id, now, &ttl, false, None, None);
+    let remember = Session::issue(user_id, now, &ttl, true, None, None);
+    assert_eq!(normal.session_id.as_uuid().get_version_num(), 7);
+    assert_eq!(normal.expires_at - now, Duration::seconds(3600));
+    assert_eq!(remember.expires_at - now, Duration::seconds(86400));
+    assert_eq!(
+      remember.expires_at - normal.expires_at,
+      Duration::seconds(86400 - 3600)
+    );
+  }
+
+  #[test]
+  fn ttl_is_capped_by_max() {
+    let long_remember = ttl(3600, 86400 * 365, 86400 * 30);
+    assert_eq!(long_remember.for_login(true), Duration::days(30));
+    assert_eq!(long_remember.for_login(false), Duration::seconds(3600));
+
+    let short_max = ttl(3600, 7200, 60);
+    assert_eq!(short_max.for_login(false), Duration::seconds(60));
+  }
+
+  #[test]
+  fn password_change_session_gets_short_fixed_ttl() {
+    let policy = ttl(3600, 86400, 604800);
+    let now = Utc::now();
+    let user_id = UserId::from_db(1).unwrap();
+
+    for remember_me in [false, true] {
+      let session = Session::issue(user_id, now, &policy, remember_me, None, None)
+        .restrict_to_password_change();
+      assert_eq!(session.scope, SessionScope::PasswordChange);
+      assert_eq!(session.expires_at - now, Session::PASSWORD_CHANGE_TTL);
+    }
+
+    // 通常の有効期間の方が短い場合は延ばさない
+    let short = Session::issue(user_id, now, &ttl(60, 60, 60), false, None, None);
+    assert_eq!(
+      short.restrict_to_password_change().expires_at - now,
+      Duration::seconds(60)
+    );
+  }
+
+  #[test]
+  fn sanitize_user_agent_truncates_and_strips() {
+    assert_eq!(
+      Session::sanitize_user_agent(" Mozilla/5.0\r\n "),
+      Some("Mozilla/5.0".into())
+    );
+    assert_eq!(Session::sanitize_user_agent("  "), None);
+
+    let long = "a".repeat(Session::MAX_USER_AGENT_LEN + 10);
+    let ua = Session::sanitize_user_agent(&long).unwrap();
+    assert_eq!(ua.chars().count(), Session::MAX_USER_AGENT_LEN);
+  }
 }
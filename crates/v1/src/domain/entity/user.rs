@@ -1,6 +1,9 @@
-use crate::domain::value_obj::{
-  birth_date::BirthDate, email_address::EmailAddress, phone_number::PhoneNumber,
-  public_id::PublicId, user_full_name::UserFullName, user_id::UserId, user_name::UserName,
+use crate::{
+  domain::value_obj::{
+    birth_date::BirthDate, email_address::EmailAddress, phone_number::PhoneNumber,
+    public_id::PublicId, user_full_name::UserFullName, user_id::UserId, user_name::UserName,
+  },
+  interfaces::http::error::{AppError, AppResult},
 };
 use chrono::{DateTime, Utc};
 
@@ -13,16 +16,136 @@ pub enum UserStatus {
   Deleted,
   Archived,
 }
-impl From<i16> for UserStatus {
-  fn from(v: i16) -> Self {
+
+/// ステータスによりログインを拒否する理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginDenyReason {
+  /// メールアドレス未確認(Pending)
+  EmailUnverified,
+  /// 利用停止中(Suspended)
+  Suspended,
+  /// 無効化・削除済み(Deactivated, Deleted, Archived)
+  Inactive,
+}
+
+impl LoginDenyReason {
+  /// クライアントに返すエラーコードを返す。
+  /// 無効化・削除済みの場合はアカウントの存在を推測されないようNoneを返す。
+  pub fn code(&self) -> Option<&'static str> {
+    match self {
+      Self::EmailUnverified => Some("EMAIL_UNVERIFIED"),
+      Self::Suspended => Some("SUSPENDED"),
+      Self::Inactive => None,
+    }
+  }
+}
+
+/// メールアドレス確認の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailVerification {
+  /// 未確認(Pending)から確認済み(Active)になった
+  Verified,
+  /// 既に確認済み(Active)だった
+  AlreadyVerified,
+}
+
+impl UserStatus {
+  /// メールアドレス確認後のステータスと結果を返す。
+  /// 確認済み(Active)の場合はステータスを変えずにAlreadyVerifiedを返す。
+  /// (確認リンクの二重クリックを冪等に扱うため，トークンの不正・期限切れとは区別する)
+  /// 確認の対象外のステータスの場合はNoneを返す。
+  pub fn verify_email(&self) -> Option<(UserStatus, EmailVerification)> {
+    match self {
+      Self::Pending => Some((Self::Active, EmailVerification::Verified)),
+      Self::Active => Some((Self::Active, EmailVerification::AlreadyVerified)),
+      Self::Deactivated | Self::Suspended | Self::Deleted | Self::Archived => None,
+    }
+  }
+
+  /// 管理者の操作で`next`に遷移できるか判定する。(同じステータスへの遷移は含まない)
+  /// Archivedは最終状態のため，どのステータスにも遷移できない。
+  pub fn can_transition_to(&self, next: UserStatus) -> bool {
+    use UserStatus::*;
+    matches!(
+      (self, next),
+      (Active, Deactivated | Suspended | Deleted)
+        | (Pending, Active | Deleted)
+        | (Deactivated, Active | Suspended | Deleted)
+        | (Suspended, Active | Deleted)
+        | (Deleted, Active | Archived)
+    )
+  }
+
+  /// 本人の操作（休止・退会）で`next`に遷移できるか判定する。(同じステータスへの遷移は含まない)
+  /// 休止・退会以外の遷移（復帰など）は管理者の操作で行う。
+  ///
+  /// | 現在        | → Deactivated | → Deleted |
+  /// |-------------|---------------|-----------|
+  /// | Active      | ○             | ○         |
+  /// | Pending     | ×             | ○         |
+  /// | Deactivated | -             | ○         |
+  /// | Suspended   | ×             | ×         |
+  /// | Deleted     | ×             | -         |
+  /// | Archived    | ×             | ×         |
+  pub fn can_owner_transition_to(&self, next: UserStatus) -> bool {
+    use UserStatus::*;
+    matches!(
+      (self, next),
+      (Active, Deactivated | Deleted) | (Pending, Deleted) | (Deactivated, Deleted)
+    )
+  }
+
+  /// 外部 I/F で使用する文字列表現を返す。
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::Active => "active",
+      Self::Pending => "pending",
+      Self::Deactivated => "deactivated",
+      Self::Suspended => "suspended",
+      Self::Deleted => "deleted",
+      Self::Archived => "archived",
+    }
+  }
+
+  /// 文字列表現から変換する。未知の値の場合はNoneを返す。
+  pub fn parse(s: &str) -> Option<Self> {
+    match s {
+      "active" => Some(Self::Active),
+      "pending" => Some(Self::Pending),
+      "deactivated" => Some(Self::Deactivated),
+      "suspended" => Some(Self::Suspended),
+      "deleted" => Some(Self::Deleted),
+      "archived" => Some(Self::Archived),
+      _ => None,
+    }
+  }
+
+  /// ログイン可能なステータスか判定する。
+  /// パスワードの検証に成功した後に呼び出すこと。
+  pub fn can_login(&self) -> Result<(), LoginDenyReason> {
+    match self {
+      Self::Active => Ok(()),
+      Self::Pending => Err(LoginDenyReason::EmailUnverified),
+      Self::Suspended => Err(LoginDenyReason::Suspended),
+      Self::Deactivated | Self::Deleted | Self::Archived => Err(LoginDenyReason::Inactive),
+    }
+  }
+}
+
+/// DBの値から変換する。(範囲外の値は500として扱う)
+impl TryFrom<i16> for UserStatus {
+  type Error = AppError;
+  fn try_from(v: i16) -> Result<Self, Self::Error> {
     match v {
-      0 => Self::Active,
-      1 => Self::Pending,
-      2 => Self::Deactivated,
-      3 => Self::Suspended,
-      4 => Self::Deleted,
-      5 => Self::Archived,
-      _ => panic!("不正なユーザーステータス値: {}", v),
+      0 => Ok(Self::Active),
+      1 => Ok(Self::Pending),
+      2 => Ok(Self::Deactivated),
+      3 => Ok(Self::Suspended),
+      4 => Ok(Self::Deleted),
+      5 => Ok(Self::Archived),
+      _ => Err(AppError::InternalServerError(Some(format!(
+        "Invalid user status in DB: {v}"
+      )))),
     }
   }
 }
@@ -39,7 +162,9 @@ impl From<UserStatus> for i16 {
   }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// ユーザーロール
+/// 宣言順に権限が強くなる。(Guest < User < ... < SuperAdmin)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum UserRole {
   Guest,
   User,
@@ -48,19 +173,36 @@ pub enum UserRole {
   Admin,
   SuperAdmin,
 }
-impl From<i16> for UserRole {
-  fn from(v: i16) -> Self {
-    match v {
-      0 => Self::User,
-      1 => Self::Guest,
-      2 => Self::Support,
-      3 => Self::Moderator,
-      4 => Self::Admin,
-      5 => Self::SuperAdmin,
-      _ => panic!("不正なユーザーロール値: {}", v),
-    }
+impl UserRole {
+  /// 全てのロール（宣言順）
+  pub const ALL: [UserRole; 6] = [
+    Self::Guest,
+    Self::User,
+    Self::Support,
+    Self::Moderator,
+    Self::Admin,
+    Self::SuperAdmin,
+  ];
+
+  /// `other`より権限が強いか判定する。(同じロールの場合はfalse)
+  pub fn outranks(&self, other: UserRole) -> bool {
+    *self > other
+  }
+}
+/// DBの値から変換する。(範囲外の値は500として扱う)
+/// `From<UserRole> for i16`の逆変換として求めるため，対応はそちらのみで定義する。
+impl TryFrom<i16> for UserRole {
+  type Error = AppError;
+  fn try_from(v: i16) -> Result<Self, Self::Error> {
+    Self::ALL
+      .into_iter()
+      .find(|&role| i16::from(role) == v)
+      .ok_or_else(|| AppError::InternalServerError(Some(format!("Invalid user role in DB: {v}"))))
   }
 }
+/// DBに保存する値（ロールとDBの値の対応の唯一の定義）
+/// users.roleの既定値(0)をUserとするため，宣言順（権限の順）とは一致しない。
+/// 既存の行の意味が変わるため，値は変更しないこと。
 impl From<UserRole> for i16 {
   fn from(r: UserRole) -> Self {
     match r {
@@ -74,6 +216,7 @@ impl From<UserRole> for i16 {
   }
 }
 
+/// Debug出力では，メールアドレス・電話番号はマスクされる
 #[derive(Debug, Clone)]
 pub struct User {
   pub user_id: UserId,
@@ -82,6 +225,8 @@ pub struct User {
   pub user_name: UserName,
   pub full_name: Option<UserFullName>,
   pub email: Option<EmailAddress>,
+  /// 予備のメールアドレス（emailとは異なる値）
+  pub recovery_email: Option<EmailAddress>,
   pub phone: Option<PhoneNumber>,
   pub birth_date: Option<BirthDate>,
   pub status: UserStatus,
@@ -90,3 +235,248 @@ pub struct User {
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
 }
+
+impl User {
+  /// パスワード再設定の送信先を返す。
+  /// 主のメールアドレスが利用できない場合は予備のメールアドレスを使用する。
+  pub fn password_reset_email(&self, primary_available: bool) -> Option<&EmailAddress> {
+    match &self.email {
+      Some(email) if primary_available => Some(email),
+      _ => self.recovery_email.as_ref(),
+    }
+  }
+
+  /// 連絡先が重複していないことを確認する。
+  /// 予備のメールアドレスは，大文字小文字を区別せずに主のメールアドレスと異なる必要がある。
+  pub fn ensure_distinct_contacts(&self) -> AppResult<()> {
+    if let (Some(email), Some(recovery)) = (&self.email, &self.recovery_email)
+      && email.as_str().eq_ignore_ascii_case(recovery.as_str())
+    {
+      return Err(AppError::UnprocessableContent(Some(
+        "予備のメールアドレス(recovery_email)はメールアドレス(email)と異なる必要があります。"
+          .into(),
+      )));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn can_login_by_status() {
+    assert_eq!(UserStatus::Active.can_login(), Ok(()));
+    assert_eq!(
+      UserStatus::Pending.can_login(),
+      Err(LoginDenyReason::EmailUnverified)
+    );
+    assert_eq!(
+      UserStatus::Suspended.can_login(),
+      Err(LoginDenyReason::Suspended)
+    );
+    for status in [
+      UserStatus::Deactivated,
+      UserStatus::Deleted,
+      UserStatus::Archived,
+    ] {
+      assert_eq!(status.can_login(), Err(LoginDenyReason::Inactive));
+    }
+  }
+
+  #[test]
+  fn verify_email_is_idempotent_for_active_users() {
+    // 1回目: Pending -> Active
+    let (status, result) = UserStatus::Pending.verify_email().unwrap();
+    assert_eq!(status, UserStatus::Active);
+    assert_eq!(result, EmailVerification::Verified);
+
+    // 2回目: Activeのまま
+    let (status, result) = status.verify_email().unwrap();
+    assert_eq!(status, UserStatus::Active);
+    assert_eq!(result, EmailVerification::AlreadyVerified);
+
+    for status in [
+      UserStatus::Deactivated,
+      UserStatus::Suspended,
+      UserStatus::Deleted,
+      UserStatus::Archived,
+    ] {
+      assert_eq!(status.verify_email(), None);
+    }
+  }
+
+  #[test]
+  fn status_transition_matrix() {
+    use UserStatus::*;
+    let all = [Active, Pending, Deactivated, Suspended, Deleted, Archived];
+    assert!(Active.can_transition_to(Suspended));
+    assert!(Suspended.can_transition_to(Active));
+    assert!(Deleted.can_transition_to(Archived));
+    assert!(!Active.can_transition_to(Pending));
+    assert!(!Pending.can_transition_to(Suspended));
+    for status in all {
+      assert!(!status.can_transition_to(status));
+      assert!(!Archived.can_transition_to(status));
+      assert_eq!(UserStatus::parse(status.as_str()), Some(status));
+    }
+    assert_eq!(UserStatus::parse("banned"), None);
+  }
+
+  #[test]
+  fn owner_transition_table() {
+    use UserStatus::*;
+    let all = [Active, Pending, Deactivated, Suspended, Deleted, Archived];
+    let allowed = [
+      (Active, Deactivated),
+      (Active, Deleted),
+      (Pending, Deleted),
+      (Deactivated, Deleted),
+    ];
+    for from in all {
+      for to in all {
+        assert_eq!(
+          from.can_owner_transition_to(to),
+          allowed.contains(&(from, to)),
+          "{} -> {}",
+          from.as_str(),
+          to.as_str()
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn db_values_round_trip_and_unknown_values_are_errors() {
+    for status in [
+      UserStatus::Active,
+      UserStatus::Pending,
+      UserStatus::Deactivated,
+      UserStatus::Suspended,
+      UserStatus::Deleted,
+      UserStatus::Archived,
+    ] {
+      assert_eq!(UserStatus::try_from(i16::from(status)).unwrap(), status);
+    }
+
+    // 範囲外の値はパニックせずにエラーを返す
+    for v in [-1, 6, i16::MAX] {
+      assert!(matches!(
+        UserStatus::try_from(v),
+        Err(AppError::InternalServerError(Some(m))) if m.contains(&v.to_string())
+      ));
+      assert!(matches!(
+        UserRole::try_from(v),
+        Err(AppError::InternalServerError(_))
+      ));
+    }
+  }
+
+  #[test]
+  fn role_db_values_round_trip() {
+    // ALLは宣言順に全てのロールを含む
+    assert!(UserRole::ALL.windows(2).all(|w| w[0] < w[1]));
+
+    // ロール → DBの値 → ロールで元に戻り，DBの値は重複しない
+    let mut values = Vec::new();
+    for role in UserRole::ALL {
+      let v = i16::from(role);
+      assert_eq!(UserRole::try_from(v).unwrap(), role);
+      assert!(!values.contains(&v), "{v}");
+      values.push(v);
+    }
+    // DBの値 → ロール → DBの値で元に戻る（範囲外はエラー）
+    for v in -1..=6 {
+      match UserRole::try_from(v) {
+        Ok(role) => assert_eq!(i16::from(role), v),
+        Err(e) => assert!(!values.contains(&v), "{v}: {e:?}"),
+      }
+    }
+    // 既存データとの互換（users.roleの既定値0はUser）
+    assert_eq!(i16::from(UserRole::User), 0);
+    assert_eq!(i16::from(UserRole::Guest), 1);
+  }
+
+  #[test]
+  fn role_outranks_only_weaker_roles() {
+    assert!(UserRole::Admin.outranks(UserRole::Moderator));
+    assert!(!UserRole::Moderator.outranks(UserRole::Moderator));
+    assert!(!UserRole::Support.outranks(UserRole::Admin));
+  }
+
+  #[test]
+  fn login_deny_reason_codes() {
+    assert_eq!(
+      LoginDenyReason::EmailUnverified.code(),
+      Some("EMAIL_UNVERIFIED")
+    );
+    assert_eq!(LoginDenyReason::Suspended.code(), Some("SUSPENDED"));
+    assert_eq!(LoginDenyReason::Inactive.code(), None);
+  }
+
+  fn user_with_contacts(email: &str, recovery_email: Option<&str>) -> User {
+    User {
+      user_id: UserId::from_db(1).unwrap(),
+      public_id: PublicId::new(),
+      randomart: String::new(),
+      user_name: UserName::new("masked_user", true).unwrap().unwrap(),
+      full_name: None,
+      email: EmailAddress::new(email, true).unwrap(),
+      recovery_email: recovery_email.and_then(|e| EmailAddress::new(e, true).unwrap()),
+      phone: PhoneNumber::new("09012345678", true).unwrap(),
+      birth_date: None,
+      status: UserStatus::Active,
+      role: UserRole::User,
+      last_login_at: None,
+      created_at: Utc::now(),
+      updated_at: Utc::now(),
+    }
+  }
+
+  #[test]
+  fn recovery_email_equal_to_email_is_rejected() {
+    let user = user_with_contacts("taro@example.com", Some("TARO@example.com"));
+    assert!(matches!(
+      user.ensure_distinct_contacts(),
+      Err(AppError::UnprocessableContent(Some(m))) if m.contains("recovery_email")
+    ));
+    for recovery in [None, Some("backup@example.com")] {
+      let user = user_with_contacts("taro@example.com", recovery);
+      assert!(user.ensure_distinct_contacts().is_ok());
+    }
+  }
+
+  #[test]
+  fn debug_output_masks_contact_information() {
+    let user = user_with_contacts("taro.yamada@example.com", None);
+
+    let debug = format!("{:?}", user);
+    assert!(debug.contains("t***@example.com"));
+    assert!(debug.contains("090****5678"));
+    assert!(!debug.contains("taro.yamada"));
+    assert!(!debug.contains("09012345678"));
+    assert_eq!(user.email.unwrap().as_str(), "taro.yamada@example.com");
+  }
+
+  #[test]
+  fn password_reset_falls_back_to_recovery_email() {
+    let email = |s: &str| EmailAddress::new(s, true).unwrap();
+    let mut user = crate::test_support::new_user("reset_user", UserStatus::Active, UserRole::User);
+    user.email = email("primary@example.com");
+    user.recovery_email = email("backup@example.com");
+
+    let target = |u: &User, available| {
+      u.password_reset_email(available)
+        .map(|e| e.as_str().to_owned())
+    };
+    assert_eq!(target(&user, true).as_deref(), Some("primary@example.com"));
+    assert_eq!(target(&user, false).as_deref(), Some("backup@example.com"));
+
+    // 主のメールアドレスが未登録の場合も予備を使用する
+    user.email = None;
+    assert_eq!(target(&user, true).as_deref(), Some("backup@example.com"));
+    user.recovery_email = None;
+    assert_eq!(target(&user, false), None);
+  }
+}
@@ -1,5 +1,5 @@
 use crate::domain::value_obj::{user_id::UserId, user_password::UserPassword};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 
 #[derive(Debug, Clone)]
 pub struct UserAuth {
@@ -8,6 +8,58 @@ pub struct UserAuth {
   pub prev_hash1: Option<UserPassword>,
   pub prev_hash2: Option<UserPassword>,
   pub login_fail_times: u16,
+  /// ログイン失敗によるロックの期限（期限を過ぎると自動的に解除される）
+  pub locked_until: Option<DateTime<Utc>>,
+  /// 次回のログイン時にパスワードの変更を必須とするか（管理者が作成・移行したアカウント向け）
+  pub must_change_password: bool,
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
 }
+
+impl UserAuth {
+  /// ロックとみなすログイン失敗回数
+  pub const MAX_LOGIN_FAIL_TIMES: u16 = 5;
+
+  /// ロックを継続する期間
+  pub const LOCK_DURATION: TimeDelta = TimeDelta::minutes(15);
+
+  /// `now`時点でロック中かどうか
+  pub fn is_locked(&self, now: DateTime<Utc>) -> bool {
+    self.locked_until.is_some_and(|until| until > now)
+  }
+
+  /// ログイン失敗を1回数える。
+  /// 上限に達した場合は`LOCK_DURATION`の間ロックし，失敗回数を数え直す。
+  pub fn record_login_failure(&mut self, now: DateTime<Utc>) {
+    self.login_fail_times = self.login_fail_times.saturating_add(1);
+    if self.login_fail_times >= Self::MAX_LOGIN_FAIL_TIMES {
+      self.locked_until = Some(now + Self::LOCK_DURATION);
+      self.login_fail_times = 0;
+    }
+  }
+
+  /// ログイン失敗回数とロックを解除する。
+  pub fn clear_login_failures(&mut self) {
+    self.login_fail_times = 0;
+    self.locked_until = None;
+  }
+
+  /// 現在のパスワードと履歴のハッシュを新しい順に返す。
+  pub fn recent_hashes(&self) -> impl Iterator<Item = &UserPassword> {
+    std::iter::once(&self.current_hash)
+      .chain(self.prev_hash1.as_ref())
+      .chain(self.prev_hash2.as_ref())
+  }
+
+  /// パスワードを変更し，履歴を current → prev_hash1 → prev_hash2 の順に繰り下げる。
+  /// (prev_hash2に保持していたハッシュは破棄する)
+  pub fn rotate_password(&mut self, new: UserPassword) {
+    let previous = std::mem::replace(&mut self.current_hash, new);
+    self.prev_hash2 = self.prev_hash1.replace(previous);
+  }
+
+  /// 保持しているパスワード履歴(prev_hash1/prev_hash2)の件数を返す。
+  pub fn password_history_depth(&self) -> u8 {
+    u8::from(self.prev_hash1.is_some()) + u8::from(self.prev_hash2.is_some())
+  }
+}
@@ -1,4 +1,7 @@
+pub mod clock_skew;
 pub mod entity;
 pub mod error;
+pub mod password_policy;
+pub mod rejection;
 pub mod repository;
 pub mod value_obj;
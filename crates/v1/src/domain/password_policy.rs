@@ -0,0 +1,390 @@
+//! パスワードポリシー
+//! --------------------------------------------------------------
+//! ・パスワードの検証ルール（長さ・使用文字・個人情報・強度）を1か所にまとめる
+//! ・違反は最初の1件で打ち切らず，全て返す
+//! ・ユーザー登録(VO)，強度評価，パスワード変更で同じポリシーを使用する
+//! --------------------------------------------------------------
+
+use crate::{
+  config,
+  interfaces::http::error::{AppError, AppResult},
+  utils::string::is_forbidden_char,
+};
+use chrono::NaiveDate;
+use std::{fmt, sync::OnceLock};
+use zxcvbn::{Score, zxcvbn};
+
+/// 検証対象のパスワードと合わせて評価する情報
+/// (パスワードに含めることができない値)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PasswordContext<'a> {
+  pub user_name: Option<&'a str>,
+  pub birth_date: Option<NaiveDate>,
+}
+
+/// パスワードポリシーの違反
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyViolation {
+  /// 長さが範囲外
+  Length { min: usize, max: usize },
+  /// 使用できない文字を含む
+  ForbiddenChar,
+  /// ユーザー名を含む
+  ContainsUserName,
+  /// 誕生日を含む
+  ContainsBirthDate,
+  /// 強度が不十分
+  Weak,
+  /// エントロピー（ビット）が不十分
+  LowEntropy { achieved: u32, required: u32 },
+}
+
+impl PolicyViolation {
+  pub const TARGET: &str = "パスワード(user_password)";
+
+  /// 違反の種類を表すコード
+  pub fn code(&self) -> &'static str {
+    match self {
+      Self::Length { .. } => "length",
+      Self::ForbiddenChar => "forbidden_char",
+      Self::ContainsUserName => "contains_user_name",
+      Self::ContainsBirthDate => "contains_birth_date",
+      Self::Weak => "weak",
+      Self::LowEntropy { .. } => "low_entropy",
+    }
+  }
+}
+
+impl fmt::Display for PolicyViolation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let target = Self::TARGET;
+    match self {
+      Self::Length { min, max } => {
+        write!(
+          f,
+          "{target}は{min}文字以上、{max}文字以下でなければなりません。"
+        )
+      }
+      Self::ForbiddenChar => write!(f, "{target}には使用できない文字が含まれています。"),
+      Self::ContainsUserName => write!(f, "{target}にはユーザー名を含めることができません。"),
+      Self::ContainsBirthDate => write!(f, "{target}には誕生日を含めることができません。"),
+      Self::Weak => write!(
+        f,
+        "{target}は強度が不十分です。より強力なパスワードを使用してください。"
+      ),
+      Self::LowEntropy { achieved, required } => write!(
+        f,
+        "{target}のエントロピーが不足しています。(推定{achieved}ビット，{required}ビット以上が必要です)"
+      ),
+    }
+  }
+}
+
+/// パスワードの検証ポリシー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordPolicy {
+  pub min_len: usize,
+  pub max_len: usize,
+  /// zxcvbnの強度スコアの下限
+  pub min_score: Score,
+  /// エントロピー（ビット）の下限（Noneの場合は判定しない）
+  pub min_bits: Option<u32>,
+}
+
+impl Default for PasswordPolicy {
+  fn default() -> Self {
+    Self::DEFAULT
+  }
+}
+
+/// 起動時に設定した検証ポリシー
+static PASSWORD_POLICY: OnceLock<PasswordPolicy> = OnceLock::new();
+
+impl PasswordPolicy {
+  /// 既定値(8〜64文字，スコア3以上，エントロピーは判定しない)
+  const DEFAULT: PasswordPolicy = PasswordPolicy {
+    min_len: 8,
+    max_len: 64,
+    min_score: Score::Three,
+    min_bits: None,
+  };
+
+  /// zxcvbnの推定試行回数はu64のため，これを超えるビット数は達成できない
+  const MAX_BITS: u32 = u64::BITS;
+
+  /// Configの[password]と[validation].password_min_bitsから生成する。
+  pub fn from_config(
+    config: &config::Password,
+    validation: &config::Validation,
+  ) -> AppResult<Self> {
+    let invalid = |message: String| {
+      AppError::InternalServerError(Some(format!("Invalid [password] config: {message}")))
+    };
+    if config.min_len == 0 || config.min_len > config.max_len {
+      return Err(invalid(format!(
+        "min_len={}, max_len={}",
+        config.min_len, config.max_len
+      )));
+    }
+    let min_score = match config.min_zxcvbn_score {
+      0 => Score::Zero,
+      1 => Score::One,
+      2 => Score::Two,
+      3 => Score::Three,
+      4 => Score::Four,
+      n => return Err(invalid(format!("min_zxcvbn_score={n} (expected 0-4)"))),
+    };
+    let min_bits = match validation.password_min_bits {
+      0 => None,
+      n if n > Self::MAX_BITS => {
+        return Err(invalid(format!(
+          "password_min_bits={n} (expected 0-{})",
+          Self::MAX_BITS
+        )));
+      }
+      n => Some(n),
+    };
+    Ok(Self {
+      min_len: config.min_len,
+      max_len: config.max_len,
+      min_score,
+      min_bits,
+    })
+  }
+
+  /// アプリケーション全体の検証ポリシーとして設定する。
+  /// (2回目以降の呼び出しは無視される)
+  pub fn install(self) {
+    let _ = PASSWORD_POLICY.set(self);
+  }
+
+  /// 設定済みの検証ポリシーを返す。(未設定の場合は既定値)
+  pub fn current() -> &'static PasswordPolicy {
+    PASSWORD_POLICY.get().unwrap_or(&Self::DEFAULT)
+  }
+
+  /// 正規化済みの平文パスワードを評価し，全ての違反を返す。
+  pub fn evaluate(
+    &self,
+    plain: &str,
+    context: &PasswordContext<'_>,
+  ) -> Result<(), Vec<PolicyViolation>> {
+    let mut violations = Vec::new();
+
+    // 長さが範囲外の場合は，長大な入力を避けるため強度を評価しない
+    let len_ok = (self.min_len..=self.max_len).contains(&plain.len());
+    if !len_ok {
+      violations.push(PolicyViolation::Length {
+        min: self.min_len,
+        max: self.max_len,
+      });
+    }
+
+    if plain.chars().any(is_forbidden_char) {
+      violations.push(PolicyViolation::ForbiddenChar);
+    }
+
+    // ユーザー名と誕生日は大文字小文字を区別せずに判定する
+    let lower_password = plain.to_lowercase();
+    let lower_user_name = context
+      .user_name
+      .map(|n| n.trim().to_lowercase())
+      .filter(|n| !n.is_empty());
+    if let Some(name) = &lower_user_name
+      && lower_password.contains(name.as_str())
+    {
+      violations.push(PolicyViolation::ContainsUserName);
+    }
+
+    if let Some(birth_date) = context.birth_date {
+      let ymd = birth_date.format("%Y%m%d").to_string();
+      let md = birth_date.format("%m%d").to_string();
+      if lower_password.contains(&ymd) || lower_password.contains(&md) {
+        violations.push(PolicyViolation::ContainsBirthDate);
+      }
+    }
+
+    if len_ok {
+      let user_inputs: Vec<&str> = lower_user_name.as_deref().into_iter().collect();
+      let entropy = zxcvbn(plain, &user_inputs);
+      if entropy.score() < self.min_score {
+        violations.push(PolicyViolation::Weak);
+      }
+      if let Some(required) = self.min_bits {
+        let achieved = entropy_bits(entropy.guesses());
+        if achieved < f64::from(required) {
+          violations.push(PolicyViolation::LowEntropy {
+            achieved: achieved as u32,
+            required,
+          });
+        }
+      }
+    }
+
+    if violations.is_empty() {
+      Ok(())
+    } else {
+      Err(violations)
+    }
+  }
+}
+
+/// zxcvbnの推定試行回数からエントロピー（ビット）を求める。
+pub fn entropy_bits(guesses: u64) -> f64 {
+  (guesses.max(1) as f64).log2()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const STRONG: &str = "A1b2C3d4!@#EfGhIjKlMnOpQrStUvWxYz$%&*()_+-=1234567890";
+
+  fn evaluate(plain: &str, user_name: Option<&str>) -> Vec<PolicyViolation> {
+    let context = PasswordContext {
+      user_name,
+      birth_date: NaiveDate::from_ymd_opt(1990, 5, 15),
+    };
+    PasswordPolicy::default()
+      .evaluate(plain, &context)
+      .err()
+      .unwrap_or_default()
+  }
+
+  #[test]
+  fn strong_password_has_no_violations() {
+    assert_eq!(evaluate(STRONG, Some("user")), vec![]);
+  }
+
+  #[test]
+  fn length_out_of_range() {
+    let length = PolicyViolation::Length { min: 8, max: 64 };
+    assert!(evaluate("Ab1!", None).contains(&length));
+    assert!(evaluate(&"Ab1!".repeat(17), None).contains(&length));
+  }
+
+  #[test]
+  fn forbidden_char() {
+    let plain = format!("{STRONG}\u{202E}");
+    assert_eq!(evaluate(&plain, None), vec![PolicyViolation::ForbiddenChar]);
+  }
+
+  #[test]
+  fn contains_user_name_ignoring_case() {
+    let plain = format!("{STRONG}Alice");
+    assert!(evaluate(&plain, Some("alice")).contains(&PolicyViolation::ContainsUserName));
+    // 空のユーザー名は判定しない
+    assert_eq!(evaluate(STRONG, Some(" ")), vec![]);
+  }
+
+  #[test]
+  fn contains_birth_date() {
+    for suffix in ["19900515", "0515"] {
+      let plain = format!("{STRONG}{suffix}");
+      assert_eq!(
+        evaluate(&plain, None),
+        vec![PolicyViolation::ContainsBirthDate]
+      );
+    }
+  }
+
+  #[test]
+  fn weak_password() {
+    assert_eq!(evaluate("password", None), vec![PolicyViolation::Weak]);
+  }
+
+  #[test]
+  fn returns_all_violations() {
+    let violations = evaluate("alice0515", Some("Alice"));
+    assert_eq!(
+      violations,
+      vec![
+        PolicyViolation::ContainsUserName,
+        PolicyViolation::ContainsBirthDate,
+        PolicyViolation::Weak,
+      ]
+    );
+    assert_eq!(violations[0].code(), "contains_user_name");
+    assert_eq!(
+      violations[0].to_string(),
+      "パスワード(user_password)にはユーザー名を含めることができません。"
+    );
+  }
+  fn config(min_len: usize, max_len: usize, min_zxcvbn_score: u8) -> config::Password {
+    config::Password {
+      min_len,
+      max_len,
+      min_zxcvbn_score,
+    }
+  }
+
+  /// 既定の[validation]のエントロピーの下限のみを差し替える
+  fn validation(password_min_bits: u32) -> config::Validation {
+    config::Validation {
+      password_min_bits,
+      ..crate::config::AppConfig::new().unwrap().validation
+    }
+  }
+
+  #[test]
+  fn from_config_matches_defaults() {
+    let config = crate::config::AppConfig::new().unwrap();
+    assert_eq!(
+      PasswordPolicy::from_config(&config.password, &config.validation).unwrap(),
+      PasswordPolicy::default()
+    );
+  }
+
+  #[test]
+  fn from_config_rejects_invalid_limits() {
+    for invalid in [config(0, 64, 3), config(10, 8, 3), config(8, 64, 5)] {
+      assert!(PasswordPolicy::from_config(&invalid, &validation(0)).is_err());
+    }
+    assert!(PasswordPolicy::from_config(&config(8, 64, 3), &validation(65)).is_err());
+  }
+
+  #[test]
+  fn entropy_threshold_boundary() {
+    let plain = "qk7vm2zp";
+    let bits = entropy_bits(zxcvbn(plain, &[]).guesses());
+    let context = PasswordContext::default();
+    // スコアの判定を無効にして，エントロピーのみで判定する
+    let policy = |min_bits: u32| {
+      PasswordPolicy::from_config(&config(8, 64, 0), &validation(min_bits)).unwrap()
+    };
+
+    // 達成したビット数ちょうどの下限は満たす
+    let at = bits.floor() as u32;
+    assert_eq!(policy(at).evaluate(plain, &context), Ok(()));
+
+    // 1ビット上の下限は満たさない
+    let violations = policy(at + 1).evaluate(plain, &context).unwrap_err();
+    assert_eq!(
+      violations,
+      vec![PolicyViolation::LowEntropy {
+        achieved: at,
+        required: at + 1,
+      }]
+    );
+    assert_eq!(violations[0].code(), "low_entropy");
+    assert!(
+      violations[0]
+        .to_string()
+        .contains(&format!("推定{at}ビット"))
+    );
+  }
+
+  #[test]
+  fn score_two_password_passes_when_minimum_is_two() {
+    // 8文字のランダムな英数字（総当たりで約10^8回 = スコア2）
+    let plain = "qk7vm2zp";
+    assert_eq!(u8::from(zxcvbn(plain, &[]).score()), 2);
+    let context = PasswordContext::default();
+    assert_eq!(
+      PasswordPolicy::default().evaluate(plain, &context),
+      Err(vec![PolicyViolation::Weak])
+    );
+    let relaxed = PasswordPolicy::from_config(&config(8, 64, 2), &validation(0)).unwrap();
+    assert_eq!(relaxed.evaluate(plain, &context), Ok(()));
+  }
+}
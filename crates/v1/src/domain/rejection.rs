@@ -0,0 +1,87 @@
+//! VOの検証で入力を拒否した理由
+//! --------------------------------------------------------------
+//! ・VOが422を返す際に，対象と理由の種類を型付きで渡す
+//! ・理由はメトリクス（`vo_rejections_total`）のラベルとして記録する
+//! ・利用者向けのメッセージは従来どおり各VOで組み立てる
+//! --------------------------------------------------------------
+
+use crate::{
+  domain::password_policy::PolicyViolation, interfaces::http::error::AppError, utils::metrics,
+};
+
+/// 入力を拒否した理由の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+  /// 必須の値が無い
+  Required,
+  /// 使用禁止文字を含む
+  ForbiddenChar,
+  /// 許可されていない文字種を含む
+  DisallowedChar,
+  /// 最小長未満
+  TooShort,
+  /// 最大長超過
+  TooLong,
+  /// 形式が不正
+  InvalidFormat,
+  /// 受け付ける範囲外の値
+  OutOfRange,
+  /// 利用できない国番号
+  DisallowedCountry,
+  /// パスワードポリシーの違反
+  Password(PolicyViolation),
+}
+
+impl RejectReason {
+  /// メトリクスのラベルに使用するコード
+  pub fn code(&self) -> &'static str {
+    match self {
+      Self::Required => "required",
+      Self::ForbiddenChar => "forbidden_char",
+      Self::DisallowedChar => "disallowed_char",
+      Self::TooShort => "too_short",
+      Self::TooLong => "too_long",
+      Self::InvalidFormat => "invalid_format",
+      Self::OutOfRange => "out_of_range",
+      Self::DisallowedCountry => "disallowed_country",
+      Self::Password(violation) => violation.code(),
+    }
+  }
+}
+
+/// 拒否した理由を記録し，422のエラーを返す。
+/// `target`は各VOの表示名（例：`ユーザー名(user_name)`）で，括弧内の項目名をラベルとする。
+pub fn reject(target: &str, reason: RejectReason, message: String) -> AppError {
+  record(target, reason);
+  AppError::UnprocessableContent(Some(message))
+}
+
+/// 拒否した理由のみを記録する。(複数の理由をまとめて1つのエラーで返す場合に使用する)
+pub fn record(target: &str, reason: RejectReason) {
+  metrics::record_vo_rejection(field_label(target), reason.code());
+}
+
+/// 表示名から括弧内の項目名を取り出す。(括弧が無い場合は表示名をそのまま返す)
+fn field_label(target: &str) -> &str {
+  target
+    .rsplit_once('(')
+    .and_then(|(_, rest)| rest.strip_suffix(')'))
+    .unwrap_or(target)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn field_label_uses_name_in_parentheses() {
+    assert_eq!(field_label("ユーザー名(user_name)"), "user_name");
+    assert_eq!(field_label("user_name"), "user_name");
+  }
+
+  #[test]
+  fn password_reason_uses_violation_code() {
+    assert_eq!(RejectReason::Password(PolicyViolation::Weak).code(), "weak");
+    assert_eq!(RejectReason::TooLong.code(), "too_long");
+  }
+}
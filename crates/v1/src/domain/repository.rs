@@ -1,11 +1,13 @@
 use crate::{
   domain::{
-    entity::{session::Session, user::User, user_auth::UserAuth},
+    entity::{audit_log::AuditLog, session::Session, user::User, user_auth::UserAuth},
     value_obj::{session_id::SessionId, user_id::UserId, user_name::UserName},
   },
   interfaces::http::error::AppResult,
 };
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
 
 #[async_trait]
 pub trait UserRepository: Send + Sync {
@@ -26,5 +28,25 @@ pub trait UserAuthRepository: Send + Sync {
 pub trait SessionRepository: Send + Sync {
   async fn insert(&self, s: &Session) -> AppResult<()>;
   async fn find(&self, id: SessionId) -> AppResult<Option<Session>>;
+  /// ユーザーの有効期限内のセッションを全て返す。
+  async fn find_by_user_id(&self, id: UserId) -> AppResult<Vec<Session>>;
   async fn delete(&self, id: SessionId) -> AppResult<()>;
 }
+
+#[async_trait]
+pub trait AuditLogRepository: Send + Sync {
+  async fn insert(&self, l: &AuditLog) -> AppResult<()>;
+  async fn find_by_target(&self, id: UserId) -> AppResult<Vec<AuditLog>>;
+}
+
+/// レート制限のカウンタを保持するストア
+/// `window`単位の固定ウィンドウでキーごとの試行回数を数える。
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+  /// `now`時点のウィンドウ内の試行回数を返す。
+  async fn hits(&self, key: &str, window: Duration, now: DateTime<Utc>) -> AppResult<u32>;
+  /// `now`時点のウィンドウの試行回数を1加算し，加算後の回数を返す。
+  async fn hit(&self, key: &str, window: Duration, now: DateTime<Utc>) -> AppResult<u32>;
+  /// キーの試行回数を破棄する。
+  async fn reset(&self, key: &str) -> AppResult<()>;
+}
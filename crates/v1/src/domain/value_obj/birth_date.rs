@@ -1,7 +1,10 @@
 //! 誕生日のVO
 
 use crate::{
-  domain::value_obj::normalized_string::NormalizedString,
+  domain::{
+    rejection::{RejectReason, reject},
+    value_obj::normalized_string::NormalizedString,
+  },
   interfaces::http::error::{AppError, AppResult},
 };
 use chrono::{Datelike, Local, NaiveDate};
@@ -11,12 +14,17 @@ pub struct BirthDate(NaiveDate);
 
 impl BirthDate {
   const TARGET: &str = "誕生日(birth_date)";
-  const LEN: usize = 8;
+  const LEN: usize = 10;
   const MINIMUM_AGE: u32 = 18;
+  /// 登録できる最低年齢（13歳未満の個人情報は保持しない）
+  pub const MIN_REGISTRATION_AGE: u32 = 13;
+  /// 受け付ける最も古い年
+  pub(crate) const MIN_YEAR: i32 = 1900;
 
   /// String/&strからBirthDate型のオブジェクトを生成する。
+  /// 未来日・1900年より前・13歳未満となる日付は受け付けない。
   pub fn new<S: AsRef<str>>(input: S, required: bool) -> AppResult<Option<Self>> {
-    // 文字列形式(YYYYmmdd)で誕生日を受け取り，正規化を行う。
+    // 文字列形式(YYYY-MM-DD)で誕生日を受け取り，正規化を行う。
     let birth_date_ns = NormalizedString::new(
       input,
       required,
@@ -32,25 +40,48 @@ impl BirthDate {
     };
 
     // NaiveDateにパースできない場合はエラーを返す。
-    let birth_date = match NaiveDate::parse_from_str(birth_date_ns.as_str(), "%Y%m%d") {
+    let birth_date = match NaiveDate::parse_from_str(birth_date_ns.as_str(), "%Y-%m-%d") {
       Ok(bd) => bd,
       Err(_) => {
-        return Err(AppError::UnprocessableContent(Some(format!(
-          "{}は`YYYYmmdd`形式で入力してください。",
-          Self::TARGET
-        ))));
+        return Err(reject(
+          Self::TARGET,
+          RejectReason::InvalidFormat,
+          format!("{}は`YYYY-MM-DD`形式で入力してください。", Self::TARGET),
+        ));
       }
     };
+    Self::try_from_naive_date(birth_date).map(Some)
+  }
+
+  /// NaiveDateから範囲を検証してBirthDate型のオブジェクトを生成する。
+  /// (JSONで日付として受け取った値に使用する)
+  pub fn try_from_naive_date(bd: NaiveDate) -> AppResult<Self> {
+    Self::validate_on(bd, Self::today())?;
+    Ok(Self(bd))
+  }
+
+  /// `today`時点で誕生日として受け付けられる日付か検証する。
+  fn validate_on(bd: NaiveDate, today: NaiveDate) -> AppResult<()> {
+    let invalid = |message: String| Err(reject(Self::TARGET, RejectReason::OutOfRange, message));
 
     // 入力値が未来日である場合はエラーを返す。
-    let today = Self::today();
-    if birth_date > today {
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{}は未来日を指定できません。",
-        Self::TARGET
-      ))));
+    if bd > today {
+      return invalid(format!("{}は未来日を指定できません。", Self::TARGET));
+    }
+    if bd.year() < Self::MIN_YEAR {
+      return invalid(format!(
+        "{}は{}-01-01以降の日付を指定してください。",
+        Self::TARGET,
+        Self::MIN_YEAR
+      ));
     }
-    Ok(Some(Self(birth_date)))
+    if Self(bd).age_years(today) < Self::MIN_REGISTRATION_AGE {
+      return invalid(format!(
+        "{}歳未満の方は登録できません。",
+        Self::MIN_REGISTRATION_AGE
+      ));
+    }
+    Ok(())
   }
 
   /// birth_dateの実態(NaiveDate)への参照を返す。
@@ -65,46 +96,31 @@ impl BirthDate {
 
   /// 年齢(満年齢)を返す。
   pub fn calculate_to_age(&self) -> AppResult<u32> {
-    let today = Self::today();
+    Ok(self.age_years(Self::today()))
+  }
+
+  /// `as_of`時点の年齢(満年齢)を返す。(誕生日より前の日付の場合は0)
+  /// 2/29生まれは，非閏年では2/28に年齢が加算される。
+  pub fn age_years(&self, as_of: NaiveDate) -> u32 {
     let birthday = self.as_naive_date();
-    let mut age = today.year() - birthday.year();
+    let years = as_of.year() - birthday.year();
 
     // 今年の誕生日が来ていなければ1を引く。
-    let birthday_this_year =
-      NaiveDate::from_ymd_opt(today.year(), birthday.month(), birthday.day());
-    let birthday_this_year = match birthday_this_year {
-      Some(bd) => bd,
-      None => {
-        // 2/29生まれかつ今年が非閏年の場合は2/28を誕生日とみなす。
-        if birthday.month() == 2 && birthday.day() == 29 {
-          NaiveDate::from_ymd_opt(today.year(), 2, 28).unwrap()
-        } else {
-          // 入力された日付が不正のため，エラーを返す。。
-          return Err(AppError::UnprocessableContent(Some(format!(
-            "{}の値が不正です。",
-            Self::TARGET
-          ))));
-        }
-      }
-    };
-    if today < birthday_this_year {
-      age -= 1;
-    }
+    let reached = birthday
+      .with_year(as_of.year())
+      .or_else(|| NaiveDate::from_ymd_opt(as_of.year(), 2, 28))
+      .is_some_and(|bd| as_of >= bd);
+    u32::try_from(years - i32::from(!reached)).unwrap_or(0)
+  }
 
-    // 年齢が負の値の場合はエラーを返す。
-    if age < 0 {
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{}の値が不正です。",
-        Self::TARGET
-      ))));
-    }
-    Ok(age as u32)
+  /// 今日時点で`years`歳以上かどうか
+  pub fn is_at_least(&self, years: u32) -> bool {
+    self.age_years(Self::today()) >= years
   }
 
   /// 対象年齢かどうか
   pub fn is_of_age(&self) -> AppResult<bool> {
-    let age = self.calculate_to_age()?;
-    if age < Self::MINIMUM_AGE {
+    if !self.is_at_least(Self::MINIMUM_AGE) {
       return Err(AppError::UnprocessableContent(Some(
         "このコンテンツの対象年齢を満たしていません。".into(),
       )));
@@ -116,3 +132,100 @@ impl BirthDate {
     Local::now().date_naive()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn date(s: &str) -> NaiveDate {
+    s.parse().unwrap()
+  }
+
+  fn validate(bd: &str, today: &str) -> AppResult<()> {
+    BirthDate::validate_on(date(bd), date(today))
+  }
+
+  fn message(result: AppResult<()>) -> String {
+    match result {
+      Err(AppError::UnprocessableContent(Some(m))) => m,
+      other => panic!("unexpected: {other:?}"),
+    }
+  }
+
+  #[test]
+  fn parses_iso_format() {
+    let bd = BirthDate::new("1990-05-15", true).unwrap().unwrap();
+    assert_eq!(bd.as_naive_date(), &date("1990-05-15"));
+    assert!(BirthDate::new("", false).unwrap().is_none());
+    for invalid in ["19900515", "1990/05/15", "1990-13-01", "2023-02-29"] {
+      let err = BirthDate::new(invalid, true).unwrap_err();
+      assert!(
+        matches!(err, AppError::UnprocessableContent(_)),
+        "{invalid}"
+      );
+    }
+  }
+
+  #[test]
+  fn rejects_future_dates() {
+    assert!(message(validate("2025-06-02", "2025-06-01")).contains("未来日"));
+  }
+
+  #[test]
+  fn rejects_dates_before_1900() {
+    let today = "2025-06-01";
+    assert!(message(validate("1899-12-31", today)).contains("1900-01-01以降"));
+    assert!(validate("1900-01-01", today).is_ok());
+  }
+
+  #[test]
+  fn minimum_age_cutoff() {
+    let today = "2025-06-01";
+    // 13歳の誕生日当日から登録できる
+    assert!(validate("2012-06-01", today).is_ok());
+    assert!(message(validate("2012-06-02", today)).contains("13歳未満"));
+  }
+
+  #[test]
+  fn leap_day_birthday_turns_13_on_feb_28_in_common_years() {
+    assert!(BirthDate::new("2012-02-29", true).is_ok());
+    assert!(validate("2012-02-29", "2025-02-27").is_err());
+    assert!(validate("2012-02-29", "2025-02-28").is_ok());
+    // 閏年は2/29が誕生日
+    assert!(validate("2011-02-28", "2024-02-27").is_err());
+    assert!(validate("2011-02-28", "2024-02-28").is_ok());
+  }
+
+  #[test]
+  fn age_increments_on_the_birthday() {
+    let bd = BirthDate::from_naive_date(date("1990-05-15"));
+    assert_eq!(bd.age_years(date("2025-05-14")), 34);
+    assert_eq!(bd.age_years(date("2025-05-15")), 35);
+    assert_eq!(bd.age_years(date("1990-05-15")), 0);
+    assert_eq!(bd.age_years(date("1980-01-01")), 0);
+  }
+
+  #[test]
+  fn leap_day_age_in_common_and_leap_years() {
+    let bd = BirthDate::from_naive_date(date("2000-02-29"));
+    // 非閏年は2/28に加算する
+    assert_eq!(bd.age_years(date("2023-02-27")), 22);
+    assert_eq!(bd.age_years(date("2023-02-28")), 23);
+    // 閏年は2/29に加算する
+    assert_eq!(bd.age_years(date("2024-02-28")), 23);
+    assert_eq!(bd.age_years(date("2024-02-29")), 24);
+  }
+
+  #[test]
+  fn is_at_least_compares_age_as_of_today() {
+    let today = BirthDate::today();
+    let turned_18_today = BirthDate::from_naive_date(
+      today
+        .with_year(today.year() - 18)
+        .unwrap_or(NaiveDate::from_ymd_opt(today.year() - 18, 2, 28).unwrap()),
+    );
+    assert!(turned_18_today.is_at_least(18));
+    assert!(!turned_18_today.is_at_least(19));
+    assert!(turned_18_today.is_of_age().is_ok());
+  }
+}
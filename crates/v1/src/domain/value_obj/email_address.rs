@@ -1,16 +1,77 @@
 use crate::{
-  domain::value_obj::normalized_string::NormalizedString,
-  interfaces::http::error::{AppError, AppResult},
+  config::Validation,
+  domain::{
+    rejection::{RejectReason, reject},
+    value_obj::normalized_string::NormalizedString,
+  },
+  interfaces::http::error::AppResult,
   utils::regex,
 };
+use std::{fmt, sync::OnceLock};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct EmailAddress(pub NormalizedString);
 
+/// 重複判定に使用する正規形の生成方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EmailPolicy {
+  /// タグ（`+`以降）を無視するプロバイダのアドレスからタグを除くかどうか
+  pub strip_plus_addressing: bool,
+}
+
+/// 起動時に設定した正規形の生成方式
+static EMAIL_POLICY: OnceLock<EmailPolicy> = OnceLock::new();
+
+impl EmailPolicy {
+  /// 既定値(タグを除かない)
+  const DEFAULT: EmailPolicy = EmailPolicy {
+    strip_plus_addressing: false,
+  };
+
+  /// タグを無視して同じメールボックスに配送するプロバイダのドメイン
+  const TAG_IGNORING_DOMAINS: &[&str] = &[
+    "gmail.com",
+    "googlemail.com",
+    "outlook.com",
+    "hotmail.com",
+    "live.com",
+    "icloud.com",
+    "fastmail.com",
+    "proton.me",
+    "protonmail.com",
+  ];
+
+  /// Configの[validation]から生成する。
+  pub fn from_config(config: &Validation) -> Self {
+    Self {
+      strip_plus_addressing: config.strip_plus_addressing,
+    }
+  }
+
+  /// アプリケーション全体の方式として設定する。
+  /// (2回目以降の呼び出しは無視される)
+  pub fn install(self) {
+    let _ = EMAIL_POLICY.set(self);
+  }
+
+  /// 設定済みの方式を返す。(未設定の場合は既定値)
+  pub fn current() -> &'static EmailPolicy {
+    EMAIL_POLICY.get().unwrap_or(&Self::DEFAULT)
+  }
+
+  /// タグを除く対象のドメインかどうか
+  fn strips_tag_for(&self, domain: &str) -> bool {
+    self.strip_plus_addressing
+      && Self::TAG_IGNORING_DOMAINS
+        .iter()
+        .any(|d| d.eq_ignore_ascii_case(domain))
+  }
+}
+
 impl EmailAddress {
   const TARGET: &str = "メールアドレス(email_address)";
-  const MIN_LEN: usize = 6;
-  const MAX_LEN: usize = 254;
+  pub(crate) const MIN_LEN: usize = 6;
+  pub(crate) const MAX_LEN: usize = 254;
 
   pub fn new<S: AsRef<str>>(input: S, required: bool) -> AppResult<Option<Self>> {
     // 正規化・必須長さチェック
@@ -30,10 +91,14 @@ impl EmailAddress {
 
     // 正規表現によるチェック
     if !regex::EMAIL_ADDRESS_REGEX.is_match(email.as_str()) {
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{}は有効なメールアドレス形式である必要があります。",
-        Self::TARGET
-      ))));
+      return Err(reject(
+        Self::TARGET,
+        RejectReason::InvalidFormat,
+        format!(
+          "{}は有効なメールアドレス形式である必要があります。",
+          Self::TARGET
+        ),
+      ));
     }
 
     // 正常時はEmailAddress型のオブジェクトを返す。
@@ -44,6 +109,55 @@ impl EmailAddress {
   pub fn as_str(&self) -> &str {
     self.0.as_str()
   }
+
+  /// ローカルパート（最後の`@`より前）を返す。(例：user+tag@example.com → user+tag)
+  pub fn local_part(&self) -> &str {
+    self.split().0
+  }
+
+  /// ドメイン（最後の`@`より後）を返す。(例：user+tag@example.com → example.com)
+  pub fn domain(&self) -> &str {
+    self.split().1
+  }
+
+  /// 最後の`@`で分割する。
+  /// 生成時の正規表現で`@`を含むことは保証されているが，含まない場合はドメインを空とする。
+  fn split(&self) -> (&str, &str) {
+    let email = self.as_str();
+    email.rsplit_once('@').unwrap_or((email, ""))
+  }
+
+  /// 起動時に設定した方式で，重複判定に使用する正規形を返す。
+  pub fn canonical(&self) -> String {
+    self.canonical_with(EmailPolicy::current())
+  }
+
+  /// 重複判定に使用する正規形を返す。(入力された値は変更しない)
+  /// タグを無視するプロバイダの場合は，タグを除いて小文字にする。(例：User+tag@Gmail.com → user@gmail.com)
+  /// それ以外のアドレスはローカルパートの扱いがプロバイダごとに異なるため，そのまま返す。
+  pub fn canonical_with(&self, policy: &EmailPolicy) -> String {
+    let (local, domain) = self.split();
+    if !policy.strips_tag_for(domain) {
+      return self.as_str().to_owned();
+    }
+    let mailbox = local.split_once('+').map_or(local, |(mailbox, _)| mailbox);
+    format!("{}@{}", mailbox.to_lowercase(), domain.to_lowercase())
+  }
+
+  /// ローカルパートの先頭1文字以外をマスクした文字列を返す。(例：t***@example.com)
+  pub fn redacted(&self) -> String {
+    let head: String = self.local_part().chars().take(1).collect();
+    format!("{head}***@{}", self.domain())
+  }
+}
+
+/// ログへの個人情報の出力を防ぐため，マスクした値を出力する
+impl fmt::Debug for EmailAddress {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("EmailAddress")
+      .field(&self.redacted())
+      .finish()
+  }
 }
 #[cfg(test)]
 mod tests {
@@ -57,6 +171,60 @@ mod tests {
     "invalid-email"
   }
 
+  #[test]
+  fn redacted_masks_local_part() {
+    let email = EmailAddress::new(valid_email(), true).unwrap().unwrap();
+    assert_eq!(email.redacted(), "t***@example.com");
+    assert_eq!(
+      format!("{:?}", email),
+      r#"EmailAddress("t***@example.com")"#
+    );
+    assert_eq!(email.as_str(), valid_email());
+  }
+
+  #[test]
+  fn tagged_address_is_split_into_parts() {
+    let email = EmailAddress::new("user+tag@sub.example.com", true)
+      .unwrap()
+      .unwrap();
+    assert_eq!(email.local_part(), "user+tag");
+    assert_eq!(email.domain(), "sub.example.com");
+  }
+
+  fn canonical(input: &str, strip_plus_addressing: bool) -> String {
+    let policy = EmailPolicy {
+      strip_plus_addressing,
+    };
+    EmailAddress::new(input, true)
+      .unwrap()
+      .unwrap()
+      .canonical_with(&policy)
+  }
+
+  #[test]
+  fn plus_addressed_emails_share_canonical_key_when_enabled() {
+    let key = canonical("user@gmail.com", true);
+    assert_eq!(key, "user@gmail.com");
+    for tagged in ["user+tag@gmail.com", "User+news+2024@GMail.com"] {
+      assert_eq!(canonical(tagged, true), key, "{tagged}");
+    }
+    // 入力された値は変更しない
+    let email = EmailAddress::new("user+tag@gmail.com", true)
+      .unwrap()
+      .unwrap();
+    assert_eq!(email.as_str(), "user+tag@gmail.com");
+  }
+
+  #[test]
+  fn plus_addressing_is_kept_when_disabled_or_unknown_provider() {
+    assert_eq!(canonical("user+tag@gmail.com", false), "user+tag@gmail.com");
+    // タグを無視するか不明なプロバイダは，そのまま返す
+    assert_eq!(
+      canonical("User+tag@example.com", true),
+      "User+tag@example.com"
+    );
+  }
+
   #[test]
   fn test_valid_email_address() {
     let result = EmailAddress::new(valid_email(), true);
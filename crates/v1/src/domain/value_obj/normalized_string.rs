@@ -1,18 +1,132 @@
 //! 空文字禁止，NFKC正規化，必須・最大長チェックを行う汎用VO
 
 use crate::{
-  interfaces::http::error::{AppError, AppResult},
+  config::{CharCategory, Validation},
+  domain::rejection::{RejectReason, reject},
+  interfaces::http::error::AppResult,
   utils::string::is_forbidden_char,
 };
+use std::{fmt::Write, sync::OnceLock};
+use tracing::{self as log, Level};
+use unicode_general_category::{GeneralCategory::*, get_general_category};
 use unicode_normalization::UnicodeNormalization;
 use unicode_segmentation::UnicodeSegmentation;
 
+/// 使用禁止文字の扱い
+///
+/// `sanitize`がtrueの場合，使用禁止文字(制御文字・双方向制御文字など)をエラーとせず除去する。
+///
+/// ## セキュリティ上の注意
+/// - 除去は利用者に通知されないため，入力した値と保存される値が一致しない場合がある。
+/// - 除去後の値で長さ・必須チェックを行うため，禁止文字のみの入力は空として扱う。
+/// - 双方向制御文字による表示の偽装は防げるが，除去により別の既存値と同じ文字列になり得る。
+///   一意性が必要な値(ユーザー名など)では，比較を必ず除去後の値で行うこと。
+/// - 不正な入力を検知・記録したい場合はfalse(拒否)を使用する。
+///
+/// `allowed`を指定した場合，使用禁止文字に加えて許可されていない大分類の文字を拒否する。
+/// (許可されていない文字は`sanitize`の値によらず除去しない)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextPolicy {
+  pub sanitize: bool,
+  pub allowed: Option<CategorySet>,
+}
+
+/// 使用を許可するUnicode一般カテゴリの大分類の集合
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CategorySet(u8);
+
+impl CategorySet {
+  /// 大分類の一覧から生成する。(空の場合はNone = 制限なし)
+  pub fn new(categories: &[CharCategory]) -> Option<Self> {
+    let bits = categories.iter().fold(0, |bits, &c| bits | Self::bit(c));
+    (bits != 0).then_some(Self(bits))
+  }
+
+  /// 文字の大分類が許可されているかを返す。
+  /// (ZWJ / ZWNJは結合文字と同様に扱う)
+  pub fn contains(&self, c: char) -> bool {
+    let category = match get_general_category(c) {
+      UppercaseLetter | LowercaseLetter | TitlecaseLetter | ModifierLetter | OtherLetter => {
+        CharCategory::Letter
+      }
+      NonspacingMark | SpacingMark | EnclosingMark => CharCategory::Mark,
+      Format if c == '\u{200C}' || c == '\u{200D}' => CharCategory::Mark,
+      DecimalNumber | LetterNumber | OtherNumber => CharCategory::Number,
+      ConnectorPunctuation | DashPunctuation | OpenPunctuation | ClosePunctuation
+      | InitialPunctuation | FinalPunctuation | OtherPunctuation => CharCategory::Punctuation,
+      MathSymbol | CurrencySymbol | ModifierSymbol | OtherSymbol => CharCategory::Symbol,
+      SpaceSeparator => CharCategory::Separator,
+      _ => return false,
+    };
+    self.0 & Self::bit(category) != 0
+  }
+
+  fn bit(category: CharCategory) -> u8 {
+    1 << category as u8
+  }
+}
+
+/// 起動時に設定した使用禁止文字の扱い
+static TEXT_POLICY: OnceLock<TextPolicy> = OnceLock::new();
+
+impl TextPolicy {
+  /// 既定値(使用禁止文字を拒否する)
+  const DEFAULT: TextPolicy = TextPolicy {
+    sanitize: false,
+    allowed: None,
+  };
+
+  /// Configの[validation]から生成する。
+  /// (許可する大分類はフィールドごとに`allowing`で指定する)
+  pub fn from_config(config: &Validation) -> Self {
+    Self {
+      sanitize: config.sanitize_forbidden_chars,
+      allowed: None,
+    }
+  }
+
+  /// 許可する大分類を指定したポリシーを返す。(Noneの場合は制限なし)
+  pub fn allowing(self, allowed: Option<CategorySet>) -> Self {
+    Self { allowed, ..self }
+  }
+
+  /// アプリケーション全体のポリシーとして設定する。
+  /// (2回目以降の呼び出しは無視される)
+  pub fn install(self) {
+    let _ = TEXT_POLICY.set(self);
+  }
+
+  /// 設定済みのポリシーを返す。(未設定の場合は既定値)
+  pub fn current() -> &'static TextPolicy {
+    TEXT_POLICY.get().unwrap_or(&Self::DEFAULT)
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NormalizedString {
   value: String,
 }
 
 impl NormalizedString {
+  /// # Constructor
+  /// 起動時に設定したポリシーで使用禁止文字を扱う。(詳細は`with_policy`を参照)
+  pub fn new<S: AsRef<str>>(
+    input: S,
+    required: bool,
+    target: &str,
+    min_len: Option<usize>,
+    max_len: Option<usize>,
+  ) -> AppResult<Option<Self>> {
+    Self::with_policy(
+      input,
+      required,
+      target,
+      min_len,
+      max_len,
+      TextPolicy::current(),
+    )
+  }
+
   /// # Constructor
   ///
   /// ## @param
@@ -21,9 +135,13 @@ impl NormalizedString {
   /// - `target`: エラーメッセージ用のパラメータ名
   /// - `min_len`: 最小文字数（Noneの場合は制限なし）
   /// - `max_len`: 最大文字数（Noneの場合は制限なし）
+  /// - `policy`: 使用禁止文字の扱いと許可する大分類
   ///
   /// ## processing
   /// - NFKC正規化 & trim
+  /// - `policy.sanitize`がtrueの場合は使用禁止文字を除去して再度trim，falseの場合はエラーを返す。
+  /// - 正規化により値が変化した場合は，伏字にした前後の値をtraceレベルで記録する。
+  /// - `policy.allowed`が指定されている場合，許可されていない大分類の文字を含むとエラーを返す。
   /// - `required`がtrueの場合は，エラーを返す。
   /// - 文字数がmin_len未満又はmax_lenを超える場合はエラーを返す。
   ///
@@ -31,35 +149,70 @@ impl NormalizedString {
   /// - 正常時：正規化済みの入力が空でなければSome(NormalizedString)を返す。
   /// - `required`がfalseの場合かつ，正規化済みのinputが空文字列の場合はNoneを返す。
   /// - 異常時：AppErrorを返す。
-  pub fn new<S: AsRef<str>>(
+  pub fn with_policy<S: AsRef<str>>(
     // S = StringにInto可能な値(&str, String)
     input: S,
     required: bool,
     target: &str,
     min_len: Option<usize>,
     max_len: Option<usize>,
+    policy: &TextPolicy,
   ) -> AppResult<Option<Self>> {
     // 文字列の正規化
     // NFKC正規化・trim処理
     // trim()は&strを返すため，to_string()でStringに戻す。
-    let normalized = input.as_ref().nfkc().collect::<String>().trim().to_string();
+    let mut normalized = input.as_ref().nfkc().collect::<String>().trim().to_string();
+
+    // 使用禁止文字を除去する。除去により両端に現れた空白も取り除く。
+    if policy.sanitize && normalized.chars().any(is_forbidden_char) {
+      normalized = normalized
+        .chars()
+        .filter(|&c| !is_forbidden_char(c))
+        .collect::<String>()
+        .trim()
+        .to_string();
+    }
+
+    // 正規化による変化を記録する。(個人情報を含むため，traceレベルかつ伏字でのみ出力する)
+    if log::enabled!(Level::TRACE) && normalized != input.as_ref() {
+      log::trace!(
+        target = target,
+        before = %redact(input.as_ref()),
+        after = %redact(&normalized),
+        "Input altered by normalization"
+      );
+    }
 
     // 値が存在するかを確認する。
     if normalized.is_empty() {
       // 値が存在しない場合，そのパラメータが必須パラメータである場合はエラーを返す。
       return if required {
-        Err(AppError::UnprocessableContent(Some(format!(
-          "{target}は必須のパラメータです。"
-        ))))
+        Err(reject(
+          target,
+          RejectReason::Required,
+          format!("{target}は必須のパラメータです。"),
+        ))
       } else {
         Ok(None)
       };
     }
 
     if normalized.chars().any(is_forbidden_char) {
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{target}に使用禁止文字を含みます。"
-      ))));
+      return Err(reject(
+        target,
+        RejectReason::ForbiddenChar,
+        format!("{target}に使用禁止文字を含みます。"),
+      ));
+    }
+
+    if let Some(allowed) = policy.allowed
+      && !normalized.chars().all(|c| allowed.contains(c))
+    {
+      return Err(reject(
+        target,
+        RejectReason::DisallowedChar,
+        format!("{target}に使用できない文字を含みます。"),
+      ));
     }
 
     // グラフェム単位で文字列長をカウントする。
@@ -67,21 +220,25 @@ impl NormalizedString {
     let len = graphemes.count();
 
     // 最小文字列長が定義されている場合
-    if let Some(min) = min_len {
-      if len < min {
-        return Err(AppError::UnprocessableContent(Some(format!(
-          "{target}は{min}文字以上で入力してください。"
-        ))));
-      }
+    if let Some(min) = min_len
+      && len < min
+    {
+      return Err(reject(
+        target,
+        RejectReason::TooShort,
+        format!("{target}は{min}文字以上で入力してください。"),
+      ));
     }
 
     // 最大文字列長が定義されている場合
-    if let Some(max) = max_len {
-      if len > max {
-        return Err(AppError::UnprocessableContent(Some(format!(
-          "{target}は{max}文字以内で入力してください。"
-        ))));
-      }
+    if let Some(max) = max_len
+      && len > max
+    {
+      return Err(reject(
+        target,
+        RejectReason::TooLong,
+        format!("{target}は{max}文字以内で入力してください。"),
+      ));
     }
     //
     Ok(Some(Self { value: normalized }))
@@ -92,9 +249,63 @@ impl NormalizedString {
   }
 }
 
+/// 正規化の前後の差を確認するため，文字の種類のみを残して伏字にする。
+/// ASCIIの英数字は`*`，その他の英数字(全角・かな・漢字など)は`#`とし，
+/// ASCIIの記号・空白はそのまま，それ以外は`\u{XXXX}`の形式で出力する。
+fn redact(s: &str) -> String {
+  s.chars()
+    .fold(String::with_capacity(s.len()), |mut out, c| {
+      match c {
+        c if c.is_ascii_alphanumeric() => out.push('*'),
+        c if c.is_alphanumeric() => out.push('#'),
+        ' ' => out.push(' '),
+        c if c.is_ascii_graphic() => out.push(c),
+        c => {
+          let _ = write!(out, "\\u{{{:04X}}}", c as u32);
+        }
+      }
+      out
+    })
+}
+
 #[cfg(test)]
 mod tests {
-  use crate::domain::value_obj::normalized_string::NormalizedString;
+  use super::redact;
+  use crate::{
+    config::CharCategory,
+    domain::value_obj::normalized_string::{CategorySet, NormalizedString, TextPolicy},
+  };
+  use std::{
+    fmt,
+    sync::{Arc, Mutex},
+  };
+  use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+    subscriber::with_default,
+  };
+  use tracing_subscriber::{
+    Layer, Registry,
+    filter::LevelFilter,
+    layer::{Context, SubscriberExt},
+  };
+
+  const REJECT: TextPolicy = TextPolicy {
+    sanitize: false,
+    allowed: None,
+  };
+  const SANITIZE: TextPolicy = TextPolicy {
+    sanitize: true,
+    allowed: None,
+  };
+
+  fn letters_only() -> TextPolicy {
+    REJECT.allowing(CategorySet::new(&[
+      CharCategory::Letter,
+      CharCategory::Mark,
+      CharCategory::Separator,
+    ]))
+  }
 
   #[test]
   fn normalizes_nfkc_differently_composed_characters() {
@@ -166,4 +377,123 @@ mod tests {
     let result = NormalizedString::new(input, true, "number", None, None).unwrap();
     assert_eq!(result.unwrap().as_str(), "123");
   }
+
+  #[test]
+  fn reject_and_sanitize_differ_on_control_and_bidi_chars() {
+    // 双方向制御文字(RLO)と制御文字(BEL)を含む入力
+    let input = "ali\u{202E}ce\u{0007}";
+    let err = NormalizedString::with_policy(input, true, "name", None, None, &REJECT).unwrap_err();
+    assert!(format!("{err:?}").contains("使用禁止文字"));
+
+    let result = NormalizedString::with_policy(input, true, "name", None, None, &SANITIZE).unwrap();
+    assert_eq!(result.unwrap().as_str(), "alice");
+  }
+
+  #[test]
+  fn sanitize_applies_length_and_required_checks_after_stripping() {
+    // 除去後は4文字となり最小長を満たさない
+    let input = "ab\u{200E}cd";
+    let err =
+      NormalizedString::with_policy(input, true, "name", Some(5), None, &SANITIZE).unwrap_err();
+    assert!(format!("{err:?}").contains("5文字以上"));
+
+    // 禁止文字のみの入力は空として扱う
+    let input = "\u{202E}\u{0007}";
+    let err =
+      NormalizedString::with_policy(input, true, "name", None, None, &SANITIZE).unwrap_err();
+    assert!(format!("{err:?}").contains("必須のパラメータ"));
+    let result =
+      NormalizedString::with_policy(input, false, "name", None, None, &SANITIZE).unwrap();
+    assert!(result.is_none());
+  }
+
+  #[test]
+  fn sanitize_trims_spaces_exposed_by_stripping() {
+    let input = "\u{0007} bob \u{202E}";
+    let result = NormalizedString::with_policy(input, true, "name", None, None, &SANITIZE).unwrap();
+    assert_eq!(result.unwrap().as_str(), "bob");
+  }
+
+  #[test]
+  fn sanitize_keeps_zwj_sequences() {
+    let input = "👨\u{200D}👩\u{0007}";
+    let result = NormalizedString::with_policy(input, true, "name", None, None, &SANITIZE).unwrap();
+    assert_eq!(result.unwrap().as_str(), "👨\u{200D}👩");
+  }
+
+  #[test]
+  fn allowlist_rejects_emoji_accepted_by_default_policy() {
+    let input = "Taro😀";
+    let result = NormalizedString::with_policy(input, true, "name", None, None, &REJECT).unwrap();
+    assert_eq!(result.unwrap().as_str(), input);
+
+    let err =
+      NormalizedString::with_policy(input, true, "name", None, None, &letters_only()).unwrap_err();
+    assert!(format!("{err:?}").contains("使用できない文字"));
+  }
+
+  #[test]
+  fn allowlist_accepts_letters_marks_and_spaces() {
+    // 結合文字(濁点)と空白を含む氏名
+    for input in ["山田 太郎", "Jose\u{0301}", "Zoë"] {
+      let result =
+        NormalizedString::with_policy(input, true, "name", None, None, &letters_only()).unwrap();
+      assert!(result.is_some(), "{input}");
+    }
+    assert!(
+      NormalizedString::with_policy("R2D2", true, "name", None, None, &letters_only()).is_err()
+    );
+    // 許可する大分類が空の場合は制限しない
+    assert_eq!(CategorySet::new(&[]), None);
+  }
+  #[test]
+  fn redact_keeps_only_char_kinds() {
+    assert_eq!(
+      redact("Ab1 ＡＢ山\u{3000}-!\u{0301}"),
+      "*** ###\\u{3000}-!\\u{0301}"
+    );
+  }
+
+  /// `level`で絞り込んだ購読者の下で`input`を正規化し，記録したイベントを返す。
+  fn trace_events(level: LevelFilter, input: &str) -> Vec<String> {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let subscriber = Registry::default()
+      .with(Recorder(events.clone()))
+      .with(level);
+    with_default(subscriber, || {
+      let _ = NormalizedString::new(input, true, "name", None, None);
+    });
+    Arc::try_unwrap(events).unwrap().into_inner().unwrap()
+  }
+
+  /// イベントのフィールドを`name=value`の形式で記録するレイヤー
+  struct Recorder(Arc<Mutex<Vec<String>>>);
+
+  impl<S: Subscriber> Layer<S> for Recorder {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+      struct Fields(String);
+      impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+          self.0.push_str(&format!("{}={value:?} ", field.name()));
+        }
+      }
+      let mut fields = Fields(String::new());
+      event.record(&mut fields);
+      self.0.lock().unwrap().push(fields.0);
+    }
+  }
+
+  #[test]
+  fn trace_event_fires_only_when_normalization_alters_input() {
+    let events = trace_events(LevelFilter::TRACE, "　ＡＢＣ　");
+    assert_eq!(events.len(), 1);
+    assert!(events[0].contains("before=\\u{3000}###\\u{3000}"));
+    assert!(events[0].contains("after=***"));
+    // 入力値そのものは出力しない
+    assert!(!events[0].contains("ＡＢＣ") && !events[0].contains("ABC"));
+
+    assert!(trace_events(LevelFilter::TRACE, "ABC").is_empty());
+    // trace未満のレベルでは出力しない
+    assert!(trace_events(LevelFilter::DEBUG, "　ＡＢＣ　").is_empty());
+  }
 }
@@ -1,26 +1,184 @@
 use crate::{
-  domain::value_obj::normalized_string::NormalizedString,
+  config::Validation,
+  domain::{
+    rejection::{RejectReason, reject},
+    value_obj::normalized_string::NormalizedString,
+  },
   interfaces::http::error::{AppError, AppResult},
   utils::regex,
 };
+use std::{fmt, sync::OnceLock};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct PhoneNumber(pub NormalizedString);
 
+/// 電話番号の形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PhoneFormat {
+  /// 国内形式(先頭0，10 or 11桁)
+  #[default]
+  Jp,
+  /// E.164形式(+国番号から始まる)
+  E164,
+}
+
+/// 電話番号の検証ポリシー
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PhonePolicy {
+  pub format: PhoneFormat,
+  /// 許可する国(ISO 3166-1 alpha-2，大文字)。空の場合は全ての国を許可する。
+  /// E.164形式の場合のみ有効。
+  pub allowed_countries: Vec<String>,
+}
+
+/// 起動時に設定した検証ポリシー
+static PHONE_POLICY: OnceLock<PhonePolicy> = OnceLock::new();
+
+/// 国番号とISOコードの対応表
+/// (同じ国番号を複数の国が共有する場合は全て列挙する)
+const COUNTRY_CALLING_CODES: &[(&str, &str)] = &[
+  ("1", "US"),
+  ("1", "CA"),
+  ("7", "RU"),
+  ("33", "FR"),
+  ("34", "ES"),
+  ("39", "IT"),
+  ("44", "GB"),
+  ("49", "DE"),
+  ("61", "AU"),
+  ("62", "ID"),
+  ("63", "PH"),
+  ("64", "NZ"),
+  ("65", "SG"),
+  ("66", "TH"),
+  ("81", "JP"),
+  ("82", "KR"),
+  ("84", "VN"),
+  ("86", "CN"),
+  ("91", "IN"),
+  ("852", "HK"),
+  ("853", "MO"),
+  ("886", "TW"),
+];
+
+impl PhonePolicy {
+  /// Configの[validation]から生成する。
+  pub fn from_config(config: &Validation) -> AppResult<Self> {
+    let format = match config.phone_format.to_lowercase().as_str() {
+      "jp" => PhoneFormat::Jp,
+      "e164" => PhoneFormat::E164,
+      other => {
+        return Err(AppError::InternalServerError(Some(format!(
+          "Unknown phone_format '{}' (expected 'jp' or 'e164')",
+          other
+        ))));
+      }
+    };
+
+    let allowed_countries = config
+      .phone_allowed_countries
+      .iter()
+      .map(|c| {
+        let code = c.trim().to_uppercase();
+        if COUNTRY_CALLING_CODES.iter().any(|(_, iso)| *iso == code) {
+          Ok(code)
+        } else {
+          Err(AppError::InternalServerError(Some(format!(
+            "Unsupported country code in phone_allowed_countries: '{}'",
+            c
+          ))))
+        }
+      })
+      .collect::<AppResult<Vec<_>>>()?;
+
+    Ok(Self {
+      format,
+      allowed_countries,
+    })
+  }
+
+  /// アプリケーション全体の検証ポリシーとして設定する。
+  /// (2回目以降の呼び出しは無視される)
+  pub fn install(self) {
+    let _ = PHONE_POLICY.set(self);
+  }
+
+  /// 設定済みの検証ポリシーを返す。(未設定の場合は国内形式)
+  pub fn current() -> &'static PhonePolicy {
+    static DEFAULT: PhonePolicy = PhonePolicy {
+      format: PhoneFormat::Jp,
+      allowed_countries: Vec::new(),
+    };
+    PHONE_POLICY.get().unwrap_or(&DEFAULT)
+  }
+
+  /// E.164形式の番号の国が許可されているか判定する。
+  fn is_allowed(&self, e164: &str) -> bool {
+    if self.allowed_countries.is_empty() {
+      return true;
+    }
+    country_codes(e164)
+      .iter()
+      .any(|iso| self.allowed_countries.iter().any(|c| c == iso))
+  }
+}
+
+/// E.164形式の番号から国番号を最長一致で解決し，該当するISOコードを返す。
+fn country_codes(e164: &str) -> Vec<&'static str> {
+  let digits = e164.trim_start_matches('+');
+  (1..=3)
+    .rev()
+    .filter_map(|n| digits.get(..n))
+    .map(|prefix| {
+      COUNTRY_CALLING_CODES
+        .iter()
+        .filter(|(code, _)| *code == prefix)
+        .map(|(_, iso)| *iso)
+        .collect::<Vec<_>>()
+    })
+    .find(|isos| !isos.is_empty())
+    .unwrap_or_default()
+}
+
+/// ISOコードに対応する国番号を返す。
+pub(crate) fn calling_code(country: &str) -> Option<&'static str> {
+  COUNTRY_CALLING_CODES
+    .iter()
+    .find(|(_, iso)| *iso == country)
+    .map(|(code, _)| *code)
+}
+
 impl PhoneNumber {
   const TARGET: &str = "電話番号(phone_number)";
   const MIN_LEN: usize = 10;
   const MAX_LEN: usize = 11;
+  const E164_MIN_LEN: usize = 9;
+  const E164_MAX_LEN: usize = 16;
 
+  /// 形式ごとの長さの下限・上限
+  pub(crate) fn length_limits(format: PhoneFormat) -> (usize, usize) {
+    match format {
+      PhoneFormat::Jp => (Self::MIN_LEN, Self::MAX_LEN),
+      PhoneFormat::E164 => (Self::E164_MIN_LEN, Self::E164_MAX_LEN),
+    }
+  }
+
+  /// 起動時に設定した検証ポリシーで電話番号を検証する。
   pub fn new<S: AsRef<str>>(input: S, required: bool) -> AppResult<Option<Self>> {
+    Self::with_policy(input, required, PhonePolicy::current())
+  }
+
+  /// 指定した検証ポリシーで電話番号を検証する。
+  pub fn with_policy<S: AsRef<str>>(
+    input: S,
+    required: bool,
+    policy: &PhonePolicy,
+  ) -> AppResult<Option<Self>> {
+    let (min_len, max_len) = Self::length_limits(policy.format);
+
     // 正規化・必須長さチェック
-    let phone_number_opt = NormalizedString::new(
-      input,
-      required,
-      Self::TARGET,
-      Some(Self::MIN_LEN),
-      Some(Self::MAX_LEN),
-    )?;
+    let phone_number_opt =
+      NormalizedString::new(input, required, Self::TARGET, Some(min_len), Some(max_len))?;
 
     // 空文字の場合はNoneを返す。
     let phone_number = match phone_number_opt {
@@ -29,13 +187,44 @@ impl PhoneNumber {
     };
 
     // 正規表現によるチェック
-    if !regex::PHONE_NUMBER_REGEX.is_match(phone_number.as_str()) {
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{}は以下のルールに従う必要があります。\n・使用可能文字：数字のみ\n・長さは{}文字以上{}文字以下\n・先頭は0で始める必要があります。",
-        Self::TARGET,
-        Self::MIN_LEN,
-        Self::MAX_LEN,
-      ))));
+    match policy.format {
+      PhoneFormat::Jp if !regex::PHONE_NUMBER_REGEX.is_match(phone_number.as_str()) => {
+        return Err(reject(
+          Self::TARGET,
+          RejectReason::InvalidFormat,
+          format!(
+            "{}は以下のルールに従う必要があります。\n・使用可能文字：数字のみ\n・長さは{}文字以上{}文字以下\n・先頭は0で始める必要があります。",
+            Self::TARGET,
+            Self::MIN_LEN,
+            Self::MAX_LEN,
+          ),
+        ));
+      }
+      PhoneFormat::E164 if !regex::E164_PHONE_NUMBER_REGEX.is_match(phone_number.as_str()) => {
+        return Err(reject(
+          Self::TARGET,
+          RejectReason::InvalidFormat,
+          format!(
+            "{}は以下のルールに従う必要があります。\n・E.164形式(例：+819012345678)\n・+の後は数字のみ\n・長さは+を含めて{}文字以上{}文字以下",
+            Self::TARGET,
+            Self::E164_MIN_LEN,
+            Self::E164_MAX_LEN,
+          ),
+        ));
+      }
+      // 許可されていない国の番号の場合はエラーを返す。
+      PhoneFormat::E164 if !policy.is_allowed(phone_number.as_str()) => {
+        return Err(reject(
+          Self::TARGET,
+          RejectReason::DisallowedCountry,
+          format!(
+            "{}の国番号は利用できません。\n・利用可能な国：{}",
+            Self::TARGET,
+            policy.allowed_countries.join(", "),
+          ),
+        ));
+      }
+      _ => {}
     }
 
     // 正常時はPhoneNumber型のオブジェクトを返す。
@@ -46,6 +235,34 @@ impl PhoneNumber {
   pub fn as_str(&self) -> &str {
     self.0.as_str()
   }
+
+  /// 先頭3文字と末尾4文字以外をマスクした文字列を返す。(例：090****5678)
+  pub fn redacted(&self) -> String {
+    const HEAD: usize = 3;
+    const TAIL: usize = 4;
+    let chars: Vec<char> = self.as_str().chars().collect();
+    let masked = chars.len().saturating_sub(HEAD + TAIL);
+    chars
+      .iter()
+      .enumerate()
+      .map(|(i, c)| {
+        if i >= HEAD && i < HEAD + masked {
+          '*'
+        } else {
+          *c
+        }
+      })
+      .collect()
+  }
+}
+
+/// ログへの個人情報の出力を防ぐため，マスクした値を出力する
+impl fmt::Debug for PhoneNumber {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("PhoneNumber")
+      .field(&self.redacted())
+      .finish()
+  }
 }
 #[cfg(test)]
 mod tests {
@@ -109,6 +326,85 @@ mod tests {
     assert!(result.is_err());
   }
 
+  fn validation(format: &str, countries: &[&str]) -> Validation {
+    Validation {
+      username_min: 3,
+      username_max: 64,
+      phone_format: format.into(),
+      phone_allowed_countries: countries.iter().map(|c| c.to_string()).collect(),
+      name_first_min: 0,
+      name_first_max: 64,
+      name_last_min: 0,
+      name_last_max: 64,
+      name_allowed_categories: vec![],
+      sanitize_forbidden_chars: false,
+      immutable_fields: vec![],
+      password_min_bits: 0,
+      strip_plus_addressing: false,
+    }
+  }
+
+  fn e164_policy(countries: &[&str]) -> PhonePolicy {
+    PhonePolicy::from_config(&validation("e164", countries)).unwrap()
+  }
+
+  #[test]
+  fn test_e164_allowed_country() {
+    let policy = e164_policy(&["jp", "US"]);
+    for num in ["+819012345678", "+14155552671"] {
+      let phone = PhoneNumber::with_policy(num, true, &policy)
+        .unwrap()
+        .unwrap();
+      assert_eq!(phone.as_str(), num);
+    }
+  }
+
+  #[test]
+  fn test_e164_disallowed_country() {
+    let policy = e164_policy(&["JP"]);
+    let err = PhoneNumber::with_policy("+821012345678", true, &policy).unwrap_err();
+    assert!(matches!(err, AppError::UnprocessableContent(Some(m)) if m.contains("JP")));
+    // 国番号が対応表に存在しない場合も拒否する
+    assert!(PhoneNumber::with_policy("+999123456789", true, &policy).is_err());
+  }
+
+  #[test]
+  fn test_e164_without_restriction() {
+    let policy = e164_policy(&[]);
+    assert!(PhoneNumber::with_policy("+999123456789", true, &policy).is_ok());
+    // 国内形式はE.164形式では拒否する
+    assert!(PhoneNumber::with_policy("09012345678", true, &policy).is_err());
+  }
+
+  #[test]
+  fn test_jp_mode_ignores_allowed_countries() {
+    let policy = PhonePolicy {
+      format: PhoneFormat::Jp,
+      allowed_countries: vec!["US".into()],
+    };
+    assert!(PhoneNumber::with_policy("09012345678", true, &policy).is_ok());
+  }
+
+  #[test]
+  fn test_unknown_policy_config() {
+    assert!(PhonePolicy::from_config(&validation("intl", &[])).is_err());
+    assert!(PhonePolicy::from_config(&validation("e164", &["XX"])).is_err());
+  }
+
+  #[test]
+  fn test_phone_number_redacted() {
+    let phone = PhoneNumber::new("09012345678", true).unwrap().unwrap();
+    assert_eq!(phone.redacted(), "090****5678");
+    assert_eq!(format!("{:?}", phone), r#"PhoneNumber("090****5678")"#);
+    assert_eq!(phone.as_str(), "09012345678");
+
+    let policy = e164_policy(&[]);
+    let phone = PhoneNumber::with_policy("+819012345678", true, &policy)
+      .unwrap()
+      .unwrap();
+    assert_eq!(phone.redacted(), "+81******5678");
+  }
+
   #[test]
   fn test_phone_number_as_str() {
     let num = "09012345678";
@@ -1,4 +1,7 @@
-use crate::interfaces::http::error::{AppError, AppResult};
+use crate::{
+  domain::rejection::{RejectReason, reject},
+  interfaces::http::error::AppResult,
+};
 use nid::Nanoid;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -20,22 +23,46 @@ impl PublicId {
       return Ok(None);
     }
     if input.len() != Self::LEN {
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{}は{}文字で入力してください。",
+      return Err(reject(
         Self::TARGET,
-        Self::LEN
-      ))));
+        RejectReason::InvalidFormat,
+        format!("{}は{}文字で入力してください。", Self::TARGET, Self::LEN),
+      ));
     }
 
     match Nanoid::try_from_str(input) {
       Ok(nanoid) => Ok(Some(Self(nanoid))),
-      Err(_) => Err(AppError::UnprocessableContent(Some(format!(
-        "{}はNanoidの形式[A-Za-z0-9_-]で入力してください。",
+      Err(_) => Err(reject(
         Self::TARGET,
-      )))),
+        RejectReason::InvalidFormat,
+        format!(
+          "{}はNanoidの形式[A-Za-z0-9_-]で入力してください。",
+          Self::TARGET,
+        ),
+      )),
     }
   }
 
+  /// シードから決定的に公開IDを生成する。
+  /// 同じシードからは常に同じ公開IDを生成する。(テストデータ・シャーディング用)
+  #[cfg(any(test, feature = "deterministic-ids"))]
+  pub fn from_seed(seed: &[u8]) -> Self {
+    use sha3::{Digest, Sha3_256};
+
+    // Nanoidのアルファベット(64文字)
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_-";
+
+    // ダイジェストの各バイトの下位6bitをアルファベットに対応させる
+    let digest = Sha3_256::digest(seed);
+    let id: String = digest
+      .iter()
+      .take(Self::LEN)
+      .map(|b| ALPHABET[(b & 0x3F) as usize] as char)
+      .collect();
+
+    Self(Nanoid::try_from_str(&id).expect("from_seed generates a valid nanoid"))
+  }
+
   /// 公開IDを文字列への参照として返す。
   pub fn as_str(&self) -> &str {
     self.0.as_str()
@@ -56,6 +83,7 @@ impl Default for PublicId {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::interfaces::http::error::AppError;
 
   #[test]
   fn test_new_generates_valid_public_id() {
@@ -112,6 +140,18 @@ mod tests {
     ));
   }
 
+  #[test]
+  fn test_from_seed_is_deterministic() {
+    let a = PublicId::from_seed(b"fixture-user-1");
+    let b = PublicId::from_seed(b"fixture-user-1");
+    assert_eq!(a, b);
+    assert_ne!(a, PublicId::from_seed(b"fixture-user-2"));
+
+    // from_stringの検証を通過すること
+    let parsed = PublicId::from_string(a.as_str(), true).unwrap().unwrap();
+    assert_eq!(parsed, a);
+  }
+
   #[test]
   fn test_as_nanoid_returns_inner() {
     let public_id = PublicId::new();
@@ -1,21 +1,52 @@
 use std::fmt::{Display, Formatter, Result};
 
+use serde::Deserialize;
 use uuid::Uuid;
 
-use crate::interfaces::http::error::{AppError, AppResult};
+use crate::{
+  domain::rejection::{RejectReason, reject},
+  interfaces::http::error::AppResult,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SessionId(Uuid);
 
+/// セッションIDの生成方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdStrategy {
+  /// ランダム(UUIDv4)
+  #[default]
+  UuidV4,
+  /// 時刻順にソート可能(UUIDv7)。インデックスの局所性が向上する。
+  UuidV7,
+}
+
+impl IdStrategy {
+  /// 方式に従ってUUIDを生成する。
+  fn generate(self) -> Uuid {
+    match self {
+      Self::UuidV4 => Uuid::new_v4(),
+      Self::UuidV7 => Uuid::now_v7(),
+    }
+  }
+}
+
 impl SessionId {
   const TARGET: &str = "セッションID(session_id)";
 
-  /// セッションIDを生成する
+  /// セッションIDを生成する(UUIDv4)
   pub fn new() -> Self {
-    Self(Uuid::new_v4())
+    Self::generate(IdStrategy::default())
+  }
+
+  /// 指定した方式でセッションIDを生成する
+  pub fn generate(strategy: IdStrategy) -> Self {
+    Self(strategy.generate())
   }
 
   /// 文字列からUUIDを生成する
+  /// UUIDのバージョンは問わない
   pub fn from_string<S: AsRef<str>>(input: S, required: bool) -> AppResult<Option<Self>> {
     let input = input.as_ref().trim();
     if !required && input.is_empty() {
@@ -23,10 +54,11 @@ impl SessionId {
     }
     match Uuid::parse_str(input) {
       Ok(u) => Ok(Some(Self(u))),
-      Err(_) => Err(AppError::UnprocessableContent(Some(format!(
-        "{}はUUIDの形式で入力してください。",
-        Self::TARGET
-      )))),
+      Err(_) => Err(reject(
+        Self::TARGET,
+        RejectReason::InvalidFormat,
+        format!("{}はUUIDの形式で入力してください。", Self::TARGET),
+      )),
     }
   }
 
@@ -34,6 +66,13 @@ impl SessionId {
   pub fn as_uuid(&self) -> &Uuid {
     &self.0
   }
+
+  /// 先頭8文字以外をマスクした文字列を返す。(例：1a2b3c4d-****)
+  /// セッションIDは認証に使用できるため，一覧などの表示にはこちらを使用する。
+  pub fn masked(&self) -> String {
+    let s = self.0.simple().to_string();
+    format!("{}-****", &s[..8])
+  }
 }
 
 /// セッションIDを文字列への参照として返す。
@@ -53,6 +92,15 @@ mod tests {
   use super::*;
   use std::collections::HashSet;
 
+  #[test]
+  fn test_masked_hides_all_but_prefix() {
+    let id = SessionId::new();
+    let masked = id.masked();
+    assert_eq!(masked.len(), 13);
+    assert!(id.to_string().starts_with(&masked[..8]));
+    assert!(!masked.contains(&id.to_string()[9..]));
+  }
+
   #[test]
   fn test_new_generates_valid_uuid() {
     let session_id = SessionId::new();
@@ -60,6 +108,39 @@ mod tests {
     assert_eq!(uuid.get_version_num(), 4);
   }
 
+  #[test]
+  fn test_generate_v7_is_time_sortable() {
+    let first = SessionId::generate(IdStrategy::UuidV7);
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    let second = SessionId::generate(IdStrategy::UuidV7);
+    assert_eq!(first.as_uuid().get_version_num(), 7);
+    assert!(first.as_uuid() < second.as_uuid());
+  }
+
+  #[test]
+  fn test_from_string_accepts_v7() {
+    let id = SessionId::generate(IdStrategy::UuidV7);
+    let parsed = SessionId::from_string(id.to_string(), true)
+      .unwrap()
+      .unwrap();
+    assert_eq!(parsed, id);
+    assert_eq!(parsed.as_uuid().get_version_num(), 7);
+  }
+
+  #[test]
+  fn test_id_strategy_deserialize() {
+    #[derive(Deserialize)]
+    struct Wrapper {
+      id_strategy: IdStrategy,
+    }
+    let parse = |s: &str| {
+      serde_json::from_str::<Wrapper>(&format!(r#"{{"id_strategy":"{s}"}}"#)).map(|w| w.id_strategy)
+    };
+    assert_eq!(parse("uuidv4").unwrap(), IdStrategy::UuidV4);
+    assert_eq!(parse("uuidv7").unwrap(), IdStrategy::UuidV7);
+    assert!(parse("ulid").is_err());
+  }
+
   #[test]
   fn test_from_string_valid_uuid_required_true() {
     let uuid = Uuid::new_v4();
@@ -1,6 +1,12 @@
 use crate::{
-  domain::value_obj::normalized_string::NormalizedString, interfaces::http::error::AppResult,
+  config::Validation,
+  domain::{
+    rejection::{RejectReason, reject},
+    value_obj::normalized_string::{CategorySet, NormalizedString, TextPolicy},
+  },
+  interfaces::http::error::{AppError, AppResult},
 };
+use std::sync::OnceLock;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UserFullName {
@@ -8,31 +14,114 @@ pub struct UserFullName {
   pub last_name: Option<NormalizedString>,
 }
 
+/// 氏名の長さの検証ポリシー
+/// 最小長は値が入力されている場合のみ適用する。(Noneの場合は制限なし)
+/// `allowed`は使用できる文字の大分類。(Noneの場合は制限なし)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamePolicy {
+  pub first_min: Option<usize>,
+  pub first_max: usize,
+  pub last_min: Option<usize>,
+  pub last_max: usize,
+  pub allowed: Option<CategorySet>,
+}
+
+impl Default for NamePolicy {
+  fn default() -> Self {
+    Self::DEFAULT
+  }
+}
+
+/// 起動時に設定した検証ポリシー
+static NAME_POLICY: OnceLock<NamePolicy> = OnceLock::new();
+
+impl NamePolicy {
+  /// 既定値(最小長なし，最大長64)
+  const DEFAULT: NamePolicy = NamePolicy {
+    first_min: None,
+    first_max: UserFullName::MAX_LEN,
+    last_min: None,
+    last_max: UserFullName::MAX_LEN,
+    allowed: None,
+  };
+
+  /// Configの[validation]から生成する。
+  pub fn from_config(config: &Validation) -> AppResult<Self> {
+    // 0は制限なしとして扱う
+    let min = |n: usize| (n > 0).then_some(n);
+    let policy = Self {
+      first_min: min(config.name_first_min),
+      first_max: config.name_first_max,
+      last_min: min(config.name_last_min),
+      last_max: config.name_last_max,
+      allowed: CategorySet::new(&config.name_allowed_categories),
+    };
+
+    for (target, min, max) in [
+      ("name_first", policy.first_min, policy.first_max),
+      ("name_last", policy.last_min, policy.last_max),
+    ] {
+      if max == 0 || min.is_some_and(|min| min > max) {
+        return Err(AppError::InternalServerError(Some(format!(
+          "Invalid {target} length limits: min={}, max={max}",
+          min.unwrap_or(0)
+        ))));
+      }
+    }
+    Ok(policy)
+  }
+
+  /// アプリケーション全体の検証ポリシーとして設定する。
+  /// (2回目以降の呼び出しは無視される)
+  pub fn install(self) {
+    let _ = NAME_POLICY.set(self);
+  }
+
+  /// 設定済みの検証ポリシーを返す。(未設定の場合は既定値)
+  pub fn current() -> &'static NamePolicy {
+    NAME_POLICY.get().unwrap_or(&Self::DEFAULT)
+  }
+}
+
 impl UserFullName {
   const FIRST_TARGET: &str = "名(FirstName)";
   const LAST_TARGET: &str = "姓(LastName)";
   const FIRST_REQUIRED: bool = false;
   const LAST_REQUIRED: bool = false;
-  const MAX_LEN: usize = 64;
+  /// DBの列長(VARCHAR(64))
+  pub(crate) const MAX_LEN: usize = 64;
 
+  /// 起動時に設定した検証ポリシーで氏名を検証する。
   pub fn new<S: AsRef<str>>(input_f: S, input_l: S) -> AppResult<Option<Self>> {
-    // 正規化・必須長さチェック
+    Self::with_policy(input_f, input_l, NamePolicy::current())
+  }
+
+  /// 指定した検証ポリシーで氏名を検証する。
+  pub fn with_policy<S: AsRef<str>>(
+    input_f: S,
+    input_l: S,
+    policy: &NamePolicy,
+  ) -> AppResult<Option<Self>> {
+    // 正規化・必須長さ・使用できる文字のチェック
+    let text_policy = TextPolicy::current().allowing(policy.allowed);
     // first_name
-    let f_opt = NormalizedString::new(
+    let f_opt = NormalizedString::with_policy(
       input_f,
       Self::FIRST_REQUIRED,
       Self::FIRST_TARGET,
-      None,
-      Some(Self::MAX_LEN),
+      policy.first_min,
+      Some(policy.first_max),
+      &text_policy,
     )?;
 
     // last_name
-    let l_opt = NormalizedString::new(
+    let l_opt = NormalizedString::with_policy(
       input_l,
       Self::LAST_REQUIRED,
       Self::LAST_TARGET,
-      None,
-      Some(Self::MAX_LEN),
+      policy.last_min,
+      Some(policy.last_max),
+      &text_policy,
     )?;
 
     // すべて空ならNoneを返す
@@ -42,12 +131,11 @@ impl UserFullName {
 
     // first_nameが空でlast_nameに値がある場合はエラー
     if f_opt.is_none() && (l_opt.is_some()) {
-      return Err(
-        crate::interfaces::http::error::AppError::UnprocessableContent(Some(format!(
-          "{}は必須のパラメータです。",
-          Self::FIRST_TARGET
-        ))),
-      );
+      return Err(reject(
+        Self::FIRST_TARGET,
+        RejectReason::Required,
+        format!("{}は必須のパラメータです。", Self::FIRST_TARGET),
+      ));
     }
 
     // first_nameがある場合はSomeで返す
@@ -72,3 +160,94 @@ impl UserFullName {
     self.last_name.as_ref().map(|s| s.as_str())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::CharCategory;
+
+  fn policy(first_min: usize, first_max: usize, last_min: usize, last_max: usize) -> NamePolicy {
+    NamePolicy::from_config(&Validation {
+      username_min: 3,
+      username_max: 64,
+      phone_format: "jp".into(),
+      phone_allowed_countries: vec![],
+      name_first_min: first_min,
+      name_first_max: first_max,
+      name_last_min: last_min,
+      name_last_max: last_max,
+      name_allowed_categories: vec![],
+      sanitize_forbidden_chars: false,
+      immutable_fields: vec![],
+      password_min_bits: 0,
+      strip_plus_addressing: false,
+    })
+    .unwrap()
+  }
+
+  #[test]
+  fn default_policy_keeps_current_limits() {
+    let name = UserFullName::with_policy("a", &"b".repeat(64), &NamePolicy::default())
+      .unwrap()
+      .unwrap();
+    assert_eq!(name.first(), "a");
+    assert!(UserFullName::with_policy("a", &"b".repeat(65), &NamePolicy::default()).is_err());
+  }
+
+  #[test]
+  fn over_long_last_name_is_rejected() {
+    let policy = policy(0, 64, 0, 10);
+    assert!(UserFullName::with_policy("Taro", "Yamada", &policy).is_ok());
+    let err = UserFullName::with_policy("Taro", "Yamadayamada", &policy).unwrap_err();
+    assert!(matches!(err, AppError::UnprocessableContent(Some(m)) if m.contains("姓")));
+  }
+
+  #[test]
+  fn too_short_present_first_name_is_rejected() {
+    let policy = policy(2, 64, 0, 64);
+    let err = UserFullName::with_policy("T", "Yamada", &policy).unwrap_err();
+    assert!(matches!(err, AppError::UnprocessableContent(Some(m)) if m.contains("名")));
+    // 未入力の場合は最小長を適用しない
+    assert!(
+      UserFullName::with_policy("", "", &policy)
+        .unwrap()
+        .is_none()
+    );
+  }
+
+  #[test]
+  fn allowed_categories_restrict_name_characters() {
+    let mut policy = policy(0, 64, 0, 64);
+    assert!(UserFullName::with_policy("Taro😀", "Yamada", &policy).is_ok());
+
+    policy.allowed = CategorySet::new(&[
+      CharCategory::Letter,
+      CharCategory::Mark,
+      CharCategory::Separator,
+    ]);
+    let err = UserFullName::with_policy("Taro😀", "Yamada", &policy).unwrap_err();
+    assert!(matches!(err, AppError::UnprocessableContent(Some(m)) if m.contains("名")));
+    assert!(UserFullName::with_policy("Taro", "Yamada", &policy).is_ok());
+  }
+
+  #[test]
+  fn invalid_limits_are_rejected() {
+    let config = |first_min, first_max| Validation {
+      username_min: 3,
+      username_max: 64,
+      phone_format: "jp".into(),
+      phone_allowed_countries: vec![],
+      name_first_min: first_min,
+      name_first_max: first_max,
+      name_last_min: 0,
+      name_last_max: 64,
+      name_allowed_categories: vec![],
+      sanitize_forbidden_chars: false,
+      immutable_fields: vec![],
+      password_min_bits: 0,
+      strip_plus_addressing: false,
+    };
+    assert!(NamePolicy::from_config(&config(10, 5)).is_err());
+    assert!(NamePolicy::from_config(&config(0, 0)).is_err());
+  }
+}
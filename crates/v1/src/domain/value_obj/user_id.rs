@@ -4,20 +4,27 @@ use crate::interfaces::http::error::{AppError, AppResult};
 pub struct UserId(i64);
 
 impl UserId {
-  const TARGET: &str = "ユーザーID(user_id)";
-
-  /// user_idが正の整数であることを保証する。
-  /// - user_idは本システムにおいて必須の値の為，存在は保証されている。
-  pub fn new(user_id: i64) -> AppResult<Self> {
+  /// DBから読み込んだuser_idを包む。
+  /// 保存済みの行は採番済みのため，0以下の値はDBの不整合として扱う。
+  pub fn from_db(user_id: i64) -> AppResult<Self> {
     if user_id <= 0 {
       return Err(AppError::InternalServerError(Some(format!(
-        "{}は正の整数でなければなりません。",
-        Self::TARGET
+        "Invalid user_id in DB: {user_id}"
       ))));
     }
     Ok(Self(user_id))
   }
 
+  /// INSERT前の仮のuser_idを返す。(INSERT後に採番した値で上書きする)
+  pub fn unsaved() -> Self {
+    Self(0)
+  }
+
+  /// INSERT前の仮のuser_idであるかを返す。
+  pub fn is_unsaved(self) -> bool {
+    self.0 == 0
+  }
+
   /// user_idの実態(i64)を返す。
   pub fn as_i64(self) -> i64 {
     self.0
@@ -28,15 +35,14 @@ impl UserId {
 mod user_id_test {
   use super::*;
   #[test]
-  fn user_id_ok() {
-    assert_eq!(UserId::new(1).unwrap().as_i64(), 1);
-  }
-  #[test]
-  fn user_id_0_err() {
-    assert!(UserId::new(0).is_err())
+  fn from_db_rejects_zero_and_negative() {
+    assert_eq!(UserId::from_db(1).unwrap().as_i64(), 1);
+    assert!(UserId::from_db(0).is_err());
+    assert!(UserId::from_db(-1).is_err());
   }
   #[test]
-  fn user_id_str_minus() {
-    assert!(UserId::new(-10).is_err())
+  fn unsaved_is_sentinel() {
+    assert!(UserId::unsaved().is_unsaved());
+    assert!(!UserId::from_db(1).unwrap().is_unsaved());
   }
 }
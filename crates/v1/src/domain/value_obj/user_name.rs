@@ -1,25 +1,97 @@
 use crate::{
-  domain::value_obj::normalized_string::NormalizedString,
+  config::Validation,
+  domain::{
+    rejection::{RejectReason, reject},
+    value_obj::normalized_string::NormalizedString,
+  },
   interfaces::http::error::{AppError, AppResult},
   utils::regex,
 };
+use std::sync::OnceLock;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UserName(pub NormalizedString);
 
+/// ユーザー名の検証ポリシー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserNamePolicy {
+  pub min_len: usize,
+  pub max_len: usize,
+}
+
+impl Default for UserNamePolicy {
+  fn default() -> Self {
+    Self::DEFAULT
+  }
+}
+
+/// 起動時に設定した検証ポリシー
+static USER_NAME_POLICY: OnceLock<UserNamePolicy> = OnceLock::new();
+
+impl UserNamePolicy {
+  /// 既定値(3〜64文字)
+  const DEFAULT: UserNamePolicy = UserNamePolicy {
+    min_len: UserName::MIN_LEN,
+    max_len: UserName::MAX_LEN,
+  };
+
+  /// 保存済みのユーザー名に適用する長さ(1〜DBの列長)
+  /// 設定の変更前に登録されたユーザー名も読み出せるよう，設定の長さ制限は適用しない。
+  const STORED: UserNamePolicy = UserNamePolicy {
+    min_len: 1,
+    max_len: UserName::MAX_LEN,
+  };
+
+  /// Configの[validation]から生成する。
+  pub fn from_config(config: &Validation) -> AppResult<Self> {
+    let (min_len, max_len) = (config.username_min, config.username_max);
+    if min_len == 0 || min_len > max_len || max_len > UserName::MAX_LEN {
+      return Err(AppError::InternalServerError(Some(format!(
+        "Invalid username length limits: min={min_len}, max={max_len} (allowed 1..={})",
+        UserName::MAX_LEN
+      ))));
+    }
+    Ok(Self { min_len, max_len })
+  }
+
+  /// アプリケーション全体の検証ポリシーとして設定する。
+  /// (2回目以降の呼び出しは無視される)
+  pub fn install(self) {
+    let _ = USER_NAME_POLICY.set(self);
+  }
+
+  /// 設定済みの検証ポリシーを返す。(未設定の場合は既定値)
+  pub fn current() -> &'static UserNamePolicy {
+    USER_NAME_POLICY.get().unwrap_or(&Self::DEFAULT)
+  }
+}
+
 impl UserName {
   const TARGET: &str = "ユーザー名(user_name)";
   const MIN_LEN: usize = 3;
-  const MAX_LEN: usize = 64;
+  /// DBの列長(VARCHAR(64))
+  pub(crate) const MAX_LEN: usize = 64;
+  /// 使用できる文字のルール
+  pub(crate) const RULES: &str = "・使用可能文字：英数字，アンダースコア，ドット，ハイフン，プラス\n・先頭末尾は，英数字，アンダーバーのみ。\n・ドットは連続できない。";
 
+  /// 起動時に設定した検証ポリシーでユーザー名を検証する。
   pub fn new<S: AsRef<str>>(input: S, required: bool) -> AppResult<Option<Self>> {
+    Self::with_policy(input, required, UserNamePolicy::current())
+  }
+
+  /// 指定した検証ポリシーでユーザー名を検証する。
+  pub fn with_policy<S: AsRef<str>>(
+    input: S,
+    required: bool,
+    policy: &UserNamePolicy,
+  ) -> AppResult<Option<Self>> {
     // 正規化・必須長さチェック
     let user_name_opt = NormalizedString::new(
       input,
       required,
       Self::TARGET,
-      Some(Self::MIN_LEN),
-      Some(Self::MAX_LEN),
+      Some(policy.min_len),
+      Some(policy.max_len),
     )?;
 
     // 空文字の場合はNoneを返す。
@@ -30,16 +102,35 @@ impl UserName {
 
     // 正規表現によるチェック
     if !regex::USER_NAME_REGEX.is_match(user_name.as_str()) {
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{}は以下のルールに従う必要があります。\n・使用可能文字：英数字，アンダースコア，ドット，ハイフン，プラス\n・先頭末尾は，英数字，アンダーバーのみ。\n・ドットは連続できない。",
-        Self::TARGET
-      ))));
+      return Err(reject(
+        Self::TARGET,
+        RejectReason::InvalidFormat,
+        format!(
+          "{}は以下のルールに従う必要があります。\n{}",
+          Self::TARGET,
+          Self::RULES
+        ),
+      ));
     }
 
     // 正常時はUserName型のオブジェクトを返す。
     Ok(Some(Self(user_name)))
   }
 
+  /// 保存済みのユーザー名を検索するための値を生成する。
+  /// 形式のみを検証し，設定の長さ制限は適用しない。(ログインなどの照合用)
+  pub fn for_lookup<S: AsRef<str>>(input: S) -> AppResult<Option<Self>> {
+    Self::with_policy(input, true, &UserNamePolicy::STORED)
+  }
+
+  /// DBから読み出したユーザー名を復元する。
+  /// 設定の長さ制限は適用せず，形式を満たさない場合は500を返す。
+  pub fn from_db(user_name: &str) -> AppResult<Self> {
+    Self::for_lookup(user_name).ok().flatten().ok_or_else(|| {
+      AppError::InternalServerError(format!("Invalid user_name in DB: {user_name}").into())
+    })
+  }
+
   /// UserNameの実態への参照を返す。
   pub fn as_str(&self) -> &str {
     self.0.as_str()
@@ -176,4 +267,50 @@ mod tests {
     let result = UserName::new(&over, true);
     assert!(result.is_err());
   }
+
+  #[test]
+  fn policy_from_config_limits_length() {
+    let config = |min, max| Validation {
+      username_min: min,
+      username_max: max,
+      ..crate::config::AppConfig::new().unwrap().validation
+    };
+    let policy = UserNamePolicy::from_config(&config(5, 10)).unwrap();
+    assert!(UserName::with_policy("abcd", true, &policy).is_err());
+    assert!(UserName::with_policy("abcde", true, &policy).is_ok());
+    assert!(UserName::with_policy("abcdefghij", true, &policy).is_ok());
+    assert!(UserName::with_policy("abcdefghijk", true, &policy).is_err());
+
+    for (min, max) in [(0, 10), (11, 10), (3, 65)] {
+      assert!(UserNamePolicy::from_config(&config(min, max)).is_err());
+    }
+    assert_eq!(
+      UserNamePolicy::from_config(&config(3, 64)).unwrap(),
+      UserNamePolicy::default()
+    );
+  }
+
+  #[test]
+  fn stored_names_ignore_configured_length_limits() {
+    let strict = UserNamePolicy {
+      min_len: 5,
+      max_len: 8,
+    };
+    // 設定を厳しくした後も，既存のユーザー名は読み出し・照合できる
+    assert!(UserName::with_policy("abc", true, &strict).is_err());
+    assert!(UserName::with_policy("long_user_name", true, &strict).is_err());
+    assert_eq!(UserName::from_db("abc").unwrap().as_str(), "abc");
+    assert_eq!(
+      UserName::for_lookup("long_user_name")
+        .unwrap()
+        .unwrap()
+        .as_str(),
+      "long_user_name"
+    );
+    // 形式を満たさない値は復元しない
+    assert!(matches!(
+      UserName::from_db("bad name"),
+      Err(AppError::InternalServerError(_))
+    ));
+  }
 }
@@ -4,16 +4,17 @@ use std::{
 };
 
 use crate::{
-  interfaces::http::error::{AppError, AppResult},
-  utils::{
-    hashing::{hashing, verify_hashed},
-    string::is_forbidden_char,
+  domain::{
+    password_policy::{PasswordContext, PasswordPolicy, PolicyViolation},
+    rejection::{self, RejectReason},
   },
+  interfaces::http::error::{AppError, AppResult},
+  utils::hashing::{HashScheme, hashing, verify_hashed},
 };
 use argon2::PasswordHash;
 use chrono::NaiveDate;
 use zeroize::Zeroize;
-use zxcvbn::{Score, zxcvbn};
+use zxcvbn::zxcvbn;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UserPassword {
@@ -21,13 +22,27 @@ pub struct UserPassword {
   hash: String,
 }
 
+/// zxcvbnによるパスワード強度の評価結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswordStrength {
+  /// 強度スコア（0〜4）
+  pub score: u8,
+  /// 推測に必要な試行回数の常用対数
+  pub guesses_log10: f64,
+  /// 推定解読時間（低速ハッシュのオフライン攻撃を想定）
+  pub crack_time: String,
+  /// 警告
+  pub warning: Option<String>,
+  /// 改善案
+  pub suggestions: Vec<String>,
+}
+
 impl UserPassword {
-  const TARGET: &str = "パスワード(user_password)";
-  const MIN_LEN: usize = 8;
-  const MAX_LEN: usize = 64;
-  const MIN_ZXCVBN_SCORE: Score = Score::Three;
+  /// bcryptハッシュの長さ（`$2b$`・コスト・salt・ハッシュ値）
+  const BCRYPT_HASH_LEN: usize = 60;
 
   /// 平文パスワードの入力を検証し，UserPassword型のオブジェクトを生成する。
+  /// 検証は`PasswordPolicy`に従い，最初の違反をエラーとして返す。
   pub fn new<S: AsRef<str>>(
     input: S,
     required: bool,
@@ -43,56 +58,19 @@ impl UserPassword {
       return Ok(None);
     }
 
-    // 長さチェック
-    if plain.len() < Self::MIN_LEN || plain.len() > Self::MAX_LEN {
-      plain.zeroize();
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{}は{}文字以上、{}文字以下でなければなりません。",
-        Self::TARGET,
-        Self::MIN_LEN,
-        Self::MAX_LEN
-      ))));
-    }
-
-    // 使用文字チェック
-    if plain.chars().any(is_forbidden_char) {
-      plain.zeroize();
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{}には使用できない文字が含まれています。",
-        Self::TARGET
-      ))));
-    }
-
-    // ユーザー名と誕生日がパスワードに含まれているかチェック
-    let lower_password = plain.to_lowercase();
-    let lower_user_name = user_name.as_ref().to_lowercase();
-    if lower_password.contains(&lower_user_name) {
+    let context = PasswordContext {
+      user_name: Some(user_name.as_ref()),
+      birth_date,
+    };
+    if let Err(violations) = PasswordPolicy::current().evaluate(&plain, &context) {
       plain.zeroize();
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{}にはユーザー名を含めることができません。",
-        Self::TARGET
-      ))));
-    }
-
-    if let Some(birth_date) = birth_date {
-      let ymd = birth_date.format("%Y%m%d").to_string();
-      let md = birth_date.format("%m%d").to_string();
-      if lower_password.contains(&ymd) || lower_password.contains(&md) {
-        plain.zeroize();
-        return Err(AppError::UnprocessableContent(Some(format!(
-          "{}には誕生日を含めることができません。",
-          Self::TARGET
-        ))));
+      // 返すのは最初の違反のみだが，記録は全ての違反について行う
+      for violation in &violations {
+        rejection::record(PolicyViolation::TARGET, RejectReason::Password(*violation));
       }
-    }
-
-    // パスワードの強度チェック
-    if zxcvbn(&plain, &[&lower_user_name]).score() < Self::MIN_ZXCVBN_SCORE {
-      plain.zeroize();
-      return Err(AppError::UnprocessableContent(Some(format!(
-        "{}は強度が不十分です。より強力なパスワードを使用してください。",
-        Self::TARGET
-      ))));
+      return Err(AppError::UnprocessableContent(Some(
+        violations[0].to_string(),
+      )));
     }
 
     // パスワードをハッシュ化する
@@ -104,15 +82,71 @@ impl UserPassword {
     Ok(Some(Self { hash }))
   }
 
+  /// 平文パスワードの強度を評価する。
+  /// 合否の判定やハッシュ化は行わず，zxcvbnの評価結果をそのまま返す。
+  /// 長大な入力でzxcvbnに時間を掛けないよう，ポリシーの最大長を超える入力は評価せずに拒否する。
+  pub fn strength(input: &str, user_name: Option<&str>) -> AppResult<PasswordStrength> {
+    let policy = PasswordPolicy::current();
+    if input.trim().len() > policy.max_len {
+      let violation = PolicyViolation::Length {
+        min: policy.min_len,
+        max: policy.max_len,
+      };
+      return Err(rejection::reject(
+        PolicyViolation::TARGET,
+        RejectReason::Password(violation),
+        violation.to_string(),
+      ));
+    }
+
+    let mut plain = input.trim().to_owned();
+    let lower_user_name = user_name.map(str::to_lowercase);
+    let user_inputs: Vec<&str> = lower_user_name.as_deref().into_iter().collect();
+
+    let entropy = zxcvbn(&plain, &user_inputs);
+    plain.zeroize();
+
+    let feedback = entropy.feedback();
+    Ok(PasswordStrength {
+      score: u8::from(entropy.score()),
+      guesses_log10: entropy.guesses_log10(),
+      crack_time: entropy
+        .crack_times()
+        .offline_slow_hashing_1e4_per_second()
+        .to_string(),
+      warning: feedback.and_then(|f| f.warning()).map(|w| w.to_string()),
+      suggestions: feedback
+        .map(|f| f.suggestions().iter().map(ToString::to_string).collect())
+        .unwrap_or_default(),
+    })
+  }
+
   //// ハッシュ化されたパスワードをVOに包む
+  /// 移行元システムのbcryptハッシュも受け付ける。(ログイン成功時にArgon2へ再ハッシュ化する)
   pub fn from_hash<S: AsRef<str>>(hash: S) -> AppResult<Self> {
     let s = hash.as_ref();
     // 形式チェックのみ行う
-    let _ = PasswordHash::new(s).map_err(|e| {
-      AppError::UnprocessableContent(Some(format!("ハッシュ文字列が不正です: {e}")))
-    })?;
+    match HashScheme::detect(s) {
+      HashScheme::Argon2 => {
+        let _ = PasswordHash::new(s).map_err(|e| {
+          AppError::UnprocessableContent(Some(format!("ハッシュ文字列が不正です: {e}")))
+        })?;
+      }
+      HashScheme::Bcrypt => {
+        if s.len() != Self::BCRYPT_HASH_LEN {
+          return Err(AppError::UnprocessableContent(Some(
+            "ハッシュ文字列が不正です: bcrypt".into(),
+          )));
+        }
+      }
+    }
     Ok(Self { hash: s.to_owned() })
   }
+
+  /// ハッシュの方式を返す
+  pub fn scheme(&self) -> HashScheme {
+    HashScheme::detect(&self.hash)
+  }
   /// Argon2 ハッシュ文字列を返す
   #[inline]
   pub fn as_hash(&self) -> &str {
@@ -147,6 +181,7 @@ impl Display for UserPassword {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::{config, utils::metrics};
   fn bd() -> NaiveDate {
     NaiveDate::from_ymd_opt(1990, 5, 15).unwrap()
   }
@@ -169,6 +204,40 @@ mod tests {
     );
   }
 
+  #[test]
+  fn weak_password_rejection_is_counted() {
+    metrics::install(&config::Metrics { enabled: true });
+    // 他のテストと並行して加算されるため，増分の下限のみを確認する
+    let before = metrics::vo_rejections("user_password", "weak");
+    let err = UserPassword::new("password", true, "user", None).unwrap_err();
+    assert!(matches!(err, AppError::UnprocessableContent(Some(m)) if m.contains("強度")));
+    assert!(metrics::vo_rejections("user_password", "weak") > before);
+  }
+
+  #[test]
+  fn strength_of_weak_password() {
+    let strength = UserPassword::strength("password", None).unwrap();
+    assert!(strength.score <= 1);
+    assert!(!strength.suggestions.is_empty());
+    assert!(!strength.crack_time.is_empty());
+  }
+
+  #[test]
+  fn strength_penalizes_user_name() {
+    let plain = "alice_wonder_2024";
+    let without = UserPassword::strength(plain, None).unwrap();
+    let with = UserPassword::strength(plain, Some("Alice_Wonder_2024")).unwrap();
+    assert!(with.score <= without.score);
+  }
+
+  #[test]
+  fn strength_rejects_input_longer_than_policy() {
+    let policy = PasswordPolicy::current();
+    assert!(UserPassword::strength(&"a".repeat(policy.max_len), None).is_ok());
+    let err = UserPassword::strength(&"a".repeat(policy.max_len + 1), None).unwrap_err();
+    assert!(matches!(err, AppError::UnprocessableContent(Some(m)) if m.contains("文字以下")));
+  }
+
   #[test]
   fn verify_success() {
     let pw = UserPassword::new(
@@ -0,0 +1,167 @@
+//! インメモリ実装 ― レート制限ストア
+//! 単一インスタンスでのみ有効。複数インスタンス構成ではPostgres実装を使用する。
+//! ウィンドウを過ぎたカウンタは，加算時に一定間隔でまとめて削除する。
+
+use crate::{domain::repository::RateLimitStore, interfaces::http::error::AppResult};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+/// 期限切れのカウンタを削除する間隔
+const SWEEP_INTERVAL: chrono::Duration = chrono::Duration::seconds(60);
+
+/// キーごとのカウンタ
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+  hits: u32,
+  /// ウィンドウの終了時刻（これ以降は削除してよい）
+  expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+  entries: HashMap<String, Entry>,
+  /// 最後に期限切れのカウンタを削除した時刻
+  last_sweep: Option<DateTime<Utc>>,
+}
+
+/// 固定ウィンドウ方式のカウンタ
+/// ウィンドウはキーごとに最初の試行時刻から開始する。
+#[derive(Debug, Default)]
+pub struct MemoryRateLimitStore {
+  inner: Mutex<Inner>,
+}
+
+impl MemoryRateLimitStore {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// 有効期限切れのカウンタを削除し，削除件数を返す
+  pub fn purge_expired(&self, now: DateTime<Utc>) -> u64 {
+    let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    Self::sweep(&mut inner, now)
+  }
+
+  /// 保持しているカウンタの件数
+  pub fn len(&self) -> usize {
+    let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    inner.entries.len()
+  }
+
+  /// 保持しているカウンタが無いかどうか
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  fn sweep(inner: &mut Inner, now: DateTime<Utc>) -> u64 {
+    let before = inner.entries.len();
+    inner.entries.retain(|_, entry| entry.expires_at > now);
+    inner.last_sweep = Some(now);
+    (before - inner.entries.len()) as u64
+  }
+
+  /// `start`から`window`の間を有効期限とする
+  fn expires_at(start: DateTime<Utc>, window: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(window)
+      .ok()
+      .and_then(|w| start.checked_add_signed(w))
+      .unwrap_or(DateTime::<Utc>::MAX_UTC)
+  }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+  async fn hits(&self, key: &str, _window: Duration, now: DateTime<Utc>) -> AppResult<u32> {
+    let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(match inner.entries.get(key) {
+      Some(entry) if now < entry.expires_at => entry.hits,
+      _ => 0,
+    })
+  }
+
+  async fn hit(&self, key: &str, window: Duration, now: DateTime<Utc>) -> AppResult<u32> {
+    let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    // 一度しか試行されないキーが残り続けないよう，一定間隔で期限切れをまとめて削除する
+    if inner
+      .last_sweep
+      .is_none_or(|last| now - last >= SWEEP_INTERVAL)
+    {
+      Self::sweep(&mut inner, now);
+    }
+    let fresh = Entry {
+      hits: 0,
+      expires_at: Self::expires_at(now, window),
+    };
+    let entry = inner.entries.entry(key.to_owned()).or_insert(fresh);
+    if now >= entry.expires_at {
+      *entry = fresh;
+    }
+    entry.hits += 1;
+    Ok(entry.hits)
+  }
+
+  async fn reset(&self, key: &str) -> AppResult<()> {
+    let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    inner.entries.remove(key);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn window_counter_expires_after_window() {
+    let store = MemoryRateLimitStore::new();
+    let window = Duration::from_secs(10);
+    let start = Utc::now();
+    assert_eq!(store.hit("k", window, start).await.unwrap(), 1);
+    assert_eq!(store.hits("k", window, start).await.unwrap(), 1);
+    let later = start + chrono::Duration::seconds(10);
+    assert_eq!(store.hits("k", window, later).await.unwrap(), 0);
+    // ウィンドウ外の加算は新しいウィンドウを開始する
+    assert_eq!(store.hit("k", window, later).await.unwrap(), 1);
+  }
+
+  #[tokio::test]
+  async fn reset_discards_counter() {
+    let store = MemoryRateLimitStore::new();
+    let window = Duration::from_secs(10);
+    let now = Utc::now();
+    store.hit("k", window, now).await.unwrap();
+    store.hit("k", window, now).await.unwrap();
+    store.reset("k").await.unwrap();
+    assert_eq!(store.hits("k", window, now).await.unwrap(), 0);
+  }
+
+  #[tokio::test]
+  async fn expired_counters_are_evicted() {
+    let store = MemoryRateLimitStore::new();
+    let window = Duration::from_secs(10);
+    let start = Utc::now();
+    for n in 0..5 {
+      store
+        .hit(&format!("once:{n}"), window, start)
+        .await
+        .unwrap();
+    }
+    assert_eq!(store.len(), 5);
+
+    // ウィンドウを過ぎても，削除の間隔に達するまではまとめて削除しない
+    let later = start + chrono::Duration::seconds(10);
+    store.hit("other", window, later).await.unwrap();
+    assert_eq!(store.len(), 6);
+
+    let swept = start + SWEEP_INTERVAL;
+    store.hit("other", window, swept).await.unwrap();
+    assert_eq!(store.len(), 1);
+
+    assert_eq!(
+      store.purge_expired(swept + chrono::Duration::seconds(10)),
+      1
+    );
+    assert!(store.is_empty());
+  }
+}
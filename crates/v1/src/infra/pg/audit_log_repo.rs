@@ -0,0 +1,122 @@
+//! PostgreSQL | audit_logs テーブル Repository
+
+use crate::{
+  domain::{
+    entity::audit_log::{AuditAction, AuditLog},
+    repository::AuditLogRepository,
+    value_obj::user_id::UserId,
+  },
+  infra::pg::user_repo::PgTx,
+  interfaces::http::error::{AppError, AppResult},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+
+#[derive(Clone)]
+pub struct PgAuditLogRepository {
+  pool: PgPool,
+}
+impl PgAuditLogRepository {
+  pub fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  /// トランザクション内で監査ログを登録する
+  /// トランザクションは呼び出し元で管理される
+  pub async fn insert_tx<'a>(&self, tx: &mut PgTx<'a>, l: &AuditLog) -> AppResult<()> {
+    Self::do_insert(&mut **tx, l).await
+  }
+
+  /// 監査ログを登録するSQLを実行
+  async fn do_insert<'e>(executor: impl PgExecutor<'e>, l: &AuditLog) -> AppResult<()> {
+    sqlx::query!(
+      r#"
+        INSERT INTO audit_logs
+          (actor_user_id, target_user_id, action, detail, created_at)
+        VALUES ($1,$2,$3,$4,$5)
+        "#,
+      l.actor_user_id.map(|id| id.as_i64()),
+      l.target_user_id.map(|id| id.as_i64()),
+      l.action.as_str(),
+      l.detail,
+      l.created_at,
+    )
+    .execute(executor)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+  }
+
+  /// 対象ユーザーの監査ログを新しい順に取得するSQLを実行
+  async fn do_find_by_target(&self, id: UserId) -> AppResult<Vec<AuditLog>> {
+    let rows = sqlx::query_as!(
+      AuditLogRow,
+      r#"SELECT actor_user_id, target_user_id, action, detail, created_at
+        FROM audit_logs
+        WHERE target_user_id = $1
+        ORDER BY audit_id DESC"#,
+      id.as_i64()
+    )
+    .fetch_all(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+
+    rows
+      .into_iter()
+      .map(TryInto::<AuditLog>::try_into)
+      .collect()
+  }
+
+  /// `cutoff`より前の監査ログの詳細(detail)を消去し，消去した件数を返す。
+  /// 行は削除しないため，操作種別・対象ユーザーによる集計は維持される。
+  pub async fn scrub_detail_before(&self, cutoff: DateTime<Utc>) -> AppResult<u64> {
+    let result = sqlx::query!(
+      r#"UPDATE audit_logs SET detail = NULL
+        WHERE created_at < $1 AND detail IS NOT NULL"#,
+      cutoff
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(result.rows_affected())
+  }
+}
+
+/* AuditLogRepositoryの実装 */
+#[async_trait]
+impl AuditLogRepository for PgAuditLogRepository {
+  async fn insert(&self, l: &AuditLog) -> AppResult<()> {
+    Self::do_insert(&self.pool, l).await
+  }
+
+  async fn find_by_target(&self, id: UserId) -> AppResult<Vec<AuditLog>> {
+    self.do_find_by_target(id).await
+  }
+}
+
+/* Row 構造体 & 変換 */
+#[derive(sqlx::FromRow)]
+struct AuditLogRow {
+  actor_user_id: Option<i64>,
+  target_user_id: Option<i64>,
+  action: String,
+  detail: Option<String>,
+  created_at: DateTime<Utc>,
+}
+
+impl TryFrom<AuditLogRow> for AuditLog {
+  type Error = AppError;
+  fn try_from(r: AuditLogRow) -> Result<Self, Self::Error> {
+    let action = AuditAction::parse(&r.action).ok_or_else(|| {
+      AppError::InternalServerError(format!("Invalid action in DB: {}", r.action).into())
+    })?;
+    Ok(Self {
+      actor_user_id: r.actor_user_id.map(UserId::from_db).transpose()?,
+      target_user_id: r.target_user_id.map(UserId::from_db).transpose()?,
+      action,
+      detail: r.detail,
+      created_at: r.created_at,
+    })
+  }
+}
@@ -0,0 +1,133 @@
+//! リクエストの処理期限
+//! --------------------------------------------------------------
+//! ・リクエストのタイムアウトまでの残り時間を，トランザクションの`statement_timeout`に反映する
+//! ・残り時間が僅かな場合は，クエリを開始せずにタイムアウトとして返す
+//! ・対象はサービスが`begin()`で開始するトランザクションと，リクエスト単位のトランザクションのみ
+//!   (ログイン・プロフィール更新などのトランザクション外の単発のクエリには設定しない。
+//!    これらはタイムアウトのミドルウェアがリクエストごと打ち切る)
+//! --------------------------------------------------------------
+
+use crate::{
+  infra::pg::user_repo::PgTx,
+  interfaces::http::error::{AppError, AppResult},
+};
+use std::time::{Duration, Instant};
+
+/// リクエストの処理期限（Noneの場合は期限なし）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+  /// 期限なし
+  pub const NONE: Deadline = Deadline(None);
+
+  /// 残り時間がこれより短い場合は，クエリを開始しない
+  const MIN_STATEMENT_TIMEOUT: Duration = Duration::from_millis(50);
+
+  /// 現在から`timeout`後を期限とする。
+  pub fn after(timeout: Duration) -> Self {
+    Self(Instant::now().checked_add(timeout))
+  }
+
+  /// 期限までの残り時間を返す。(期限なしの場合はNone，期限切れの場合は0)
+  pub fn remaining(&self) -> Option<Duration> {
+    self
+      .0
+      .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+  }
+
+  /// クエリに設定する`statement_timeout`を返す。(期限なしの場合はNone)
+  /// 残り時間が`MIN_STATEMENT_TIMEOUT`未満の場合は408を返す。
+  pub fn statement_timeout(&self) -> AppResult<Option<Duration>> {
+    match self.remaining() {
+      None => Ok(None),
+      Some(remaining) if remaining < Self::MIN_STATEMENT_TIMEOUT => Err(timed_out()),
+      Some(remaining) => Ok(Some(remaining)),
+    }
+  }
+
+  /// トランザクション内のクエリに残り時間を`statement_timeout`として設定する。
+  /// (`SET LOCAL`と同じく，トランザクションの終了時に元に戻る)
+  pub async fn apply_tx<'a>(&self, tx: &mut PgTx<'a>) -> AppResult<()> {
+    let Some(timeout) = self.statement_timeout()? else {
+      return Ok(());
+    };
+    sqlx::query_scalar!(
+      r#"SELECT set_config('statement_timeout', $1, true)"#,
+      format!("{}ms", timeout.as_millis())
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+  }
+}
+
+/// 処理期限を超えた場合のエラーを返す
+pub fn timed_out() -> AppError {
+  AppError::RequestTimeout(Some("処理が時間内に完了しませんでした。".into()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use sqlx::PgPool;
+
+  #[test]
+  fn statement_timeout_follows_remaining_time() {
+    assert_eq!(Deadline::NONE.statement_timeout().unwrap(), None);
+
+    let timeout = Deadline::after(Duration::from_secs(10))
+      .statement_timeout()
+      .unwrap()
+      .unwrap();
+    assert!(timeout <= Duration::from_secs(10) && timeout > Duration::from_secs(9));
+
+    // 期限間近・期限切れの場合はクエリを開始しない
+    for remaining in [Duration::ZERO, Duration::from_millis(10)] {
+      assert!(matches!(
+        Deadline::after(remaining).statement_timeout(),
+        Err(AppError::RequestTimeout(_))
+      ));
+    }
+  }
+
+  async fn statement_timeout(tx: &mut PgTx<'_>) -> String {
+    sqlx::query_scalar!(r#"SELECT current_setting('statement_timeout') AS "v!""#)
+      .fetch_one(&mut **tx)
+      .await
+      .unwrap()
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn near_deadline_sets_short_statement_timeout(pool: PgPool) {
+    let mut tx = pool.begin().await.unwrap();
+    Deadline::after(Duration::from_millis(300))
+      .apply_tx(&mut tx)
+      .await
+      .unwrap();
+    let value = statement_timeout(&mut tx).await;
+    let ms: u64 = value.trim_end_matches("ms").parse().unwrap();
+    assert!((50..=300).contains(&ms), "{value}");
+    tx.rollback().await.unwrap();
+
+    // トランザクションの終了後は元に戻る
+    let mut tx = pool.begin().await.unwrap();
+    assert_eq!(statement_timeout(&mut tx).await, "0");
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn statement_exceeding_deadline_is_timeout(pool: PgPool) {
+    let mut tx = pool.begin().await.unwrap();
+    Deadline::after(Duration::from_millis(100))
+      .apply_tx(&mut tx)
+      .await
+      .unwrap();
+    let err = sqlx::query_scalar!(r#"SELECT 1 AS "one!" FROM pg_sleep(1)"#)
+      .fetch_one(&mut *tx)
+      .await
+      .map_err(AppError::from)
+      .unwrap_err();
+    assert!(matches!(err, AppError::RequestTimeout(_)));
+  }
+}
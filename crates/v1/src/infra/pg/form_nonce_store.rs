@@ -0,0 +1,168 @@
+//! PostgreSQL | form_nonces テーブル 登録フォームのワンタイムトークン
+//! 複数インスタンス間で使用済みの状態を共有する。
+//! 使用済みの行は有効期限まで残し，再送信を判別できるようにする。
+
+use crate::{
+  domain::clock_skew::ClockSkew,
+  infra::pg::user_repo::PgTx,
+  interfaces::http::error::{AppError, AppResult},
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// トークンの使用結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceOutcome {
+  /// 未使用のトークンを使用済みにした
+  Consumed,
+  /// 既に使用済み（二重送信）
+  Reused,
+  /// 有効期限切れ
+  Expired,
+  /// 発行していないトークン
+  Unknown,
+}
+
+#[derive(Clone)]
+pub struct PgFormNonceStore {
+  pool: PgPool,
+}
+
+impl PgFormNonceStore {
+  pub fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  /// トークンを登録する
+  pub async fn issue(&self, nonce: &str, expires_at: DateTime<Utc>) -> AppResult<()> {
+    sqlx::query!(
+      r#"INSERT INTO form_nonces (nonce, expires_at) VALUES ($1, $2)"#,
+      nonce,
+      expires_at
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+  }
+
+  /// トランザクション内でトークンを使用済みにする。
+  /// (有効期限は`ClockSkew`の猶予を含めて判定する)
+  /// トランザクションがロールバックされた場合は未使用に戻る。
+  /// (同時に使用された場合は，後の呼び出しが先のコミットを待って`Reused`になる)
+  pub async fn consume_tx<'a>(
+    &self,
+    tx: &mut PgTx<'a>,
+    nonce: &str,
+    now: DateTime<Utc>,
+  ) -> AppResult<NonceOutcome> {
+    let consumed = sqlx::query_scalar!(
+      r#"UPDATE form_nonces SET consumed_at = $2
+        WHERE nonce = $1 AND consumed_at IS NULL AND expires_at > $3
+        RETURNING nonce"#,
+      nonce,
+      now,
+      ClockSkew::current().expiry_cutoff(now)
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+    if consumed.is_some() {
+      return Ok(NonceOutcome::Consumed);
+    }
+
+    let row = sqlx::query!(
+      r#"SELECT consumed_at FROM form_nonces WHERE nonce = $1"#,
+      nonce
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+    Ok(match row {
+      None => NonceOutcome::Unknown,
+      Some(r) if r.consumed_at.is_some() => NonceOutcome::Reused,
+      Some(_) => NonceOutcome::Expired,
+    })
+  }
+
+  /// 有効期限切れ（猶予を含む）のトークンを削除し，削除件数を返す
+  pub async fn purge_expired(&self, now: DateTime<Utc>) -> AppResult<u64> {
+    let result = sqlx::query!(
+      r#"DELETE FROM form_nonces WHERE expires_at <= $1"#,
+      ClockSkew::current().expiry_cutoff(now)
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(result.rows_affected())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::Duration;
+
+  async fn consume(store: &PgFormNonceStore, nonce: &str, now: DateTime<Utc>) -> NonceOutcome {
+    let mut tx = store.pool.begin().await.unwrap();
+    let outcome = store.consume_tx(&mut tx, nonce, now).await.unwrap();
+    tx.commit().await.unwrap();
+    outcome
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn nonce_is_consumed_once(pool: PgPool) {
+    let store = PgFormNonceStore::new(pool);
+    let now = Utc::now();
+    store.issue("n1", now + Duration::minutes(5)).await.unwrap();
+
+    assert_eq!(consume(&store, "n1", now).await, NonceOutcome::Consumed);
+    assert_eq!(consume(&store, "n1", now).await, NonceOutcome::Reused);
+    assert_eq!(consume(&store, "n2", now).await, NonceOutcome::Unknown);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn rollback_keeps_nonce_unused(pool: PgPool) {
+    let store = PgFormNonceStore::new(pool);
+    let now = Utc::now();
+    store.issue("n1", now + Duration::minutes(5)).await.unwrap();
+
+    let mut tx = store.pool.begin().await.unwrap();
+    let outcome = store.consume_tx(&mut tx, "n1", now).await.unwrap();
+    assert_eq!(outcome, NonceOutcome::Consumed);
+    tx.rollback().await.unwrap();
+    assert_eq!(consume(&store, "n1", now).await, NonceOutcome::Consumed);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn expired_nonce_is_rejected_and_purged(pool: PgPool) {
+    let store = PgFormNonceStore::new(pool);
+    let now = Utc::now();
+    let leeway = ClockSkew::current().leeway;
+    store
+      .issue("old", now - leeway - Duration::seconds(5))
+      .await
+      .unwrap();
+    store
+      .issue("new", now + Duration::minutes(5))
+      .await
+      .unwrap();
+
+    assert_eq!(consume(&store, "old", now).await, NonceOutcome::Expired);
+    assert_eq!(store.purge_expired(now).await.unwrap(), 1);
+    assert_eq!(consume(&store, "new", now).await, NonceOutcome::Consumed);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn nonce_within_clock_skew_leeway_is_consumed(pool: PgPool) {
+    let store = PgFormNonceStore::new(pool);
+    let now = Utc::now();
+    store
+      .issue("late", now - ClockSkew::current().leeway / 2)
+      .await
+      .unwrap();
+
+    assert_eq!(store.purge_expired(now).await.unwrap(), 0);
+    assert_eq!(consume(&store, "late", now).await, NonceOutcome::Consumed);
+  }
+}
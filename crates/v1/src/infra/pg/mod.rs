@@ -1,3 +1,10 @@
+pub mod audit_log_repo;
+pub mod deadline;
+pub mod form_nonce_store;
+pub mod rate_limit_store;
+pub mod registration_cooldown_store;
+pub mod schema;
 pub mod session_repo;
 pub mod user_auth_repo;
 pub mod user_repo;
+pub mod verification_token_store;
@@ -0,0 +1,155 @@
+//! PostgreSQL | rate_limit_buckets テーブル レート制限ストア
+//! 複数インスタンス間でカウンタを共有する。
+//! ウィンドウはエポック秒を`window`で区切ったバケットに揃える。
+
+use crate::{
+  domain::repository::RateLimitStore,
+  interfaces::http::error::{AppError, AppResult},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::time::Duration;
+
+#[derive(Clone)]
+pub struct PgRateLimitStore {
+  pool: PgPool,
+}
+impl PgRateLimitStore {
+  pub fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  /// 有効期限切れのバケットを削除し，削除件数を返す
+  pub async fn purge_expired(&self, now: DateTime<Utc>) -> AppResult<u64> {
+    let result = sqlx::query!(
+      r#"DELETE FROM rate_limit_buckets WHERE expires_at <= $1"#,
+      now
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(result.rows_affected())
+  }
+
+  /// `now`が属するバケットの開始時刻(エポック秒)と有効期限を返す
+  fn bucket(window: Duration, now: DateTime<Utc>) -> (i64, DateTime<Utc>) {
+    let window_secs = i64::try_from(window.as_secs()).unwrap_or(i64::MAX).max(1);
+    let start = now.timestamp().div_euclid(window_secs) * window_secs;
+    let expires_at = DateTime::from_timestamp(start.saturating_add(window_secs), 0)
+      .unwrap_or(DateTime::<Utc>::MAX_UTC);
+    (start, expires_at)
+  }
+}
+
+#[async_trait]
+impl RateLimitStore for PgRateLimitStore {
+  async fn hits(&self, key: &str, window: Duration, now: DateTime<Utc>) -> AppResult<u32> {
+    let (start, _) = Self::bucket(window, now);
+    let hits = sqlx::query_scalar!(
+      r#"SELECT hits FROM rate_limit_buckets
+        WHERE bucket_key = $1 AND bucket_start = $2"#,
+      key,
+      start
+    )
+    .fetch_optional(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(hits.map_or(0, |h| u32::try_from(h).unwrap_or(0)))
+  }
+
+  async fn hit(&self, key: &str, window: Duration, now: DateTime<Utc>) -> AppResult<u32> {
+    let (start, expires_at) = Self::bucket(window, now);
+    // 同時に加算されても失われないよう，1文で原子的に加算する
+    let hits = sqlx::query_scalar!(
+      r#"INSERT INTO rate_limit_buckets (bucket_key, bucket_start, hits, expires_at)
+        VALUES ($1, $2, 1, $3)
+        ON CONFLICT (bucket_key, bucket_start)
+        DO UPDATE SET hits = rate_limit_buckets.hits + 1
+        RETURNING hits"#,
+      key,
+      start,
+      expires_at
+    )
+    .fetch_one(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(u32::try_from(hits).unwrap_or(0))
+  }
+
+  async fn reset(&self, key: &str) -> AppResult<()> {
+    sqlx::query!(
+      r#"DELETE FROM rate_limit_buckets WHERE bucket_key = $1"#,
+      key
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn at(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(secs, 0).unwrap()
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn hit_increments_within_bucket(pool: PgPool) {
+    let store = PgRateLimitStore::new(pool);
+    let window = Duration::from_secs(60);
+
+    assert_eq!(store.hit("login_ip:a", window, at(1200)).await.unwrap(), 1);
+    assert_eq!(store.hit("login_ip:a", window, at(1259)).await.unwrap(), 2);
+    assert_eq!(store.hits("login_ip:a", window, at(1230)).await.unwrap(), 2);
+    // 他のキーには影響しない
+    assert_eq!(store.hits("login_ip:b", window, at(1230)).await.unwrap(), 0);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn next_bucket_starts_from_zero(pool: PgPool) {
+    let store = PgRateLimitStore::new(pool);
+    let window = Duration::from_secs(60);
+
+    store.hit("k", window, at(1200)).await.unwrap();
+    store.hit("k", window, at(1210)).await.unwrap();
+    assert_eq!(store.hits("k", window, at(1260)).await.unwrap(), 0);
+    assert_eq!(store.hit("k", window, at(1260)).await.unwrap(), 1);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn reset_and_purge_expired(pool: PgPool) {
+    let store = PgRateLimitStore::new(pool);
+    let window = Duration::from_secs(60);
+
+    store.hit("old", window, at(1200)).await.unwrap();
+    store.hit("current", window, at(1300)).await.unwrap();
+    // 1200のバケットは1260で期限切れ
+    assert_eq!(store.purge_expired(at(1300)).await.unwrap(), 1);
+    assert_eq!(store.hits("current", window, at(1300)).await.unwrap(), 1);
+
+    store.reset("current").await.unwrap();
+    assert_eq!(store.hits("current", window, at(1300)).await.unwrap(), 0);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn concurrent_hits_are_not_lost(pool: PgPool) {
+    let store = PgRateLimitStore::new(pool);
+    let window = Duration::from_secs(60);
+    let now = at(1200);
+
+    let tasks: Vec<_> = (0..10)
+      .map(|_| {
+        let store = store.clone();
+        tokio::spawn(async move { store.hit("shared", window, now).await.unwrap() })
+      })
+      .collect();
+    for t in tasks {
+      t.await.unwrap();
+    }
+    assert_eq!(store.hits("shared", window, now).await.unwrap(), 10);
+  }
+}
@@ -0,0 +1,188 @@
+//! PostgreSQL | registration_cooldowns テーブル 接続元IPごとの登録のクールダウン
+//! 複数インスタンス間でクールダウンを共有する。
+//! 期限切れの行はメンテナンスで削除する。
+
+use crate::interfaces::http::error::{AppError, AppResult};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::net::IpAddr;
+
+/// クールダウンの取得結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CooldownClaim {
+  /// クールダウンを開始した（`until`まで同じIPからの登録を受け付けない）
+  Claimed { until: DateTime<Utc> },
+  /// クールダウン中
+  Cooling { until: DateTime<Utc> },
+}
+
+#[derive(Clone)]
+pub struct PgRegistrationCooldownStore {
+  pool: PgPool,
+}
+
+impl PgRegistrationCooldownStore {
+  /// 取り消しと競合した場合に開始を試みる回数
+  const CLAIM_ATTEMPTS: usize = 3;
+
+  pub fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  /// クールダウン中でなければ，`until`までのクールダウンを開始する。
+  /// (同時に呼び出された場合も，開始できるのは1件のみ)
+  pub async fn claim(
+    &self,
+    ip: IpAddr,
+    until: DateTime<Utc>,
+    now: DateTime<Utc>,
+  ) -> AppResult<CooldownClaim> {
+    let ip = ip.to_string();
+    for _ in 0..Self::CLAIM_ATTEMPTS {
+      let claimed = sqlx::query_scalar!(
+        r#"INSERT INTO registration_cooldowns (ip, expires_at) VALUES ($1, $2)
+          ON CONFLICT (ip) DO UPDATE SET expires_at = EXCLUDED.expires_at
+            WHERE registration_cooldowns.expires_at <= $3
+          RETURNING expires_at"#,
+        ip,
+        until,
+        now
+      )
+      .fetch_optional(&self.pool)
+      .await
+      .map_err(AppError::from)?;
+      // 取り消しで照合できるよう，保存した値（マイクロ秒に丸めた値）を返す
+      if let Some(until) = claimed {
+        return Ok(CooldownClaim::Claimed { until });
+      }
+
+      let current = sqlx::query_scalar!(
+        r#"SELECT expires_at FROM registration_cooldowns WHERE ip = $1"#,
+        ip
+      )
+      .fetch_optional(&self.pool)
+      .await
+      .map_err(AppError::from)?;
+      if let Some(until) = current {
+        return Ok(CooldownClaim::Cooling { until });
+      }
+      // INSERTとSELECTの間に取り消された場合は，開始し直す
+    }
+    // 取り消しと開始が繰り返し競合する場合は，同じIPから登録中としてクールダウン中と扱う
+    Ok(CooldownClaim::Cooling { until })
+  }
+
+  /// `claim`で開始したクールダウンを取り消す。(登録に失敗した場合)
+  /// 他の呼び出しで開始し直したクールダウンは取り消さない。
+  pub async fn release(&self, ip: IpAddr, until: DateTime<Utc>) -> AppResult<()> {
+    sqlx::query!(
+      r#"DELETE FROM registration_cooldowns WHERE ip = $1 AND expires_at = $2"#,
+      ip.to_string(),
+      until
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+  }
+
+  /// 期限切れのクールダウンを削除し，削除件数を返す
+  pub async fn purge_expired(&self, now: DateTime<Utc>) -> AppResult<u64> {
+    let result = sqlx::query!(
+      r#"DELETE FROM registration_cooldowns WHERE expires_at <= $1"#,
+      now
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(result.rows_affected())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::{Duration, SubsecRound};
+  use std::net::Ipv4Addr;
+
+  const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn claim_blocks_until_expiry(pool: PgPool) {
+    let store = PgRegistrationCooldownStore::new(pool);
+    // DBの精度（マイクロ秒）に合わせる
+    let now = Utc::now().trunc_subsecs(6);
+    let until = now + Duration::minutes(1);
+
+    assert_eq!(
+      store.claim(IP, until, now).await.unwrap(),
+      CooldownClaim::Claimed { until }
+    );
+    // クールダウン中は期限を延長しない
+    let later = now + Duration::seconds(30);
+    assert_eq!(
+      store
+        .claim(IP, later + Duration::minutes(1), later)
+        .await
+        .unwrap(),
+      CooldownClaim::Cooling { until }
+    );
+    // 別のIPは制限しない
+    let other = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    assert!(matches!(
+      store.claim(other, until, now).await.unwrap(),
+      CooldownClaim::Claimed { .. }
+    ));
+    // 期限後は再び開始できる
+    let next = until + Duration::minutes(1);
+    assert_eq!(
+      store.claim(IP, next, until).await.unwrap(),
+      CooldownClaim::Claimed { until: next }
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn release_and_purge(pool: PgPool) {
+    let store = PgRegistrationCooldownStore::new(pool);
+    let now = Utc::now();
+    let CooldownClaim::Claimed { until } = store
+      .claim(IP, now + Duration::minutes(1), now)
+      .await
+      .unwrap()
+    else {
+      panic!("not claimed");
+    };
+    store.release(IP, until).await.unwrap();
+    assert!(matches!(
+      store.claim(IP, until, now).await.unwrap(),
+      CooldownClaim::Claimed { .. }
+    ));
+
+    assert_eq!(store.purge_expired(now).await.unwrap(), 0);
+    assert_eq!(store.purge_expired(until).await.unwrap(), 1);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn claim_survives_concurrent_release(pool: PgPool) {
+    let store = PgRegistrationCooldownStore::new(pool);
+    // 開始と取り消しを並行して繰り返しても，行が見つからないエラーにならない
+    let tasks: Vec<_> = (0..8)
+      .map(|_| {
+        let store = store.clone();
+        tokio::spawn(async move {
+          for _ in 0..20 {
+            let now = Utc::now();
+            let claim = store.claim(IP, now + Duration::minutes(1), now).await?;
+            if let CooldownClaim::Claimed { until } = claim {
+              store.release(IP, until).await?;
+            }
+          }
+          AppResult::Ok(())
+        })
+      })
+      .collect();
+    for t in tasks {
+      t.await.unwrap().unwrap();
+    }
+  }
+}
@@ -0,0 +1,83 @@
+//! スキーマ(マイグレーション)のバージョン確認
+//! --------------------------------------------------------------
+//! ・バイナリに埋め込んだマイグレーションを期待値とする
+//! ・DBの`_sqlx_migrations`に適用済みとして記録されたバージョンと比較する
+//! --------------------------------------------------------------
+
+use crate::interfaces::http::error::{AppError, AppResult};
+use serde::Serialize;
+use sqlx::{PgPool, migrate::Migrator};
+
+/// バイナリが前提とするマイグレーション
+static MIGRATOR: Migrator = sqlx::migrate!("../../migrations");
+
+/// 未適用(テーブル自体が存在しない)場合のSQLSTATE
+const UNDEFINED_TABLE: &str = "42P01";
+
+/// スキーマのバージョンの比較結果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SchemaStatus {
+  /// 適用済みの最新バージョン（未適用の場合はNone）
+  pub applied_version: Option<i64>,
+  /// バイナリが前提とする最新バージョン
+  pub expected_version: Option<i64>,
+  /// 未適用のバージョン
+  pub missing: Vec<i64>,
+}
+
+impl SchemaStatus {
+  /// DBに適用済みのマイグレーションを確認する。
+  pub async fn check(pool: &PgPool) -> AppResult<Self> {
+    let applied = sqlx::query_scalar::<_, i64>(
+      "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await;
+    let applied = match applied {
+      Ok(versions) => versions,
+      Err(sqlx::Error::Database(db)) if db.code().as_deref() == Some(UNDEFINED_TABLE) => Vec::new(),
+      Err(e) => return Err(AppError::from(e)),
+    };
+    Ok(Self::compare(&applied, MIGRATOR.iter().map(|m| m.version)))
+  }
+
+  fn compare(applied: &[i64], expected: impl Iterator<Item = i64>) -> Self {
+    let expected: Vec<i64> = expected.collect();
+    Self {
+      applied_version: applied.iter().copied().max(),
+      expected_version: expected.iter().copied().max(),
+      missing: expected
+        .into_iter()
+        .filter(|v| !applied.contains(v))
+        .collect(),
+    }
+  }
+
+  /// 必要なマイグレーションが全て適用済みか判定する。
+  pub fn is_up_to_date(&self) -> bool {
+    self.missing.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reports_missing_versions() {
+    let status = SchemaStatus::compare(&[1, 2], [1, 2, 3].into_iter());
+    assert_eq!(status.applied_version, Some(2));
+    assert_eq!(status.expected_version, Some(3));
+    assert_eq!(status.missing, vec![3]);
+    assert!(!status.is_up_to_date());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn reports_latest_applied_migration(pool: PgPool) {
+    let status = SchemaStatus::check(&pool).await.unwrap();
+    let latest = MIGRATOR.iter().map(|m| m.version).max();
+    assert_eq!(status.applied_version, latest);
+    assert_eq!(status.expected_version, latest);
+    assert!(status.is_up_to_date());
+  }
+}
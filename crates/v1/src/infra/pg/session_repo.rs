@@ -2,12 +2,26 @@
 
 use crate::{
   domain::{
-    entity::session::Session,
-    value_obj::{session_id::SessionId, user_id::UserId},
+    clock_skew::ClockSkew,
+    entity::session::{Session, SessionScope},
+    repository::SessionRepository,
+    value_obj::{public_id::PublicId, session_id::SessionId, user_id::UserId},
   },
+  infra::pg::user_repo::PgTx,
   interfaces::http::error::{AppError, AppResult},
 };
-use sqlx::PgPool;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+
+/// セッション一覧の絞り込み条件
+#[derive(Debug, Clone, Default)]
+pub struct SessionFilter {
+  /// 指定したユーザーのセッションのみ
+  pub user_id: Option<UserId>,
+  /// true := 有効期限内のみ，false := 有効期限切れのみ
+  pub active: Option<bool>,
+}
 
 #[derive(Clone)]
 pub struct PgSessionRepository {
@@ -20,35 +34,177 @@ impl PgSessionRepository {
 
   /* ---------- INSERT ---------- */
   pub async fn insert(&self, s: &Session) -> AppResult<()> {
+    Self::insert_inner(&self.pool, s).await
+  }
+
+  /// トランザクション内でセッションを登録する
+  /// トランザクションは呼び出し元で管理される
+  pub async fn insert_tx<'a>(&self, tx: &mut PgTx<'a>, s: &Session) -> AppResult<()> {
+    Self::insert_inner(&mut **tx, s).await
+  }
+
+  async fn insert_inner<'e>(executor: impl PgExecutor<'e>, s: &Session) -> AppResult<()> {
     sqlx::query!(
       r#"
             INSERT INTO sessions
-              (session_id, user_id, created_at, expires_at)
-            VALUES ($1,$2,$3,$4)
+              (session_id, user_id, created_at, expires_at, user_agent, ip, scope)
+            VALUES ($1,$2,$3,$4,$5,$6,$7)
             "#,
       s.session_id.as_uuid(),
       s.user_id.as_i64(),
       s.created_at,
       s.expires_at,
+      s.user_agent.as_deref(),
+      s.ip.map(|ip| ip.to_string()),
+      i16::from(s.scope),
     )
-    .execute(&self.pool)
+    .execute(executor)
     .await
     .map_err(AppError::from)?;
     Ok(())
   }
 
   /* ---------- SELECT ---------- */
+  /// 有効期限切れのセッションは存在しないものとして扱い，その場で削除する
+  /// (有効期限は[security].clock_skew_secsの猶予を含めて判定する)
   pub async fn find(&self, sid: SessionId) -> AppResult<Option<Session>> {
     let row = sqlx::query_as!(
       SessionRow,
-      r#"SELECT * FROM sessions WHERE session_id=$1"#,
+      r#"SELECT session_id, user_id, created_at, expires_at, user_agent, ip, scope FROM sessions WHERE session_id=$1"#,
       sid.as_uuid()
     )
     .fetch_optional(&self.pool)
     .await
     .map_err(AppError::from)?;
 
-    row.map(TryInto::<Session>::try_into).transpose()
+    let Some(session) = row.map(TryInto::<Session>::try_into).transpose()? else {
+      return Ok(None);
+    };
+    // 期限切れの行は件数の集計のため残し，存在しないものとして扱う
+    if session.expires_at <= ClockSkew::current().expiry_cutoff(Utc::now()) {
+      return Ok(None);
+    }
+    Ok(Some(session))
+  }
+
+  /// ユーザーの有効期限内のセッションを作成日時の降順に全て返す
+  /// (全端末からのログアウト等に使用する)
+  pub async fn find_by_user_id(&self, user_id: UserId) -> AppResult<Vec<Session>> {
+    let rows = sqlx::query_as!(
+      SessionRow,
+      r#"SELECT session_id, user_id, created_at, expires_at, user_agent, ip, scope FROM sessions
+        WHERE user_id = $1 AND expires_at > $2
+        ORDER BY created_at DESC, session_id DESC"#,
+      user_id.as_i64(),
+      ClockSkew::current().expiry_cutoff(Utc::now())
+    )
+    .fetch_all(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+
+    rows.into_iter().map(TryInto::<Session>::try_into).collect()
+  }
+
+  /// 作成日時の降順に，`after`より後のセッションを最大`limit`件返す
+  /// `after`は前ページの最後のセッションの(created_at, seq)，有効期限は`now`時点で判定する
+  /// (session_idはBearerトークンのため，ページの境界には連番のseqを使用する)
+  /// `before`を指定した場合は，`before`より前（新しい）のセッションを`before`に近い順（昇順）に返す
+  pub async fn page(
+    &self,
+    filter: &SessionFilter,
+    now: DateTime<Utc>,
+    after: Option<(DateTime<Utc>, i64)>,
+    before: Option<(DateTime<Utc>, i64)>,
+    limit: i64,
+  ) -> AppResult<Vec<(Session, PublicId, i64)>> {
+    let (after_at, after_seq) = after.unzip();
+    let (before_at, before_seq) = before.unzip();
+    // 有効期限の判定は参照時と同じく，許容する時刻のずれを含める
+    let cutoff = ClockSkew::current().expiry_cutoff(now);
+    let rows = sqlx::query!(
+      r#"
+            SELECT s.session_id, s.user_id, s.created_at, s.expires_at,
+                   s.user_agent, s.ip, s.scope, s.seq, u.public_id
+            FROM sessions s
+            JOIN users u ON u.user_id = s.user_id
+            WHERE ($1::BIGINT IS NULL OR s.user_id = $1)
+              AND ($2::BOOLEAN IS NULL OR (s.expires_at > $3) = $2)
+              AND ($4::TIMESTAMPTZ IS NULL OR (s.created_at, s.seq) < ($4, $5))
+              AND ($6::TIMESTAMPTZ IS NULL OR (s.created_at, s.seq) > ($6, $7))
+            ORDER BY
+              CASE WHEN $6::TIMESTAMPTZ IS NULL THEN NULL ELSE s.created_at END ASC,
+              CASE WHEN $6::TIMESTAMPTZ IS NULL THEN NULL ELSE s.seq END ASC,
+              s.created_at DESC, s.seq DESC
+            LIMIT $8
+            "#,
+      filter.user_id.map(|id| id.as_i64()),
+      filter.active,
+      cutoff,
+      after_at,
+      after_seq,
+      before_at,
+      before_seq,
+      limit
+    )
+    .fetch_all(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+
+    rows
+      .into_iter()
+      .map(|r| {
+        let public_id = PublicId::from_string(&r.public_id, true)?.ok_or_else(|| {
+          AppError::InternalServerError(format!("Invalid public_id in DB: {}", r.public_id).into())
+        })?;
+        let session = SessionRow {
+          session_id: r.session_id,
+          user_id: r.user_id,
+          created_at: r.created_at,
+          expires_at: r.expires_at,
+          user_agent: r.user_agent,
+          ip: r.ip,
+          scope: r.scope,
+        }
+        .try_into()?;
+        Ok((session, public_id, r.seq))
+      })
+      .collect()
+  }
+
+  /// 絞り込み条件に一致するセッションの件数を返す
+  pub async fn count(&self, filter: &SessionFilter, now: DateTime<Utc>) -> AppResult<i64> {
+    let count = sqlx::query_scalar!(
+      r#"
+            SELECT COUNT(*) AS "count!"
+            FROM sessions s
+            WHERE ($1::BIGINT IS NULL OR s.user_id = $1)
+              AND ($2::BOOLEAN IS NULL OR (s.expires_at > $3) = $2)
+            "#,
+      filter.user_id.map(|id| id.as_i64()),
+      filter.active,
+      ClockSkew::current().expiry_cutoff(now)
+    )
+    .fetch_one(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(count)
+  }
+
+  /* ---------- UPDATE ---------- */
+  /// `cutoff`より前に作成されたセッションの端末情報(user_agent, ip)を消去する。
+  /// 行は削除しないため，件数による集計は維持される。
+  pub async fn scrub_metadata_before(&self, cutoff: DateTime<Utc>) -> AppResult<u64> {
+    let result = sqlx::query!(
+      r#"
+            UPDATE sessions SET user_agent = NULL, ip = NULL
+            WHERE created_at < $1 AND (user_agent IS NOT NULL OR ip IS NOT NULL)
+            "#,
+      cutoff
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(result.rows_affected())
   }
 
   /* ---------- DELETE ---------- */
@@ -59,6 +215,50 @@ impl PgSessionRepository {
       .map_err(AppError::from)?;
     Ok(())
   }
+
+  /// トランザクション内でユーザーの全セッションを削除し，削除件数を返す
+  /// トランザクションは呼び出し元で管理される
+  pub async fn delete_by_user_id_tx<'a>(
+    &self,
+    tx: &mut PgTx<'a>,
+    user_id: UserId,
+  ) -> AppResult<u64> {
+    let result = sqlx::query!("DELETE FROM sessions WHERE user_id=$1", user_id.as_i64())
+      .execute(&mut **tx)
+      .await
+      .map_err(AppError::from)?;
+    Ok(result.rows_affected())
+  }
+
+  /// 有効期限切れ(猶予を含む)のセッションを全て削除し，削除件数を返す
+  pub async fn purge_expired(&self) -> AppResult<u64> {
+    let cutoff = ClockSkew::current().expiry_cutoff(Utc::now());
+    let result = sqlx::query!("DELETE FROM sessions WHERE expires_at <= $1", cutoff)
+      .execute(&self.pool)
+      .await
+      .map_err(AppError::from)?;
+    Ok(result.rows_affected())
+  }
+}
+
+/* -------- SessionRepositoryの実装 -------- */
+#[async_trait]
+impl SessionRepository for PgSessionRepository {
+  async fn insert(&self, s: &Session) -> AppResult<()> {
+    self.insert(s).await
+  }
+
+  async fn find(&self, id: SessionId) -> AppResult<Option<Session>> {
+    self.find(id).await
+  }
+
+  async fn find_by_user_id(&self, id: UserId) -> AppResult<Vec<Session>> {
+    self.find_by_user_id(id).await
+  }
+
+  async fn delete(&self, id: SessionId) -> AppResult<()> {
+    self.delete(id).await
+  }
 }
 
 /* -------- Row 構造体 & 変換 -------- */
@@ -66,8 +266,11 @@ impl PgSessionRepository {
 struct SessionRow {
   session_id: uuid::Uuid,
   user_id: i64,
-  created_at: chrono::DateTime<chrono::Utc>,
-  expires_at: chrono::DateTime<chrono::Utc>,
+  created_at: DateTime<Utc>,
+  expires_at: DateTime<Utc>,
+  user_agent: Option<String>,
+  ip: Option<String>,
+  scope: i16,
 }
 
 impl TryFrom<SessionRow> for Session {
@@ -75,9 +278,191 @@ impl TryFrom<SessionRow> for Session {
   fn try_from(r: SessionRow) -> Result<Self, Self::Error> {
     Ok(Self {
       session_id: SessionId::from_string(r.session_id.to_string(), true)?.unwrap(),
-      user_id: UserId::new(r.user_id)?,
+      user_id: UserId::from_db(r.user_id)?,
       created_at: r.created_at,
       expires_at: r.expires_at,
+      user_agent: r.user_agent,
+      ip: r
+        .ip
+        .map(|ip| {
+          ip.parse()
+            .map_err(|_| AppError::InternalServerError(Some(format!("Invalid ip in DB: {}", ip))))
+        })
+        .transpose()?,
+      scope: SessionScope::try_from(r.scope)?,
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    domain::entity::user::{UserRole, UserStatus},
+    test_support::seed_user,
+  };
+  use chrono::Duration;
+
+  fn session(user_id: UserId, user_agent: Option<String>, ip: Option<&str>) -> Session {
+    let now = Utc::now();
+    Session {
+      session_id: SessionId::new(),
+      user_id,
+      created_at: now,
+      expires_at: now + Duration::hours(1),
+      user_agent,
+      ip: ip.map(|ip| ip.parse().unwrap()),
+      scope: SessionScope::Full,
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn round_trip_with_metadata(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "device_user", UserStatus::Active, UserRole::User).await;
+    let repo = PgSessionRepository::new(pool);
+
+    let ua = Session::sanitize_user_agent("Mozilla/5.0 (X11; Linux x86_64)");
+    let s = session(user.user_id, ua.clone(), Some("2001:db8::1"));
+    repo.insert(&s).await.unwrap();
+
+    let found = repo.find(s.session_id.clone()).await.unwrap().unwrap();
+    assert_eq!(found.user_agent, ua);
+    assert_eq!(found.ip, s.ip);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn round_trip_without_metadata(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "plain_user", UserStatus::Active, UserRole::User).await;
+    let repo = PgSessionRepository::new(pool);
+
+    let s = session(user.user_id, None, None);
+    repo.insert(&s).await.unwrap();
+
+    let found = repo.find(s.session_id.clone()).await.unwrap().unwrap();
+    assert_eq!(found.user_agent, None);
+    assert_eq!(found.ip, None);
+    assert_eq!(found.user_id, user.user_id);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn find_by_user_id_returns_active_sessions(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "multi_device", UserStatus::Active, UserRole::User).await;
+    let (other, _) = seed_user(&pool, "other_device", UserStatus::Active, UserRole::User).await;
+    let repo = PgSessionRepository::new(pool.clone());
+
+    let mut tx = pool.begin().await.unwrap();
+    let sessions = [
+      session(user.user_id, None, None),
+      session(user.user_id, None, None),
+    ];
+    for s in &sessions {
+      repo.insert_tx(&mut tx, s).await.unwrap();
+    }
+    let mut expired = session(user.user_id, None, None);
+    expired.expires_at = expired.created_at - Duration::minutes(10);
+    repo.insert_tx(&mut tx, &expired).await.unwrap();
+    repo
+      .insert_tx(&mut tx, &session(other.user_id, None, None))
+      .await
+      .unwrap();
+    tx.commit().await.unwrap();
+
+    let repo: &dyn SessionRepository = &repo;
+    let mut found: Vec<_> = repo
+      .find_by_user_id(user.user_id)
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|s| s.session_id)
+      .collect();
+    let mut expected: Vec<_> = sessions.iter().map(|s| s.session_id.clone()).collect();
+    found.sort_by_key(|id| *id.as_uuid());
+    expected.sort_by_key(|id| *id.as_uuid());
+    assert_eq!(found, expected);
+  }
+  async fn session_exists(pool: &PgPool, sid: &SessionId) -> bool {
+    sqlx::query_scalar!(
+      r#"SELECT EXISTS(SELECT 1 FROM sessions WHERE session_id=$1) AS "exists!""#,
+      sid.as_uuid()
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn find_ignores_but_keeps_expired_session(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "expired_user", UserStatus::Active, UserRole::User).await;
+    let repo = PgSessionRepository::new(pool.clone());
+    let mut expired = session(user.user_id, None, None);
+    expired.expires_at = expired.created_at - Duration::minutes(10);
+    repo.insert(&expired).await.unwrap();
+    assert!(session_exists(&pool, &expired.session_id).await);
+
+    assert!(
+      repo
+        .find(expired.session_id.clone())
+        .await
+        .unwrap()
+        .is_none()
+    );
+    assert!(session_exists(&pool, &expired.session_id).await);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn expiry_allows_clock_skew_leeway(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "skew_user", UserStatus::Active, UserRole::User).await;
+    let repo = PgSessionRepository::new(pool.clone());
+    let leeway = ClockSkew::current().leeway;
+    let margin = Duration::seconds(5);
+
+    // 期限を過ぎていても許容範囲内のセッションは有効
+    let mut within = session(user.user_id, None, None);
+    within.expires_at = Utc::now() - leeway + margin;
+    // 許容範囲を過ぎたセッションは失効している
+    let mut beyond = session(user.user_id, None, None);
+    beyond.expires_at = Utc::now() - leeway - margin;
+    for s in [&within, &beyond] {
+      repo.insert(s).await.unwrap();
+    }
+
+    let found = repo.find_by_user_id(user.user_id).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].session_id, within.session_id);
+    assert!(
+      repo
+        .find(within.session_id.clone())
+        .await
+        .unwrap()
+        .is_some()
+    );
+    assert!(
+      repo
+        .find(beyond.session_id.clone())
+        .await
+        .unwrap()
+        .is_none()
+    );
+    // 許容範囲を過ぎたセッションのみ削除される
+    assert_eq!(repo.purge_expired().await.unwrap(), 1);
+    assert!(session_exists(&pool, &within.session_id).await);
+    assert!(!session_exists(&pool, &beyond.session_id).await);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn purge_expired_deletes_only_expired_sessions(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "purge_user", UserStatus::Active, UserRole::User).await;
+    let repo = PgSessionRepository::new(pool.clone());
+    let active = session(user.user_id, None, None);
+    repo.insert(&active).await.unwrap();
+    for _ in 0..2 {
+      let mut expired = session(user.user_id, None, None);
+      expired.expires_at = expired.created_at - Duration::minutes(10);
+      repo.insert(&expired).await.unwrap();
+    }
+
+    assert_eq!(repo.purge_expired().await.unwrap(), 2);
+    assert_eq!(repo.purge_expired().await.unwrap(), 0);
+    assert!(session_exists(&pool, &active.session_id).await);
+  }
+}
@@ -2,6 +2,7 @@
 //! --------------------------------------------------------------
 //! ・INSERT を共通メソッド `insert_inner` に集約
 //! ・Tx あり / なしをラップして呼び出せるようにする
+//! ・UPDATE は Executor を受け取る `update_inner` に集約
 //! --------------------------------------------------------------
 
 use crate::{
@@ -14,7 +15,7 @@ use crate::{
 };
 use async_trait::async_trait;
 use chrono::Utc;
-use sqlx::{PgPool, Postgres, Transaction};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
 
 /// Tx 型エイリアス
 pub type PgTx<'a> = Transaction<'a, Postgres>;
@@ -49,14 +50,16 @@ impl PgUserAuthRepository {
             INSERT INTO user_auths
               (user_id, current_hashed_password,
                prev_hashed_password_1, prev_hashed_password_2,
-               login_fail_times, created_at, updated_at)
-            VALUES ($1,$2,$3,$4,$5,$6,$7)
+               login_fail_times, locked_until, must_change_password, created_at, updated_at)
+            VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)
             "#,
       a.user_id.as_i64(),
       a.current_hash.as_hash(),
       a.prev_hash1.as_ref().map(|h| h.as_hash()),
       a.prev_hash2.as_ref().map(|h| h.as_hash()),
       a.login_fail_times as i16,
+      a.locked_until,
+      a.must_change_password,
       a.created_at,
       a.updated_at,
     )
@@ -80,24 +83,58 @@ impl PgUserAuthRepository {
     row.map(TryInto::<UserAuth>::try_into).transpose()
   }
 
+  /* ===== SELECT FOR UPDATE (Tx あり) ===== */
+  /// トランザクション内でユーザーIDを指定して認証情報を取得し，行をロックする
+  /// トランザクションは呼び出し元で管理される
+  pub async fn find_for_update_tx<'a>(
+    &self,
+    tx: &mut PgTx<'a>,
+    user_id: UserId,
+  ) -> AppResult<Option<UserAuth>> {
+    let row = sqlx::query_as!(
+      AuthRow,
+      r#"SELECT * FROM user_auths WHERE user_id=$1 FOR UPDATE"#,
+      user_id.as_i64()
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+
+    row.map(TryInto::<UserAuth>::try_into).transpose()
+  }
+
+  /* ===== UPDATE (Tx あり) ===== */
+  pub async fn update_tx<'a>(&self, tx: &mut PgTx<'a>, a: &UserAuth) -> AppResult<()> {
+    self.update_inner(&mut **tx, a).await
+  }
+
   /// ユーザー認証情報を更新するSQLを実行
   async fn do_update(&self, a: &UserAuth) -> AppResult<()> {
+    self.update_inner(&self.pool, a).await
+  }
+
+  /// ユーザー認証情報を更新するSQL本体
+  async fn update_inner<'e, E: PgExecutor<'e>>(&self, executor: E, a: &UserAuth) -> AppResult<()> {
     sqlx::query!(
       r#"UPDATE user_auths
         SET current_hashed_password = $1,
             prev_hashed_password_1  = $2,
             prev_hashed_password_2  = $3,
             login_fail_times        = $4,
-            updated_at              = $5
-      WHERE user_id = $6"#,
+            locked_until            = $5,
+            must_change_password    = $6,
+            updated_at              = $7
+      WHERE user_id = $8"#,
       a.current_hash.as_hash(),
       a.prev_hash1.as_ref().map(|h| h.as_hash()),
       a.prev_hash2.as_ref().map(|h| h.as_hash()),
       a.login_fail_times as i16,
+      a.locked_until,
+      a.must_change_password,
       Utc::now(),
       a.user_id.as_i64()
     )
-    .execute(&self.pool)
+    .execute(executor)
     .await
     .map_err(AppError::from)?;
     Ok(())
@@ -128,6 +165,8 @@ struct AuthRow {
   prev_hashed_password_1: Option<String>,
   prev_hashed_password_2: Option<String>,
   login_fail_times: i32,
+  locked_until: Option<chrono::DateTime<Utc>>,
+  must_change_password: bool,
   created_at: chrono::DateTime<Utc>,
   updated_at: chrono::DateTime<Utc>,
 }
@@ -136,7 +175,7 @@ impl TryFrom<AuthRow> for UserAuth {
   type Error = AppError;
   fn try_from(r: AuthRow) -> Result<Self, Self::Error> {
     Ok(Self {
-      user_id: UserId::new(r.user_id)?,
+      user_id: UserId::from_db(r.user_id)?,
       current_hash: UserPassword::from_hash(r.current_hashed_password)?,
       prev_hash1: r
         .prev_hashed_password_1
@@ -147,6 +186,8 @@ impl TryFrom<AuthRow> for UserAuth {
         .map(UserPassword::from_hash)
         .transpose()?,
       login_fail_times: r.login_fail_times as u16,
+      locked_until: r.locked_until,
+      must_change_password: r.must_change_password,
       created_at: r.created_at,
       updated_at: r.updated_at,
     })
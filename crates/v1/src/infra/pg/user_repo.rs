@@ -1,6 +1,7 @@
 use crate::{
   domain::{
     entity::user::{User, UserRole, UserStatus},
+    repository::UserRepository,
     value_obj::{
       birth_date::BirthDate, email_address::EmailAddress, phone_number::PhoneNumber,
       public_id::PublicId, user_full_name::UserFullName, user_id::UserId, user_name::UserName,
@@ -8,7 +9,8 @@ use crate::{
   },
   interfaces::http::error::{AppError, AppResult},
 };
-use chrono::Utc;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 
 /// `PgPool` を受け取り、ユーザー関連のリポジトリを初期化する
@@ -34,10 +36,10 @@ impl PgUserRepository {
         INSERT INTO users
           (public_id, randomart, user_name,
             first_name, last_name,
-            email, phone, birth_date,
+            email, email_canonical, phone, birth_date,
             status, role,
             last_login_at, created_at, updated_at)
-        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13)
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14)
         RETURNING user_id
         "#,
       u.public_id.as_str(),
@@ -46,6 +48,7 @@ impl PgUserRepository {
       u.full_name.as_ref().map(|n| n.first()),
       u.full_name.as_ref().and_then(|n| n.last()),
       u.email.as_ref().map(|e| e.as_str()),
+      u.email.as_ref().map(|e| e.canonical()),
       u.phone.as_ref().map(|p| p.as_str()),
       u.birth_date.as_ref().map(|b| b.as_naive_date()),
       i16::from(u.status),
@@ -56,7 +59,7 @@ impl PgUserRepository {
     )
     .fetch_one(&self.pool)
     .await
-    .map_err(AppError::from)
+    .map_err(map_insert_error)
   }
 
   /// トランザクション内でのユーザー登録
@@ -68,10 +71,10 @@ impl PgUserRepository {
         INSERT INTO users
           (public_id, randomart, user_name,
             first_name, last_name,
-            email, phone, birth_date,
+            email, email_canonical, phone, birth_date,
             status, role,
             last_login_at, created_at, updated_at)
-        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13)
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14)
         RETURNING user_id
         "#,
       u.public_id.as_str(),
@@ -80,6 +83,7 @@ impl PgUserRepository {
       u.full_name.as_ref().map(|n| n.first()),
       u.full_name.as_ref().and_then(|n| n.last()),
       u.email.as_ref().map(|e| e.as_str()),
+      u.email.as_ref().map(|e| e.canonical()),
       u.phone.as_ref().map(|p| p.as_str()),
       u.birth_date.as_ref().map(|b| b.as_naive_date()),
       i16::from(u.status),
@@ -90,6 +94,34 @@ impl PgUserRepository {
     )
     .fetch_one(&mut **tx) // PostgreSQL の RowStream を参照として渡す
     .await
+    .map_err(map_insert_error)
+  }
+
+  /// トランザクション内でのuser_name存在チェック
+  /// ステータスを問わず，同じユーザー名が存在する場合はtrueを返す
+  pub async fn exists_by_username_tx<'a>(
+    &self,
+    tx: &mut PgTx<'a>,
+    name: &UserName,
+  ) -> AppResult<bool> {
+    sqlx::query_scalar!(
+      r#"SELECT EXISTS(SELECT 1 FROM users WHERE user_name = $1) AS "exists!""#,
+      name.as_str()
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(AppError::from)
+  }
+
+  /// user_name存在チェック
+  /// ステータスを問わず，同じユーザー名が存在する場合はtrueを返す
+  pub async fn exists_by_username(&self, name: &UserName) -> AppResult<bool> {
+    sqlx::query_scalar!(
+      r#"SELECT EXISTS(SELECT 1 FROM users WHERE user_name = $1) AS "exists!""#,
+      name.as_str()
+    )
+    .fetch_one(&self.pool)
+    .await
     .map_err(AppError::from)
   }
 
@@ -107,6 +139,7 @@ impl PgUserRepository {
         first_name,
         last_name,
         email,
+        recovery_email,
         phone,
         birth_date,
         status,
@@ -133,7 +166,7 @@ impl PgUserRepository {
       UserRow,
       r#"SELECT
         user_id, public_id, randomart, user_name,
-        first_name, last_name, email, phone, birth_date,
+        first_name, last_name, email, recovery_email, phone, birth_date,
         status, role, last_login_at, created_at, updated_at
       FROM users
       WHERE user_name = $1 AND status = 0"#,
@@ -146,6 +179,116 @@ impl PgUserRepository {
     row.map(TryInto::<User>::try_into).transpose()
   }
 
+  /// user_name 検索（ログイン用）
+  /// ユーザー名を指定して，ステータスを問わずユーザー情報を取得する
+  /// (ステータスによるログイン拒否の理由を返すため)
+  pub async fn find_by_username_any_status(&self, name: &UserName) -> AppResult<Option<User>> {
+    let row = sqlx::query_as!(
+      UserRow,
+      r#"SELECT
+        user_id, public_id, randomart, user_name,
+        first_name, last_name, email, recovery_email, phone, birth_date,
+        status, role, last_login_at, created_at, updated_at
+      FROM users
+      WHERE user_name = $1"#,
+      name.as_str()
+    )
+    .fetch_optional(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+
+    row.map(TryInto::<User>::try_into).transpose()
+  }
+
+  /// public_id 検索
+  /// 公開IDを指定して，ステータスを問わずユーザー情報を取得する
+  /// ユーザーが存在しない場合は `None` を返す
+  pub async fn find_by_public_id(&self, public_id: &PublicId) -> AppResult<Option<User>> {
+    let row = sqlx::query_as!(
+      UserRow,
+      r#"SELECT
+        user_id, public_id, randomart, user_name,
+        first_name, last_name, email, recovery_email, phone, birth_date,
+        status, role, last_login_at, created_at, updated_at
+      FROM users
+      WHERE public_id = $1"#,
+      public_id.as_str()
+    )
+    .fetch_optional(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+
+    row.map(TryInto::<User>::try_into).transpose()
+  }
+
+  /// トランザクション内で公開IDを指定して，ステータスを問わずユーザー情報を取得し行をロックする
+  /// トランザクションは呼び出し元で管理される
+  pub async fn find_by_public_id_for_update_tx<'a>(
+    &self,
+    tx: &mut PgTx<'a>,
+    public_id: &PublicId,
+  ) -> AppResult<Option<User>> {
+    let row = sqlx::query_as!(
+      UserRow,
+      r#"SELECT
+        user_id, public_id, randomart, user_name,
+        first_name, last_name, email, recovery_email, phone, birth_date,
+        status, role, last_login_at, created_at, updated_at
+      FROM users
+      WHERE public_id = $1
+      FOR UPDATE"#,
+      public_id.as_str()
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+
+    row.map(TryInto::<User>::try_into).transpose()
+  }
+
+  /// トランザクション内でユーザーIDを指定して，ステータスを問わずユーザー情報を取得し行をロックする
+  /// トランザクションは呼び出し元で管理される
+  pub async fn find_by_user_id_for_update_tx<'a>(
+    &self,
+    tx: &mut PgTx<'a>,
+    id: UserId,
+  ) -> AppResult<Option<User>> {
+    let row = sqlx::query_as!(
+      UserRow,
+      r#"SELECT
+        user_id, public_id, randomart, user_name,
+        first_name, last_name, email, recovery_email, phone, birth_date,
+        status, role, last_login_at, created_at, updated_at
+      FROM users
+      WHERE user_id = $1
+      FOR UPDATE"#,
+      id.as_i64()
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+
+    row.map(TryInto::<User>::try_into).transpose()
+  }
+
+  /// トランザクション内でユーザーのステータスを更新する
+  /// トランザクションは呼び出し元で管理される
+  pub async fn update_status_tx<'a>(&self, tx: &mut PgTx<'a>, u: &User) -> AppResult<()> {
+    sqlx::query!(
+      r#"UPDATE users
+        SET status = $1,
+          updated_at = $2
+        WHERE user_id = $3"#,
+      i16::from(u.status),
+      Utc::now(),
+      u.user_id.as_i64()
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+  }
+
   /// ユーザーのステータスを更新する
   pub async fn update_status(&self, u: &User) -> AppResult<()> {
     sqlx::query!(
@@ -162,6 +305,22 @@ impl PgUserRepository {
     .map_err(AppError::from)?;
     Ok(())
   }
+  /// ユーザーの最終ログイン日時を更新する
+  /// (ログインはプロフィールの変更ではないため，updated_atは更新しない)
+  pub async fn update_last_login_at(&self, u: &User) -> AppResult<()> {
+    sqlx::query!(
+      r#"UPDATE users
+        SET last_login_at = $1
+        WHERE user_id = $2"#,
+      u.last_login_at,
+      u.user_id.as_i64()
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+  }
+
   /// ユーザーのロールを更新する
   pub async fn update_role(&self, u: &User) -> AppResult<()> {
     sqlx::query!(
@@ -179,6 +338,205 @@ impl PgUserRepository {
     Ok(())
   }
 
+  /// ユーザーのプロフィール(氏名・連絡先・誕生日・予備のメールアドレス)を更新する
+  /// 値がNoneの項目はNULLで更新する
+  pub async fn update_profile(&self, u: &User) -> AppResult<()> {
+    sqlx::query!(
+      r#"UPDATE users
+        SET first_name = $1,
+            last_name  = $2,
+            email      = $3,
+            email_canonical = CASE WHEN email IS DISTINCT FROM $3::VARCHAR THEN $4 ELSE email_canonical END,
+            phone      = $5,
+            birth_date = $6,
+            recovery_email = $7,
+            updated_at = $8
+        WHERE user_id  = $9"#,
+      u.full_name.as_ref().map(|n| n.first()),
+      u.full_name.as_ref().and_then(|n| n.last()),
+      u.email.as_ref().map(|e| e.as_str()),
+      u.email.as_ref().map(|e| e.canonical()),
+      u.phone.as_ref().map(|p| p.as_str()),
+      u.birth_date.as_ref().map(|b| b.as_naive_date()),
+      u.recovery_email.as_ref().map(|e| e.as_str()),
+      Utc::now(),
+      u.user_id.as_i64()
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(map_insert_error)?;
+    Ok(())
+  }
+
+  /// 取得時から更新されていない場合のみ，プロフィールを更新する
+  /// `expected`は取得時のupdated_at。更新した場合は新しいupdated_atを，他の更新により
+  /// 変更されていた場合は`None`を返す。
+  pub async fn update_profile_if_unmodified(
+    &self,
+    u: &User,
+    expected: DateTime<Utc>,
+  ) -> AppResult<Option<DateTime<Utc>>> {
+    sqlx::query_scalar!(
+      r#"UPDATE users
+        SET first_name = $1,
+            last_name  = $2,
+            email      = $3,
+            email_canonical = CASE WHEN email IS DISTINCT FROM $3::VARCHAR THEN $4 ELSE email_canonical END,
+            phone      = $5,
+            birth_date = $6,
+            recovery_email = $7,
+            updated_at = $8
+        WHERE user_id  = $9 AND updated_at = $10
+        RETURNING updated_at"#,
+      u.full_name.as_ref().map(|n| n.first()),
+      u.full_name.as_ref().and_then(|n| n.last()),
+      u.email.as_ref().map(|e| e.as_str()),
+      u.email.as_ref().map(|e| e.canonical()),
+      u.phone.as_ref().map(|p| p.as_str()),
+      u.birth_date.as_ref().map(|b| b.as_naive_date()),
+      u.recovery_email.as_ref().map(|e| e.as_str()),
+      Utc::now(),
+      u.user_id.as_i64(),
+      expected
+    )
+    .fetch_optional(&self.pool)
+    .await
+    .map_err(map_insert_error)
+  }
+
+  /// トランザクション内でユーザーの公開IDとランダムアートを更新する
+  /// user_idは変更しない
+  pub async fn update_public_id_tx<'a>(&self, tx: &mut PgTx<'a>, u: &User) -> AppResult<()> {
+    sqlx::query!(
+      r#"UPDATE users
+        SET public_id  = $1,
+            randomart  = $2,
+            updated_at = $3
+        WHERE user_id  = $4"#,
+      u.public_id.as_str(),
+      u.randomart,
+      Utc::now(),
+      u.user_id.as_i64()
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+  }
+
+  /// user_idの昇順に，`after`より後のユーザーの公開IDとランダムアートを最大`limit`件返す
+  /// ランダムアートの一括再生成で，ユーザーを順に走査するために使用する
+  pub async fn page_randomart(
+    &self,
+    after: i64,
+    limit: i64,
+  ) -> AppResult<Vec<(UserId, PublicId, String)>> {
+    let rows = sqlx::query!(
+      r#"SELECT user_id, public_id, randomart
+        FROM users
+        WHERE user_id > $1
+        ORDER BY user_id
+        LIMIT $2"#,
+      after,
+      limit
+    )
+    .fetch_all(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+
+    rows
+      .into_iter()
+      .map(|r| {
+        let public_id = PublicId::from_string(&r.public_id, true)?.ok_or_else(|| {
+          AppError::InternalServerError(format!("Invalid public_id in DB: {}", r.public_id).into())
+        })?;
+        Ok((UserId::from_db(r.user_id)?, public_id, r.randomart))
+      })
+      .collect()
+  }
+
+  /// トランザクション内でランダムアートのみを更新する
+  /// 走査後に公開IDが再発行された場合は更新せず，falseを返す
+  /// トランザクションは呼び出し元で管理される
+  pub async fn update_randomart_tx<'a>(
+    &self,
+    tx: &mut PgTx<'a>,
+    user_id: UserId,
+    public_id: &PublicId,
+    randomart: &str,
+  ) -> AppResult<bool> {
+    let result = sqlx::query!(
+      r#"UPDATE users
+        SET randomart  = $1,
+            updated_at = $2
+        WHERE user_id  = $3 AND public_id = $4"#,
+      randomart,
+      Utc::now(),
+      user_id.as_i64(),
+      public_id.as_str()
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+    Ok(result.rows_affected() == 1)
+  }
+
+  /// メールアドレスを持つユーザーをuser_idの昇順に取得する
+  /// `after`より大きいuser_idから`limit`件を，保存済みの正規形と共に返す
+  pub async fn page_emails(
+    &self,
+    after: i64,
+    limit: i64,
+  ) -> AppResult<Vec<(UserId, EmailAddress, Option<String>)>> {
+    let rows = sqlx::query!(
+      r#"SELECT user_id, email AS "email!", email_canonical
+        FROM users
+        WHERE user_id > $1 AND email IS NOT NULL
+        ORDER BY user_id
+        LIMIT $2"#,
+      after,
+      limit
+    )
+    .fetch_all(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+
+    rows
+      .into_iter()
+      .map(|r| {
+        let email = EmailAddress::new(&r.email, true)?.ok_or_else(|| {
+          AppError::InternalServerError(
+            format!("Invalid email in DB: user_id={}", r.user_id).into(),
+          )
+        })?;
+        Ok((UserId::from_db(r.user_id)?, email, r.email_canonical))
+      })
+      .collect()
+  }
+
+  /// メールアドレスの正規形を更新する
+  /// 取得後にメールアドレスが変更されていた場合は更新せず，falseを返す
+  /// 他のユーザーと正規形が重複する場合は409を返す
+  pub async fn update_email_canonical(
+    &self,
+    user_id: UserId,
+    email: &EmailAddress,
+    canonical: &str,
+  ) -> AppResult<bool> {
+    let result = sqlx::query!(
+      r#"UPDATE users
+        SET email_canonical = $1
+        WHERE user_id = $2 AND email = $3"#,
+      canonical,
+      user_id.as_i64(),
+      email.as_str()
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(map_insert_error)?;
+    Ok(result.rows_affected() == 1)
+  }
+
   /// ユーザーを削除する
   /// ユーザーIDを指定して、ユーザーをDBから物理削除する
   pub async fn delete(&self, u: &User) -> AppResult<()> {
@@ -192,10 +550,101 @@ impl PgUserRepository {
     .map_err(AppError::from)?;
     Ok(())
   }
+
+  /// ユーザー情報の全項目を更新するSQLを実行
+  /// user_idとcreated_atは変更しない
+  async fn do_update(&self, u: &User) -> AppResult<()> {
+    sqlx::query!(
+      r#"UPDATE users
+        SET public_id      = $1,
+            randomart      = $2,
+            user_name      = $3,
+            first_name     = $4,
+            last_name      = $5,
+            email          = $6,
+            email_canonical = CASE WHEN email IS DISTINCT FROM $6::VARCHAR THEN $7 ELSE email_canonical END,
+            recovery_email = $8,
+            phone          = $9,
+            birth_date     = $10,
+            status         = $11,
+            role           = $12,
+            last_login_at  = $13,
+            updated_at     = $14
+        WHERE user_id = $15"#,
+      u.public_id.as_str(),
+      u.randomart,
+      u.user_name.as_str(),
+      u.full_name.as_ref().map(|n| n.first()),
+      u.full_name.as_ref().and_then(|n| n.last()),
+      u.email.as_ref().map(|e| e.as_str()),
+      u.email.as_ref().map(|e| e.canonical()),
+      u.recovery_email.as_ref().map(|e| e.as_str()),
+      u.phone.as_ref().map(|p| p.as_str()),
+      u.birth_date.as_ref().map(|b| b.as_naive_date()),
+      i16::from(u.status),
+      i16::from(u.role),
+      u.last_login_at,
+      Utc::now(),
+      u.user_id.as_i64()
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(map_insert_error)?;
+    Ok(())
+  }
+}
+
+/* UserRepositoryの実装 */
+#[async_trait]
+impl UserRepository for PgUserRepository {
+  async fn insert(&self, u: &User) -> AppResult<()> {
+    self.insert_ntx(u).await.map(|_| ())
+  }
+
+  async fn find_by_user_id(&self, id: UserId) -> AppResult<Option<User>> {
+    self.find_by_user_id(id).await
+  }
+
+  async fn find_by_username(&self, name: &UserName) -> AppResult<Option<User>> {
+    self.find_by_username(name).await
+  }
+
+  async fn update(&self, u: &User) -> AppResult<()> {
+    self.do_update(u).await
+  }
 }
 
 /* 内部関数 */
 
+/// user_nameの一意制約
+const USER_NAME_UNIQUE: &str = "users_user_name_key";
+/// emailの一意制約（入力された値・正規形）
+const EMAIL_UNIQUE: [&str; 2] = ["users_email_key", "users_email_canonical_key"];
+
+/// ユーザー名重複時のエラーを返す
+pub fn user_name_taken() -> AppError {
+  AppError::Conflict(Some("ユーザー名(user_name)は既に使用されています。".into()))
+}
+
+/// メールアドレス重複時のエラーを返す
+pub fn email_taken() -> AppError {
+  AppError::Conflict(Some(
+    "メールアドレス(email_address)は既に使用されています。".into(),
+  ))
+}
+
+/// INSERT・UPDATE時のエラーを変換する
+/// user_name・emailの一意制約違反は，専用のメッセージを返す
+fn map_insert_error(err: sqlx::Error) -> AppError {
+  match &err {
+    sqlx::Error::Database(db) if db.constraint() == Some(USER_NAME_UNIQUE) => user_name_taken(),
+    sqlx::Error::Database(db) if db.constraint().is_some_and(|c| EMAIL_UNIQUE.contains(&c)) => {
+      email_taken()
+    }
+    _ => AppError::from(err),
+  }
+}
+
 /// users テーブルの行を表す構造体
 #[derive(sqlx::FromRow)]
 struct UserRow {
@@ -206,6 +655,7 @@ struct UserRow {
   first_name: Option<String>,
   last_name: Option<String>,
   email: Option<String>,
+  recovery_email: Option<String>,
   phone: Option<String>,
   birth_date: Option<chrono::NaiveDate>,
   status: i16,
@@ -220,32 +670,195 @@ impl TryFrom<UserRow> for User {
   type Error = AppError;
   fn try_from(r: UserRow) -> Result<Self, Self::Error> {
     Ok(Self {
-      user_id: UserId::new(r.user_id)?,
+      user_id: UserId::from_db(r.user_id)?,
       public_id: PublicId::from_string(&r.public_id, true)?.ok_or_else(|| {
         AppError::InternalServerError(format!("Invalid public_id in DB: {}", r.public_id).into())
       })?,
       randomart: r.randomart,
-      user_name: UserName::new(&r.user_name, true)?.ok_or_else(|| {
-        AppError::InternalServerError(format!("Invalid user_name in DB: {}", r.user_name).into())
-      })?,
+      user_name: UserName::from_db(&r.user_name)?,
       full_name: match (r.first_name, r.last_name) {
-        (Some(f), Some(l)) if !f.is_empty() || !l.is_empty() => UserFullName::new(f, l)?,
+        (Some(f), l) => UserFullName::new(f, l.unwrap_or_default())?,
         _ => None,
       },
       email: r
         .email
         .and_then(|e| EmailAddress::new(e, true).transpose())
         .transpose()?,
+      recovery_email: r
+        .recovery_email
+        .and_then(|e| EmailAddress::new(e, true).transpose())
+        .transpose()?,
       phone: r
         .phone
         .and_then(|p| PhoneNumber::new(p, true).transpose())
         .transpose()?,
       birth_date: r.birth_date.map(BirthDate::from_naive_date),
-      status: UserStatus::from(r.status),
-      role: UserRole::from(r.role),
+      status: UserStatus::try_from(r.status)?,
+      role: UserRole::try_from(r.role)?,
       last_login_at: r.last_login_at,
       created_at: r.created_at,
       updated_at: r.updated_at,
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_support::{new_user, seed_user};
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn trait_insert_and_find(pool: PgPool) {
+    let repo: &dyn UserRepository = &PgUserRepository::new(pool);
+    let user = new_user("trait_user", UserStatus::Active, UserRole::User);
+    repo.insert(&user).await.unwrap();
+
+    let found = repo
+      .find_by_username(&user.user_name)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(found.public_id, user.public_id);
+    let by_id = repo.find_by_user_id(found.user_id).await.unwrap().unwrap();
+    assert_eq!(by_id.user_name, user.user_name);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn trait_update_writes_all_columns(pool: PgPool) {
+    let (mut user, _) = seed_user(&pool, "before_name", UserStatus::Active, UserRole::User).await;
+    let repo = PgUserRepository::new(pool.clone());
+    user.user_name = UserName::new("after_name", true).unwrap().unwrap();
+    user.role = UserRole::Moderator;
+    user.status = UserStatus::Suspended;
+    user.email = EmailAddress::new("after@example.com", true).unwrap();
+    UserRepository::update(&repo, &user).await.unwrap();
+
+    let stored = repo
+      .find_by_public_id(&user.public_id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(stored.user_name, user.user_name);
+    assert_eq!(stored.role, UserRole::Moderator);
+    assert_eq!(stored.status, UserStatus::Suspended);
+    assert_eq!(stored.email, user.email);
+    // Active以外のユーザーは取得しない
+    assert!(repo.find_by_user_id(user.user_id).await.unwrap().is_none());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn trait_update_rejects_duplicate_username(pool: PgPool) {
+    seed_user(&pool, "taken_name", UserStatus::Active, UserRole::User).await;
+    let (mut user, _) = seed_user(&pool, "other_name", UserStatus::Active, UserRole::User).await;
+    user.user_name = UserName::new("taken_name", true).unwrap().unwrap();
+    let err = UserRepository::update(&PgUserRepository::new(pool), &user)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::Conflict(Some(m)) if m.contains("user_name")));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn update_profile_rejects_duplicate_email(pool: PgPool) {
+    let (mut taken, _) = seed_user(&pool, "email_owner", UserStatus::Active, UserRole::User).await;
+    let (mut user, _) = seed_user(&pool, "email_other", UserStatus::Active, UserRole::User).await;
+    let repo = PgUserRepository::new(pool);
+    taken.email = EmailAddress::new("taken@example.com", true).unwrap();
+    repo.update_profile(&taken).await.unwrap();
+
+    user.email = taken.email.clone();
+    let err = repo.update_profile(&user).await.unwrap_err();
+    assert!(matches!(err, AppError::Conflict(Some(m)) if m.contains("email_address")));
+  }
+
+  async fn stored_canonical(pool: &PgPool, user: &User) -> Option<String> {
+    sqlx::query_scalar!(
+      "SELECT email_canonical FROM users WHERE user_id = $1",
+      user.user_id.as_i64()
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn email_canonical_is_recomputed_only_when_email_changes(pool: PgPool) {
+    let (mut user, _) = seed_user(&pool, "canon_user", UserStatus::Active, UserRole::User).await;
+    let repo = PgUserRepository::new(pool.clone());
+    user.email = EmailAddress::new("canon@example.com", true).unwrap();
+    repo.update_profile(&user).await.unwrap();
+    // 以前の方式で生成された正規形を再現する
+    sqlx::query!(
+      "UPDATE users SET email_canonical = 'legacy@example.com' WHERE user_id = $1",
+      user.user_id.as_i64()
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // メールアドレス以外の更新では正規形を変更しない
+    user.birth_date = None;
+    repo.update_profile(&user).await.unwrap();
+    UserRepository::update(&repo, &user).await.unwrap();
+    assert_eq!(
+      stored_canonical(&pool, &user).await.as_deref(),
+      Some("legacy@example.com")
+    );
+
+    user.email = EmailAddress::new("changed@example.com", true).unwrap();
+    repo.update_profile(&user).await.unwrap();
+    assert_eq!(
+      stored_canonical(&pool, &user).await.as_deref(),
+      Some("changed@example.com")
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn update_email_canonical_skips_changed_email(pool: PgPool) {
+    let (mut user, _) = seed_user(&pool, "canon_skip", UserStatus::Active, UserRole::User).await;
+    let repo = PgUserRepository::new(pool.clone());
+    user.email = EmailAddress::new("current@example.com", true).unwrap();
+    repo.update_profile(&user).await.unwrap();
+
+    let stale = EmailAddress::new("stale@example.com", true)
+      .unwrap()
+      .unwrap();
+    assert!(
+      !repo
+        .update_email_canonical(user.user_id, &stale, "stale@example.com")
+        .await
+        .unwrap()
+    );
+    let page = repo.page_emails(0, 10).await.unwrap();
+    assert_eq!(page.len(), 1);
+    let (user_id, email, canonical) = &page[0];
+    assert!(
+      repo
+        .update_email_canonical(*user_id, email, "current+x@example.com")
+        .await
+        .unwrap()
+    );
+    assert_eq!(canonical.as_deref(), Some("current@example.com"));
+    assert_eq!(
+      stored_canonical(&pool, &user).await.as_deref(),
+      Some("current+x@example.com")
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn unknown_status_in_db_is_internal_error(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "corrupt_row", UserStatus::Active, UserRole::User).await;
+    sqlx::query!(
+      "UPDATE users SET status = 99 WHERE user_id = $1",
+      user.user_id.as_i64()
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let err = PgUserRepository::new(pool)
+      .find_by_public_id(&user.public_id)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, AppError::InternalServerError(Some(m)) if m.contains("99")));
+  }
+}
@@ -0,0 +1,177 @@
+//! PostgreSQL | verification_tokens テーブル メールアドレス確認用のトークン
+//! 使用済みの行は有効期限まで残し，確認リンクの二重クリックを判別できるようにする。
+//! 期限切れの行はメンテナンスで削除する。
+
+use crate::{
+  domain::{clock_skew::ClockSkew, value_obj::user_id::UserId},
+  infra::pg::user_repo::PgTx,
+  interfaces::http::error::{AppError, AppResult},
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// トークンの使用結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenOutcome {
+  /// 未使用のトークンを使用済みにした
+  Consumed(UserId),
+  /// 既に使用済み（二重クリック）
+  Reused(UserId),
+  /// 有効期限切れ
+  Expired,
+  /// 発行していないトークン
+  Unknown,
+}
+
+#[derive(Clone)]
+pub struct PgVerificationTokenStore {
+  pool: PgPool,
+}
+
+impl PgVerificationTokenStore {
+  pub fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  /// トークンを登録する
+  pub async fn issue(
+    &self,
+    token: &str,
+    user_id: UserId,
+    expires_at: DateTime<Utc>,
+  ) -> AppResult<()> {
+    sqlx::query!(
+      r#"INSERT INTO verification_tokens (token, user_id, expires_at) VALUES ($1, $2, $3)"#,
+      token,
+      user_id.as_i64(),
+      expires_at
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+  }
+
+  /// トランザクション内でトークンを使用済みにする。
+  /// (有効期限は`ClockSkew`の猶予を含めて判定する)
+  /// トランザクションがロールバックされた場合は未使用に戻る。
+  pub async fn consume_tx<'a>(
+    &self,
+    tx: &mut PgTx<'a>,
+    token: &str,
+    now: DateTime<Utc>,
+  ) -> AppResult<TokenOutcome> {
+    let consumed = sqlx::query_scalar!(
+      r#"UPDATE verification_tokens SET used_at = $2
+        WHERE token = $1 AND used_at IS NULL AND expires_at > $3
+        RETURNING user_id"#,
+      token,
+      now,
+      ClockSkew::current().expiry_cutoff(now)
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+    if let Some(user_id) = consumed {
+      return Ok(TokenOutcome::Consumed(UserId::from_db(user_id)?));
+    }
+
+    let row = sqlx::query!(
+      r#"SELECT user_id, used_at FROM verification_tokens WHERE token = $1"#,
+      token
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+    Ok(match row {
+      None => TokenOutcome::Unknown,
+      Some(r) if r.used_at.is_some() => TokenOutcome::Reused(UserId::from_db(r.user_id)?),
+      Some(_) => TokenOutcome::Expired,
+    })
+  }
+
+  /// 有効期限切れ（猶予を含む）のトークンを削除し，削除件数を返す
+  pub async fn purge_expired(&self, now: DateTime<Utc>) -> AppResult<u64> {
+    let result = sqlx::query!(
+      r#"DELETE FROM verification_tokens WHERE expires_at <= $1"#,
+      ClockSkew::current().expiry_cutoff(now)
+    )
+    .execute(&self.pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(result.rows_affected())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    domain::entity::user::{UserRole, UserStatus},
+    test_support::seed_user,
+  };
+  use chrono::Duration;
+
+  async fn consume(
+    store: &PgVerificationTokenStore,
+    token: &str,
+    now: DateTime<Utc>,
+  ) -> TokenOutcome {
+    let mut tx = store.pool.begin().await.unwrap();
+    let outcome = store.consume_tx(&mut tx, token, now).await.unwrap();
+    tx.commit().await.unwrap();
+    outcome
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn token_is_consumed_once(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "token_user", UserStatus::Pending, UserRole::User).await;
+    let store = PgVerificationTokenStore::new(pool);
+    let now = Utc::now();
+    store
+      .issue("t1", user.user_id, now + Duration::hours(1))
+      .await
+      .unwrap();
+
+    assert_eq!(
+      consume(&store, "t1", now).await,
+      TokenOutcome::Consumed(user.user_id)
+    );
+    assert_eq!(
+      consume(&store, "t1", now).await,
+      TokenOutcome::Reused(user.user_id)
+    );
+    assert_eq!(consume(&store, "t2", now).await, TokenOutcome::Unknown);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn expired_token_is_rejected_and_purged(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "token_expired", UserStatus::Pending, UserRole::User).await;
+    let store = PgVerificationTokenStore::new(pool);
+    let now = Utc::now();
+    let leeway = ClockSkew::current().leeway;
+    store
+      .issue("old", user.user_id, now - leeway - Duration::seconds(5))
+      .await
+      .unwrap();
+
+    assert_eq!(consume(&store, "old", now).await, TokenOutcome::Expired);
+    assert_eq!(store.purge_expired(now).await.unwrap(), 1);
+    assert_eq!(consume(&store, "old", now).await, TokenOutcome::Unknown);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn token_within_clock_skew_leeway_is_consumed(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "token_skew", UserStatus::Pending, UserRole::User).await;
+    let store = PgVerificationTokenStore::new(pool);
+    let now = Utc::now();
+    let expires_at = now - ClockSkew::current().leeway / 2;
+    store.issue("late", user.user_id, expires_at).await.unwrap();
+
+    assert_eq!(store.purge_expired(now).await.unwrap(), 0);
+    assert_eq!(
+      consume(&store, "late", now).await,
+      TokenOutcome::Consumed(user.user_id)
+    );
+  }
+}
@@ -0,0 +1,392 @@
+//! 認証・認可のエクストラクタ
+//! --------------------------------------------------------------
+//! ・`Authorization: Bearer <session_id>` からセッションを解決する
+//! ・`SessionToken` はセッションIDのみを取り出す（Cookieの`session_id`も受け付ける）
+//! ・パスワード変更のみに使用できるセッションは`PasswordChangeUser`でのみ受け付ける
+//! ・[auth].mode = "trusted_header" の場合，認証プロキシが付与したヘッダの公開IDで解決する
+//! ・`RequireRole<R>` で必要なロール以上であることを要求する
+//! --------------------------------------------------------------
+
+use crate::{
+  config::{Auth, AuthMode},
+  domain::{
+    clock_skew::ClockSkew,
+    entity::{
+      session::{Session, SessionScope},
+      user::{User, UserRole, UserStatus},
+    },
+    value_obj::{public_id::PublicId, session_id::SessionId},
+  },
+  infra::pg::{session_repo::PgSessionRepository, user_repo::PgUserRepository},
+  interfaces::http::error::{AppError, AppResult},
+};
+use axum::{
+  extract::{ConnectInfo, FromRequestParts},
+  http::{
+    HeaderValue,
+    header::{AUTHORIZATION, COOKIE},
+    request::Parts,
+  },
+};
+use chrono::Utc;
+use sqlx::PgPool;
+use std::{marker::PhantomData, net::SocketAddr};
+
+/// リクエストを送信した認証済みユーザー
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+  pub user: User,
+  /// 認証プロキシのヘッダで識別した場合はNone
+  pub session_id: Option<SessionId>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for CurrentUser {
+  type Rejection = AppError;
+
+  async fn from_request_parts(parts: &mut Parts, _state: &S) -> AppResult<Self> {
+    // レート制限で識別済みの場合は，同じリクエスト内で再度問い合わせない
+    if let Some(current) = parts.extensions.get::<Self>() {
+      return Ok(current.clone());
+    }
+    let pool = pool(parts)?;
+
+    // 認証プロキシからのリクエストの場合はヘッダの公開IDで識別する
+    if let Some(user) = trusted_user(parts, &pool).await? {
+      return Ok(Self {
+        user,
+        session_id: None,
+      });
+    }
+
+    let session_id = bearer_session_id(parts)?;
+    let (user, session) = resolve_session(&pool, session_id).await?;
+    if session.scope != SessionScope::Full {
+      return Err(password_change_required());
+    }
+    Ok(Self {
+      user,
+      session_id: Some(session.session_id),
+    })
+  }
+}
+
+/// パスワード変更のみに使用できるセッションも受け付ける認証済みユーザー
+/// (セッションIDはAuthorizationヘッダまたはCookieから取り出す)
+#[derive(Debug, Clone)]
+pub struct PasswordChangeUser {
+  pub current: CurrentUser,
+  /// 認証プロキシのヘッダで識別した場合はFull
+  pub scope: SessionScope,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for PasswordChangeUser {
+  type Rejection = AppError;
+
+  async fn from_request_parts(parts: &mut Parts, state: &S) -> AppResult<Self> {
+    // 通常のセッションで識別済みの場合は，同じリクエスト内で再度問い合わせない
+    if let Some(current) = parts.extensions.get::<CurrentUser>() {
+      return Ok(Self {
+        current: current.clone(),
+        scope: SessionScope::Full,
+      });
+    }
+    let pool = pool(parts)?;
+
+    if let Some(user) = trusted_user(parts, &pool).await? {
+      return Ok(Self {
+        current: CurrentUser {
+          user,
+          session_id: None,
+        },
+        scope: SessionScope::Full,
+      });
+    }
+
+    let SessionToken(session_id) = SessionToken::from_request_parts(parts, state).await?;
+    let (user, session) = resolve_session(&pool, session_id).await?;
+    Ok(Self {
+      current: CurrentUser {
+        user,
+        session_id: Some(session.session_id),
+      },
+      scope: session.scope,
+    })
+  }
+}
+
+/// リクエストに付与されたセッションID
+/// セッションの存在・有効期限は確認しない。(ログアウト等，セッション自体を操作する場合に使用する)
+#[derive(Debug, Clone)]
+pub struct SessionToken(pub SessionId);
+
+impl<S: Send + Sync> FromRequestParts<S> for SessionToken {
+  type Rejection = AppError;
+
+  async fn from_request_parts(parts: &mut Parts, _state: &S) -> AppResult<Self> {
+    // Authorizationヘッダを優先し，無い場合はCookieから取り出す
+    if parts.headers.contains_key(AUTHORIZATION) {
+      return bearer_session_id(parts).map(Self);
+    }
+    let token = cookie_session_id(parts).ok_or_else(unauthorized)?;
+    Ok(Self(
+      SessionId::from_string(token, true)?.ok_or_else(unauthorized)?,
+    ))
+  }
+}
+
+/// `RequireRole<R>`で要求するロールを型で表す。
+pub trait RoleBound: Send + Sync {
+  const ROLE: UserRole;
+}
+
+/// Support以上
+pub struct Support;
+impl RoleBound for Support {
+  const ROLE: UserRole = UserRole::Support;
+}
+
+/// Moderator以上
+pub struct Moderator;
+impl RoleBound for Moderator {
+  const ROLE: UserRole = UserRole::Moderator;
+}
+
+/// Admin以上
+pub struct Admin;
+impl RoleBound for Admin {
+  const ROLE: UserRole = UserRole::Admin;
+}
+
+/// ロール`R`以上の認証済みユーザーのみ通過させるエクストラクタ
+pub struct RequireRole<R: RoleBound>(pub CurrentUser, pub PhantomData<R>);
+
+impl<S: Send + Sync, R: RoleBound> FromRequestParts<S> for RequireRole<R> {
+  type Rejection = AppError;
+
+  async fn from_request_parts(parts: &mut Parts, state: &S) -> AppResult<Self> {
+    let current = CurrentUser::from_request_parts(parts, state).await?;
+    if current.user.role < R::ROLE {
+      return Err(AppError::Forbidden(Some(
+        "この操作を行う権限がありません。".into(),
+      )));
+    }
+    Ok(Self(current, PhantomData))
+  }
+}
+
+fn pool(parts: &Parts) -> AppResult<PgPool> {
+  parts
+    .extensions
+    .get::<PgPool>()
+    .cloned()
+    .ok_or_else(|| AppError::InternalServerError(Some("PgPool extension missing".into())))
+}
+
+/// 信頼する認証プロキシからのリクエストの場合，ヘッダの公開IDで有効なユーザーを返す。
+async fn trusted_user(parts: &mut Parts, pool: &PgPool) -> AppResult<Option<User>> {
+  let Some(public_id) = trusted_public_id(parts).await? else {
+    return Ok(None);
+  };
+  PgUserRepository::new(pool.clone())
+    .find_by_public_id(&public_id)
+    .await?
+    .filter(|u| u.status == UserStatus::Active)
+    .map(Some)
+    .ok_or_else(unauthorized)
+}
+
+/// セッションIDから有効なセッションとユーザーを解決する。
+/// セッションが存在しない・有効期限切れ・有効なユーザーが存在しない場合は401
+async fn resolve_session(pool: &PgPool, session_id: SessionId) -> AppResult<(User, Session)> {
+  let session = PgSessionRepository::new(pool.clone())
+    .find(session_id)
+    .await?
+    .filter(|s| !ClockSkew::current().is_expired(s.expires_at, Utc::now()))
+    .ok_or_else(unauthorized)?;
+  let user = PgUserRepository::new(pool.clone())
+    .find_by_user_id(session.user_id)
+    .await?
+    .ok_or_else(unauthorized)?;
+  Ok((user, session))
+}
+
+/// 信頼する認証プロキシからのリクエストの場合，ヘッダの公開IDを返す。
+/// trusted_header以外のモード・プロキシ以外の接続元・ヘッダ無しの場合はNone
+async fn trusted_public_id(parts: &mut Parts) -> AppResult<Option<PublicId>> {
+  let Some(auth) = parts
+    .extensions
+    .get::<Auth>()
+    .filter(|a| a.mode == AuthMode::TrustedHeader)
+    .cloned()
+  else {
+    return Ok(None);
+  };
+  let trusted = ConnectInfo::<SocketAddr>::from_request_parts(parts, &())
+    .await
+    .is_ok_and(|ConnectInfo(peer)| auth.trusted_proxies.contains(&peer.ip()));
+  if !trusted {
+    return Ok(None);
+  }
+  match parts
+    .headers
+    .get(auth.trusted_header.as_str())
+    .map(|v| v.to_str())
+  {
+    None => Ok(None),
+    Some(Ok(value)) => Ok(Some(
+      PublicId::from_string(value, true)?.ok_or_else(unauthorized)?,
+    )),
+    Some(Err(_)) => Err(unauthorized()),
+  }
+}
+
+/// `Authorization: Bearer <session_id>`からセッションIDを取り出す。
+fn bearer_session_id(parts: &Parts) -> AppResult<SessionId> {
+  let value = parts
+    .headers
+    .get(AUTHORIZATION)
+    .and_then(|v| v.to_str().ok())
+    .ok_or_else(unauthorized)?;
+
+  let token = value.strip_prefix("Bearer ").ok_or_else(unauthorized)?;
+  SessionId::from_string(token, true)?.ok_or_else(unauthorized)
+}
+
+/// セッションIDを保持するCookieの名前
+pub const SESSION_COOKIE: &str = "session_id";
+
+/// セッションIDを渡すCookieの属性
+/// `Path`はbase_pathの配下に限定する（未設定の場合は`/`）
+#[derive(Debug, Clone)]
+pub struct SessionCookie {
+  path: String,
+}
+
+impl SessionCookie {
+  pub fn new(base_path: &str) -> Self {
+    let path = if base_path.is_empty() { "/" } else { base_path };
+    Self {
+      path: path.to_owned(),
+    }
+  }
+
+  /// `Set-Cookie`ヘッダの値を生成する。
+  pub fn header(&self, session_id: &str, max_age: i64) -> AppResult<HeaderValue> {
+    let cookie = format!(
+      "{SESSION_COOKIE}={session_id}; Path={}; Max-Age={max_age}; HttpOnly; Secure; SameSite=Strict",
+      self.path
+    );
+    HeaderValue::from_str(&cookie).map_err(|e| AppError::InternalServerError(Some(e.to_string())))
+  }
+}
+
+/// `Cookie: session_id=<session_id>`からセッションIDの文字列を取り出す。
+fn cookie_session_id(parts: &Parts) -> Option<&str> {
+  parts
+    .headers
+    .get_all(COOKIE)
+    .iter()
+    .filter_map(|v| v.to_str().ok())
+    .flat_map(|v| v.split(';'))
+    .filter_map(|pair| pair.trim().split_once('='))
+    .find_map(|(name, value)| (name == SESSION_COOKIE).then_some(value))
+}
+
+fn unauthorized() -> AppError {
+  AppError::Unauthorized(Some("認証が必要です。".into()))
+}
+
+/// パスワード変更のみに使用できるセッションで，他の操作を行おうとした場合のエラー
+pub fn password_change_required() -> AppError {
+  AppError::Forbidden(Some("PASSWORD_CHANGE_REQUIRED".into()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_support::seed_user;
+  use axum::{
+    Extension, Router,
+    body::{Body, to_bytes},
+    extract::{Request, connect_info::MockConnectInfo},
+    http::StatusCode,
+    routing::get,
+  };
+  use std::net::{IpAddr, Ipv4Addr};
+  use tower::ServiceExt;
+
+  const PROXY: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5));
+
+  fn app(pool: PgPool, peer: IpAddr) -> Router {
+    Router::new()
+      .route(
+        "/me",
+        get(|current: CurrentUser| async move { current.user.user_name.as_str().to_owned() }),
+      )
+      .layer(Extension(pool))
+      .layer(Extension(Auth {
+        mode: AuthMode::TrustedHeader,
+        trusted_header: "x-auth-user".into(),
+        trusted_proxies: vec![PROXY],
+      }))
+      .layer(MockConnectInfo(SocketAddr::new(peer, 50000)))
+  }
+
+  async fn me(app: Router, public_id: &PublicId) -> (StatusCode, String) {
+    let req = Request::get("/me")
+      .header("x-auth-user", public_id.as_str())
+      .body(Body::empty())
+      .unwrap();
+    let res = app.oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn trusted_header_is_honored_from_trusted_proxy(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "proxy_user", UserStatus::Active, UserRole::User).await;
+    let (status, body) = me(app(pool, PROXY), &user.public_id).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "proxy_user");
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn trusted_header_is_ignored_from_untrusted_peer(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "spoofed_user", UserStatus::Active, UserRole::User).await;
+    let untrusted = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+    let (status, _) = me(app(pool, untrusted), &user.public_id).await;
+    // ヘッダは無視され，セッションが無いため401
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+  }
+
+  async fn token(header: Option<(&str, String)>) -> (StatusCode, String) {
+    let app = Router::new().route(
+      "/token",
+      get(|SessionToken(sid): SessionToken| async move { sid.to_string() }),
+    );
+    let mut req = Request::get("/token");
+    if let Some((name, value)) = header {
+      req = req.header(name, value);
+    }
+    let res = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+    let status = res.status();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+  }
+
+  #[tokio::test]
+  async fn session_token_from_header_or_cookie() {
+    let sid = SessionId::new();
+    for header in [
+      ("authorization", format!("Bearer {sid}")),
+      ("cookie", format!("theme=dark; {SESSION_COOKIE}={sid}")),
+    ] {
+      assert_eq!(token(Some(header)).await, (StatusCode::OK, sid.to_string()));
+    }
+    let (status, _) = token(None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = token(Some(("cookie", "theme=dark".into()))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+  }
+}
@@ -0,0 +1,84 @@
+//! リクエスト元の端末情報のエクストラクタ
+//! --------------------------------------------------------------
+//! ・`User-Agent` ヘッダを保存用に整形して取り出す
+//! ・接続元IPアドレスを `ConnectInfo` から取り出す
+//! --------------------------------------------------------------
+
+use crate::domain::entity::session::Session;
+use axum::{
+  extract::{ConnectInfo, FromRequestParts},
+  http::{header::USER_AGENT, request::Parts},
+};
+use std::{
+  convert::Infallible,
+  net::{IpAddr, SocketAddr},
+};
+
+/// リクエスト元の端末情報（取得できない項目はNone）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientInfo {
+  pub user_agent: Option<String>,
+  pub ip: Option<IpAddr>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+  type Rejection = Infallible;
+
+  async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+    let user_agent = parts
+      .headers
+      .get(USER_AGENT)
+      .and_then(|v| v.to_str().ok())
+      .and_then(Session::sanitize_user_agent);
+    // ConnectInfoが無い場合（テスト等）はNoneとする
+    let ip = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+      .await
+      .ok()
+      .map(|ConnectInfo(addr)| addr.ip());
+    Ok(Self { user_agent, ip })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{
+    Router,
+    body::{Body, to_bytes},
+    extract::connect_info::MockConnectInfo,
+    http::Request,
+    routing::get,
+  };
+  use tower::ServiceExt;
+
+  async fn describe(client: ClientInfo) -> String {
+    format!("{:?}|{:?}", client.user_agent, client.ip)
+  }
+
+  async fn call(app: Router, user_agent: Option<&str>) -> String {
+    let mut req = Request::get("/");
+    if let Some(ua) = user_agent {
+      req = req.header(USER_AGENT, ua);
+    }
+    let res = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+  }
+
+  #[tokio::test]
+  async fn extracts_user_agent_and_ip() {
+    let app = Router::new()
+      .route("/", get(describe))
+      .layer(MockConnectInfo(SocketAddr::from(([192, 0, 2, 10], 50000))));
+    assert_eq!(
+      call(app, Some("curl/8.0")).await,
+      r#"Some("curl/8.0")|Some(192.0.2.10)"#
+    );
+  }
+
+  #[tokio::test]
+  async fn missing_metadata_is_none() {
+    let app = Router::new().route("/", get(describe));
+    assert_eq!(call(app, None).await, "None|None");
+  }
+}
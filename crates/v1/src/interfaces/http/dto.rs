@@ -1,5 +1,13 @@
 /// APIレスポンスの標準フォーマットを定義する。
+use crate::config::App;
+use axum::{
+  Json,
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use chrono::Utc;
 use serde::Serialize;
+use std::sync::OnceLock;
 
 /// 正常時のレスポンス構造体。
 #[derive(Debug, Serialize)]
@@ -12,9 +20,75 @@ pub struct ApiResponse<T> {
   pub timestamp: i64,
 }
 
+/// 正常時のレスポンスの形式
+/// `envelope`がtrueの場合は`ApiResponse`で包み，falseの場合はデータのみを返す。
+/// (エラーレスポンスは常に`ApiError`の形式で返す)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResponseFormat {
+  pub envelope: bool,
+}
+
+/// 起動時に設定したレスポンスの形式
+static RESPONSE_FORMAT: OnceLock<ResponseFormat> = OnceLock::new();
+
+impl ResponseFormat {
+  /// 既定値(データのみを返す)
+  const DEFAULT: ResponseFormat = ResponseFormat { envelope: false };
+
+  /// Configの[app]から生成する。
+  pub fn from_config(config: &App) -> Self {
+    Self {
+      envelope: config.response_envelope,
+    }
+  }
+
+  /// アプリケーション全体の形式として設定する。
+  /// (2回目以降の呼び出しは無視される)
+  pub fn install(self) {
+    let _ = RESPONSE_FORMAT.set(self);
+  }
+
+  /// 設定済みの形式を返す。(未設定の場合は既定値)
+  pub fn current() -> &'static ResponseFormat {
+    RESPONSE_FORMAT.get().unwrap_or(&Self::DEFAULT)
+  }
+
+  /// データをこの形式のレスポンスに変換する。
+  pub fn render<T: Serialize>(&self, data: T) -> Response {
+    if !self.envelope {
+      return Json(data).into_response();
+    }
+    Json(ApiResponse {
+      data,
+      message: StatusCode::OK.canonical_reason().unwrap_or("OK").to_owned(),
+      timestamp: Utc::now().timestamp(),
+    })
+    .into_response()
+  }
+}
+
+/// 正常時のレスポンス。起動時に設定した形式で返す。
+#[derive(Debug)]
+pub struct ApiJson<T>(pub T);
+
+impl<T: Serialize> IntoResponse for ApiJson<T> {
+  fn into_response(self) -> Response {
+    ResponseFormat::current().render(self.0)
+  }
+}
+
+/// 正常時のレスポンスを生成する。
+pub fn ok<T: Serialize>(data: T) -> ApiJson<T> {
+  ApiJson(data)
+}
+
 /// エラーレスポンス構造体。
-#[derive(Debug, Serialize)]
+/// ([app].problem_jsonがtrueの場合は，RFC 7807の`type`・`instance`を設定して返す)
+#[derive(Debug, Clone, Serialize)]
 pub struct ApiError {
+  /// エラーの種類を示すURI（Problem Detailsの形式で返す場合のみ）。
+  #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+  pub problem_type: Option<String>,
   /// エラーに対応するHTTPステータスコード。
   pub status: u16,
   /// エラーの簡潔な要約。
@@ -27,4 +101,48 @@ pub struct ApiError {
   pub instance: Option<String>,
   /// エラーレスポンスが生成された時刻（UNIXタイムスタンプ）。
   pub timestamp: i64,
+  /// エラーを返したインスタンスの識別子（5xxかつ設定時のみ）。
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub instance_id: Option<String>,
+  /// エラーを返したインスタンスのリージョン（5xxかつ設定時のみ）。
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub region: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{application::user::dto::RegisterResponse, utils::randomart::RandomartBody};
+  use axum::body::to_bytes;
+
+  fn register_response() -> RegisterResponse {
+    RegisterResponse {
+      public_id: "pid".into(),
+      randomart: RandomartBody::Text("art".into()),
+    }
+  }
+
+  async fn body(res: Response) -> serde_json::Value {
+    assert_eq!(res.status(), StatusCode::OK);
+    let bytes = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+  }
+
+  #[tokio::test]
+  async fn bare_format_returns_payload_only() {
+    let v = body(ResponseFormat { envelope: false }.render(register_response())).await;
+    assert_eq!(
+      v,
+      serde_json::json!({"public_id": "pid", "randomart": "art"})
+    );
+  }
+
+  #[tokio::test]
+  async fn envelope_format_wraps_payload() {
+    let v = body(ResponseFormat { envelope: true }.render(register_response())).await;
+    assert_eq!(v["data"]["public_id"], "pid");
+    assert_eq!(v["data"]["randomart"], "art");
+    assert_eq!(v["message"], "OK");
+    assert!(v["timestamp"].is_i64());
+  }
 }
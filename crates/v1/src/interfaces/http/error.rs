@@ -1,10 +1,21 @@
 //! HTTPレイヤ専用の上位Error型・Result型及び変換ロジック
 
-use super::dto::ApiError;
+use super::{
+  dto::ApiError,
+  middleware::request_id::{self, RequestId},
+};
+use crate::{
+  domain::entity::user::LoginDenyReason,
+  utils::{logger::instance_tags, shutdown},
+};
 use AppError::*;
 use axum::{
   Json,
-  http::StatusCode,
+  extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+  http::{
+    HeaderValue, Method, StatusCode,
+    header::{ALLOW, RETRY_AFTER},
+  },
   response::{IntoResponse, Response},
 };
 use chrono::Utc;
@@ -16,12 +27,17 @@ use tracing as log;
 /// プロジェクト全体で使用するResult型
 pub type AppResult<T> = Result<T, AppError>;
 
+/// 503を返す際にRetry-Afterで示す再試行までの秒数
+const RETRY_AFTER_SECS: u64 = 5;
+
 /// SQLSTATE（PostgreSQL）
 mod sqlstate {
   pub const UNIQUE_VIOLATION: &str = "23505";
   pub const FK_VIOLATION: &str = "23503";
   pub const NOT_NULL_VIOLATION: &str = "23502";
   pub const CHECK_VIOLATION: &str = "23514";
+  /// statement_timeoutによる中断
+  pub const QUERY_CANCELED: &str = "57014";
 }
 
 /// HTTP レイヤの上位エラー
@@ -36,16 +52,30 @@ pub enum AppError {
   Forbidden(Option<String>),
   #[error("Not Found")]
   NotFound(Option<String>),
+  #[error("Method Not Allowed")]
+  MethodNotAllowed(Option<String>),
   #[error("Request Timeout")]
   RequestTimeout(Option<String>),
   #[error("Conflict")]
   Conflict(Option<String>),
+  #[error("Precondition Failed")]
+  PreconditionFailed(Option<String>),
+  #[error("Payload Too Large")]
+  PayloadTooLarge(Option<String>),
+  #[error("URI Too Long")]
+  UriTooLong(Option<String>),
+  #[error("Unsupported Media Type")]
+  UnsupportedMediaType(Option<String>),
   #[error("I'm a Teapot")]
   ImATeapot(Option<String>),
   #[error("Unprocessable Content")]
   UnprocessableContent(Option<String>),
+  #[error("Too Many Requests")]
+  TooManyRequests(Option<String>),
   #[error("Internal Server Error")]
   InternalServerError(Option<String>),
+  #[error("Service Unavailable")]
+  ServiceUnavailable(Option<String>),
 }
 
 impl AppError {
@@ -56,11 +86,18 @@ impl AppError {
       Unauthorized(_) => StatusCode::UNAUTHORIZED,
       Forbidden(_) => StatusCode::FORBIDDEN,
       NotFound(_) => StatusCode::NOT_FOUND,
+      MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
       RequestTimeout(_) => StatusCode::REQUEST_TIMEOUT,
       Conflict(_) => StatusCode::CONFLICT,
+      PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+      PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+      UriTooLong(_) => StatusCode::URI_TOO_LONG,
+      UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
       ImATeapot(_) => StatusCode::IM_A_TEAPOT,
       UnprocessableContent(_) => StatusCode::UNPROCESSABLE_ENTITY,
+      TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
       InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+      ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
     }
   }
 
@@ -71,12 +108,48 @@ impl AppError {
       | Unauthorized(d)
       | Forbidden(d)
       | NotFound(d)
+      | MethodNotAllowed(d)
       | RequestTimeout(d)
       | Conflict(d)
+      | PreconditionFailed(d)
+      | PayloadTooLarge(d)
+      | UriTooLong(d)
+      | UnsupportedMediaType(d)
       | ImATeapot(d)
       | UnprocessableContent(d)
-      | InternalServerError(d) => d.as_ref(),
+      | TooManyRequests(d)
+      | InternalServerError(d)
+      | ServiceUnavailable(d) => d.as_ref(),
+    }
+  }
+
+  /// 許可するメソッドをAllowヘッダに設定したレスポンスに変換する。
+  /// (405以外のエラーにはAllowヘッダを付与しない)
+  pub fn into_response_with_allow(self, methods: &[Method]) -> Response {
+    let is_method_not_allowed = matches!(self, MethodNotAllowed(_));
+    let mut response = self.into_response();
+    let allow = methods
+      .iter()
+      .map(Method::as_str)
+      .collect::<Vec<_>>()
+      .join(",");
+    if is_method_not_allowed && let Ok(v) = HeaderValue::from_str(&allow) {
+      response.headers_mut().insert(ALLOW, v);
     }
+    response
+  }
+
+  /// 再試行までの秒数をRetry-Afterヘッダに設定したレスポンスに変換する。
+  /// (429以外のエラーにはRetry-Afterヘッダを付与しない)
+  pub fn into_response_with_retry_after(self, secs: u64) -> Response {
+    let is_too_many_requests = matches!(self, TooManyRequests(_));
+    let mut response = self.into_response();
+    if is_too_many_requests {
+      response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(secs));
+    }
+    response
   }
 }
 
@@ -86,17 +159,21 @@ impl IntoResponse for AppError {
     let status = self.status_code();
 
     // ログを出力する。
-    // (500系はError, それ以外はWarn)
+    // (500系はError, それ以外はWarn。リクエストIDはミドルウェアの内側でのみ付与される)
+    let request_id = request_id::current();
+    let request_id = request_id.as_ref().map(RequestId::as_str);
     if status.is_server_error() {
-      log::error!(error=?self, "Internal server error");
+      log::error!(error=?self, request_id, "Internal server error");
     } else {
-      log::warn!(error=?self, "Client error");
+      log::warn!(error=?self, request_id, "Client error");
     }
 
     // Statusに応じてResponseBodyを構築する。
-    // （500系にはDetailを含めない。）
+    // （500系にはDetailを含めず，インスタンス情報を含める。）
     let body = if status.is_server_error() {
+      let tags = instance_tags();
       ApiError {
+        problem_type: None,
         status: status.as_u16(),
         message: status
           .canonical_reason()
@@ -105,18 +182,32 @@ impl IntoResponse for AppError {
         detail: None,
         instance: None,
         timestamp: Utc::now().timestamp(),
+        instance_id: tags.and_then(|t| t.instance_id.clone()),
+        region: tags.and_then(|t| t.region.clone()),
       }
     } else {
       ApiError {
+        problem_type: None,
         status: status.as_u16(),
         message: status.canonical_reason().unwrap_or("Error").to_string(),
         detail: self.detail().cloned(),
         instance: None,
         timestamp: Utc::now().timestamp(),
+        instance_id: None,
+        region: None,
       }
     };
 
-    (status, Json(body)).into_response()
+    // 503は一時的な状態のため，再試行までの目安を示す
+    // (本文はProblem Detailsの形式に置き換えられるよう拡張にも保持する)
+    let mut response = (status, Json(body.clone())).into_response();
+    response.extensions_mut().insert(body);
+    if status == StatusCode::SERVICE_UNAVAILABLE {
+      response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+    }
+    response
   }
 }
 
@@ -134,9 +225,82 @@ impl From<&str> for AppError {
   }
 }
 
+impl From<LoginDenyReason> for AppError {
+  /// ステータスによるログイン拒否をAppErrorに変換する。
+  /// - 未確認・利用停止中：403（Detailにエラーコードを設定する）
+  /// - 無効化・削除済み：401（認証失敗と区別しない）
+  fn from(reason: LoginDenyReason) -> Self {
+    match reason.code() {
+      Some(code) => Forbidden(Some(code.to_owned())),
+      None => Unauthorized(Some(
+        "ユーザー名またはパスワードが正しくありません。".into(),
+      )),
+    }
+  }
+}
+
+impl From<JsonRejection> for AppError {
+  /// JSONボディの読み込みエラーをAppErrorに変換する。
+  /// - 不明なフィールド：400（Detailにフィールド名を設定する）
+  /// - 型・必須項目の不一致：422
+  /// - JSONの構文エラー：400
+  /// - Content-Typeの不一致：415
+  fn from(rejection: JsonRejection) -> Self {
+    match rejection {
+      JsonRejection::JsonDataError(e) => {
+        let text = e.body_text();
+        match unknown_field(&text) {
+          Some(field) => BadRequest(Some(format!("不明なフィールド`{field}`が含まれています。"))),
+          None => UnprocessableContent(Some(text)),
+        }
+      }
+      JsonRejection::JsonSyntaxError(_) => {
+        BadRequest(Some("リクエストボディが正しいJSONではありません。".into()))
+      }
+      JsonRejection::MissingJsonContentType(_) => UnsupportedMediaType(Some(
+        "Content-Typeはapplication/jsonである必要があります。".into(),
+      )),
+      e if e.status() == StatusCode::PAYLOAD_TOO_LARGE => PayloadTooLarge(Some(e.body_text())),
+      e => BadRequest(Some(e.body_text())),
+    }
+  }
+}
+
+/// serdeのエラーメッセージから不明なフィールド名を取り出す。
+fn unknown_field(message: &str) -> Option<&str> {
+  let (_, rest) = message.split_once("unknown field `")?;
+  rest.split_once('`').map(|(field, _)| field)
+}
+
+impl From<QueryRejection> for AppError {
+  /// クエリ文字列の解析エラーを400に変換する。
+  fn from(rejection: QueryRejection) -> Self {
+    BadRequest(Some(rejection.body_text()))
+  }
+}
+
+impl From<PathRejection> for AppError {
+  /// パスパラメータの解析エラーを400に変換する。
+  /// (ルート定義とハンドラの不整合によるエラーは500とする)
+  fn from(rejection: PathRejection) -> Self {
+    match rejection {
+      PathRejection::FailedToDeserializePathParams(e) => BadRequest(Some(e.body_text())),
+      other => InternalServerError(Some(other.body_text())),
+    }
+  }
+}
+
 impl From<SqlxError> for AppError {
   /// SqlxのエラーをAppErrorに変換する。
   fn from(err: SqlxError) -> Self {
+    Self::from_sqlx(err, shutdown::is_shutting_down())
+  }
+}
+
+impl AppError {
+  /// SqlxのエラーをAppErrorに変換する。
+  /// シャットダウン中にプールが閉じられた場合は，タイムアウトではなく503とする。
+  fn from_sqlx(err: SqlxError, shutting_down: bool) -> Self {
     match err {
       SqlxError::RowNotFound => NotFound(Some("Resource not found".into())),
       SqlxError::PoolTimedOut => RequestTimeout(Some("Database timeout".into())),
@@ -147,13 +311,28 @@ impl From<SqlxError> for AppError {
         | Some(Cow::Borrowed(sqlstate::CHECK_VIOLATION)) => {
           Conflict(Some("Integrity violation".into()))
         }
+        Some(Cow::Borrowed(sqlstate::QUERY_CANCELED)) => {
+          RequestTimeout(Some("Database timeout".into()))
+        }
         _code => InternalServerError(Some("Database internal error".into())),
       },
       // 型ごとに判定できる場合は，文字列化せずに判定する
       SqlxError::Io(ref io_err) if io_err.kind() == std::io::ErrorKind::TimedOut => {
         RequestTimeout(Some("Database timeout".into()))
       }
+      SqlxError::PoolClosed if shutting_down => {
+        ServiceUnavailable(Some("Server is shutting down".into()))
+      }
       SqlxError::PoolClosed => RequestTimeout(Some("Database pool closed".into())),
+      // 列の型・NULL許容の不一致はスキーマとコードの乖離を示すため，列名を記録する
+      SqlxError::ColumnDecode { index, source } => {
+        log::error!(column = %index, error = %source, "Failed to decode column (schema drift?)");
+        InternalServerError(Some(format!("DB column decode error: {index}: {source}")))
+      }
+      SqlxError::ColumnNotFound(column) => {
+        log::error!(column = %column, "Column not found (schema drift?)");
+        InternalServerError(Some(format!("DB column not found: {column}")))
+      }
       e => {
         let msg = e.to_string();
         // msgに"timeout"が含まれていれば408エラー。
@@ -191,11 +370,31 @@ mod tests {
       AppError::NotFound(None).status_code(),
       StatusCode::NOT_FOUND
     );
+    assert_eq!(
+      AppError::MethodNotAllowed(None).status_code(),
+      StatusCode::METHOD_NOT_ALLOWED
+    );
     assert_eq!(
       AppError::RequestTimeout(None).status_code(),
       StatusCode::REQUEST_TIMEOUT
     );
     assert_eq!(AppError::Conflict(None).status_code(), StatusCode::CONFLICT);
+    assert_eq!(
+      AppError::PreconditionFailed(None).status_code(),
+      StatusCode::PRECONDITION_FAILED
+    );
+    assert_eq!(
+      AppError::UriTooLong(None).status_code(),
+      StatusCode::URI_TOO_LONG
+    );
+    assert_eq!(
+      AppError::PayloadTooLarge(None).status_code(),
+      StatusCode::PAYLOAD_TOO_LARGE
+    );
+    assert_eq!(
+      AppError::UnsupportedMediaType(None).status_code(),
+      StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
     assert_eq!(
       AppError::ImATeapot(None).status_code(),
       StatusCode::IM_A_TEAPOT
@@ -204,10 +403,29 @@ mod tests {
       AppError::UnprocessableContent(None).status_code(),
       StatusCode::UNPROCESSABLE_ENTITY
     );
+    assert_eq!(
+      AppError::TooManyRequests(None).status_code(),
+      StatusCode::TOO_MANY_REQUESTS
+    );
     assert_eq!(
       AppError::InternalServerError(None).status_code(),
       StatusCode::INTERNAL_SERVER_ERROR
     );
+    assert_eq!(
+      AppError::ServiceUnavailable(None).status_code(),
+      StatusCode::SERVICE_UNAVAILABLE
+    );
+  }
+
+  #[test]
+  // 405に許可するメソッドがAllowヘッダとして付与されるか。
+  fn test_method_not_allowed_allow_header() {
+    let res = MethodNotAllowed(None).into_response_with_allow(&[Method::GET, Method::HEAD]);
+    assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(res.headers()[ALLOW], "GET,HEAD");
+
+    let res = BadRequest(None).into_response_with_allow(&[Method::GET]);
+    assert!(!res.headers().contains_key(ALLOW));
   }
 
   #[test]
@@ -239,4 +457,92 @@ mod tests {
       _ => panic!("Expected RequestTimeout variant"),
     }
   }
+
+  #[test]
+  // 列のデコード失敗は列名を含む専用のメッセージになるか。
+  fn test_from_sqlx_column_decode() {
+    let err = SqlxError::ColumnDecode {
+      index: "\"email\"".into(),
+      source: "unexpected null; try decoding as an `Option`".into(),
+    };
+    match AppError::from(err) {
+      InternalServerError(Some(msg)) => {
+        assert!(
+          msg.starts_with("DB column decode error: \"email\""),
+          "{msg}"
+        );
+        assert!(msg.contains("unexpected null"), "{msg}");
+      }
+      other => panic!("Expected InternalServerError variant, got {other:?}"),
+    }
+  }
+
+  #[test]
+  // 存在しない列は列名を含む専用のメッセージになるか。
+  fn test_from_sqlx_column_not_found() {
+    let err = SqlxError::ColumnNotFound("recovery_email".into());
+    match AppError::from(err) {
+      InternalServerError(Some(msg)) => assert_eq!(msg, "DB column not found: recovery_email"),
+      other => panic!("Expected InternalServerError variant, got {other:?}"),
+    }
+    // 応答には詳細を含めない
+    let res = AppError::from(SqlxError::ColumnNotFound("x".into())).into_response();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  // シャットダウン中に閉じられたプールは503とRetry-Afterを返すか。
+  async fn test_pool_closed_during_shutdown(pool: sqlx::PgPool) {
+    pool.close().await;
+    let closed = || async {
+      sqlx::query("SELECT 1")
+        .execute(&pool)
+        .await
+        .map(|_| ())
+        .unwrap_err()
+    };
+
+    let err = AppError::from_sqlx(closed().await, true);
+    assert!(matches!(err, ServiceUnavailable(_)));
+    let res = err.into_response();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.headers()[RETRY_AFTER], RETRY_AFTER_SECS.to_string());
+
+    // シャットダウン中でなければ従来どおりタイムアウトとして扱う
+    let err = AppError::from_sqlx(closed().await, false);
+    assert!(matches!(err, RequestTimeout(_)));
+    assert!(!err.into_response().headers().contains_key(RETRY_AFTER));
+  }
+
+  #[test]
+  // ログイン拒否理由ごとに適切なエラーへ変換されるか。
+  fn test_login_deny_reason_mapping() {
+    let err = AppError::from(LoginDenyReason::EmailUnverified);
+    assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+    assert_eq!(err.detail().map(String::as_str), Some("EMAIL_UNVERIFIED"));
+
+    let err = AppError::from(LoginDenyReason::Suspended);
+    assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+    assert_eq!(err.detail().map(String::as_str), Some("SUSPENDED"));
+
+    let err = AppError::from(LoginDenyReason::Inactive);
+    assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+  }
+
+  #[tokio::test]
+  // 412はクライアントエラーとして扱い，Detailをレスポンスに含めるか。
+  async fn test_precondition_failed_response() {
+    let err = PreconditionFailed(Some("ETag mismatch".into()));
+    assert!(!err.status_code().is_server_error());
+    assert_eq!(err.detail().map(String::as_str), Some("ETag mismatch"));
+
+    let res = err.into_response();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["status"], 412);
+    assert_eq!(v["detail"], "ETag mismatch");
+  }
 }
@@ -0,0 +1,148 @@
+//! HTTP ハンドラ ― 管理者向け
+
+use crate::{
+  application::{
+    admin::{
+      dto::{
+        AuthMetaView, BulkStatusReport, BulkStatusRequest, MaintenanceBatchQuery,
+        RandomartVerifyEntry, RandomartVerifyResult, RequirePasswordChangeResponse,
+        SessionListQuery, SessionPage, UnlockResponse,
+      },
+      service::AdminService,
+    },
+    maintenance::service::{EmailCanonicalReport, MaintenanceService, RandomartReport},
+  },
+  infra::pg::deadline::Deadline,
+  interfaces::http::{
+    auth::{Admin, Moderator, RequireRole, Support},
+    dto::{ApiJson, ok},
+    error::AppResult,
+    handler::parse_public_id,
+    json::ValidatedJson,
+    middleware::transaction::RequestTx,
+    pagination::Paginated,
+  },
+};
+use axum::extract::{
+  Extension, OriginalUri, Path, Query,
+  rejection::{PathRejection, QueryRejection},
+};
+
+/// GET /admin/users/{public_id}/auth
+/// 認証メタデータ（ハッシュ値を除く）を返す
+pub async fn auth_meta_handler(
+  _: RequireRole<Support>,
+  Extension(service): Extension<AdminService>,
+  path: Result<Path<String>, PathRejection>,
+) -> AppResult<ApiJson<AuthMetaView>> {
+  let public_id = parse_public_id(path)?;
+  let response = service.auth_meta(&public_id).await?;
+  Ok(ok(response))
+}
+
+/// POST /admin/users/{public_id}/unlock
+/// ログイン失敗回数をリセットし，リセット前の回数を返す
+/// (リクエスト単位のトランザクションで更新する)
+pub async fn unlock_handler(
+  RequireRole(actor, _): RequireRole<Support>,
+  Extension(service): Extension<AdminService>,
+  tx: RequestTx,
+  path: Result<Path<String>, PathRejection>,
+) -> AppResult<ApiJson<UnlockResponse>> {
+  let public_id = parse_public_id(path)?;
+  let response = service
+    .unlock(&mut *tx.lock().await?, &actor.user, &public_id)
+    .await?;
+  Ok(ok(response))
+}
+
+/// POST /admin/users/{public_id}/require-password-change
+/// 次回ログイン時のパスワード変更を要求し，既存のセッションを無効化する
+/// (リクエスト単位のトランザクションで更新する)
+pub async fn require_password_change_handler(
+  RequireRole(actor, _): RequireRole<Support>,
+  Extension(service): Extension<AdminService>,
+  tx: RequestTx,
+  path: Result<Path<String>, PathRejection>,
+) -> AppResult<ApiJson<RequirePasswordChangeResponse>> {
+  let public_id = parse_public_id(path)?;
+  let response = service
+    .require_password_change(&mut *tx.lock().await?, &actor.user, &public_id)
+    .await?;
+  Ok(ok(response))
+}
+
+/// POST /admin/users/status-bulk
+/// ユーザーのステータスを一括で変更し，ユーザーごとの結果を入力順に返す
+pub async fn bulk_status_handler(
+  RequireRole(actor, _): RequireRole<Moderator>,
+  Extension(service): Extension<AdminService>,
+  deadline: Deadline,
+  ValidatedJson(request): ValidatedJson<BulkStatusRequest>,
+) -> AppResult<ApiJson<BulkStatusReport>> {
+  let response = service
+    .with_deadline(deadline)
+    .bulk_status(&actor.user, request)
+    .await?;
+  Ok(ok(response))
+}
+
+/// POST /admin/randomart/verify
+/// 公開IDごとにランダムアートを再計算し，期待値との照合結果を入力順に返す
+pub async fn verify_randomart_handler(
+  _: RequireRole<Support>,
+  Extension(service): Extension<AdminService>,
+  ValidatedJson(entries): ValidatedJson<Vec<RandomartVerifyEntry>>,
+) -> AppResult<ApiJson<Vec<RandomartVerifyResult>>> {
+  let response = service.verify_randomart(entries).await?;
+  Ok(ok(response))
+}
+
+/// POST /admin/maintenance/randomart?batch_size=
+/// 保存済みのランダムアートを現在のアルゴリズムで再生成し，処理した件数を返す
+/// (タイムアウトした場合も完了したバッチの更新は残るため，再度実行すると続きから処理する)
+pub async fn regenerate_randomart_handler(
+  _: RequireRole<Admin>,
+  Extension(service): Extension<MaintenanceService>,
+  query: Result<Query<MaintenanceBatchQuery>, QueryRejection>,
+) -> AppResult<ApiJson<RandomartReport>> {
+  let Query(query) = query?;
+  let response = service.regenerate_all_randomart(query.batch_size()).await?;
+  Ok(ok(response))
+}
+
+/// POST /admin/maintenance/email-canonical?batch_size=
+/// メールアドレスの正規形を現在の方式で再計算し，処理した件数を返す
+/// ([validation].strip_plus_addressingを変更した後に実行する。再実行しても結果は変わらない)
+pub async fn recanonicalize_emails_handler(
+  _: RequireRole<Admin>,
+  Extension(service): Extension<MaintenanceService>,
+  query: Result<Query<MaintenanceBatchQuery>, QueryRejection>,
+) -> AppResult<ApiJson<EmailCanonicalReport>> {
+  let Query(query) = query?;
+  let response = service.recanonicalize_emails(query.batch_size()).await?;
+  Ok(ok(response))
+}
+
+/// GET /admin/sessions?user=&active=&limit=&cursor=&before=
+/// ユーザー・有効期限の状態で絞り込んだセッションを返す（セッションIDはマスクする）
+/// 前後のページのURLをLinkヘッダに，全件数をX-Total-Countヘッダに設定する
+/// (URLにbase_pathを含めるため，ネストで取り除かれる前のURIを使用する)
+pub async fn sessions_handler(
+  _: RequireRole<Support>,
+  Extension(service): Extension<AdminService>,
+  OriginalUri(uri): OriginalUri,
+  query: Result<Query<SessionListQuery>, QueryRejection>,
+) -> AppResult<Paginated<SessionPage>> {
+  let Query(query) = query?;
+  let response = service.sessions(query).await?;
+  let (next, prev) = (response.next_cursor.clone(), response.prev_cursor.clone());
+  let total = response.total;
+  Ok(Paginated::new(
+    response,
+    &uri,
+    next.as_deref(),
+    prev.as_deref(),
+    total,
+  ))
+}
@@ -0,0 +1,276 @@
+//! HTTP ハンドラ ― ヘルスチェック
+//! GETとして登録したルートはHEADにも応答する。(ボディは空，Content-LengthはGETと同一)
+
+use crate::{
+  config::Health,
+  infra::pg::schema::SchemaStatus,
+  interfaces::http::{
+    auth::{Admin, RequireRole},
+    error::{AppError, AppResult},
+  },
+  utils::metrics,
+};
+use axum::{
+  Json,
+  extract::Extension,
+  http::{
+    HeaderMap, HeaderName, StatusCode,
+    header::{ACCEPT, CONTENT_TYPE},
+  },
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::{fmt::Display, future::Future, time::Duration};
+use tracing as log;
+
+/// GET /healthz
+/// プロセスが応答可能であれば常に200を返す
+pub async fn healthz_handler() -> &'static str {
+  "ok"
+}
+
+/// GET /readyz
+/// Postgresに接続できる場合のみ200を返す
+/// 接続の確立を含めて[health].db_timeout_msを超えた場合も503を返す
+pub async fn readyz_handler(
+  Extension(pool): Extension<PgPool>,
+  Extension(health): Extension<Health>,
+) -> AppResult<&'static str> {
+  let check = async { sqlx::query("SELECT 1").execute(&pool).await.map(|_| ()) };
+  probe(check, health.db_timeout()).await?;
+  Ok("ok")
+}
+
+/// ヘルスチェックの結果
+#[derive(Debug, Serialize)]
+pub struct HealthStatus {
+  /// "ok" | "unavailable"
+  pub status: &'static str,
+  /// "up" | "down"
+  pub db: &'static str,
+}
+
+/// GET /health
+/// Postgresの疎通を確認し，結果をJSONで返す（ロードバランサ・Kubernetesのprobe向け）
+/// 失敗または[health].db_timeout_msを超えた場合は503を返す
+pub async fn health_handler(
+  Extension(pool): Extension<PgPool>,
+  Extension(health): Extension<Health>,
+) -> (StatusCode, Json<HealthStatus>) {
+  let check = async { sqlx::query("SELECT 1").execute(&pool).await.map(|_| ()) };
+  match probe(check, health.db_timeout()).await {
+    Ok(()) => (
+      StatusCode::OK,
+      Json(HealthStatus {
+        status: "ok",
+        db: "up",
+      }),
+    ),
+    Err(_) => (
+      StatusCode::SERVICE_UNAVAILABLE,
+      Json(HealthStatus {
+        status: "unavailable",
+        db: "down",
+      }),
+    ),
+  }
+}
+
+/// GET /schema
+/// 適用済みのマイグレーションのバージョンを返す
+/// バイナリが前提とするマイグレーションが未適用の場合は503を返す
+pub async fn schema_handler(
+  Extension(pool): Extension<PgPool>,
+) -> AppResult<(StatusCode, Json<SchemaStatus>)> {
+  let status = SchemaStatus::check(&pool).await?;
+  if status.is_up_to_date() {
+    return Ok((StatusCode::OK, Json(status)));
+  }
+  log::warn!(missing = ?status.missing, "Database schema is behind the binary");
+  Ok((StatusCode::SERVICE_UNAVAILABLE, Json(status)))
+}
+
+/// GET /metrics
+/// メトリクスをPrometheusのテキスト形式で返す（[metrics].enabledがtrueの場合のみ配置する）
+/// 入力の傾向が外部に漏れないよう，Admin以上のセッションを要求する。
+/// AcceptでOpenMetricsを要求された場合は，リクエストIDのexemplarを含むOpenMetricsの形式で返す。
+pub async fn metrics_handler(
+  _: RequireRole<Admin>,
+  headers: HeaderMap,
+) -> ([(HeaderName, &'static str); 1], String) {
+  let openmetrics = headers
+    .get_all(ACCEPT)
+    .iter()
+    .filter_map(|v| v.to_str().ok())
+    .any(|v| v.contains("application/openmetrics-text"));
+  if openmetrics {
+    return (
+      [(CONTENT_TYPE, metrics::OPENMETRICS_CONTENT_TYPE)],
+      metrics::render_openmetrics(),
+    );
+  }
+  (
+    [(CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+    metrics::render(),
+  )
+}
+
+/// 疎通確認を`timeout`以内に完了させる。
+/// 失敗またはタイムアウトの場合は503を返す。
+async fn probe<F, E>(check: F, timeout: Duration) -> AppResult<()>
+where
+  F: Future<Output = Result<(), E>>,
+  E: Display,
+{
+  match tokio::time::timeout(timeout, check).await {
+    Ok(Ok(())) => Ok(()),
+    Ok(Err(e)) => {
+      log::warn!("Readiness check failed: {}", e);
+      Err(AppError::ServiceUnavailable(Some(
+        "データベースに接続できません。".into(),
+      )))
+    }
+    Err(_) => {
+      log::warn!("Readiness check timed out after {:?}", timeout);
+      Err(AppError::ServiceUnavailable(Some(
+        "データベースの応答がタイムアウトしました。".into(),
+      )))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Method, Request, StatusCode, header},
+    response::Response,
+    routing::get,
+  };
+  use tower::ServiceExt;
+
+  fn app(pool: PgPool) -> Router {
+    Router::new()
+      .route("/healthz", get(healthz_handler))
+      .route("/readyz", get(readyz_handler))
+      .route("/health", get(health_handler))
+      .route("/schema", get(schema_handler))
+      .layer(Extension(pool))
+      .layer(Extension(Health {
+        db_timeout_ms: 1000,
+      }))
+  }
+
+  async fn send(app: Router, method: Method, uri: &str) -> Response {
+    app
+      .oneshot(
+        Request::builder()
+          .method(method)
+          .uri(uri)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap()
+  }
+
+  /// HEADはGETと同じステータス・Content-Lengthで，ボディが空であること
+  async fn assert_head_matches_get(app: Router, uri: &str, status: StatusCode) {
+    let get = send(app.clone(), Method::GET, uri).await;
+    let head = send(app, Method::HEAD, uri).await;
+    assert_eq!(get.status(), status);
+    assert_eq!(head.status(), status);
+    assert_eq!(
+      head.headers().get(header::CONTENT_LENGTH),
+      get.headers().get(header::CONTENT_LENGTH)
+    );
+    let body = to_bytes(head.into_body(), usize::MAX).await.unwrap();
+    assert!(body.is_empty());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn head_healthz_and_readyz(pool: PgPool) {
+    let app = app(pool);
+    assert_head_matches_get(app.clone(), "/healthz", StatusCode::OK).await;
+    assert_head_matches_get(app, "/readyz", StatusCode::OK).await;
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn schema_reports_latest_applied_version(pool: PgPool) {
+    let latest: i64 = sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations")
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+    let res = send(app(pool.clone()), Method::GET, "/schema").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["applied_version"], latest);
+    assert_eq!(v["expected_version"], latest);
+    assert_eq!(v["missing"], serde_json::json!([]));
+
+    // 最新のマイグレーションが未適用の場合は503
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = $1")
+      .bind(latest)
+      .execute(&pool)
+      .await
+      .unwrap();
+    let res = send(app(pool), Method::GET, "/schema").await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["missing"], serde_json::json!([latest]));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn head_readyz_when_database_is_down(pool: PgPool) {
+    pool.close().await;
+    assert_head_matches_get(app(pool), "/readyz", StatusCode::SERVICE_UNAVAILABLE).await;
+  }
+
+  async fn health(pool: PgPool) -> (StatusCode, serde_json::Value) {
+    let res = send(app(pool), Method::GET, "/health").await;
+    let status = res.status();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn health_reports_database_up(pool: PgPool) {
+    let (status, v) = health(pool).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v, serde_json::json!({ "status": "ok", "db": "up" }));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn health_reports_database_down_with_closed_pool(pool: PgPool) {
+    pool.close().await;
+    let (status, v) = health(pool).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(v["db"], "down");
+  }
+
+  #[tokio::test]
+  async fn slow_check_is_reported_unhealthy_within_timeout() {
+    let timeout = Duration::from_millis(50);
+    let slow = async {
+      tokio::time::sleep(Duration::from_secs(10)).await;
+      Ok::<(), sqlx::Error>(())
+    };
+    let started = std::time::Instant::now();
+    let result = probe(slow, timeout).await;
+    assert!(started.elapsed() < Duration::from_secs(1));
+    match result {
+      Err(AppError::ServiceUnavailable(Some(reason))) => assert!(reason.contains("タイムアウト")),
+      other => panic!("unexpected result: {other:?}"),
+    }
+  }
+
+  #[tokio::test]
+  async fn fast_check_within_timeout_is_healthy() {
+    let fast = async { Ok::<(), sqlx::Error>(()) };
+    assert!(probe(fast, Duration::from_millis(50)).await.is_ok());
+  }
+}
@@ -1 +1,22 @@
+use crate::{
+  domain::value_obj::public_id::PublicId,
+  interfaces::http::error::{AppError, AppResult},
+};
+use axum::extract::{Path, rejection::PathRejection};
+
+pub mod admin;
+pub mod health;
 pub mod user;
+
+/// パスパラメータから公開IDを生成する
+/// パーセントエンコードを復号した値を検証し，復号できない場合・前後に空白を含む場合は400を返す
+pub(crate) fn parse_public_id(path: Result<Path<String>, PathRejection>) -> AppResult<PublicId> {
+  let Path(input) = path?;
+  if input.trim() != input {
+    return Err(AppError::BadRequest(Some(
+      "公開IDの前後に空白を含めることはできません。".into(),
+    )));
+  }
+  PublicId::from_string(input, true)?
+    .ok_or_else(|| AppError::UnprocessableContent(Some("公開IDは必須です。".into())))
+}
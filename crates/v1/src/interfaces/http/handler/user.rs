@@ -2,51 +2,334 @@
 
 use crate::{
   application::user::{
-    dto::{RegisterRequest, RegisterResponse},
+    dto::{
+      ChangePasswordRequest, ChangeStatusRequest, FormNonceResponse, LoginOutcome, LoginRequest,
+      OwnSessionList, PasswordStrengthRequest, PasswordStrengthResponse, ProfileResponse,
+      RandomartResponse, RegisterRequest, RegisterResponse, RegisterSchema, RotateIdResponse,
+      StatusResponse, UpdateProfileRequest, UsernameAvailabilityQuery,
+      UsernameAvailabilityResponse, VerifyEmailRequest, VerifyEmailResponse,
+    },
     service::UserService,
   },
-  domain::repository::{UserAuthRepository, UserRepository},
-  interfaces::http::error::AppResult,
+  config,
+  domain::{
+    entity::{session::SessionScope, user::UserStatus},
+    password_policy::{PasswordContext, PasswordPolicy},
+    value_obj::user_password::UserPassword,
+  },
+  infra::pg::deadline::Deadline,
+  interfaces::http::{
+    auth::{
+      CurrentUser, PasswordChangeUser, SessionCookie, SessionToken, password_change_required,
+    },
+    client::ClientInfo,
+    dto::{ApiJson, ok},
+    error::{AppError, AppResult},
+    handler::parse_public_id,
+    json::ValidatedJson,
+    precondition::{Precondition, etag_header},
+  },
+};
+use axum::{
+  extract::{Extension, Path, Query, rejection::PathRejection},
+  http::{
+    StatusCode,
+    header::{ETAG, SET_COOKIE},
+  },
+  response::{IntoResponse, Response},
 };
-use async_trait::async_trait;
-use axum::{Json, extract::Extension};
+use chrono::Utc;
 
 // ユーザー登録ハンドラ
 pub async fn register_handler(
   Extension(service): Extension<UserService>,
-  Json(request): Json<RegisterRequest>,
-) -> AppResult<Json<RegisterResponse>> {
-  let response = service.register(request).await?;
-  Ok(Json(response))
-}
-
-// /// ユーザー登録ユースケースの振る舞いを抽象化する
-// #[async_trait]
-// pub trait UserRegisterUsecase: Send + Sync {
-//   async fn register(&self, req: RegisterRequest) -> AppResult<RegisterResponse>;
-// }
-
-// /// UserServiceへのブランケットの実装
-// #[async_trait]
-// impl<R, A> UserRegisterUsecase for UserService<R, A>
-// where
-//   R: UserRepository + Send + Sync,
-//   A: UserAuthRepository + Send + Sync,
-// {
-//   async fn register(&self, req: RegisterRequest) -> AppResult<RegisterResponse> {
-//     UserService::register(self, req).await
-//   }
-// }
-
-// /* Axum ハンドラ */
-// /// POST /register
-// pub async fn register_handler<Svc>(
-//   Extension(svc): Extension<Svc>,
-//   Json(req): Json<RegisterRequest>,
-// ) -> AppResult<Json<RegisterResponse>>
-// where
-//   Svc: UserRegisterUsecase + Clone + Send + Sync + 'static,
-// {
-//   let res = svc.register(req).await?;
-//   Ok(Json(res))
-// }
+  deadline: Deadline,
+  ValidatedJson(request): ValidatedJson<RegisterRequest>,
+) -> AppResult<ApiJson<RegisterResponse>> {
+  let response = service.with_deadline(deadline).register(request).await?;
+  Ok(ok(response))
+}
+
+// 登録フォームのワンタイムトークン発行ハンドラ
+// 認証不要（[registration].form_nonceが無効の場合は404）
+pub async fn register_nonce_handler(
+  Extension(service): Extension<UserService>,
+) -> AppResult<ApiJson<FormNonceResponse>> {
+  let response = service.issue_form_nonce().await?;
+  Ok(ok(response))
+}
+
+// 登録フォームの入力仕様ハンドラ
+// 認証不要（設定済みの検証ルールのみを返し，秘匿情報は含めない）
+pub async fn register_schema_handler(
+  Extension(validation): Extension<config::Validation>,
+  Extension(password): Extension<config::Password>,
+) -> AppResult<ApiJson<RegisterSchema>> {
+  Ok(ok(RegisterSchema::from_config(&validation, &password)?))
+}
+
+// メールアドレス確認ハンドラ
+// 認証不要（確認リンクのトークンでユーザーを特定する）
+pub async fn verify_email_handler(
+  Extension(service): Extension<UserService>,
+  deadline: Deadline,
+  ValidatedJson(request): ValidatedJson<VerifyEmailRequest>,
+) -> AppResult<ApiJson<VerifyEmailResponse>> {
+  let response = service
+    .with_deadline(deadline)
+    .verify_email(&request.token)
+    .await?;
+  Ok(ok(response))
+}
+
+// ログインハンドラ
+// 認証不要（発行したセッションIDを以降のリクエストのBearerトークンとして使用する）
+// パスワードの変更が必要な場合は403を返し，パスワード変更用のセッションをCookieで渡す
+pub async fn login_handler(
+  client: ClientInfo,
+  Extension(service): Extension<UserService>,
+  Extension(session_cookie): Extension<SessionCookie>,
+  ValidatedJson(request): ValidatedJson<LoginRequest>,
+) -> AppResult<Response> {
+  match service.login(request, client).await? {
+    LoginOutcome::Authenticated(response) => Ok(ok(response).into_response()),
+    LoginOutcome::PasswordChangeRequired(response) => {
+      let max_age = (response.expires_at - Utc::now()).num_seconds().max(0);
+      let cookie = session_cookie.header(&response.session_id, max_age)?;
+      Ok(([(SET_COOKIE, cookie)], password_change_required()).into_response())
+    }
+  }
+}
+
+// ログアウトハンドラ
+// AuthorizationヘッダまたはCookieのセッションを削除する（成功時は204）
+pub async fn logout_handler(
+  SessionToken(session_id): SessionToken,
+  Extension(service): Extension<UserService>,
+) -> AppResult<StatusCode> {
+  service.logout(session_id).await?;
+  Ok(StatusCode::NO_CONTENT)
+}
+
+// 本人のセッション一覧ハンドラ
+// 端末を識別できるよう，作成時の端末情報(User-Agent・IP)を含めて返す
+pub async fn own_sessions_handler(
+  current: CurrentUser,
+  Extension(service): Extension<UserService>,
+) -> AppResult<ApiJson<OwnSessionList>> {
+  let response = service
+    .own_sessions(current.user.user_id, current.session_id.as_ref())
+    .await?;
+  Ok(ok(response))
+}
+
+// パスワード変更ハンドラ
+// パスワード変更用のセッションでも実行でき，変更後はそのセッションを削除する（成功時は204）
+pub async fn change_password_handler(
+  PasswordChangeUser { current, scope }: PasswordChangeUser,
+  Extension(service): Extension<UserService>,
+  client: ClientInfo,
+  ValidatedJson(request): ValidatedJson<ChangePasswordRequest>,
+) -> AppResult<StatusCode> {
+  service
+    .change_password(
+      current.user.user_id,
+      request.current_password,
+      request.new_password,
+      client,
+    )
+    .await?;
+  if scope == SessionScope::PasswordChange
+    && let Some(session_id) = current.session_id
+  {
+    service.logout(session_id).await?;
+  }
+  Ok(StatusCode::NO_CONTENT)
+}
+
+// ユーザー名の利用可否確認ハンドラ
+// 認証不要（列挙を抑えるため，リクエスト数の制限対象のルートに配置する）
+pub async fn username_available_handler(
+  Extension(service): Extension<UserService>,
+  Query(query): Query<UsernameAvailabilityQuery>,
+) -> AppResult<ApiJson<UsernameAvailabilityResponse>> {
+  let response = service.username_availability(&query.q).await?;
+  Ok(ok(response))
+}
+
+// 公開ID再発行ハンドラ
+// 本人またはAdmin以上のみ実行できる（旧公開IDへの参照は無効になる）
+pub async fn rotate_id_handler(
+  current: CurrentUser,
+  Extension(service): Extension<UserService>,
+  deadline: Deadline,
+  path: Result<Path<String>, PathRejection>,
+) -> AppResult<ApiJson<RotateIdResponse>> {
+  let public_id = parse_public_id(path)?;
+  let response = service
+    .with_deadline(deadline)
+    .rotate_public_id(&current.user, &public_id)
+    .await?;
+  Ok(ok(response))
+}
+
+// ユーザー取得ハンドラ
+// 本人またはSupport以上のみ取得できる（認証情報は含めない）
+// 公開IDの形式が不正な場合は，DBを参照せずに422を返す
+pub async fn get_user_handler(
+  current: CurrentUser,
+  Extension(service): Extension<UserService>,
+  path: Result<Path<String>, PathRejection>,
+) -> AppResult<impl IntoResponse> {
+  let public_id = parse_public_id(path)?;
+  let is_owner = current.user.public_id == public_id;
+  let user = service.get_by_public_id(&current.user, public_id).await?;
+  // 更新時にIf-Matchで指定する版
+  Ok((
+    [(ETAG, etag_header(user.updated_at))],
+    ok(ProfileResponse::new(&user, is_owner)),
+  ))
+}
+
+// プロフィール更新ハンドラ
+// 本人またはAdmin以上のみ実行できる（省略した項目は変更せず，nullの項目は消去する）
+// If-Match・If-Unmodified-Sinceで指定した版から変更されている場合は412を返す
+pub async fn update_profile_handler(
+  current: CurrentUser,
+  Extension(service): Extension<UserService>,
+  precondition: Precondition,
+  path: Result<Path<String>, PathRejection>,
+  ValidatedJson(request): ValidatedJson<UpdateProfileRequest>,
+) -> AppResult<impl IntoResponse> {
+  let public_id = parse_public_id(path)?;
+  let response = service
+    .update_profile(&current.user, &public_id, request, &precondition)
+    .await?;
+  Ok(([(ETAG, etag_header(response.updated_at))], ok(response)))
+}
+
+// ステータス変更ハンドラ（休止・退会）
+// 本人またはAdmin以上のみ実行できる（変更後は対象ユーザーの全セッションを無効にする）
+pub async fn change_status_handler(
+  current: CurrentUser,
+  Extension(service): Extension<UserService>,
+  deadline: Deadline,
+  path: Result<Path<String>, PathRejection>,
+  ValidatedJson(request): ValidatedJson<ChangeStatusRequest>,
+) -> AppResult<ApiJson<StatusResponse>> {
+  let public_id = parse_public_id(path)?;
+  let service = service.with_deadline(deadline);
+  let next = UserStatus::parse(&request.status).ok_or_else(|| {
+    AppError::UnprocessableContent(Some(format!(
+      "ステータス(status)の値が正しくありません: {}",
+      request.status
+    )))
+  })?;
+  let response = service
+    .change_status_by_public_id(&current.user, public_id, next)
+    .await?;
+  Ok(ok(response))
+}
+
+// ランダムアート取得ハンドラ
+// 認証不要（公開IDから取得できる情報のみを返す）
+pub async fn randomart_handler(
+  Extension(service): Extension<UserService>,
+  path: Result<Path<String>, PathRejection>,
+) -> AppResult<ApiJson<RandomartResponse>> {
+  let public_id = parse_public_id(path)?;
+  let response = service.randomart(&public_id).await?;
+  Ok(ok(response))
+}
+
+// パスワード強度評価ハンドラ
+// 何も登録せず，評価結果のみを返す（パスワードはログに出力しない）
+pub async fn password_strength_handler(
+  ValidatedJson(request): ValidatedJson<PasswordStrengthRequest>,
+) -> AppResult<ApiJson<PasswordStrengthResponse>> {
+  let strength = UserPassword::strength(&request.password, request.user_name.as_deref())?;
+  let context = PasswordContext {
+    user_name: request.user_name.as_deref(),
+    birth_date: None,
+  };
+  let violations = PasswordPolicy::current()
+    .evaluate(request.password.trim(), &context)
+    .err()
+    .unwrap_or_default();
+  Ok(ok(PasswordStrengthResponse::new(strength, &violations)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+    routing::post,
+  };
+  use tower::ServiceExt;
+
+  #[tokio::test]
+  async fn weak_password_returns_low_score_with_suggestions() {
+    let app = Router::new().route("/password/strength", post(password_strength_handler));
+    let res = app
+      .oneshot(
+        Request::post("/password/strength")
+          .header(header::CONTENT_TYPE, "application/json")
+          .body(Body::from(r#"{"password":"password","user_name":"alice"}"#))
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(v["score"].as_u64().unwrap() <= 1);
+    assert!(!v["suggestions"].as_array().unwrap().is_empty());
+    assert!(v["crack_time"].is_string());
+    assert_eq!(
+      v["violations"][0],
+      "パスワード(user_password)は強度が不十分です。より強力なパスワードを使用してください。"
+    );
+    assert!(!String::from_utf8_lossy(&body).contains("\"password\""));
+  }
+
+  #[tokio::test]
+  async fn password_longer_than_policy_is_rejected_without_evaluation() {
+    let app = Router::new().route("/password/strength", post(password_strength_handler));
+    let password = "a".repeat(PasswordPolicy::current().max_len + 1);
+    let res = app
+      .oneshot(
+        Request::post("/password/strength")
+          .header(header::CONTENT_TYPE, "application/json")
+          .body(Body::from(
+            serde_json::json!({ "password": password }).to_string(),
+          ))
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+  }
+
+  #[tokio::test]
+  async fn unknown_field_is_rejected_with_field_name() {
+    let app = Router::new().route("/password/strength", post(password_strength_handler));
+    let res = app
+      .oneshot(
+        Request::post("/password/strength")
+          .header(header::CONTENT_TYPE, "application/json")
+          .body(Body::from(r#"{"passwrod":"password"}"#))
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["detail"], "不明なフィールド`passwrod`が含まれています。");
+  }
+}
@@ -0,0 +1,99 @@
+//! JSONボディのエクストラクタ
+//! --------------------------------------------------------------
+//! ・`axum::Json`と同様にデシリアライズする
+//! ・失敗時は`AppError`に変換し，標準のエラーフォーマットで返す
+//! ・DTOに`#[serde(deny_unknown_fields)]`を指定した場合は，不明なフィールド名を400で返す
+//! --------------------------------------------------------------
+
+use crate::interfaces::http::error::AppError;
+use axum::{
+  Json,
+  extract::{FromRequest, Request},
+};
+use serde::de::DeserializeOwned;
+
+/// デシリアライズに失敗した場合に`AppError`を返すJSONエクストラクタ
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+  T: DeserializeOwned,
+  S: Send + Sync,
+{
+  type Rejection = AppError;
+
+  async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+    let Json(value) = Json::<T>::from_request(req, state).await?;
+    Ok(Self(value))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{StatusCode, header},
+    response::Response,
+    routing::post,
+  };
+  use serde::Deserialize;
+  use tower::ServiceExt;
+
+  #[derive(Deserialize)]
+  #[serde(deny_unknown_fields)]
+  #[allow(dead_code)]
+  struct Payload {
+    name: String,
+    age: Option<u32>,
+  }
+
+  async fn send(content_type: Option<&str>, body: &'static str) -> Response {
+    let app = Router::new().route(
+      "/",
+      post(|ValidatedJson(_): ValidatedJson<Payload>| async { "ok" }),
+    );
+    let mut req = Request::post("/");
+    if let Some(ct) = content_type {
+      req = req.header(header::CONTENT_TYPE, ct);
+    }
+    app
+      .oneshot(req.body(Body::from(body)).unwrap())
+      .await
+      .unwrap()
+  }
+
+  async fn detail(res: Response) -> String {
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    v["detail"].as_str().unwrap_or_default().to_owned()
+  }
+
+  #[tokio::test]
+  async fn unknown_field_is_bad_request_naming_the_field() {
+    let res = send(Some("application/json"), r#"{"name":"a","agee":1}"#).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+      detail(res).await,
+      "不明なフィールド`agee`が含まれています。"
+    );
+  }
+
+  #[tokio::test]
+  async fn other_rejections_keep_their_status() {
+    let res = send(Some("application/json"), r#"{"age":1}"#).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(detail(res).await.contains("missing field `name`"));
+
+    let res = send(Some("application/json"), r#"{"name":"#).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = send(None, r#"{"name":"a"}"#).await;
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let res = send(Some("application/json"), r#"{"name":"a"}"#).await;
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+}
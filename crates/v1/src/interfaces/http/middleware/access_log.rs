@@ -0,0 +1,97 @@
+//! アクセスログ
+//! --------------------------------------------------------------
+//! ・[log].access がtrueの場合，リクエストごとにメソッド・パス・ステータス・処理時間・
+//!   リクエストIDをtarget `access`で出力する（JSON形式ではトップレベルのキーになる）
+//! ・[app].emit_response_time がtrueの場合，処理時間を`X-Response-Time-Ms`ヘッダで返す
+//! ・全ての処理時間を計測するため，ルータの最も外側に配置する
+//! --------------------------------------------------------------
+
+use crate::{interfaces::http::middleware::request_id, utils::logger::ACCESS_TARGET};
+use axum::{
+  extract::{Request, State},
+  http::{HeaderName, HeaderValue},
+  middleware::Next,
+  response::Response,
+};
+use std::time::Instant;
+use tracing as log;
+
+/// サーバーの処理時間(ミリ秒)を返すヘッダ
+pub const X_RESPONSE_TIME_MS: HeaderName = HeaderName::from_static("x-response-time-ms");
+
+/// アクセスログの出力方法
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AccessLog {
+  /// リクエストごとにログを出力する
+  pub log: bool,
+  /// 処理時間を`X-Response-Time-Ms`ヘッダで返す
+  pub emit_response_time: bool,
+}
+
+/// 後続の処理時間を計測してログに出力する。
+/// (クエリ文字列は個人情報を含み得るため出力しない)
+pub async fn log(State(options): State<AccessLog>, req: Request, next: Next) -> Response {
+  let started = Instant::now();
+  let method = req.method().clone();
+  let path = req.uri().path().to_owned();
+
+  let mut res = next.run(req).await;
+  // ヘッダの設定・ログの出力にかかる時間は含めない
+  let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+  if options.log {
+    let request_id = request_id::current();
+    log::info!(
+      target: ACCESS_TARGET,
+      method = %method,
+      path = %path,
+      status = res.status().as_u16(),
+      elapsed_ms = (elapsed_ms * 1000.0).round() / 1000.0,
+      request_id = request_id.as_ref().map(|id| id.as_str()),
+      "request completed"
+    );
+  }
+  if options.emit_response_time
+    && let Ok(v) = HeaderValue::from_str(&format!("{elapsed_ms:.3}"))
+  {
+    res.headers_mut().insert(X_RESPONSE_TIME_MS, v);
+  }
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{Router, body::Body, http::StatusCode, middleware, routing::get};
+  use tower::ServiceExt;
+
+  async fn get_root(emit_response_time: bool) -> Response {
+    let options = AccessLog {
+      log: false,
+      emit_response_time,
+    };
+    let app = Router::new()
+      .route("/", get(|| async { "ok" }))
+      .layer(middleware::from_fn_with_state(options, log));
+    let req = Request::get("/").body(Body::empty()).unwrap();
+    app.oneshot(req).await.unwrap()
+  }
+
+  #[tokio::test]
+  async fn response_time_header_is_a_number_when_enabled() {
+    let res = get_root(true).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let ms: f64 = res.headers()[X_RESPONSE_TIME_MS]
+      .to_str()
+      .unwrap()
+      .parse()
+      .unwrap();
+    assert!(ms >= 0.0);
+  }
+
+  #[tokio::test]
+  async fn response_time_header_is_absent_when_disabled() {
+    let res = get_root(false).await;
+    assert!(!res.headers().contains_key(X_RESPONSE_TIME_MS));
+  }
+}
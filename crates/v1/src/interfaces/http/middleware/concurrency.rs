@@ -0,0 +1,159 @@
+//! 同時処理数の制限
+//! 上限を超えたリクエストは待機させずに503で即座に返す。
+//! [app].route_concurrencyで指定したルートは，全体の上限とは別の上限で数える。
+
+use crate::{config::App, interfaces::http::error::AppError};
+use axum::{
+  extract::{MatchedPath, Request, State},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Semaphore;
+
+/// 全体の上限と，ルート単位の上限
+#[derive(Debug)]
+pub struct ConcurrencyLimits {
+  global: Arc<Semaphore>,
+  /// ルートの定義（base_pathを含む） → そのルート専用のセマフォ
+  routes: HashMap<String, Arc<Semaphore>>,
+}
+
+impl ConcurrencyLimits {
+  /// 全体の上限を`max`とする。
+  pub fn new(max: usize) -> Self {
+    Self {
+      global: Arc::new(Semaphore::new(max)),
+      routes: HashMap::new(),
+    }
+  }
+
+  /// ルートの定義`path`に専用の上限`max`を設定する。
+  pub fn with_route(mut self, path: impl Into<String>, max: usize) -> Self {
+    self
+      .routes
+      .insert(path.into(), Arc::new(Semaphore::new(max)));
+    self
+  }
+
+  /// Configの[app]から生成する。ルートの定義にはbase_pathを付与する。
+  pub fn from_config(config: &App) -> Self {
+    config
+      .route_concurrency
+      .iter()
+      .fold(Self::new(config.max_concurrent_requests), |limits, r| {
+        limits.with_route(
+          format!("{}{}", config.base_path(), r.path),
+          r.max_concurrent_requests,
+        )
+      })
+  }
+
+  /// ミドルウェアの状態として共有できる形に変換する。
+  pub fn into_shared(self) -> Arc<Self> {
+    Arc::new(self)
+  }
+
+  /// ルートに適用するセマフォを返す。（専用の上限が無い場合は全体の上限）
+  fn semaphore(&self, path: Option<&MatchedPath>) -> &Arc<Semaphore> {
+    path
+      .and_then(|p| self.routes.get(p.as_str()))
+      .unwrap_or(&self.global)
+  }
+}
+
+/// 許可を取得できた場合のみ後続の処理を行う。
+/// (ルートの定義を参照するため，`Router::layer`でルーティング後に適用する)
+pub async fn limit(
+  State(limits): State<Arc<ConcurrencyLimits>>,
+  req: Request,
+  next: Next,
+) -> Response {
+  let semaphore = limits.semaphore(req.extensions().get::<MatchedPath>());
+  // レスポンスを返すまで許可を保持する
+  match semaphore.clone().try_acquire_owned() {
+    Ok(_permit) => next.run(req).await,
+    Err(_) => AppError::ServiceUnavailable(Some(
+      "サーバーが混み合っています。しばらくしてから再度お試しください。".into(),
+    ))
+    .into_response(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{Router, body::Body, http::StatusCode, middleware, routing::get};
+  use tokio::sync::Notify;
+  use tower::ServiceExt;
+
+  /// `/slow`・`/cheap/slow`は，`release`の許可を1つ取得するまで待機する
+  fn app(limits: ConcurrencyLimits, started: Arc<Notify>, release: Arc<Semaphore>) -> Router {
+    let slow = move || async move {
+      started.notify_one();
+      release.acquire().await.unwrap().forget();
+      "done"
+    };
+    Router::new()
+      .route("/slow", get(slow.clone()))
+      .route("/cheap/slow", get(slow))
+      .route("/fast", get(|| async { "fast" }))
+      .route("/cheap", get(|| async { "cheap" }))
+      .layer(middleware::from_fn_with_state(limits.into_shared(), limit))
+  }
+
+  fn request(path: &str) -> Request {
+    Request::builder().uri(path).body(Body::empty()).unwrap()
+  }
+
+  #[tokio::test]
+  async fn sheds_requests_beyond_limit() {
+    let started = Arc::new(Notify::new());
+    let release = Arc::new(Semaphore::new(0));
+    let app = app(ConcurrencyLimits::new(1), started.clone(), release.clone());
+
+    // 1件目が許可を保持したまま待機する
+    let first = tokio::spawn(app.clone().oneshot(request("/slow")));
+    started.notified().await;
+
+    // 上限を超えたリクエストは503
+    let res = app.clone().oneshot(request("/fast")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // 1件目が完了すると再び受け付ける
+    release.add_permits(1);
+    let res = first.await.unwrap().unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = app.oneshot(request("/fast")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn overridden_route_has_its_own_limit() {
+    let started = Arc::new(Notify::new());
+    let release = Arc::new(Semaphore::new(0));
+    let limits = ConcurrencyLimits::new(1)
+      .with_route("/cheap", 2)
+      .with_route("/cheap/slow", 1);
+    let app = app(limits, started.clone(), release.clone());
+
+    // 全体の上限に達しても，専用の上限を持つルートは受け付ける
+    let global = tokio::spawn(app.clone().oneshot(request("/slow")));
+    started.notified().await;
+    let res = app.clone().oneshot(request("/fast")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let res = app.clone().oneshot(request("/cheap")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 専用の上限に達した場合は，そのルートのみ503
+    let own = tokio::spawn(app.clone().oneshot(request("/cheap/slow")));
+    started.notified().await;
+    let res = app.clone().oneshot(request("/cheap/slow")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    release.add_permits(2);
+    for handle in [global, own] {
+      assert_eq!(handle.await.unwrap().unwrap().status(), StatusCode::OK);
+    }
+  }
+}
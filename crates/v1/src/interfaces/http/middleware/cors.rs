@@ -0,0 +1,322 @@
+//! CORS
+//! --------------------------------------------------------------
+//! ・ルートごとに許可するメソッドを登録したレジストリからポリシーを組み立てる
+//! ・プリフライトは登録されたルート・メソッド・オリジンのみ204で許可し，それ以外は403を返す
+//! ・プリフライトの結果は[cors].max_age_secsの間ブラウザにキャッシュさせる
+//! --------------------------------------------------------------
+
+use crate::{config::Cors, interfaces::http::error::AppError};
+use axum::{
+  extract::{Request, State},
+  http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use std::{sync::Arc, time::Duration};
+
+/// ブラウザからの送信を許可するリクエストヘッダ
+const ALLOWED_HEADERS: &str = "authorization, content-type, if-match, if-unmodified-since";
+/// ブラウザのスクリプトから参照できるレスポンスヘッダ
+/// (楽観ロックのETag，一覧のページングに使うLink・件数，問い合わせに使うリクエストID，
+/// 再試行までの待ち時間，処理時間)
+const EXPOSED_HEADERS: &str =
+  "etag, link, x-total-count, x-request-id, retry-after, x-response-time-ms";
+
+/// ルートごとに許可するメソッド
+#[derive(Debug, Clone)]
+struct RoutePolicy {
+  /// Axumのルートと同じ書式のパス（`{param}`は任意の1セグメントに一致する）
+  pattern: &'static str,
+  methods: Vec<Method>,
+}
+
+impl RoutePolicy {
+  fn matches(&self, path: &str) -> bool {
+    let mut pattern = self.pattern.split('/');
+    let mut path = path.split('/');
+    loop {
+      match (pattern.next(), path.next()) {
+        (None, None) => return true,
+        (Some(p), Some(s)) if p.starts_with('{') && p.ends_with('}') && !s.is_empty() => {}
+        (Some(p), Some(s)) if p == s => {}
+        _ => return false,
+      }
+    }
+  }
+}
+
+/// ルートポリシーのレジストリから組み立てたCORSポリシー
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+  /// 許可するオリジン（`*`は全てのオリジンを許可する）
+  origins: Vec<String>,
+  max_age: Duration,
+  /// ルートを配置したパス（判定前にリクエストのパスから取り除く）
+  base_path: String,
+  routes: Vec<RoutePolicy>,
+}
+
+impl CorsPolicy {
+  /// Configの[cors]から生成する。ルートは`route`で登録する。
+  pub fn new(config: &Cors) -> Self {
+    Self {
+      origins: config.allowed_origins.clone(),
+      max_age: Duration::from_secs(config.max_age_secs),
+      base_path: String::new(),
+      routes: Vec::new(),
+    }
+  }
+
+  /// ルートを配置したパスを設定する。(登録するルートにはパスを含めない)
+  pub fn with_base_path(mut self, base_path: &str) -> Self {
+    self.base_path = base_path.to_owned();
+    self
+  }
+
+  /// ルートと許可するメソッドを登録する。
+  pub fn route(mut self, pattern: &'static str, methods: &[Method]) -> Self {
+    self.routes.push(RoutePolicy {
+      pattern,
+      methods: methods.to_vec(),
+    });
+    self
+  }
+
+  /// ミドルウェアの状態として共有できる形に変換する。
+  pub fn into_shared(self) -> Arc<Self> {
+    Arc::new(self)
+  }
+
+  /// 許可されたオリジンの場合，Access-Control-Allow-Originに設定する値を返す。
+  fn allow_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+    if self.origins.iter().any(|o| o == "*") {
+      return Some(HeaderValue::from_static("*"));
+    }
+    let origin_str = origin.to_str().ok()?;
+    self
+      .origins
+      .iter()
+      .any(|o| o == origin_str)
+      .then(|| origin.clone())
+  }
+
+  /// パスに対して許可されたメソッドを返す。(未登録のルートはNone)
+  fn methods_for(&self, path: &str) -> Option<&[Method]> {
+    let path = path.strip_prefix(self.base_path.as_str())?;
+    self
+      .routes
+      .iter()
+      .find(|r| r.matches(path))
+      .map(|r| r.methods.as_slice())
+  }
+
+  /// プリフライトを評価し，許可する場合は204のレスポンスを返す。
+  fn preflight(&self, path: &str, headers: &HeaderMap) -> Result<Response, AppError> {
+    let forbidden = || AppError::Forbidden(Some("CORSポリシーで許可されていません。".into()));
+
+    let origin = headers.get(header::ORIGIN).ok_or_else(forbidden)?;
+    let allow_origin = self.allow_origin(origin).ok_or_else(forbidden)?;
+    let requested = headers
+      .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+      .and_then(|v| Method::from_bytes(v.as_bytes()).ok())
+      .ok_or_else(forbidden)?;
+    let methods = self.methods_for(path).ok_or_else(forbidden)?;
+    if !methods.contains(&requested) {
+      return Err(forbidden());
+    }
+
+    let allow_methods = methods
+      .iter()
+      .map(Method::as_str)
+      .collect::<Vec<_>>()
+      .join(", ");
+    let mut res = StatusCode::NO_CONTENT.into_response();
+    let h = res.headers_mut();
+    h.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+    if let Ok(v) = HeaderValue::from_str(&allow_methods) {
+      h.insert(header::ACCESS_CONTROL_ALLOW_METHODS, v);
+    }
+    h.insert(
+      header::ACCESS_CONTROL_ALLOW_HEADERS,
+      HeaderValue::from_static(ALLOWED_HEADERS),
+    );
+    h.insert(
+      header::ACCESS_CONTROL_MAX_AGE,
+      HeaderValue::from(self.max_age.as_secs()),
+    );
+    h.insert(header::VARY, HeaderValue::from_static("origin"));
+    Ok(res)
+  }
+}
+
+/// CORSのプリフライトに応答し，通常のリクエストには許可ヘッダを付与する。
+pub async fn cors(State(policy): State<Arc<CorsPolicy>>, req: Request, next: Next) -> Response {
+  let is_preflight = req.method() == Method::OPTIONS
+    && req
+      .headers()
+      .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+  if is_preflight {
+    return policy
+      .preflight(req.uri().path(), req.headers())
+      .unwrap_or_else(IntoResponse::into_response);
+  }
+
+  // 許可されたルート・メソッドの場合のみオリジンを許可する
+  let allow_origin = req
+    .headers()
+    .get(header::ORIGIN)
+    .and_then(|o| policy.allow_origin(o))
+    .filter(|_| {
+      policy
+        .methods_for(req.uri().path())
+        .is_some_and(|m| m.contains(req.method()))
+    });
+
+  let mut res = next.run(req).await;
+  if let Some(origin) = allow_origin {
+    let h = res.headers_mut();
+    h.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    h.insert(
+      header::ACCESS_CONTROL_EXPOSE_HEADERS,
+      HeaderValue::from_static(EXPOSED_HEADERS),
+    );
+    h.append(header::VARY, HeaderValue::from_static("origin"));
+  }
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{
+    Router,
+    body::Body,
+    middleware,
+    routing::{get, post},
+  };
+  use tower::ServiceExt;
+
+  fn policy() -> CorsPolicy {
+    CorsPolicy::new(&Cors {
+      allowed_origins: vec!["https://app.example.com".into()],
+      max_age_secs: 600,
+    })
+    .route("/users/{public_id}", &[Method::GET])
+    .route("/register", &[Method::POST])
+  }
+
+  fn app() -> Router {
+    Router::new()
+      .route("/users/{public_id}", get(|| async { "profile" }))
+      .route("/register", post(|| async { "registered" }))
+      .layer(middleware::from_fn_with_state(policy().into_shared(), cors))
+  }
+
+  async fn preflight(uri: &str, origin: &str, method: &str) -> Response {
+    let req = Request::builder()
+      .method(Method::OPTIONS)
+      .uri(uri)
+      .header(header::ORIGIN, origin)
+      .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+      .body(Body::empty())
+      .unwrap();
+    app().oneshot(req).await.unwrap()
+  }
+
+  #[test]
+  fn route_pattern_matches_single_segment_params() {
+    let policy = policy();
+    assert!(policy.methods_for("/users/abc").is_some());
+    assert!(policy.methods_for("/users/").is_none());
+    assert!(policy.methods_for("/users/abc/rotate-id").is_none());
+    assert!(policy.methods_for("/register").is_some());
+  }
+
+  #[test]
+  fn route_pattern_matches_under_base_path() {
+    let policy = policy().with_base_path("/api/v1");
+    assert!(policy.methods_for("/api/v1/users/abc").is_some());
+    assert!(policy.methods_for("/api/v1/register").is_some());
+    assert!(policy.methods_for("/register").is_none());
+    assert!(policy.methods_for("/api/v1x/register").is_none());
+  }
+
+  #[tokio::test]
+  async fn preflight_allows_registered_method_with_max_age() {
+    let res = preflight("/users/abc", "https://app.example.com", "GET").await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let h = res.headers();
+    assert_eq!(
+      h[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+      "https://app.example.com"
+    );
+    assert_eq!(h[header::ACCESS_CONTROL_ALLOW_METHODS], "GET");
+    assert_eq!(h[header::ACCESS_CONTROL_MAX_AGE], "600");
+  }
+
+  #[tokio::test]
+  async fn preflight_rejects_disallowed_method_for_route() {
+    // 公開プロフィールはGETのみ
+    let res = preflight("/users/abc", "https://app.example.com", "POST").await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert!(
+      !res
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+
+    // 登録はPOSTのみ
+    let res = preflight("/register", "https://app.example.com", "DELETE").await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = preflight("/register", "https://app.example.com", "POST").await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+  }
+
+  #[tokio::test]
+  async fn preflight_rejects_unknown_origin_and_route() {
+    let res = preflight("/register", "https://evil.example.com", "POST").await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = preflight("/admin", "https://app.example.com", "GET").await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+  }
+
+  #[tokio::test]
+  async fn simple_request_gets_allow_origin_only_when_permitted() {
+    let req = Request::get("/users/abc")
+      .header(header::ORIGIN, "https://app.example.com")
+      .body(Body::empty())
+      .unwrap();
+    let res = app().oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+      res.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+      "https://app.example.com"
+    );
+    let exposed: Vec<_> = res.headers()[header::ACCESS_CONTROL_EXPOSE_HEADERS]
+      .to_str()
+      .unwrap()
+      .split(", ")
+      .collect();
+    for name in [
+      "etag",
+      "link",
+      "x-total-count",
+      "x-request-id",
+      "retry-after",
+      "x-response-time-ms",
+    ] {
+      assert!(exposed.contains(&name), "{name} is not exposed");
+    }
+
+    let req = Request::get("/users/abc")
+      .header(header::ORIGIN, "https://evil.example.com")
+      .body(Body::empty())
+      .unwrap();
+    let res = app().oneshot(req).await.unwrap();
+    assert!(
+      !res
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+  }
+}
@@ -0,0 +1,105 @@
+//! リクエストのタイムアウト
+//! --------------------------------------------------------------
+//! ・[app].request_timeout_msを超えたリクエストは処理を打ち切り，408を返す
+//! ・処理期限を`Deadline`としてリクエストに設定し，ハンドラからサービスのトランザクションに引き継ぐ
+//!   (トランザクションを開始するハンドラ：登録・メールアドレス確認・ステータス変更・
+//!    ステータスの一括変更・公開ID再発行)
+//! ・リクエスト単位のトランザクション（`transaction`）を適用したルートは，ミドルウェアが引き継ぐ
+//!   (対象のルート：ログインのロック解除・パスワード変更の要求)
+//! ・レート制限（`rate_limit`）はユーザーの識別でDBを参照するため，この内側に配置する
+//! --------------------------------------------------------------
+
+use crate::infra::pg::deadline::{Deadline, timed_out};
+use axum::{
+  extract::{FromRequestParts, Request, State},
+  http::request::Parts,
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use std::{convert::Infallible, time::Duration};
+
+/// ミドルウェアを適用していない場合は期限なしとする
+impl<S: Send + Sync> FromRequestParts<S> for Deadline {
+  type Rejection = Infallible;
+
+  async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    Ok(
+      parts
+        .extensions
+        .get::<Deadline>()
+        .copied()
+        .unwrap_or_default(),
+    )
+  }
+}
+
+/// 処理期限を設定して後続の処理を行い，`timeout`を超えた場合は打ち切る。
+/// (Noneの場合は期限を設けない)
+pub async fn limit(
+  State(timeout): State<Option<Duration>>,
+  mut req: Request,
+  next: Next,
+) -> Response {
+  let Some(timeout) = timeout else {
+    return next.run(req).await;
+  };
+  req.extensions_mut().insert(Deadline::after(timeout));
+  // 打ち切った場合，処理中のトランザクションは破棄時にロールバックされる
+  match tokio::time::timeout(timeout, next.run(req)).await {
+    Ok(res) => res,
+    Err(_) => timed_out().into_response(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{Router, body::Body, http::StatusCode, middleware, routing::get};
+  use tower::ServiceExt;
+
+  fn app(timeout: Option<Duration>) -> Router {
+    Router::new()
+      .route(
+        "/remaining",
+        get(|deadline: Deadline| async move {
+          deadline
+            .remaining()
+            .map_or("none".to_owned(), |r| r.as_millis().to_string())
+        }),
+      )
+      .route(
+        "/slow",
+        get(|| async {
+          tokio::time::sleep(Duration::from_secs(5)).await;
+          "done"
+        }),
+      )
+      .layer(middleware::from_fn_with_state(timeout, limit))
+  }
+
+  async fn get_body(app: Router, path: &str) -> (StatusCode, String) {
+    let req = Request::get(path).body(Body::empty()).unwrap();
+    let res = app.oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+  }
+
+  #[tokio::test]
+  async fn deadline_is_passed_to_handler() {
+    let (_, body) = get_body(app(Some(Duration::from_secs(2))), "/remaining").await;
+    let ms: u64 = body.parse().unwrap();
+    assert!(ms <= 2000 && ms > 1000, "{ms}");
+
+    let (_, body) = get_body(app(None), "/remaining").await;
+    assert_eq!(body, "none");
+  }
+
+  #[tokio::test]
+  async fn slow_request_is_cut_off() {
+    let (status, _) = get_body(app(Some(Duration::from_millis(50))), "/slow").await;
+    assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+  }
+}
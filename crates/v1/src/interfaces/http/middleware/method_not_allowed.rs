@@ -0,0 +1,67 @@
+//! 許可されていないメソッドへの応答
+//! ルータが返す空の405を，エラーレスポンスの形式に揃える。
+
+use crate::interfaces::http::error::AppError;
+use axum::{
+  extract::Request,
+  http::{StatusCode, header},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+
+/// ルータが返した405をAppError::MethodNotAllowedに置き換える。
+pub async fn to_json(req: Request, next: Next) -> Response {
+  let res = next.run(req).await;
+  // ハンドラが返したエラーレスポンス(Content-Type有り)はそのまま返す
+  if res.status() != StatusCode::METHOD_NOT_ALLOWED
+    || res.headers().contains_key(header::CONTENT_TYPE)
+  {
+    return res;
+  }
+
+  // Allowヘッダはルータがこのレスポンスに付与する
+  AppError::MethodNotAllowed(Some("このメソッドは許可されていません。".into())).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{
+    Router,
+    body::{Body, to_bytes},
+    middleware,
+    routing::post,
+  };
+  use tower::ServiceExt;
+
+  fn app() -> Router {
+    Router::new()
+      .route("/register", post(|| async { "registered" }))
+      .layer(middleware::from_fn(to_json))
+  }
+
+  #[tokio::test]
+  async fn wrong_method_returns_json_with_allow_header() {
+    let res = app()
+      .oneshot(Request::get("/register").body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(res.headers()[header::ALLOW], "POST");
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/json");
+
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["status"], 405);
+    assert_eq!(v["detail"], "このメソッドは許可されていません。");
+  }
+
+  #[tokio::test]
+  async fn allowed_method_is_untouched() {
+    let res = app()
+      .oneshot(Request::post("/register").body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+}
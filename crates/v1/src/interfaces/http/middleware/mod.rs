@@ -0,0 +1,12 @@
+pub mod access_log;
+pub mod concurrency;
+pub mod cors;
+pub mod deadline;
+pub mod method_not_allowed;
+pub mod problem_json;
+pub mod rate_limit;
+pub mod request_id;
+pub mod signup_cooldown;
+pub mod trailing_slash;
+pub mod transaction;
+pub mod uri_limit;
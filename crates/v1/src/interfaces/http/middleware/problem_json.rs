@@ -0,0 +1,102 @@
+//! RFC 7807（Problem Details）形式のエラーレスポンス
+//! --------------------------------------------------------------
+//! ・`AppError`が返したエラーレスポンスを`application/problem+json`で返し直す
+//! ・`instance`にリクエストのパスを，`type`に`about:blank`を設定する
+//! ・`into_response`はリクエストを参照できないため，本文を拡張に保持させて書き換える
+//! --------------------------------------------------------------
+
+use crate::interfaces::http::dto::ApiError;
+use axum::{
+  Json,
+  extract::Request,
+  http::{HeaderValue, header},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+
+/// Problem DetailsのContent-Type
+pub const PROBLEM_JSON: &str = "application/problem+json";
+/// ステータスコード以上の意味を持たないエラーの`type`（RFC 7807 4.2）
+const ABOUT_BLANK: &str = "about:blank";
+
+/// エラーレスポンスをProblem Detailsの形式に置き換える。
+/// (`AppError`以外が返したレスポンスはそのまま返す)
+pub async fn to_problem_json(req: Request, next: Next) -> Response {
+  let path = req.uri().path().to_owned();
+  let mut res = next.run(req).await;
+  let Some(mut body) = res.extensions_mut().remove::<ApiError>() else {
+    return res;
+  };
+  body.instance = Some(path);
+  body.problem_type = Some(ABOUT_BLANK.to_owned());
+
+  // ステータス・ヘッダ（Allow, Retry-Afterなど）は元のレスポンスのものを使用する
+  let (mut parts, _) = res.into_parts();
+  let (_, body) = Json(body).into_response().into_parts();
+  parts.headers.remove(header::CONTENT_LENGTH);
+  parts
+    .headers
+    .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+  Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::interfaces::http::error::AppError;
+  use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::StatusCode,
+    middleware,
+    routing::get,
+  };
+  use tower::ServiceExt;
+
+  fn app() -> Router {
+    Router::new()
+      .route("/ok", get(|| async { "ok" }))
+      .route(
+        "/users/{id}",
+        get(|| async {
+          AppError::NotFound(Some("ユーザーが見つかりません。".into()))
+        }),
+      )
+      .fallback(|| async { AppError::NotFound(None) })
+      .layer(middleware::from_fn(to_problem_json))
+  }
+
+  async fn get_path(path: &str) -> Response {
+    app()
+      .oneshot(Request::get(path).body(Body::empty()).unwrap())
+      .await
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn error_is_problem_json_with_instance() {
+    for path in ["/users/abc", "/missing"] {
+      let res = get_path(path).await;
+      assert_eq!(res.status(), StatusCode::NOT_FOUND);
+      assert_eq!(res.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+
+      let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+      let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+      assert_eq!(v["instance"], path);
+      assert_eq!(v["type"], ABOUT_BLANK);
+      assert_eq!(v["status"], 404);
+    }
+  }
+
+  #[tokio::test]
+  async fn success_is_untouched() {
+    let res = get_path("/ok").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(
+      res.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain")
+    );
+  }
+}
@@ -0,0 +1,228 @@
+//! リクエストのレート制限
+//! --------------------------------------------------------------
+//! ・認証済みのリクエストはユーザー単位で数え，ロールごとの上限を適用する
+//! ・未認証（認証に失敗した場合を含む）のリクエストは接続元IP単位で数える
+//! ・上限を超えたリクエストは429を返す
+//! ・識別したユーザーはリクエストの拡張に保存し，後続の`CurrentUser`で再度問い合わせない
+//! ・ユーザーの識別はDBを参照するため，処理期限(deadline)の内側に配置する
+//! --------------------------------------------------------------
+
+use crate::{
+  application::user::throttle::store_from_config,
+  config::{RateLimit, RoleRateLimits},
+  domain::repository::RateLimitStore,
+  interfaces::http::{auth::CurrentUser, client::ClientInfo, error::AppError},
+};
+use axum::{
+  extract::{FromRequestParts, Request, State},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use sqlx::PgPool;
+use std::{sync::Arc, time::Duration};
+
+/// ロール・接続元ごとの上限を適用するリミッタ
+#[derive(Clone)]
+pub struct RequestLimiter {
+  store: Arc<dyn RateLimitStore>,
+  window: Duration,
+  anonymous_max: u32,
+  by_role: RoleRateLimits,
+}
+
+impl RequestLimiter {
+  /// Configの[rate_limit]と任意のストアから生成する。
+  pub fn new(config: &RateLimit, store: Arc<dyn RateLimitStore>) -> Self {
+    Self {
+      store,
+      window: Duration::from_secs(config.request_window_secs.max(1)),
+      anonymous_max: config.request_anonymous_max,
+      by_role: config.by_role.clone(),
+    }
+  }
+
+  /// Configの[rate_limit]から生成する。ストアはbackendの設定に従う。
+  pub fn from_config(config: &RateLimit, pool: PgPool) -> Self {
+    Self::new(config, store_from_config(config, pool))
+  }
+
+  /// ミドルウェアの状態として共有できる形に変換する。
+  pub fn into_shared(self) -> Arc<Self> {
+    Arc::new(self)
+  }
+}
+
+/// 上限に達していない場合のみ後続の処理を行う。
+/// 認証の成否はハンドラ側で判定するため，ここでは上限の選択にのみ使用する。
+pub async fn limit(
+  State(limiter): State<Arc<RequestLimiter>>,
+  req: Request,
+  next: Next,
+) -> Response {
+  let (mut parts, body) = req.into_parts();
+  let (key, max) = match CurrentUser::from_request_parts(&mut parts, &()).await {
+    Ok(current) => {
+      let limit = (
+        format!("req_user:{}", current.user.user_id.as_i64()),
+        limiter.by_role.max_for(current.user.role),
+      );
+      parts.extensions.insert(current);
+      limit
+    }
+    Err(_) => {
+      let Ok(client) = ClientInfo::from_request_parts(&mut parts, &()).await;
+      let ip = client
+        .ip
+        .map_or_else(|| "unknown".to_owned(), |ip| ip.to_string());
+      (format!("req_ip:{ip}"), limiter.anonymous_max)
+    }
+  };
+
+  if max > 0 {
+    match limiter.store.hit(&key, limiter.window, Utc::now()).await {
+      Ok(hits) if hits > max => {
+        return AppError::TooManyRequests(Some(
+          "リクエスト数が上限に達しました。しばらくしてから再度お試しください。".into(),
+        ))
+        .into_response();
+      }
+      Ok(_) => {}
+      Err(e) => return e.into_response(),
+    }
+  }
+  next.run(Request::from_parts(parts, body)).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    config::AppConfig,
+    domain::{
+      entity::{
+        session::{Session, SessionScope},
+        user::{UserRole, UserStatus},
+      },
+      value_obj::session_id::SessionId,
+    },
+    infra::{
+      memory::rate_limit_store::MemoryRateLimitStore, pg::session_repo::PgSessionRepository,
+    },
+    test_support::seed_user,
+  };
+  use axum::{
+    Extension, Router,
+    body::Body,
+    extract::connect_info::MockConnectInfo,
+    http::{StatusCode, header},
+    middleware,
+    routing::get,
+  };
+  use std::net::SocketAddr;
+  use tower::ServiceExt;
+
+  fn app(pool: PgPool) -> Router {
+    let mut config = AppConfig::new().unwrap().rate_limit;
+    config.request_anonymous_max = 2;
+    config.by_role.admin = 5;
+    let limiter = RequestLimiter::new(&config, Arc::new(MemoryRateLimitStore::new()));
+    Router::new()
+      .route("/items", get(|| async { "ok" }))
+      .route(
+        "/me",
+        get(|current: Option<Extension<CurrentUser>>| async move {
+          current.map_or_else(String::new, |Extension(c)| {
+            c.user.user_name.as_str().to_owned()
+          })
+        }),
+      )
+      .layer(middleware::from_fn_with_state(limiter.into_shared(), limit))
+      .layer(Extension(pool))
+      .layer(MockConnectInfo(SocketAddr::from(([192, 0, 2, 10], 50000))))
+  }
+
+  /// `n`回リクエストし，成功した回数を返す。
+  async fn successes(app: &Router, token: Option<&str>, n: usize) -> usize {
+    let mut ok = 0;
+    for _ in 0..n {
+      let mut req = Request::get("/items");
+      if let Some(token) = token {
+        req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+      }
+      let res = app
+        .clone()
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+      match res.status() {
+        StatusCode::OK => ok += 1,
+        status => assert_eq!(status, StatusCode::TOO_MANY_REQUESTS),
+      }
+    }
+    ok
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn admin_gets_higher_limit_than_anonymous(pool: PgPool) {
+    let (admin, _) = seed_user(&pool, "limit_admin", UserStatus::Active, UserRole::Admin).await;
+    let session_id = SessionId::new();
+    let now = Utc::now();
+    PgSessionRepository::new(pool.clone())
+      .insert(&Session {
+        session_id: session_id.clone(),
+        user_id: admin.user_id,
+        created_at: now,
+        expires_at: now + chrono::Duration::hours(1),
+        user_agent: None,
+        ip: None,
+        scope: SessionScope::Full,
+      })
+      .await
+      .unwrap();
+    let token = session_id.as_uuid().to_string();
+
+    let app = app(pool);
+    assert_eq!(successes(&app, None, 6).await, 2);
+    // 同じ接続元でも，認証済みの管理者はロールの上限が適用される
+    assert_eq!(successes(&app, Some(&token), 6).await, 5);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn identified_user_is_stored_for_later_extractors(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "limit_cached", UserStatus::Active, UserRole::User).await;
+    let session_id = SessionId::new();
+    let now = Utc::now();
+    PgSessionRepository::new(pool.clone())
+      .insert(&Session {
+        session_id: session_id.clone(),
+        user_id: user.user_id,
+        created_at: now,
+        expires_at: now + chrono::Duration::hours(1),
+        user_agent: None,
+        ip: None,
+        scope: SessionScope::Full,
+      })
+      .await
+      .unwrap();
+    let me = |token: Option<String>| {
+      let mut req = Request::get("/me");
+      if let Some(token) = token {
+        req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+      }
+      app(pool.clone()).oneshot(req.body(Body::empty()).unwrap())
+    };
+
+    let res = me(Some(session_id.as_uuid().to_string())).await.unwrap();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert_eq!(&body[..], b"limit_cached");
+    // 未認証の場合は保存しない
+    let res = me(None).await.unwrap();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert!(body.is_empty());
+  }
+}
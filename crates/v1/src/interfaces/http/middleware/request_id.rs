@@ -0,0 +1,139 @@
+//! リクエストID
+//! --------------------------------------------------------------
+//! ・`X-Request-Id`ヘッダの値をリクエストIDとして使用する（無い・不正な場合はUUIDを生成する）
+//! ・リクエストの拡張・tracingのspanに設定し，レスポンスのヘッダで返す
+//! ・`into_response`などリクエストを参照できない処理からは`current`で取得する
+//! ・全てのログに付与するため，ルータの最も外側に配置する
+//! --------------------------------------------------------------
+
+use axum::{
+  extract::Request,
+  http::{HeaderName, HeaderValue},
+  middleware::Next,
+  response::Response,
+};
+use std::{fmt, sync::Arc};
+use tracing::{self as log, Instrument};
+use uuid::Uuid;
+
+/// リクエストIDを受け渡すヘッダ
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// 受け付けるリクエストIDの最大長
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+  /// 処理中のリクエストのID
+  static CURRENT: RequestId;
+}
+
+/// リクエストID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(Arc<str>);
+
+impl RequestId {
+  /// ヘッダの値から生成する。
+  /// 空・長すぎる・表示可能なASCII以外を含む場合はNone
+  fn from_header(value: &HeaderValue) -> Option<Self> {
+    let s = value.to_str().ok()?;
+    let valid = !s.is_empty() && s.len() <= MAX_LEN && s.bytes().all(|b| b.is_ascii_graphic());
+    valid.then(|| Self(s.into()))
+  }
+
+  fn generate() -> Self {
+    Self(Uuid::new_v4().to_string().into())
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl fmt::Display for RequestId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+/// 処理中のリクエストのIDを返す。(ミドルウェアの外側ではNone)
+pub fn current() -> Option<RequestId> {
+  CURRENT.try_with(RequestId::clone).ok()
+}
+
+/// リクエストIDを設定して後続の処理を行い，レスポンスのヘッダで返す。
+pub async fn assign(mut req: Request, next: Next) -> Response {
+  let id = req
+    .headers()
+    .get(X_REQUEST_ID)
+    .and_then(RequestId::from_header)
+    .unwrap_or_else(RequestId::generate);
+  req.extensions_mut().insert(id.clone());
+
+  let span = log::info_span!("request", request_id = %id);
+  let mut res = CURRENT
+    .scope(id.clone(), next.run(req).instrument(span))
+    .await;
+  // 受け付けた値は表示可能なASCIIのみのため，変換に失敗しない
+  if let Ok(v) = HeaderValue::from_str(id.as_str()) {
+    res.headers_mut().insert(X_REQUEST_ID, v);
+  }
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{Extension, Router, body::Body, middleware, routing::get};
+  use tower::ServiceExt;
+
+  /// 拡張とtask-localの値が一致する場合のみ，そのIDを本文で返す
+  async fn echo(Extension(id): Extension<RequestId>) -> String {
+    assert_eq!(current().as_ref(), Some(&id));
+    id.to_string()
+  }
+
+  async fn get_root(request_id: Option<&str>) -> Response {
+    let app = Router::new()
+      .route("/", get(echo))
+      .layer(middleware::from_fn(assign));
+    let mut req = Request::get("/");
+    if let Some(id) = request_id {
+      req = req.header(X_REQUEST_ID, id);
+    }
+    app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap()
+  }
+
+  async fn body_text(res: Response) -> String {
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+  }
+
+  #[tokio::test]
+  async fn provided_request_id_is_preserved() {
+    let res = get_root(Some("client-req-42")).await;
+    assert_eq!(res.headers()[X_REQUEST_ID], "client-req-42");
+    assert_eq!(body_text(res).await, "client-req-42");
+  }
+
+  #[tokio::test]
+  async fn missing_or_invalid_request_id_is_generated() {
+    for provided in [
+      None,
+      Some(""),
+      Some("has space"),
+      Some(&*"a".repeat(MAX_LEN + 1)),
+    ] {
+      let res = get_root(provided).await;
+      let id = res.headers()[X_REQUEST_ID].to_str().unwrap().to_owned();
+      assert!(Uuid::parse_str(&id).is_ok(), "{provided:?} -> {id}");
+      assert_eq!(body_text(res).await, id);
+    }
+  }
+
+  #[test]
+  fn current_is_none_outside_middleware() {
+    assert_eq!(current(), None);
+  }
+}
@@ -0,0 +1,196 @@
+//! 接続元IPごとのアカウント登録のクールダウン
+//! --------------------------------------------------------------
+//! ・同じ接続元IPからは，[registration].ip_cooldown_secsごとに1件のみ登録を受け付ける
+//! ・クールダウン中は429と，再試行までの秒数をRetry-Afterで返す
+//! ・登録に失敗した場合（2xx以外）はクールダウンを取り消す
+//! ・リクエスト数ではなく登録の完了を数える点で，[rate_limit]とは独立して適用する
+//! --------------------------------------------------------------
+
+use crate::{
+  config::Registration,
+  infra::pg::registration_cooldown_store::{CooldownClaim, PgRegistrationCooldownStore},
+  interfaces::http::{client::ClientInfo, error::AppError},
+};
+use axum::{
+  extract::{FromRequestParts, Request, State},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing as log;
+
+/// 登録のクールダウン
+#[derive(Clone)]
+pub struct SignupCooldown {
+  store: PgRegistrationCooldownStore,
+  cooldown: Duration,
+}
+
+impl SignupCooldown {
+  pub fn new(store: PgRegistrationCooldownStore, cooldown: Duration) -> Self {
+    Self { store, cooldown }
+  }
+
+  /// Configの[registration]から生成する。(ip_cooldown_secsが0の場合はNone)
+  pub fn from_config(config: &Registration, pool: PgPool) -> Option<Self> {
+    let secs = i64::try_from(config.ip_cooldown_secs).unwrap_or(i64::MAX);
+    (secs > 0).then(|| {
+      Self::new(
+        PgRegistrationCooldownStore::new(pool),
+        Duration::seconds(secs.min(i64::MAX / 1000)),
+      )
+    })
+  }
+
+  /// ミドルウェアの状態として共有できる形に変換する。
+  pub fn into_shared(self) -> Arc<Self> {
+    Arc::new(self)
+  }
+}
+
+/// クールダウン中でない場合のみ後続の処理を行う。
+/// (接続元IPが不明な場合は制限しない)
+pub async fn limit(
+  State(cooldown): State<Option<Arc<SignupCooldown>>>,
+  req: Request,
+  next: Next,
+) -> Response {
+  let Some(cooldown) = cooldown else {
+    return next.run(req).await;
+  };
+  let (mut parts, body) = req.into_parts();
+  let Ok(client) = ClientInfo::from_request_parts(&mut parts, &()).await;
+  let req = Request::from_parts(parts, body);
+  let Some(ip) = client.ip else {
+    return next.run(req).await;
+  };
+
+  let now = Utc::now();
+  let until = match cooldown.store.claim(ip, now + cooldown.cooldown, now).await {
+    Ok(CooldownClaim::Claimed { until }) => until,
+    Ok(CooldownClaim::Cooling { until }) => {
+      // 端数は切り上げ，少なくとも1秒とする
+      let millis = (until - now).num_milliseconds().max(1);
+      let secs = u64::try_from((millis + 999) / 1000).unwrap_or(1);
+      return AppError::TooManyRequests(Some(
+        "短時間に複数のアカウントを登録することはできません。しばらくしてから再度お試しください。"
+          .into(),
+      ))
+      .into_response_with_retry_after(secs);
+    }
+    Err(e) => return e.into_response(),
+  };
+
+  let res = next.run(req).await;
+  if !res.status().is_success()
+    && let Err(e) = cooldown.store.release(ip, until).await
+  {
+    // 取り消せない場合は，期限まで登録を受け付けないだけのため，レスポンスは変えない
+    log::warn!("Failed to release signup cooldown: {}", e);
+  }
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{
+    Router,
+    body::Body,
+    extract::ConnectInfo,
+    http::{StatusCode, header::RETRY_AFTER},
+    middleware,
+    routing::post,
+  };
+  use std::net::SocketAddr;
+  use tower::ServiceExt;
+
+  fn app(pool: PgPool, cooldown_secs: i64) -> Router {
+    let cooldown = SignupCooldown::new(
+      PgRegistrationCooldownStore::new(pool),
+      Duration::seconds(cooldown_secs),
+    );
+    Router::new()
+      .route("/register", post(|| async { StatusCode::CREATED }))
+      .route(
+        "/register/fail",
+        post(|| async { AppError::Conflict(None) }),
+      )
+      .layer(middleware::from_fn_with_state(
+        Some(cooldown.into_shared()),
+        limit,
+      ))
+  }
+
+  fn request(path: &str, ip: [u8; 4]) -> Request {
+    let mut req = Request::post(path).body(Body::empty()).unwrap();
+    req
+      .extensions_mut()
+      .insert(ConnectInfo(SocketAddr::from((ip, 40000))));
+    req
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn back_to_back_signups_from_one_ip_are_blocked(pool: PgPool) {
+    let app = app(pool, 600);
+    let res = app
+      .clone()
+      .oneshot(request("/register", [192, 0, 2, 1]))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    // クールダウン中は429と残り時間のRetry-Afterを返す
+    let res = app
+      .clone()
+      .oneshot(request("/register", [192, 0, 2, 1]))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = res.headers()[RETRY_AFTER]
+      .to_str()
+      .unwrap()
+      .parse()
+      .unwrap();
+    assert!((599..=600).contains(&retry_after), "{retry_after}");
+
+    // 別のIPからは登録できる
+    let res = app
+      .oneshot(request("/register", [192, 0, 2, 2]))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn failed_signup_does_not_start_cooldown(pool: PgPool) {
+    let app = app(pool, 600);
+    let res = app
+      .clone()
+      .oneshot(request("/register/fail", [192, 0, 2, 1]))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let res = app
+      .oneshot(request("/register", [192, 0, 2, 1]))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn signup_is_accepted_again_after_cooldown(pool: PgPool) {
+    let app = app(pool, 1);
+    for _ in 0..2 {
+      let res = app
+        .clone()
+        .oneshot(request("/register", [192, 0, 2, 1]))
+        .await
+        .unwrap();
+      assert_eq!(res.status(), StatusCode::CREATED);
+      tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    }
+  }
+}
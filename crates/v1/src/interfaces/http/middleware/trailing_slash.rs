@@ -0,0 +1,63 @@
+//! パス末尾のスラッシュの正規化
+//! `/register/`を`/register`として扱う。(ルート`/`はそのまま)
+//! ルーティング前に適用する必要があるため，ルータの外側に配置する。
+
+use axum::{
+  extract::Request,
+  http::{Uri, uri::PathAndQuery},
+  middleware::Next,
+  response::Response,
+};
+
+/// パス末尾のスラッシュを取り除いてから後続の処理を行う。
+pub async fn trim(mut req: Request, next: Next) -> Response {
+  if let Some(uri) = trimmed(req.uri()) {
+    *req.uri_mut() = uri;
+  }
+  next.run(req).await
+}
+
+/// 末尾のスラッシュを取り除いたURIを返す。(変更が無い場合はNone)
+fn trimmed(uri: &Uri) -> Option<Uri> {
+  let path = uri.path();
+  let trimmed = path.trim_end_matches('/');
+  if trimmed.len() == path.len() {
+    return None;
+  }
+  let trimmed = if trimmed.is_empty() { "/" } else { trimmed };
+  if trimmed == path {
+    return None;
+  }
+
+  let path_and_query = match uri.query() {
+    Some(q) => format!("{trimmed}?{q}"),
+    None => trimmed.to_owned(),
+  };
+  let mut parts = uri.clone().into_parts();
+  parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+  Uri::from_parts(parts).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn trim_str(uri: &str) -> String {
+    let uri: Uri = uri.parse().unwrap();
+    trimmed(&uri).unwrap_or(uri).to_string()
+  }
+
+  #[test]
+  fn trims_trailing_slashes_and_keeps_query() {
+    assert_eq!(trim_str("/register/"), "/register");
+    assert_eq!(trim_str("/users/abc//"), "/users/abc");
+    assert_eq!(trim_str("/search/?q=x"), "/search?q=x");
+  }
+
+  #[test]
+  fn leaves_root_and_canonical_paths_unchanged() {
+    assert_eq!(trim_str("/"), "/");
+    assert_eq!(trim_str("//"), "/");
+    assert_eq!(trim_str("/register"), "/register");
+  }
+}
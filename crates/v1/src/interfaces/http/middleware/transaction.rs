@@ -0,0 +1,168 @@
+//! リクエスト単位のトランザクション
+//! --------------------------------------------------------------
+//! ・リクエストの開始時にトランザクションを開始し，`RequestTx`としてハンドラに渡す
+//! ・レスポンスが2xxの場合はコミットし，それ以外はロールバックする
+//! ・複数のリポジトリ呼び出しを1つのトランザクションで行うルートに`route_layer`で適用する
+//! ・リクエストに処理期限が設定されている場合は，残り時間を`statement_timeout`に設定する
+//! --------------------------------------------------------------
+
+use crate::{
+  infra::pg::{deadline::Deadline, user_repo::PgTx},
+  interfaces::http::error::{AppError, AppResult},
+};
+use axum::{
+  extract::{FromRequestParts, Request, State},
+  http::request::Parts,
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedMappedMutexGuard, OwnedMutexGuard};
+use tracing as log;
+
+/// リクエスト単位で共有するトランザクション
+/// ミドルウェアがレスポンスの確定後に取り出して終了させるため，ハンドラは所有権を持たない。
+#[derive(Clone)]
+pub struct RequestTx(Arc<Mutex<Option<PgTx<'static>>>>);
+
+/// `RequestTx::lock`で取得するトランザクションへの参照
+/// (借用を持たないため，await をまたいで保持してもハンドラのFutureはSendのまま)
+pub type TxGuard = OwnedMappedMutexGuard<Option<PgTx<'static>>, PgTx<'static>>;
+
+impl RequestTx {
+  /// トランザクションへの排他的な参照を取得する。
+  /// 参照を保持している間は同じリクエスト内の他の呼び出しが待機する。
+  pub async fn lock(&self) -> AppResult<TxGuard> {
+    OwnedMutexGuard::try_map(self.0.clone().lock_owned().await, Option::as_mut).map_err(|_| {
+      AppError::InternalServerError(Some("Request transaction already finished".into()))
+    })
+  }
+
+  /// トランザクションを取り出す。(以降の`lock`はエラーになる)
+  async fn take(&self) -> Option<PgTx<'static>> {
+    self.0.lock().await.take()
+  }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RequestTx {
+  type Rejection = AppError;
+
+  async fn from_request_parts(parts: &mut Parts, _state: &S) -> AppResult<Self> {
+    parts.extensions.get::<RequestTx>().cloned().ok_or_else(|| {
+      AppError::InternalServerError(Some("Transaction middleware not applied".into()))
+    })
+  }
+}
+
+/// トランザクションを開始して後続の処理を行い，レスポンスのステータスで終了させる。
+pub async fn transaction(State(pool): State<PgPool>, mut req: Request, next: Next) -> Response {
+  let mut tx = match pool.begin().await {
+    Ok(tx) => tx,
+    Err(e) => return AppError::from(e).into_response(),
+  };
+  let deadline = req.extensions().get::<Deadline>().copied();
+  if let Some(deadline) = deadline
+    && let Err(e) = deadline.apply_tx(&mut tx).await
+  {
+    return e.into_response();
+  }
+  let tx = RequestTx(Arc::new(Mutex::new(Some(tx))));
+  req.extensions_mut().insert(tx.clone());
+
+  let res = next.run(req).await;
+  let Some(inner) = tx.take().await else {
+    return res;
+  };
+  if res.status().is_success() {
+    // コミットに失敗した場合は変更が反映されていないため，成功として返さない
+    if let Err(e) = inner.commit().await {
+      return AppError::from(e).into_response();
+    }
+  } else if let Err(e) = inner.rollback().await {
+    // ロールバックに失敗しても接続の返却時に破棄される
+    log::warn!("Failed to roll back request transaction: {}", e);
+  }
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    domain::entity::user::{UserRole, UserStatus},
+    infra::pg::user_repo::PgUserRepository,
+    test_support::new_user,
+  };
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+  };
+  use tower::ServiceExt;
+
+  /// 2件のユーザーを登録した後，`fail`がtrueの場合はエラーを返す。
+  async fn insert_two(pool: PgPool, tx: RequestTx, fail: bool) -> AppResult<StatusCode> {
+    let repo = PgUserRepository::new(pool);
+    let users = ["tx_first", "tx_second"].map(|n| new_user(n, UserStatus::Active, UserRole::User));
+    for user in users {
+      repo.insert_tx(&mut *tx.lock().await?, &user).await?;
+    }
+    if fail {
+      return Err(AppError::Conflict(Some("failed after inserts".into())));
+    }
+    Ok(StatusCode::CREATED)
+  }
+
+  async fn ok_handler(Extension(pool): Extension<PgPool>, tx: RequestTx) -> AppResult<StatusCode> {
+    insert_two(pool, tx, false).await
+  }
+
+  async fn err_handler(Extension(pool): Extension<PgPool>, tx: RequestTx) -> AppResult<StatusCode> {
+    insert_two(pool, tx, true).await
+  }
+
+  fn app(pool: PgPool) -> Router {
+    Router::new()
+      .route("/ok", post(ok_handler))
+      .route("/err", post(err_handler))
+      .route_layer(middleware::from_fn_with_state(pool.clone(), transaction))
+      .layer(Extension(pool))
+  }
+
+  async fn user_count(pool: &PgPool) -> i64 {
+    sqlx::query_scalar!("SELECT COUNT(*) FROM users")
+      .fetch_one(pool)
+      .await
+      .unwrap()
+      .unwrap_or(0)
+  }
+
+  fn request(path: &str) -> Request {
+    Request::post(path).body(Body::empty()).unwrap()
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn error_response_rolls_back_all_writes(pool: PgPool) {
+    let res = app(pool.clone()).oneshot(request("/err")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    assert_eq!(user_count(&pool).await, 0);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn success_response_commits_all_writes(pool: PgPool) {
+    let res = app(pool.clone()).oneshot(request("/ok")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert_eq!(user_count(&pool).await, 2);
+  }
+
+  #[tokio::test]
+  async fn extractor_without_middleware_is_internal_error() {
+    let app = Router::new().route("/", get(|_tx: RequestTx| async { "unreachable" }));
+    let req = Request::get("/").body(Body::empty()).unwrap();
+    let res = app.oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+  }
+}
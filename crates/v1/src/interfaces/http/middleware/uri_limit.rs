@@ -0,0 +1,73 @@
+//! URIの長さの制限
+//! パス・クエリを含むURIが上限を超えたリクエストは，ルーティング前に414で返す。
+
+use crate::interfaces::http::error::AppError;
+use axum::{
+  extract::{Request, State},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+
+/// URIの長さ(バイト数)が`max`以下の場合のみ後続の処理を行う。
+pub async fn limit(State(max): State<usize>, req: Request, next: Next) -> Response {
+  let len = req.uri().path_and_query().map_or(0, |pq| pq.as_str().len());
+  if len > max {
+    return AppError::UriTooLong(Some(format!("URIは{max}バイト以下である必要があります。")))
+      .into_response();
+  }
+  next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{
+    Router,
+    body::Body,
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+  };
+  use tower::ServiceExt;
+
+  fn app(max: usize) -> Router {
+    Router::new()
+      .route("/items/{id}", post(|| async { "ok" }))
+      .route("/search", get(|| async { "ok" }))
+      .layer(middleware::from_fn_with_state(max, limit))
+  }
+
+  async fn status(app: Router, method: &str, uri: &str) -> StatusCode {
+    let req = Request::builder()
+      .method(method)
+      .uri(uri)
+      .body(Body::empty())
+      .unwrap();
+    app.oneshot(req).await.unwrap().status()
+  }
+
+  #[tokio::test]
+  async fn rejects_over_length_uri() {
+    let long_id = "a".repeat(64);
+    assert_eq!(
+      status(app(32), "POST", &format!("/items/{long_id}")).await,
+      StatusCode::URI_TOO_LONG
+    );
+    let long_query = format!("/search?q={}", "x".repeat(64));
+    assert_eq!(
+      status(app(32), "GET", &long_query).await,
+      StatusCode::URI_TOO_LONG
+    );
+    // ルートが存在しない場合もルーティング前に拒否する
+    assert_eq!(
+      status(app(32), "GET", &format!("/missing/{long_id}")).await,
+      StatusCode::URI_TOO_LONG
+    );
+  }
+
+  #[tokio::test]
+  async fn accepts_uri_within_limit() {
+    assert_eq!(status(app(32), "POST", "/items/abc").await, StatusCode::OK);
+    assert_eq!(status(app(32), "GET", "/search?q=x").await, StatusCode::OK);
+  }
+}
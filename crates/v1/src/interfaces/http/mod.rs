@@ -1,3 +1,10 @@
+pub mod auth;
+pub mod client;
 pub mod dto;
 pub mod error;
 pub mod handler;
+pub mod json;
+pub mod middleware;
+pub mod pagination;
+pub mod precondition;
+pub mod router;
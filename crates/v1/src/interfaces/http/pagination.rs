@@ -0,0 +1,103 @@
+//! ページ分割したレスポンスのヘッダ
+//! --------------------------------------------------------------
+//! ・`Link`（RFC 8288）に前後のページのURLを`rel="next"`/`rel="prev"`で設定する
+//! ・`X-Total-Count`に絞り込み条件に一致する全件数を設定する
+//! ・URLはリクエストのパスとクエリを基に，カーソルのパラメータのみを差し替えて生成する
+//! --------------------------------------------------------------
+
+use crate::interfaces::http::dto::ApiJson;
+use axum::{
+  http::{HeaderName, HeaderValue, Uri, header::LINK},
+  response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+/// 全件数を返すヘッダ
+pub const X_TOTAL_COUNT: HeaderName = HeaderName::from_static("x-total-count");
+
+/// 次のページを指定するクエリパラメータ
+const NEXT_PARAM: &str = "cursor";
+/// 前のページを指定するクエリパラメータ
+const PREV_PARAM: &str = "before";
+
+/// ページ分割したレスポンス
+/// 本文は`ApiJson`と同じ形式で返し，ページの情報をヘッダに設定する。
+#[derive(Debug)]
+pub struct Paginated<T> {
+  data: T,
+  total: i64,
+  links: Vec<String>,
+}
+
+impl<T> Paginated<T> {
+  /// `uri`はリクエストのURI（前後のページのURLの生成に使用する）
+  pub fn new(
+    data: T,
+    uri: &Uri,
+    next_cursor: Option<&str>,
+    prev_cursor: Option<&str>,
+    total: i64,
+  ) -> Self {
+    let links = [
+      ("next", NEXT_PARAM, next_cursor),
+      ("prev", PREV_PARAM, prev_cursor),
+    ]
+    .into_iter()
+    .filter_map(|(rel, param, cursor)| {
+      cursor.map(|c| format!("<{}>; rel=\"{rel}\"", page_url(uri, param, c)))
+    })
+    .collect();
+    Self { data, total, links }
+  }
+}
+
+impl<T: Serialize> IntoResponse for Paginated<T> {
+  fn into_response(self) -> Response {
+    let mut response = ApiJson(self.data).into_response();
+    let headers = response.headers_mut();
+    headers.insert(X_TOTAL_COUNT, HeaderValue::from(self.total));
+    if !self.links.is_empty()
+      && let Ok(v) = HeaderValue::from_str(&self.links.join(", "))
+    {
+      headers.insert(LINK, v);
+    }
+    response
+  }
+}
+
+/// リクエストのクエリからカーソルのパラメータを除き，`param=cursor`を追加したURLを返す。
+fn page_url(uri: &Uri, param: &str, cursor: &str) -> String {
+  let mut query: Vec<String> = uri
+    .query()
+    .unwrap_or_default()
+    .split('&')
+    .filter(|pair| {
+      let name = pair.split_once('=').map_or(*pair, |(name, _)| name);
+      !pair.is_empty() && name != NEXT_PARAM && name != PREV_PARAM
+    })
+    .map(str::to_owned)
+    .collect();
+  query.push(format!("{param}={}", urlencoding::encode(cursor)));
+  format!("{}?{}", uri.path(), query.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn page_url_replaces_cursor_params() {
+    let uri: Uri = "/admin/sessions?user=abc&cursor=old&limit=2&before=old"
+      .parse()
+      .unwrap();
+    assert_eq!(
+      page_url(&uri, NEXT_PARAM, "1_a b"),
+      "/admin/sessions?user=abc&limit=2&cursor=1_a%20b"
+    );
+    let uri: Uri = "/admin/sessions".parse().unwrap();
+    assert_eq!(
+      page_url(&uri, PREV_PARAM, "1_x"),
+      "/admin/sessions?before=1_x"
+    );
+  }
+}
@@ -0,0 +1,168 @@
+//! 条件付きリクエスト（楽観的排他制御）
+//! --------------------------------------------------------------
+//! ・リソースの版は`updated_at`（マイクロ秒）を基にした`ETag`で表す
+//! ・更新時に`If-Match`（または`If-Unmodified-Since`）で取得時の版を指定できる
+//! ・指定した版と現在の版が異なる場合は412を返す
+//! --------------------------------------------------------------
+
+use crate::interfaces::http::error::{AppError, AppResult};
+use axum::{
+  extract::FromRequestParts,
+  http::{
+    HeaderMap, HeaderValue,
+    header::{IF_MATCH, IF_UNMODIFIED_SINCE},
+    request::Parts,
+  },
+};
+use chrono::{DateTime, Utc};
+use std::convert::Infallible;
+
+/// 更新日時からETag（強いエンティティタグ）を生成する。
+pub fn etag(updated_at: DateTime<Utc>) -> String {
+  format!("\"{}\"", updated_at.timestamp_micros())
+}
+
+/// ETagをヘッダの値に変換する。
+pub fn etag_header(updated_at: DateTime<Utc>) -> HeaderValue {
+  // 数字と`"`のみのため，変換に失敗しない
+  HeaderValue::from_str(&etag(updated_at)).expect("ETag is a valid header value")
+}
+
+/// 更新の事前条件
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Precondition {
+  /// 指定なし（常に更新する）
+  #[default]
+  None,
+  /// If-Match（いずれかのETagに一致する場合のみ更新する。`*`は任意の版に一致）
+  IfMatch(Vec<String>),
+  /// If-Unmodified-Since（指定日時以降に更新されていない場合のみ更新する）
+  UnmodifiedSince(DateTime<Utc>),
+}
+
+impl Precondition {
+  /// リクエストヘッダから生成する。
+  /// 両方を指定した場合はIf-Matchを優先し，日付として解釈できないIf-Unmodified-Sinceは無視する。(RFC 9110)
+  pub fn from_headers(headers: &HeaderMap) -> Self {
+    if let Some(value) = headers.get(IF_MATCH) {
+      let tags = value
+        .to_str()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_owned)
+        .collect();
+      return Self::IfMatch(tags);
+    }
+    headers
+      .get(IF_UNMODIFIED_SINCE)
+      .and_then(|v| v.to_str().ok())
+      .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+      .map_or(Self::None, |since| {
+        Self::UnmodifiedSince(since.with_timezone(&Utc))
+      })
+  }
+
+  /// 現在の版が事前条件を満たすか判定し，満たさない場合は412を返す。
+  /// (HTTP-dateは秒単位のため，If-Unmodified-Sinceは秒単位で比較する)
+  pub fn check(&self, updated_at: DateTime<Utc>) -> AppResult<()> {
+    let satisfied = match self {
+      Self::None => true,
+      Self::IfMatch(tags) => {
+        let current = etag(updated_at);
+        tags.iter().any(|t| t == "*" || *t == current)
+      }
+      Self::UnmodifiedSince(since) => updated_at.timestamp() <= since.timestamp(),
+    };
+    if satisfied {
+      Ok(())
+    } else {
+      Err(precondition_failed())
+    }
+  }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Precondition {
+  type Rejection = Infallible;
+
+  async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    Ok(Self::from_headers(&parts.headers))
+  }
+}
+
+/// 事前条件を満たさない場合のエラーを返す
+pub fn precondition_failed() -> AppError {
+  AppError::PreconditionFailed(Some(
+    "他の更新により内容が変更されています。最新の内容を取得してから再度お試しください。".into(),
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::TimeZone;
+
+  fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+      headers.insert(
+        axum::http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+        HeaderValue::from_str(value).unwrap(),
+      );
+    }
+    headers
+  }
+
+  fn updated_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 7, 1, 12, 0, 0).unwrap() + chrono::Duration::microseconds(123)
+  }
+
+  #[test]
+  fn if_match_compares_etags() {
+    let current = etag(updated_at());
+    assert_eq!(current, "\"1751371200000123\"");
+
+    let matching =
+      Precondition::from_headers(&headers(&[("if-match", &format!("\"1\", {current}"))]));
+    assert!(matching.check(updated_at()).is_ok());
+    assert!(
+      Precondition::from_headers(&headers(&[("if-match", "*")]))
+        .check(updated_at())
+        .is_ok()
+    );
+
+    // 古い版・弱いタグは一致しない
+    for stale in ["\"1751371200000122\"", "W/\"1751371200000123\""] {
+      let err = Precondition::from_headers(&headers(&[("if-match", stale)]))
+        .check(updated_at())
+        .unwrap_err();
+      assert!(matches!(err, AppError::PreconditionFailed(_)), "{stale}");
+    }
+  }
+
+  #[test]
+  fn if_unmodified_since_compares_seconds() {
+    let check = |date: &str| {
+      Precondition::from_headers(&headers(&[("if-unmodified-since", date)])).check(updated_at())
+    };
+    assert!(check("Tue, 01 Jul 2025 12:00:00 GMT").is_ok());
+    assert!(matches!(
+      check("Tue, 01 Jul 2025 11:59:59 GMT"),
+      Err(AppError::PreconditionFailed(_))
+    ));
+    // 解釈できない日付は無視する
+    assert!(check("yesterday").is_ok());
+
+    // If-Matchを優先する
+    let both = Precondition::from_headers(&headers(&[
+      ("if-match", "\"1\""),
+      ("if-unmodified-since", "Tue, 01 Jul 2025 12:00:00 GMT"),
+    ]));
+    assert!(both.check(updated_at()).is_err());
+    assert_eq!(
+      Precondition::from_headers(&HeaderMap::new()),
+      Precondition::None
+    );
+  }
+}
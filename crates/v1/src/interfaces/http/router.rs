@@ -0,0 +1,1358 @@
+//! ルーティング定義
+//! --------------------------------------------------------------
+//! ・サービスの初期化とExtensionへの注入
+//! ・ルートとミドルウェアの組立て
+//! --------------------------------------------------------------
+
+use crate::{
+  application::{
+    admin::service::AdminService,
+    maintenance::service::MaintenanceService,
+    user::{
+      service::UserService,
+      throttle::{LoginThrottle, RegistrationThrottle},
+    },
+  },
+  config::AppConfig,
+  domain::entity::session::SessionPolicy,
+  interfaces::http::{
+    auth::SessionCookie,
+    error::AppError,
+    handler,
+    middleware::{
+      access_log::{self, AccessLog},
+      concurrency::{self, ConcurrencyLimits},
+      cors::{self, CorsPolicy},
+      deadline, method_not_allowed, problem_json,
+      rate_limit::{self, RequestLimiter},
+      request_id,
+      signup_cooldown::{self, SignupCooldown},
+      trailing_slash, transaction, uri_limit,
+    },
+  },
+};
+use axum::{
+  Router,
+  extract::Extension,
+  http::Method,
+  middleware,
+  routing::{get, patch, post},
+};
+use chrono::Duration;
+use sqlx::PgPool;
+
+/// アプリケーションのルータを構築して返す。
+pub fn build_app(config: &AppConfig, pool: PgPool) -> Router {
+  // サービスの初期化
+  // ログインとロック解除で同じカウンタを参照するため，スロットルは共有する
+  let login_throttle = LoginThrottle::from_config(&config.rate_limit, pool.clone());
+  let svc = UserService::new(pool.clone())
+    .with_uniqueness_strategy(config.registration.uniqueness_strategy)
+    .with_randomart_source(config.randomart.source)
+    .with_randomart_format(config.randomart.response_format)
+    .with_session_policy(SessionPolicy::from_config(&config.session))
+    .with_login_throttle(login_throttle.clone())
+    .with_registration_throttle(RegistrationThrottle::from_config(
+      &config.rate_limit,
+      pool.clone(),
+    ))
+    .with_immutable_fields(config.validation.immutable_fields.clone())
+    .with_strict_username_check(config.registration.strict_username_check)
+    .with_form_nonce_ttl(config.registration.form_nonce.then(|| {
+      let secs = i64::try_from(config.registration.form_nonce_ttl_secs).unwrap_or(i64::MAX);
+      Duration::seconds(secs.min(i64::MAX / 1000))
+    }))
+    .with_verification_token_ttl({
+      let secs = i64::try_from(config.registration.verification_token_ttl_secs).unwrap_or(i64::MAX);
+      Duration::seconds(secs.min(i64::MAX / 1000))
+    });
+  let admin_svc = AdminService::new(pool.clone()).with_login_throttle(login_throttle);
+  let maintenance_svc = MaintenanceService::new(pool.clone(), &config.maintenance);
+
+  // 同時処理数・リクエスト数の制限対象となるルート
+  let limited = Router::new()
+    .route(
+      "/register",
+      post(handler::user::register_handler).layer(middleware::from_fn_with_state(
+        SignupCooldown::from_config(&config.registration, pool.clone())
+          .map(SignupCooldown::into_shared),
+        signup_cooldown::limit,
+      )),
+    )
+    .route(
+      "/register/nonce",
+      get(handler::user::register_nonce_handler),
+    )
+    .route(
+      "/register/schema",
+      get(handler::user::register_schema_handler),
+    )
+    .route("/verify-email", post(handler::user::verify_email_handler))
+    .route("/login", post(handler::user::login_handler))
+    .route("/logout", post(handler::user::logout_handler))
+    .route("/sessions", get(handler::user::own_sessions_handler))
+    .route(
+      "/username/available",
+      get(handler::user::username_available_handler),
+    )
+    .route(
+      "/users/{public_id}",
+      get(handler::user::get_user_handler).patch(handler::user::update_profile_handler),
+    )
+    .route(
+      "/users/{public_id}/status",
+      patch(handler::user::change_status_handler),
+    )
+    .route(
+      "/users/{public_id}/rotate-id",
+      post(handler::user::rotate_id_handler),
+    )
+    .route(
+      "/randomart/{public_id}",
+      get(handler::user::randomart_handler),
+    )
+    .route(
+      "/password/strength",
+      post(handler::user::password_strength_handler),
+    )
+    .route(
+      "/password/change",
+      post(handler::user::change_password_handler),
+    )
+    .route(
+      "/admin/users/{public_id}/auth",
+      get(handler::admin::auth_meta_handler),
+    )
+    .route(
+      "/admin/users/{public_id}/unlock",
+      post(handler::admin::unlock_handler).route_layer(middleware::from_fn_with_state(
+        pool.clone(),
+        transaction::transaction,
+      )),
+    )
+    .route(
+      "/admin/users/{public_id}/require-password-change",
+      post(handler::admin::require_password_change_handler).route_layer(
+        middleware::from_fn_with_state(pool.clone(), transaction::transaction),
+      ),
+    )
+    .route(
+      "/admin/users/status-bulk",
+      post(handler::admin::bulk_status_handler),
+    )
+    .route(
+      "/admin/randomart/verify",
+      post(handler::admin::verify_randomart_handler),
+    )
+    .route("/admin/sessions", get(handler::admin::sessions_handler))
+    .route(
+      "/admin/maintenance/randomart",
+      post(handler::admin::regenerate_randomart_handler),
+    )
+    .route(
+      "/admin/maintenance/email-canonical",
+      post(handler::admin::recanonicalize_emails_handler),
+    )
+    // レート制限はユーザーの識別でDBを参照するため，処理期限の内側に配置する
+    .layer(middleware::from_fn_with_state(
+      RequestLimiter::from_config(&config.rate_limit, pool.clone()).into_shared(),
+      rate_limit::limit,
+    ))
+    .layer(middleware::from_fn_with_state(
+      config.app.request_timeout(),
+      deadline::limit,
+    ))
+    .layer(middleware::from_fn_with_state(
+      ConcurrencyLimits::from_config(&config.app).into_shared(),
+      concurrency::limit,
+    ));
+
+  // 死活監視のルート
+  let health = Router::new()
+    .route("/healthz", get(handler::health::healthz_handler))
+    .route("/readyz", get(handler::health::readyz_handler))
+    .route("/health", get(handler::health::health_handler))
+    .route("/schema", get(handler::health::schema_handler));
+
+  // 軽量なルートは制限の対象外とする
+  let api = Router::new()
+    .route("/", get(root))
+    .merge(health.clone())
+    .merge(limited);
+  // メトリクスはAdmin以上のみが参照できる（死活監視とは異なり，health_at_rootでも直下に配置しない）
+  let api = if config.metrics.enabled {
+    api.route("/metrics", get(handler::health::metrics_handler))
+  } else {
+    api
+  };
+
+  // base_pathが設定されている場合は全てのルートをその配下に配置する
+  // (health_at_rootがtrueの場合は，死活監視のルートをルート直下にも配置する)
+  let base_path = config.app.base_path();
+  let routes = match (base_path.is_empty(), config.app.health_at_root) {
+    (true, _) => api,
+    (false, true) => Router::new().nest(base_path, api).merge(health),
+    (false, false) => Router::new().nest(base_path, api),
+  };
+
+  // URIの長さは全てのルートで制限する
+  let app = routes
+    .fallback(not_found)
+    .layer(Extension(svc))
+    .layer(Extension(admin_svc))
+    .layer(Extension(maintenance_svc))
+    .layer(Extension(pool))
+    .layer(Extension(config.health.clone()))
+    .layer(Extension(config.auth.clone()))
+    .layer(Extension(config.validation.clone()))
+    .layer(Extension(config.password.clone()))
+    .layer(Extension(SessionCookie::new(config.app.base_path())))
+    .layer(middleware::from_fn(method_not_allowed::to_json))
+    .layer(middleware::from_fn_with_state(
+      cors_policy(config).into_shared(),
+      cors::cors,
+    ))
+    .layer(middleware::from_fn_with_state(
+      config.app.max_uri_len,
+      uri_limit::limit,
+    ))
+    .layer(middleware::from_fn_with_state(
+      AccessLog {
+        log: config.log.access,
+        emit_response_time: config.app.emit_response_time,
+      },
+      access_log::log,
+    ));
+  // problem_jsonがtrueの場合は，全てのエラーをProblem Detailsの形式で返す
+  let app = if config.app.problem_json {
+    app.layer(middleware::from_fn(problem_json::to_problem_json))
+  } else {
+    app
+  };
+
+  // strict_trailing_slashがfalseの場合は`/register/`を`/register`として扱う
+  // (ルーティング前に正規化するため，外側のルータから委譲する)
+  let app = if config.app.strict_trailing_slash {
+    app
+  } else {
+    Router::new()
+      .fallback_service(app)
+      .layer(middleware::from_fn(trailing_slash::trim))
+  };
+  // アクセスログ・エラーログにリクエストIDを付与するため，末尾のスラッシュの正規化よりも外側に配置する
+  app.layer(middleware::from_fn(request_id::assign))
+}
+
+/// ブラウザからの呼び出しを許可するルートとメソッド
+/// 登録の無いルートはクロスオリジンでは呼び出せない
+fn cors_policy(config: &AppConfig) -> CorsPolicy {
+  CorsPolicy::new(&config.cors)
+    .with_base_path(config.app.base_path())
+    .route("/register", &[Method::POST])
+    .route("/register/nonce", &[Method::GET])
+    .route("/register/schema", &[Method::GET])
+    .route("/verify-email", &[Method::POST])
+    .route("/login", &[Method::POST])
+    .route("/logout", &[Method::POST])
+    .route("/sessions", &[Method::GET])
+    .route("/username/available", &[Method::GET])
+    .route("/users/{public_id}", &[Method::GET, Method::PATCH])
+    .route("/users/{public_id}/status", &[Method::PATCH])
+    .route("/users/{public_id}/rotate-id", &[Method::POST])
+    .route("/randomart/{public_id}", &[Method::GET])
+    .route("/password/strength", &[Method::POST])
+    .route("/password/change", &[Method::POST])
+    .route("/admin/users/{public_id}/auth", &[Method::GET])
+    .route("/admin/users/{public_id}/unlock", &[Method::POST])
+    .route(
+      "/admin/users/{public_id}/require-password-change",
+      &[Method::POST],
+    )
+    .route("/admin/users/status-bulk", &[Method::POST])
+    .route("/admin/randomart/verify", &[Method::POST])
+    .route("/admin/sessions", &[Method::GET])
+    .route("/admin/maintenance/randomart", &[Method::POST])
+    .route("/admin/maintenance/email-canonical", &[Method::POST])
+}
+
+/// 未登録のルートに対するハンドラー
+/// エラーレスポンスの形式を他のエラーと揃える
+async fn not_found() -> AppError {
+  AppError::NotFound(Some("リソースが見つかりません".into()))
+}
+
+/// rootハンドラー
+async fn root() -> String {
+  "Hello, world!".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::logger::ACCESS_TARGET;
+  use crate::{
+    domain::{
+      entity::{
+        session::Session,
+        user::{User, UserRole, UserStatus},
+      },
+      repository::{AuditLogRepository, UserAuthRepository},
+      value_obj::public_id::PublicId,
+    },
+    infra::pg::{
+      audit_log_repo::PgAuditLogRepository, session_repo::PgSessionRepository,
+      user_auth_repo::PgUserAuthRepository,
+    },
+    test_support::{PASSWORD, seed_user},
+  };
+  use axum::{
+    body::Body,
+    body::to_bytes,
+    http::{Request, StatusCode, header},
+  };
+  use chrono::{Duration, Utc};
+  use std::{
+    fmt,
+    sync::{Arc, Mutex},
+  };
+  use tower::ServiceExt;
+  use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+  };
+  use tracing_subscriber::{
+    Layer,
+    layer::{Context, SubscriberExt},
+  };
+
+  async fn post_register(strict: bool, pool: PgPool, uri: &str) -> StatusCode {
+    let mut config = AppConfig::new().unwrap();
+    config.app.strict_trailing_slash = strict;
+    let req = Request::post(uri)
+      .header(header::CONTENT_TYPE, "application/json")
+      .body(Body::from("{}"))
+      .unwrap();
+    build_app(&config, pool)
+      .oneshot(req)
+      .await
+      .unwrap()
+      .status()
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn strict_policy_rejects_trailing_slash(pool: PgPool) {
+    assert_eq!(
+      post_register(true, pool.clone(), "/register/").await,
+      StatusCode::NOT_FOUND
+    );
+    // 正規の形式はハンドラまで到達する（ボディ不正のため422）
+    assert_eq!(
+      post_register(true, pool, "/register").await,
+      StatusCode::UNPROCESSABLE_ENTITY
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn lenient_policy_accepts_both_forms(pool: PgPool) {
+    assert_eq!(
+      post_register(false, pool.clone(), "/register/").await,
+      StatusCode::UNPROCESSABLE_ENTITY
+    );
+    assert_eq!(
+      post_register(false, pool, "/register").await,
+      StatusCode::UNPROCESSABLE_ENTITY
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn request_id_is_returned_for_both_trailing_slash_policies(pool: PgPool) {
+    for strict in [true, false] {
+      let mut config = AppConfig::new().unwrap();
+      config.app.strict_trailing_slash = strict;
+      let req = Request::post("/register/")
+        .header("x-request-id", "slash-req-1")
+        .body(Body::empty())
+        .unwrap();
+      let res = build_app(&config, pool.clone()).oneshot(req).await.unwrap();
+      assert_eq!(
+        res.headers()["x-request-id"],
+        "slash-req-1",
+        "strict={strict}"
+      );
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn verify_email_route_activates_user(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "router_verify", UserStatus::Pending, UserRole::User).await;
+    let token = UserService::new(pool.clone())
+      .issue_verification_token(user.user_id)
+      .await
+      .unwrap()
+      .token;
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+    let verify = |token: &str| {
+      Request::post("/verify-email")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(format!(r#"{{"token":"{token}"}}"#)))
+        .unwrap()
+    };
+
+    let res = app.clone().oneshot(verify(&token)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["result"], "verified");
+
+    let res = app.oneshot(verify("unknown")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn login_session_is_usable_until_logout(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "router_login", UserStatus::Active, UserRole::User).await;
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+    let req = Request::post("/login")
+      .header(header::CONTENT_TYPE, "application/json")
+      .body(Body::from(format!(
+        r#"{{"user_name":"router_login","password":"{PASSWORD}"}}"#
+      )))
+      .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["public_id"], user.public_id.as_str());
+
+    let req = Request::patch(format!("/users/{}", user.public_id.as_str()))
+      .header(header::CONTENT_TYPE, "application/json")
+      .header(
+        header::AUTHORIZATION,
+        format!("Bearer {}", v["session_id"].as_str().unwrap()),
+      )
+      .body(Body::from("{}"))
+      .unwrap();
+    assert_eq!(
+      app.clone().oneshot(req).await.unwrap().status(),
+      StatusCode::OK
+    );
+
+    // ログアウト後のセッションは使用できない
+    let logout = || {
+      Request::post("/logout")
+        .header(
+          header::AUTHORIZATION,
+          format!("Bearer {}", v["session_id"].as_str().unwrap()),
+        )
+        .body(Body::empty())
+        .unwrap()
+    };
+    let res = app.clone().oneshot(logout()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let res = app.oneshot(logout()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn own_sessions_lists_device_metadata(pool: PgPool) {
+    seed_user(&pool, "sessions_owner", UserStatus::Active, UserRole::User).await;
+    seed_user(&pool, "sessions_other", UserStatus::Active, UserRole::User).await;
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+    let login = |user_name: &str, user_agent: &str| {
+      let req = Request::post("/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::USER_AGENT, user_agent)
+        .body(Body::from(format!(
+          r#"{{"user_name":"{user_name}","password":"{PASSWORD}"}}"#
+        )))
+        .unwrap();
+      let app = app.clone();
+      async move {
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        v["session_id"].as_str().unwrap().to_owned()
+      }
+    };
+    let laptop = login("sessions_owner", "laptop-browser/1.0").await;
+    login("sessions_owner", "phone-app/2.0").await;
+    login("sessions_other", "other-device/3.0").await;
+    let list = |bearer: Option<&str>| {
+      let mut req = Request::get("/sessions");
+      if let Some(token) = bearer {
+        req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+      }
+      app.clone().oneshot(req.body(Body::empty()).unwrap())
+    };
+
+    let res = list(None).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = list(Some(&laptop)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let sessions = v["sessions"].as_array().unwrap();
+    // 本人のセッションのみを新しい順に返す
+    let agents: Vec<_> = sessions
+      .iter()
+      .map(|s| s["user_agent"].as_str().unwrap())
+      .collect();
+    assert_eq!(agents, ["phone-app/2.0", "laptop-browser/1.0"]);
+    let current: Vec<_> = sessions
+      .iter()
+      .map(|s| s["current"].as_bool().unwrap())
+      .collect();
+    assert_eq!(current, [false, true]);
+    // Bearerトークンとして使用できる値は返さない
+    assert!(!body.windows(laptop.len()).any(|w| w == laptop.as_bytes()));
+    assert!(
+      sessions
+        .iter()
+        .all(|s| s["session_id"].as_str().unwrap().ends_with("-****"))
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn forced_password_change_session_is_limited_to_change(pool: PgPool) {
+    let (user, mut auth) =
+      seed_user(&pool, "forced_login", UserStatus::Active, UserRole::User).await;
+    auth.must_change_password = true;
+    PgUserAuthRepository::new(pool.clone())
+      .update(&auth)
+      .await
+      .unwrap();
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+    let login = |password: &str| {
+      Request::post("/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(format!(
+          r#"{{"user_name":"forced_login","password":"{password}"}}"#
+        )))
+        .unwrap()
+    };
+
+    let res = app.clone().oneshot(login(PASSWORD)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let cookie = res.headers()[header::SET_COOKIE].to_str().unwrap();
+    assert!(cookie.contains("HttpOnly"));
+    let cookie = cookie.split(';').next().unwrap().to_owned();
+    let sid = cookie.split_once('=').unwrap().1.to_owned();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["detail"], "PASSWORD_CHANGE_REQUIRED");
+
+    // パスワード変更用のセッションでは他の操作を行えない
+    let req = Request::patch(format!("/users/{}", user.public_id.as_str()))
+      .header(header::CONTENT_TYPE, "application/json")
+      .header(header::AUTHORIZATION, format!("Bearer {sid}"))
+      .body(Body::from("{}"))
+      .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let new_password = "Vq8#tR2!mZ5@kW9$";
+    let req = Request::post("/password/change")
+      .header(header::CONTENT_TYPE, "application/json")
+      .header(header::COOKIE, cookie)
+      .body(Body::from(format!(
+        r#"{{"current_password":"{PASSWORD}","new_password":"{new_password}"}}"#
+      )))
+      .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    // 変更後は通常のログインができる
+    let res = app.oneshot(login(new_password)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+
+  /// パスワード変更用のセッションのCookieはbase_pathの配下に限定する
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn password_change_cookie_is_scoped_to_base_path(pool: PgPool) {
+    let (_, mut auth) = seed_user(&pool, "cookie_path", UserStatus::Active, UserRole::User).await;
+    auth.must_change_password = true;
+    PgUserAuthRepository::new(pool.clone())
+      .update(&auth)
+      .await
+      .unwrap();
+
+    let mut config = AppConfig::new().unwrap();
+    for (base_path, expected) in [("", "Path=/;"), ("/api/", "Path=/api;")] {
+      config.app.base_path = base_path.into();
+      let req = Request::post(format!("{}/login", config.app.base_path()))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(format!(
+          r#"{{"user_name":"cookie_path","password":"{PASSWORD}"}}"#
+        )))
+        .unwrap();
+      let res = build_app(&config, pool.clone()).oneshot(req).await.unwrap();
+      assert_eq!(res.status(), StatusCode::FORBIDDEN, "{base_path}");
+      let cookie = res.headers()[header::SET_COOKIE].to_str().unwrap();
+      assert!(cookie.contains(expected), "{cookie}");
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn get_user_returns_profile_to_owner_and_staff(pool: PgPool) {
+    let (owner, _) = seed_user(&pool, "get_owner", UserStatus::Active, UserRole::User).await;
+    let (other, _) = seed_user(&pool, "get_other", UserStatus::Active, UserRole::User).await;
+    let (staff, _) = seed_user(&pool, "get_staff", UserStatus::Active, UserRole::Support).await;
+    let repo = PgSessionRepository::new(pool.clone());
+    let mut tokens = Vec::new();
+    for user in [&owner, &other, &staff] {
+      let session = Session::issue(
+        user.user_id,
+        Utc::now(),
+        &SessionPolicy::default(),
+        false,
+        None,
+        None,
+      );
+      repo.insert(&session).await.unwrap();
+      tokens.push(session.session_id.to_string());
+    }
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+    let send = |method: Method, path: String, token: &str| {
+      let req = Request::builder()
+        .method(method)
+        .uri(path)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap();
+      app.clone().oneshot(req)
+    };
+    let path = format!("/users/{}", owner.public_id.as_str());
+
+    let res = send(Method::GET, path.clone(), &tokens[0]).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let length = res.headers().get(header::CONTENT_LENGTH).cloned();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["user_name"], "get_owner");
+    assert!(v.get("password").is_none() && v.get("current_hash").is_none());
+
+    // HEADはGETと同じステータス・Content-Lengthで，ボディが空
+    let res = send(Method::HEAD, path.clone(), &tokens[0]).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get(header::CONTENT_LENGTH).cloned(), length);
+    assert!(
+      to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap()
+        .is_empty()
+    );
+
+    let res = send(Method::GET, path.clone(), &tokens[2]).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = send(Method::GET, path, &tokens[1]).await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = send(Method::GET, "/users/not-a-public-id".into(), &tokens[2])
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let unknown = format!("/users/{}", PublicId::new().as_str());
+    let res = send(Method::GET, unknown, &tokens[2]).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn patch_status_maps_transitions_to_statuses(pool: PgPool) {
+    let (admin, _) = seed_user(&pool, "patch_admin", UserStatus::Active, UserRole::Admin).await;
+    let (deleted, _) = seed_user(&pool, "patch_deleted", UserStatus::Deleted, UserRole::User).await;
+    let (active, _) = seed_user(&pool, "patch_active", UserStatus::Active, UserRole::User).await;
+    let session = Session::issue(
+      admin.user_id,
+      Utc::now(),
+      &SessionPolicy::default(),
+      false,
+      None,
+      None,
+    );
+    PgSessionRepository::new(pool.clone())
+      .insert(&session)
+      .await
+      .unwrap();
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+    let send = |user: &User, body: &str| {
+      let req = Request::patch(format!("/users/{}/status", user.public_id.as_str()))
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+          header::AUTHORIZATION,
+          format!("Bearer {}", session.session_id),
+        )
+        .body(Body::from(body.to_owned()))
+        .unwrap();
+      app.clone().oneshot(req)
+    };
+
+    let res = send(&deleted, r#"{"status":"active"}"#).await.unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let res = send(&active, r#"{"status":"banned"}"#).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let res = send(&active, r#"{"status":"deactivated"}"#).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["status"], "deactivated");
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn stale_if_match_update_is_rejected(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "etag_user", UserStatus::Active, UserRole::User).await;
+    let session = Session::issue(
+      user.user_id,
+      Utc::now(),
+      &SessionPolicy::default(),
+      false,
+      None,
+      None,
+    );
+    PgSessionRepository::new(pool.clone())
+      .insert(&session)
+      .await
+      .unwrap();
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+    let path = format!("/users/{}", user.public_id.as_str());
+    let bearer = format!("Bearer {}", session.session_id);
+    let patch = |etag: &str, body: &str| {
+      let req = Request::patch(path.clone())
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, bearer.clone())
+        .header(header::IF_MATCH, etag)
+        .body(Body::from(body.to_owned()))
+        .unwrap();
+      app.clone().oneshot(req)
+    };
+
+    let req = Request::get(path.clone())
+      .header(header::AUTHORIZATION, bearer.clone())
+      .body(Body::empty())
+      .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let fetched = res.headers()[header::ETAG].to_str().unwrap().to_owned();
+
+    let res = patch(&fetched, r#"{"email":"first@example.com"}"#)
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let updated = res.headers()[header::ETAG].to_str().unwrap().to_owned();
+    assert_ne!(updated, fetched);
+
+    // 取得後に他の更新があった版では更新できない
+    let res = patch(&fetched, r#"{"email":"second@example.com"}"#)
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let res = patch(&updated, r#"{"email":"second@example.com"}"#)
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn public_id_path_is_decoded_and_validated(pool: PgPool) {
+    let (user, _) = seed_user(&pool, "path_user", UserStatus::Active, UserRole::User).await;
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+    let id = user.public_id.as_str();
+    let get = |path: String| {
+      let app = app.clone();
+      async move {
+        let req = Request::get(path).body(Body::empty()).unwrap();
+        app.oneshot(req).await.unwrap().status()
+      }
+    };
+
+    // パーセントエンコードされた文字は復号して検証する
+    let (head, tail) = id.split_at(1);
+    let encoded = format!("%{:02X}{tail}", head.as_bytes()[0]);
+    assert_eq!(get(format!("/randomart/{encoded}")).await, StatusCode::OK);
+
+    for path in [
+      format!("/randomart/%20{id}"),
+      format!("/randomart/{id}%20"),
+      format!("/randomart/%FF{tail}"),
+    ] {
+      assert_eq!(get(path).await, StatusCode::BAD_REQUEST);
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn session_list_sets_link_and_total_count_headers(pool: PgPool) {
+    let (admin, _) = seed_user(&pool, "list_admin", UserStatus::Active, UserRole::Admin).await;
+    let (user, _) = seed_user(&pool, "list_user", UserStatus::Active, UserRole::User).await;
+    let repo = PgSessionRepository::new(pool.clone());
+    let policy = SessionPolicy::default();
+    let admin_session = Session::issue(admin.user_id, Utc::now(), &policy, false, None, None);
+    repo.insert(&admin_session).await.unwrap();
+    for age in 0..5 {
+      let created_at = Utc::now() - Duration::minutes(age);
+      let session = Session::issue(user.user_id, created_at, &policy, false, None, None);
+      repo.insert(&session).await.unwrap();
+    }
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+    let list = |uri: String| {
+      let app = app.clone();
+      let req = Request::get(uri)
+        .header(
+          header::AUTHORIZATION,
+          format!("Bearer {}", admin_session.session_id),
+        )
+        .body(Body::empty())
+        .unwrap();
+      async move {
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["x-total-count"], "5");
+        res
+          .headers()
+          .get(header::LINK)
+          .map(|v| v.to_str().unwrap().to_owned())
+      }
+    };
+    let target = |link: &str, rel: &str| -> String {
+      link
+        .split(", ")
+        .find(|l| l.ends_with(&format!("rel=\"{rel}\"")))
+        .and_then(|l| l.strip_prefix('<'))
+        .and_then(|l| l.split_once('>'))
+        .map(|(url, _)| url.to_owned())
+        .unwrap()
+    };
+
+    let first = format!("/admin/sessions?user={}&limit=2", user.public_id.as_str());
+    let link = list(first.clone()).await.unwrap();
+    assert!(!link.contains("rel=\"prev\""));
+    let next = target(&link, "next");
+    assert!(next.starts_with(&first) && next.contains("&cursor="));
+
+    // 2ページ目は前後のページへのリンクを持つ
+    let link = list(next).await.unwrap();
+    let prev = target(&link, "prev");
+    assert!(prev.starts_with(&first) && prev.contains("&before="));
+    let last = list(target(&link, "next")).await.unwrap();
+    assert!(!last.contains("rel=\"next\""));
+
+    // 前のページは1ページ目と同じく，次のページへのリンクのみを持つ
+    let link = list(prev).await.unwrap();
+    assert!(!link.contains("rel=\"prev\""));
+    assert!(link.contains("rel=\"next\""));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn session_list_never_exposes_session_ids(pool: PgPool) {
+    let (admin, _) = seed_user(&pool, "leak_admin", UserStatus::Active, UserRole::Admin).await;
+    let (user, _) = seed_user(&pool, "leak_user", UserStatus::Active, UserRole::User).await;
+    let repo = PgSessionRepository::new(pool.clone());
+    let policy = SessionPolicy::default();
+    let admin_session = Session::issue(admin.user_id, Utc::now(), &policy, false, None, None);
+    repo.insert(&admin_session).await.unwrap();
+    let mut secrets = vec![admin_session.session_id.to_string()];
+    for age in 0..3 {
+      let created_at = Utc::now() - Duration::minutes(age);
+      let session = Session::issue(user.user_id, created_at, &policy, false, None, None);
+      repo.insert(&session).await.unwrap();
+      secrets.push(session.session_id.to_string());
+    }
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+
+    // 1件ずつ次のページをたどり，全てのレスポンスを確認する
+    let mut uri = "/admin/sessions?limit=1".to_owned();
+    for _ in 0..4 {
+      let req = Request::get(&uri)
+        .header(
+          header::AUTHORIZATION,
+          format!("Bearer {}", admin_session.session_id),
+        )
+        .body(Body::empty())
+        .unwrap();
+      let res = app.clone().oneshot(req).await.unwrap();
+      assert_eq!(res.status(), StatusCode::OK);
+      let headers = format!("{:?}", res.headers());
+      let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+      let body = String::from_utf8(body.to_vec()).unwrap();
+      for secret in &secrets {
+        let simple = secret.replace('-', "");
+        for text in [&headers, &body] {
+          assert!(!text.contains(secret.as_str()) && !text.contains(&simple));
+        }
+      }
+      let v: serde_json::Value = serde_json::from_str(&body).unwrap();
+      let Some(cursor) = v["next_cursor"].as_str() else {
+        break;
+      };
+      uri = format!("/admin/sessions?limit=1&cursor={cursor}");
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn maintenance_routes_require_admin(pool: PgPool) {
+    let (admin, _) = seed_user(&pool, "maint_admin", UserStatus::Active, UserRole::Admin).await;
+    let (support, _) = seed_user(
+      &pool,
+      "maint_support",
+      UserStatus::Active,
+      UserRole::Support,
+    )
+    .await;
+    sqlx::query!(
+      "UPDATE users SET randomart = 'stale' WHERE user_id = $1",
+      support.user_id.as_i64()
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    let repo = PgSessionRepository::new(pool.clone());
+    let policy = SessionPolicy::default();
+    let mut tokens = Vec::new();
+    for user in [&admin, &support] {
+      let session = Session::issue(user.user_id, Utc::now(), &policy, false, None, None);
+      repo.insert(&session).await.unwrap();
+      tokens.push(format!("Bearer {}", session.session_id));
+    }
+    let app = build_app(&AppConfig::new().unwrap(), pool);
+    let send = |uri: &str, bearer: &str| {
+      let req = Request::post(uri)
+        .header(header::AUTHORIZATION, bearer)
+        .body(Body::empty())
+        .unwrap();
+      app.clone().oneshot(req)
+    };
+
+    for uri in [
+      "/admin/maintenance/randomart",
+      "/admin/maintenance/email-canonical",
+    ] {
+      let res = send(uri, &tokens[1]).await.unwrap();
+      assert_eq!(res.status(), StatusCode::FORBIDDEN, "{uri}");
+    }
+    let res = send("/admin/maintenance/randomart?batch_size=1", &tokens[0])
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+      (v["scanned"].as_u64(), v["updated"].as_u64()),
+      (Some(2), Some(1))
+    );
+
+    let res = send("/admin/maintenance/email-canonical", &tokens[0])
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn unlock_route_commits_or_rolls_back_request_transaction(pool: PgPool) {
+    let (support, _) = seed_user(&pool, "tx_support", UserStatus::Active, UserRole::Support).await;
+    let (user, mut auth) = seed_user(&pool, "tx_locked", UserStatus::Active, UserRole::User).await;
+    let auth_repo = PgUserAuthRepository::new(pool.clone());
+    let audit_repo = PgAuditLogRepository::new(pool.clone());
+    let session = Session::issue(
+      support.user_id,
+      Utc::now(),
+      &SessionPolicy::default(),
+      false,
+      None,
+      None,
+    );
+    PgSessionRepository::new(pool.clone())
+      .insert(&session)
+      .await
+      .unwrap();
+    let app = build_app(&AppConfig::new().unwrap(), pool.clone());
+    let unlock = || {
+      let req = Request::post(format!("/admin/users/{}/unlock", user.public_id.as_str()))
+        .header(
+          header::AUTHORIZATION,
+          format!("Bearer {}", session.session_id),
+        )
+        .body(Body::empty())
+        .unwrap();
+      app.clone().oneshot(req)
+    };
+
+    // 監査ログの記録に失敗した場合は，失敗回数のリセットも反映しない
+    auth.login_fail_times = 7;
+    auth_repo.update(&auth).await.unwrap();
+    sqlx::query("ALTER TABLE audit_logs ADD CONSTRAINT reject_insert CHECK (false) NOT VALID")
+      .execute(&pool)
+      .await
+      .unwrap();
+    let res = unlock().await.unwrap();
+    assert!(!res.status().is_success());
+    let stored = auth_repo.find(user.user_id).await.unwrap().unwrap();
+    assert_eq!(stored.login_fail_times, 7);
+
+    // 成功した場合は両方の変更をコミットする
+    sqlx::query("ALTER TABLE audit_logs DROP CONSTRAINT reject_insert")
+      .execute(&pool)
+      .await
+      .unwrap();
+    let res = unlock().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let stored = auth_repo.find(user.user_id).await.unwrap().unwrap();
+    assert_eq!(stored.login_fail_times, 0);
+    assert_eq!(
+      audit_repo.find_by_target(user.user_id).await.unwrap().len(),
+      1
+    );
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn unknown_path_returns_json_not_found(pool: PgPool) {
+    let config = AppConfig::new().unwrap();
+    let app = build_app(&config, pool);
+    let res = app
+      .clone()
+      .oneshot(Request::get("/no/such/path").body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/json");
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["detail"], "リソースが見つかりません");
+
+    // 登録済みのルートは影響を受けない
+    let res = app
+      .oneshot(Request::get("/healthz").body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn metrics_route_is_placed_only_when_enabled(pool: PgPool) {
+    let (admin, _) = seed_user(&pool, "metrics_admin", UserStatus::Active, UserRole::Admin).await;
+    let (support, _) = seed_user(
+      &pool,
+      "metrics_support",
+      UserStatus::Active,
+      UserRole::Support,
+    )
+    .await;
+    let repo = PgSessionRepository::new(pool.clone());
+    let mut tokens = Vec::new();
+    for user in [&admin, &support] {
+      let session = Session::issue(
+        user.user_id,
+        Utc::now(),
+        &SessionPolicy::default(),
+        false,
+        None,
+        None,
+      );
+      repo.insert(&session).await.unwrap();
+      tokens.push(format!("Bearer {}", session.session_id));
+    }
+    let get_metrics = |uri: &str, bearer: Option<&str>| {
+      let mut req = Request::get(uri);
+      if let Some(bearer) = bearer {
+        req = req.header(header::AUTHORIZATION, bearer);
+      }
+      req.body(Body::empty()).unwrap()
+    };
+
+    let mut config = AppConfig::new().unwrap();
+    let res = build_app(&config, pool.clone())
+      .oneshot(get_metrics("/metrics", Some(&tokens[0])))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    config.metrics.enabled = true;
+    let app = build_app(&config, pool.clone());
+    let res = app
+      .clone()
+      .oneshot(get_metrics("/metrics", None))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = app
+      .clone()
+      .oneshot(get_metrics("/metrics", Some(&tokens[1])))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // health_at_rootでも直下には配置しない
+    config.app.base_path = "/api".into();
+    config.app.health_at_root = true;
+    let prefixed = build_app(&config, pool);
+    let res = prefixed
+      .clone()
+      .oneshot(get_metrics("/healthz", None))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = prefixed
+      .clone()
+      .oneshot(get_metrics("/metrics", Some(&tokens[0])))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = prefixed
+      .clone()
+      .oneshot(get_metrics("/api/metrics", Some(&tokens[0])))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = app
+      .oneshot(get_metrics("/metrics", Some(&tokens[0])))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(
+      res.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain")
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("# TYPE vo_rejections_total counter"));
+
+    // OpenMetricsを要求された場合はexemplarを出力できる形式で返す
+    let mut req = get_metrics("/api/metrics", Some(&tokens[0]));
+    req.headers_mut().insert(
+      header::ACCEPT,
+      "application/openmetrics-text;version=1.0.0,text/plain;q=0.5"
+        .parse()
+        .unwrap(),
+    );
+    let res = prefixed.oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+      res.headers()[header::CONTENT_TYPE],
+      crate::utils::metrics::OPENMETRICS_CONTENT_TYPE
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.ends_with("# EOF\n"));
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn route_concurrency_override_applies_under_base_path(pool: PgPool) {
+    let mut config = AppConfig::new().unwrap();
+    // 全体の上限を0にし，上限を個別に指定したルートのみ受け付けることを確認する
+    config.app.max_concurrent_requests = 0;
+    config.app.route_concurrency = vec![crate::config::RouteConcurrency {
+      path: "/register/schema".into(),
+      max_concurrent_requests: 1,
+    }];
+    for base_path in ["", "/api"] {
+      config.app.base_path = base_path.into();
+      let app = build_app(&config, pool.clone());
+      let get = |path: &str| {
+        app.clone().oneshot(
+          Request::get(format!("{base_path}{path}"))
+            .body(Body::empty())
+            .unwrap(),
+        )
+      };
+      let res = get("/register/schema").await.unwrap();
+      assert_eq!(res.status(), StatusCode::OK, "{base_path}");
+      let res = get("/username/available?user_name=someone").await.unwrap();
+      assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE, "{base_path}");
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn wrong_method_returns_json_method_not_allowed(pool: PgPool) {
+    let config = AppConfig::new().unwrap();
+    let res = build_app(&config, pool)
+      .oneshot(Request::get("/register").body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(res.headers()[header::ALLOW], "POST");
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["status"], 405);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn register_schema_reflects_validation_config(pool: PgPool) {
+    let mut config = AppConfig::new().unwrap();
+    config.validation.username_min = 5;
+    config.validation.phone_format = "e164".into();
+    config.validation.phone_allowed_countries = vec!["JP".into()];
+    config.password.min_len = 12;
+    let res = build_app(&config, pool)
+      .oneshot(
+        Request::get("/register/schema")
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let field = |name: &str| {
+      v["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["name"] == name)
+        .unwrap()
+        .clone()
+    };
+
+    let user_name = field("user_name");
+    assert_eq!(user_name["required"], true);
+    assert_eq!(user_name["type"], "string");
+    assert_eq!(user_name["constraints"]["min_length"], 5);
+    assert_eq!(user_name["constraints"]["max_length"], 64);
+    assert!(user_name["hint"].as_str().unwrap().starts_with("5文字以上"));
+    assert_eq!(field("password")["constraints"]["min_length"], 12);
+    assert_eq!(
+      field("phone")["constraints"]["allowed_values"],
+      serde_json::json!(["JP"])
+    );
+    assert_eq!(field("birth_date")["constraints"]["min_age"], 13);
+    assert_eq!(field("email")["required"], false);
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn problem_json_sets_instance_to_request_path(pool: PgPool) {
+    let mut config = AppConfig::new().unwrap();
+    config.app.problem_json = true;
+    let app = build_app(&config, pool);
+    for (req, status) in [
+      (
+        Request::get("/admin/sessions").body(Body::empty()).unwrap(),
+        StatusCode::UNAUTHORIZED,
+      ),
+      (
+        Request::get("/register").body(Body::empty()).unwrap(),
+        StatusCode::METHOD_NOT_ALLOWED,
+      ),
+    ] {
+      let path = req.uri().path().to_owned();
+      let res = app.clone().oneshot(req).await.unwrap();
+      assert_eq!(res.status(), status);
+      assert_eq!(
+        res.headers()[header::CONTENT_TYPE],
+        problem_json::PROBLEM_JSON
+      );
+      let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+      let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+      assert_eq!(v["instance"], path);
+      assert_eq!(v["type"], "about:blank");
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn routes_resolve_under_base_path(pool: PgPool) {
+    let (admin, _) = seed_user(&pool, "prefix_admin", UserStatus::Active, UserRole::Admin).await;
+    let repo = PgSessionRepository::new(pool.clone());
+    let policy = SessionPolicy::default();
+    let session = Session::issue(admin.user_id, Utc::now(), &policy, false, None, None);
+    repo.insert(&session).await.unwrap();
+    let older = Utc::now() - Duration::minutes(1);
+    let other = Session::issue(admin.user_id, older, &policy, false, None, None);
+    repo.insert(&other).await.unwrap();
+
+    let mut config = AppConfig::new().unwrap();
+    config.app.base_path = "/api/v1/".into();
+    config.app.problem_json = true;
+    let send = |app: Router, method: Method, uri: &str| {
+      let req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+          header::AUTHORIZATION,
+          format!("Bearer {}", session.session_id),
+        )
+        .body(Body::from("{}"))
+        .unwrap();
+      async move { app.oneshot(req).await.unwrap() }
+    };
+    let app = build_app(&config, pool.clone());
+
+    // 全てのルートはbase_pathの配下で解決される
+    let res = send(app.clone(), Method::POST, "/api/v1/register").await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["instance"], "/api/v1/register");
+    let res = send(app.clone(), Method::POST, "/register").await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // Linkヘッダのリンクもbase_pathを含む
+    let res = send(app.clone(), Method::GET, "/api/v1/admin/sessions?limit=1").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let link = res.headers()[header::LINK].to_str().unwrap();
+    assert!(link.starts_with("</api/v1/admin/sessions?limit=1&cursor="));
+
+    // 死活監視のルートはhealth_at_rootがtrueの場合のみルート直下にも配置される
+    for (health_at_root, root_status) in [(true, StatusCode::OK), (false, StatusCode::NOT_FOUND)] {
+      config.app.health_at_root = health_at_root;
+      let app = build_app(&config, pool.clone());
+      let res = send(app.clone(), Method::GET, "/api/v1/healthz").await;
+      assert_eq!(res.status(), StatusCode::OK);
+      let res = send(app, Method::GET, "/healthz").await;
+      assert_eq!(res.status(), root_status);
+    }
+  }
+
+  /// アクセスログのイベントのフィールドを記録するレイヤー
+  struct AccessRecorder(Arc<Mutex<Vec<String>>>);
+
+  impl<S: Subscriber> Layer<S> for AccessRecorder {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+      struct Fields(String);
+      impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+          self.0.push_str(&format!("{}={value:?} ", field.name()));
+        }
+      }
+      if event.metadata().target() != ACCESS_TARGET {
+        return;
+      }
+      let mut fields = Fields(String::new());
+      event.record(&mut fields);
+      self.0.lock().unwrap().push(fields.0);
+    }
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn access_log_is_emitted_only_when_enabled(pool: PgPool) {
+    for enabled in [true, false] {
+      let mut config = AppConfig::new().unwrap();
+      config.log.access = enabled;
+      let app = build_app(&config, pool.clone());
+      let events = Arc::new(Mutex::new(Vec::new()));
+      let subscriber = tracing_subscriber::registry().with(AccessRecorder(events.clone()));
+      let guard = tracing::subscriber::set_default(subscriber);
+      let res = app
+        .oneshot(Request::get("/healthz").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+      drop(guard);
+      assert_eq!(res.status(), StatusCode::OK);
+
+      let events = events.lock().unwrap();
+      if enabled {
+        assert_eq!(events.len(), 1);
+        for field in [
+          "method=GET",
+          "path=/healthz",
+          "status=200",
+          "elapsed_ms=",
+          "request_id=",
+        ] {
+          assert!(events[0].contains(field), "{field} in {}", events[0]);
+        }
+      } else {
+        assert!(events.is_empty());
+      }
+    }
+  }
+}
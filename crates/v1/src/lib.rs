@@ -4,3 +4,6 @@ pub mod domain;
 pub mod infra;
 pub mod interfaces;
 pub mod utils;
+
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -1,27 +1,38 @@
 //! エントリーポイント
 //! --------------------------------------------------------------
 //! ・Config 読み込み & Logger 初期化
-//! ・PgPool 生成 → UserService へ注入
+//! ・PgPool 生成 → ルータへ注入
 //! ・Axum ルータを構築して起動
 //! --------------------------------------------------------------
 
-use axum::{
-  Router,
-  extract::Extension,
-  routing::{get, post},
-};
 use sqlx::postgres::PgPoolOptions;
 use std::net::{IpAddr, SocketAddr};
-use tokio::{net::TcpListener, signal};
+use tokio::signal;
 use tracing as log;
 use v1::{
-  application::user::service::UserService,
+  application::maintenance::service::MaintenanceService,
   config::AppConfig,
+  domain::{
+    clock_skew::ClockSkew,
+    password_policy::PasswordPolicy,
+    value_obj::{
+      email_address::EmailPolicy, normalized_string::TextPolicy, phone_number::PhonePolicy,
+      user_full_name::NamePolicy, user_name::UserNamePolicy,
+    },
+  },
+  infra::pg::schema::SchemaStatus,
   interfaces::http::{
+    dto::ResponseFormat,
     error::{AppError, AppResult},
-    handler,
+    router::build_app,
+  },
+  utils::{
+    listener,
+    logger::{InstanceTags, init_tracing},
+    metrics,
+    randomart::{self, SymbolMapping},
+    self_test, server,
   },
-  utils::logger::init_tracing,
 };
 
 #[tokio::main]
@@ -30,9 +41,30 @@ async fn main() -> AppResult<()> {
   let config = AppConfig::new()?;
 
   // ロギングの設定
-  init_tracing(&config.log);
+  init_tracing(&config.log, InstanceTags::from_config(&config.app));
   log::info!("Configuration loaded: version {}", config.app.version);
 
+  // 検証ルールの自己診断（矛盾する設定の場合は起動しない）
+  self_test::run(&config)?;
+
+  // ユーザー名・電話番号・氏名の検証方式，メールアドレスの重複判定の方式を設定
+  UserNamePolicy::from_config(&config.validation)?.install();
+  PhonePolicy::from_config(&config.validation)?.install();
+  NamePolicy::from_config(&config.validation)?.install();
+  TextPolicy::from_config(&config.validation).install();
+  EmailPolicy::from_config(&config.validation).install();
+  // パスワードの検証ポリシーを設定
+  PasswordPolicy::from_config(&config.password, &config.validation)?.install();
+  // 有効期限の判定で許容する時刻のずれを設定
+  ClockSkew::from_config(&config.security)?.install();
+  // ランダムアートのシンボルの変換方式を設定
+  SymbolMapping::from_config(&config.randomart)?.install();
+  randomart::install_max_input_bytes(&config.randomart);
+  // 正常時のレスポンスの形式を設定
+  ResponseFormat::from_config(&config.app).install();
+  // 検証で拒否した件数の記録の有無を設定
+  metrics::install(&config.metrics);
+
   // Postgres接続
   // URL
   let postgres_url = config.postgres_url();
@@ -45,15 +77,20 @@ async fn main() -> AppResult<()> {
     })?;
   log::info!("Connected to the postgres");
 
-  // リポジトリの初期化
-  let svc = UserService::new(postgres_pool.clone());
+  // スキーマの確認（未適用のマイグレーションがある場合は警告のみ出力する）
+  match SchemaStatus::check(&postgres_pool).await {
+    Ok(status) if status.is_up_to_date() => {
+      log::info!("Database schema version: {:?}", status.applied_version)
+    }
+    Ok(status) => log::warn!(missing = ?status.missing, "Database schema is behind the binary"),
+    Err(e) => log::warn!(error = ?e, "Failed to check the database schema"),
+  }
+
+  // 定期メンテナンス（個人情報の消去など）を起動
+  MaintenanceService::new(postgres_pool.clone(), &config.maintenance).spawn();
 
   // ルーティング定義
-  let app = Router::new()
-    .route("/", get(root))
-    .route("/register", post(handler::user::register_handler))
-    .layer(Extension(svc))
-    .layer(Extension(postgres_pool));
+  let app = build_app(&config, postgres_pool.clone());
 
   // サーバーのアドレスを指定
   let ip: IpAddr = config
@@ -64,32 +101,40 @@ async fn main() -> AppResult<()> {
   let address = SocketAddr::new(ip, config.app.port);
 
   // 指定したアドレスでTCPリスナーをバインド
-  let listener = TcpListener::bind(&address)
-    .await
-    .map_err(|e| AppError::InternalServerError(format!("Failed to bind: {}", e).into()))?;
+  // (backlog, reuseaddr, nodelay はConfigの値を適用する)
+  let listener = listener::bind(address, &config.app)?;
   log::info!("▶ Server running on http://{}", &address);
 
   // Axumサーバーを起動
-  axum::serve(listener, app.into_make_service())
-    .with_graceful_shutdown(shutdown_signal())
-    .await
-    .map_err(|e| {
-      AppError::InternalServerError(format!("Failed to start application: {}", e).into())
-    })?;
+  // (シャットダウン時は処理中のリクエストの完了を待ち，接続プールを閉じる)
+  server::serve(listener, app, postgres_pool, &config.app, shutdown_signal()).await?;
 
   Ok(())
 }
 
-/// rootハンドラー
-async fn root() -> String {
-  "Hello, world!".to_string()
-}
-
 /// サーバーのシャットダウン
+/// Ctrl+C（SIGINT）またはSIGTERM（コンテナのオーケストレータが送信する）を待機する
 async fn shutdown_signal() {
-  // Ctrl+C（SIGINT）シグナルを待機
-  signal::ctrl_c()
-    .await
-    .expect("Failed to install Ctrl+C handler.");
-  log::info!("Shutting down the server...");
+  let ctrl_c = async {
+    signal::ctrl_c()
+      .await
+      .expect("Failed to install Ctrl+C handler.");
+  };
+
+  #[cfg(unix)]
+  let terminate = async {
+    signal::unix::signal(signal::unix::SignalKind::terminate())
+      .expect("Failed to install SIGTERM handler.")
+      .recv()
+      .await;
+  };
+  // Unix以外ではCtrl+Cのみを待機する
+  #[cfg(not(unix))]
+  let terminate = std::future::pending::<()>();
+
+  let received = tokio::select! {
+    () = ctrl_c => "SIGINT",
+    () = terminate => "SIGTERM",
+  };
+  log::info!("Received {}. Shutting down the server...", received);
 }
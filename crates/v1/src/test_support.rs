@@ -0,0 +1,76 @@
+//! テスト用のデータ投入ヘルパー
+
+use crate::{
+  domain::{
+    entity::{
+      user::{User, UserRole, UserStatus},
+      user_auth::UserAuth,
+    },
+    value_obj::{
+      public_id::PublicId, user_id::UserId, user_name::UserName, user_password::UserPassword,
+    },
+  },
+  infra::pg::{user_auth_repo::PgUserAuthRepository, user_repo::PgUserRepository},
+  utils::{hashing::hashing, randomart::generate_randomart},
+};
+use chrono::Utc;
+use sqlx::PgPool;
+
+/// テストユーザーの平文パスワード
+pub const PASSWORD: &str = "Xk9#mP2$vL7qR4!w";
+
+/// 未登録のユーザーエンティティを生成する。(user_idは仮の値)
+pub fn new_user(user_name: &str, status: UserStatus, role: UserRole) -> User {
+  let now = Utc::now();
+  let public_id = PublicId::new();
+  User {
+    user_id: UserId::from_db(1).unwrap(),
+    randomart: generate_randomart(&public_id),
+    public_id,
+    user_name: UserName::new(user_name, true).unwrap().unwrap(),
+    full_name: None,
+    email: None,
+    recovery_email: None,
+    phone: None,
+    birth_date: None,
+    status,
+    role,
+    last_login_at: None,
+    created_at: now,
+    updated_at: now,
+  }
+}
+
+/// ユーザーと認証情報をDBに登録し，採番済みのエンティティを返す。
+pub async fn seed_user(
+  pool: &PgPool,
+  user_name: &str,
+  status: UserStatus,
+  role: UserRole,
+) -> (User, UserAuth) {
+  let now = Utc::now();
+  let mut user = new_user(user_name, status, role);
+  let new_id = PgUserRepository::new(pool.clone())
+    .insert_ntx(&user)
+    .await
+    .unwrap();
+  user.user_id = UserId::from_db(new_id).unwrap();
+
+  let auth = UserAuth {
+    user_id: user.user_id,
+    current_hash: UserPassword::from_hash(hashing(PASSWORD).unwrap()).unwrap(),
+    prev_hash1: None,
+    prev_hash2: None,
+    login_fail_times: 0,
+    locked_until: None,
+    must_change_password: false,
+    created_at: now,
+    updated_at: now,
+  };
+  PgUserAuthRepository::new(pool.clone())
+    .insert(&auth)
+    .await
+    .unwrap();
+
+  (user, auth)
+}
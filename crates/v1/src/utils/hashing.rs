@@ -1,4 +1,5 @@
 //! ハッシュ化・ハッシュ値検証を行う
+//! 新規のハッシュ化はArgon2のみ。移行元システムのbcryptハッシュは検証のみ行う。
 
 use crate::interfaces::http::error::{AppError, AppResult};
 use argon2::{
@@ -20,8 +21,40 @@ pub fn hashing(plain: &str) -> AppResult<String> {
   Ok(hash.to_string())
 }
 
+/// ハッシュ文字列の方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashScheme {
+  Argon2,
+  /// 移行元システムから取り込んだbcryptハッシュ（`$2a$`/`$2b$`/`$2y$`）
+  Bcrypt,
+}
+
+impl HashScheme {
+  /// ハッシュ文字列の接頭辞から方式を判定する。(bcrypt以外はArgon2として扱う)
+  pub fn detect(hashed: &str) -> Self {
+    if ["$2a$", "$2b$", "$2y$"]
+      .iter()
+      .any(|prefix| hashed.starts_with(prefix))
+    {
+      Self::Bcrypt
+    } else {
+      Self::Argon2
+    }
+  }
+
+  /// 検証に成功した時点でArgon2へ再ハッシュ化すべきかどうか
+  pub fn needs_rehash(self) -> bool {
+    self != Self::Argon2
+  }
+}
+
 /// 平文文字列とハッシュ文字列を検証する。
+/// bcryptハッシュの場合はbcryptで検証する。
 pub fn verify_hashed(plain: &str, hashed: &str) -> AppResult<()> {
+  if HashScheme::detect(hashed) == HashScheme::Bcrypt {
+    return verify_bcrypt(plain, hashed);
+  }
+
   let parsed = PasswordHash::new(hashed)
     .map_err(|e| AppError::UnprocessableContent(Some(format!("ハッシュ文字列が不正です: {e}"))))?;
 
@@ -37,6 +70,19 @@ pub fn verify_hashed(plain: &str, hashed: &str) -> AppResult<()> {
   }
 }
 
+/// bcryptハッシュを検証する。
+fn verify_bcrypt(plain: &str, hashed: &str) -> AppResult<()> {
+  match bcrypt::verify(plain, hashed) {
+    Ok(true) => Ok(()),
+    Ok(false) => Err(AppError::Unauthorized(Some(
+      "パスワードが一致しません。".into(),
+    ))),
+    Err(e) => Err(AppError::InternalServerError(Some(format!(
+      "Hash verify error: {e}"
+    )))),
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -52,4 +98,20 @@ mod tests {
     let hash = hashing("secret").unwrap();
     assert!(verify_hashed("wrong", &hash).is_err());
   }
+
+  #[test]
+  fn bcrypt_hash_is_verified_and_flagged_for_rehash() {
+    let hash = bcrypt::hash("secret", 4).unwrap();
+    assert_eq!(HashScheme::detect(&hash), HashScheme::Bcrypt);
+    assert!(HashScheme::detect(&hash).needs_rehash());
+    assert!(verify_hashed("secret", &hash).is_ok());
+    assert!(matches!(
+      verify_hashed("wrong", &hash),
+      Err(AppError::Unauthorized(_))
+    ));
+
+    let argon2 = hashing("secret").unwrap();
+    assert_eq!(HashScheme::detect(&argon2), HashScheme::Argon2);
+    assert!(!HashScheme::detect(&argon2).needs_rehash());
+  }
 }
@@ -0,0 +1,94 @@
+//! TCPリスナーを生成する。
+//! Configで指定したTCPオプション(nodelay, reuseaddr, backlog)を適用する。
+
+use crate::{
+  config::App,
+  interfaces::http::error::{AppError, AppResult},
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// 指定したアドレスにバインドしたTCPリスナーを返す。
+pub fn bind(address: SocketAddr, config: &App) -> AppResult<TcpListener> {
+  let socket = build_socket(address, config)?;
+  TcpListener::from_std(socket.into())
+    .map_err(|e| AppError::InternalServerError(format!("Failed to bind: {}", e).into()))
+}
+
+/// TCPオプションを適用し，listen状態のソケットを返す。
+fn build_socket(address: SocketAddr, config: &App) -> AppResult<Socket> {
+  let to_err =
+    |e: std::io::Error| AppError::InternalServerError(format!("Failed to bind: {}", e).into());
+
+  let socket = Socket::new(
+    Domain::for_address(address),
+    Type::STREAM,
+    Some(Protocol::TCP),
+  )
+  .map_err(to_err)?;
+  socket
+    .set_reuse_address(config.reuse_address)
+    .map_err(to_err)?;
+  socket.set_tcp_nodelay(config.tcp_nodelay).map_err(to_err)?;
+  // tokioのリスナーに変換するため，ノンブロッキングにする
+  socket.set_nonblocking(true).map_err(to_err)?;
+  socket.bind(&address.into()).map_err(to_err)?;
+  // backlogはi32の範囲に丸める
+  let backlog = i32::try_from(config.listen_backlog).unwrap_or(i32::MAX);
+  socket.listen(backlog).map_err(to_err)?;
+  Ok(socket)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn app(tcp_nodelay: bool, reuse_address: bool) -> App {
+    App {
+      host: "127.0.0.1".into(),
+      port: 0,
+      version: "0.0.0".into(),
+      listen_backlog: 16,
+      tcp_nodelay,
+      reuse_address,
+      max_concurrent_requests: 1,
+      route_concurrency: Vec::new(),
+      max_uri_len: 8192,
+      shutdown_drain_secs: 30,
+      request_timeout_ms: 0,
+      strict_trailing_slash: true,
+      emit_response_time: false,
+      response_envelope: false,
+      problem_json: false,
+      base_path: String::new(),
+      health_at_root: true,
+      instance_id: None,
+      region: None,
+    }
+  }
+
+  fn loopback() -> SocketAddr {
+    "127.0.0.1:0".parse().unwrap()
+  }
+
+  #[test]
+  fn applies_configured_options() {
+    let socket = build_socket(loopback(), &app(true, true)).unwrap();
+    assert!(socket.reuse_address().unwrap());
+    assert!(socket.tcp_nodelay().unwrap());
+  }
+
+  #[test]
+  fn options_can_be_disabled() {
+    let socket = build_socket(loopback(), &app(false, false)).unwrap();
+    assert!(!socket.reuse_address().unwrap());
+    assert!(!socket.tcp_nodelay().unwrap());
+  }
+
+  #[tokio::test]
+  async fn bind_returns_tokio_listener() {
+    let listener = bind(loopback(), &app(true, true)).unwrap();
+    assert_ne!(listener.local_addr().unwrap().port(), 0);
+  }
+}
@@ -1,16 +1,86 @@
-use crate::config::Log;
+use crate::config::{App, Log, LogFormat};
+use std::sync::OnceLock;
+use tracing::{Event, Subscriber};
 use tracing_subscriber::{
-  fmt::{self, time::UtcTime},
+  EnvFilter, Layer,
+  filter::filter_fn,
+  fmt::{self, FmtContext, FormatEvent, FormatFields, MakeWriter, format::Writer, time::UtcTime},
   layer::SubscriberExt,
+  registry::LookupSpan,
   util::SubscriberInitExt,
 };
 
-pub fn init_tracing(config: &Log) {
-  // filter = Configで設定されているLogのレベル
-  let filter = config.level_filter();
+/// アクセスログのターゲット
+pub const ACCESS_TARGET: &str = "access";
 
+/// ログ・エラーレスポンスに付与するインスタンス情報
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstanceTags {
+  pub instance_id: Option<String>,
+  pub region: Option<String>,
+}
+
+impl InstanceTags {
+  /// Configの[app]から生成する。
+  pub fn from_config(app: &App) -> Self {
+    Self {
+      instance_id: app.instance_id.clone(),
+      region: app.region.clone(),
+    }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.instance_id.is_none() && self.region.is_none()
+  }
+
+  /// 値が設定されているタグを(キー, 値)の組で返す。
+  fn pairs(&self) -> impl Iterator<Item = (&'static str, &str)> {
+    [
+      ("instance_id", self.instance_id.as_deref()),
+      ("region", self.region.as_deref()),
+    ]
+    .into_iter()
+    .filter_map(|(k, v)| v.map(|v| (k, v)))
+  }
+}
+
+/// 起動時に設定したインスタンス情報
+static INSTANCE_TAGS: OnceLock<InstanceTags> = OnceLock::new();
+
+/// 起動時に設定したインスタンス情報を返す。(未設定の場合はNone)
+pub fn instance_tags() -> Option<&'static InstanceTags> {
+  INSTANCE_TAGS.get().filter(|t| !t.is_empty())
+}
+
+pub fn init_tracing(config: &Log, tags: InstanceTags) {
+  // filter = Configで設定されているLogのレベル（ターゲットごとの指定を含む）
+  // 読み込み時に検証済みのため，失敗した場合はレベルのみで絞り込む
+  let filter = config
+    .env_filter()
+    .unwrap_or_else(|_| EnvFilter::new(config.level.as_str()));
+  let _ = INSTANCE_TAGS.set(tags.clone());
+
+  tracing_subscriber::registry()
+    .with(fmt_layer(config.format, tags, std::io::stdout))
+    .with(filter)
+    .init();
+}
+
+/// 指定したフォーマットのfmtレイヤーを構築する。
+/// 各イベントにはインスタンス情報を付与する。
+fn fmt_layer<S, W>(
+  format: LogFormat,
+  tags: InstanceTags,
+  writer: W,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+  S: Subscriber + for<'a> LookupSpan<'a>,
+  W: for<'a> MakeWriter<'a> + Clone + Send + Sync + 'static,
+{
+  let access_writer = writer.clone();
   // ログのフォーマットを定義する
-  let fmt_layer = fmt::layer()
+  let layer = fmt::layer()
+    .with_writer(writer)
     .with_timer(UtcTime::rfc_3339())
     .with_level(true)
     .with_target(false)
@@ -18,16 +88,248 @@ pub fn init_tracing(config: &Log) {
     // .with_thread_names(true)
     ;
 
-  // Json，またはPrettyでフォーマットをする
-  if config.is_json() {
-    tracing_subscriber::registry()
-      .with(fmt_layer.json())
-      .with(filter)
-      .init();
-  } else {
-    tracing_subscriber::registry()
-      .with(fmt_layer.pretty())
-      .with(filter)
-      .init();
+  // Json，Pretty，またはCompactでフォーマットをする
+  match format {
+    // アクセスログはログ収集基盤で集計できるよう，フィールドをトップレベルのキーで出力する
+    // (リクエストIDはイベントのフィールドに含まれるため，spanは出力しない)
+    LogFormat::Json => {
+      let access = fmt::layer()
+        .with_writer(access_writer)
+        .with_timer(UtcTime::rfc_3339())
+        .with_level(true)
+        .with_target(false)
+        .json()
+        .flatten_event(true)
+        .with_current_span(false)
+        .with_span_list(false)
+        .map_event_format({
+          let tags = tags.clone();
+          |f| TaggedFormat::new(f, tags, true)
+        })
+        .with_filter(filter_fn(|m| m.target() == ACCESS_TARGET));
+      let rest = layer
+        .json()
+        .map_event_format(|f| TaggedFormat::new(f, tags, true))
+        .with_filter(filter_fn(|m| m.target() != ACCESS_TARGET));
+      access.and_then(rest).boxed()
+    }
+    LogFormat::Pretty => layer
+      .pretty()
+      .map_event_format(|f| TaggedFormat::new(f, tags, false))
+      .boxed(),
+    // Compactはログ収集基盤向けに1行・ANSIエスケープ無しで出力する
+    LogFormat::Compact => layer
+      .compact()
+      .with_ansi(false)
+      .map_event_format(|f| TaggedFormat::new(f, tags, false))
+      .boxed(),
+  }
+}
+
+/// イベントにインスタンス情報を定数フィールドとして付与するフォーマッタ
+pub struct TaggedFormat<F> {
+  inner: F,
+  tags: InstanceTags,
+  json: bool,
+}
+
+impl<F> TaggedFormat<F> {
+  pub fn new(inner: F, tags: InstanceTags, json: bool) -> Self {
+    Self { inner, tags, json }
+  }
+}
+
+impl<S, N, F> FormatEvent<S, N> for TaggedFormat<F>
+where
+  S: Subscriber + for<'a> LookupSpan<'a>,
+  N: for<'a> FormatFields<'a> + 'static,
+  F: FormatEvent<S, N>,
+{
+  fn format_event(
+    &self,
+    ctx: &FmtContext<'_, S, N>,
+    mut writer: Writer<'_>,
+    event: &Event<'_>,
+  ) -> std::fmt::Result {
+    if self.tags.is_empty() {
+      return self.inner.format_event(ctx, writer, event);
+    }
+
+    if !self.json {
+      // テキスト形式の場合は先頭に付与する
+      for (k, v) in self.tags.pairs() {
+        write!(writer, "{k}={v} ")?;
+      }
+      return self.inner.format_event(ctx, writer, event);
+    }
+
+    // JSON形式の場合はオブジェクトの先頭にキーを差し込む
+    let mut buf = String::new();
+    self.inner.format_event(ctx, Writer::new(&mut buf), event)?;
+    let Some(body) = buf.strip_prefix('{') else {
+      return writer.write_str(&buf);
+    };
+    writer.write_char('{')?;
+    for (k, v) in self.tags.pairs() {
+      let v = serde_json::to_string(v).map_err(|_| std::fmt::Error)?;
+      write!(writer, "\"{k}\":{v},")?;
+    }
+    writer.write_str(body)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::{
+    io,
+    sync::{Arc, Mutex},
+  };
+  use tracing_subscriber::filter::LevelFilter;
+
+  /// 出力を保持するWriter
+  #[derive(Clone, Default)]
+  struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+  impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  impl<'a> MakeWriter<'a> for Buffer {
+    type Writer = Self;
+    fn make_writer(&'a self) -> Self::Writer {
+      self.clone()
+    }
+  }
+
+  impl Buffer {
+    fn contents(&self) -> String {
+      String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+  }
+
+  fn tags() -> InstanceTags {
+    InstanceTags {
+      instance_id: Some("node-1".into()),
+      region: Some("ap-northeast-1".into()),
+    }
+  }
+
+  #[test]
+  fn json_events_carry_instance_tags() {
+    let buf = Buffer::default();
+    let subscriber = tracing_subscriber::registry().with(
+      fmt::layer()
+        .json()
+        .with_writer(buf.clone())
+        .map_event_format(|f| TaggedFormat::new(f, tags(), true)),
+    );
+    tracing::subscriber::with_default(subscriber, || tracing::info!(answer = 42, "hello"));
+
+    let line = buf.contents();
+    let v: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+    assert_eq!(v["instance_id"], "node-1");
+    assert_eq!(v["region"], "ap-northeast-1");
+    assert_eq!(v["fields"]["message"], "hello");
+  }
+
+  #[test]
+  fn text_events_carry_instance_tags() {
+    let buf = Buffer::default();
+    let subscriber = tracing_subscriber::registry().with(
+      fmt::layer()
+        .with_ansi(false)
+        .with_writer(buf.clone())
+        .map_event_format(|f| TaggedFormat::new(f, tags(), false)),
+    );
+    tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+
+    let line = buf.contents();
+    assert!(line.starts_with("instance_id=node-1 region=ap-northeast-1 "));
+    assert!(line.contains("hello"));
+  }
+
+  /// 指定したフォーマットのレイヤーで1件のイベントを出力する。
+  fn emit(format: LogFormat) -> String {
+    let buf = Buffer::default();
+    let subscriber =
+      tracing_subscriber::registry().with(fmt_layer(format, InstanceTags::default(), buf.clone()));
+    tracing::subscriber::with_default(subscriber, || tracing::info!(answer = 42, "hello"));
+    buf.contents()
+  }
+
+  #[test]
+  fn json_format_selects_json_layer() {
+    let out = emit(LogFormat::Json);
+    let v: serde_json::Value = serde_json::from_str(out.trim()).unwrap();
+    assert_eq!(v["fields"]["message"], "hello");
+    assert_eq!(v["fields"]["answer"], 42);
+  }
+
+  #[test]
+  fn json_access_events_have_top_level_fields() {
+    let buf = Buffer::default();
+    let subscriber =
+      tracing_subscriber::registry().with(fmt_layer(LogFormat::Json, tags(), buf.clone()));
+    tracing::subscriber::with_default(subscriber, || {
+      tracing::info!(
+        target: ACCESS_TARGET,
+        method = "GET",
+        path = "/healthz",
+        status = 200u16,
+        elapsed_ms = 1.5,
+        "request completed"
+      );
+      tracing::info!(answer = 42, "hello");
+    });
+
+    let out = buf.contents();
+    let lines: Vec<serde_json::Value> = out
+      .lines()
+      .map(|l| serde_json::from_str(l).unwrap())
+      .collect();
+    assert_eq!(lines.len(), 2, "{out}");
+    let access = &lines[0];
+    assert_eq!(access["method"], "GET");
+    assert_eq!(access["path"], "/healthz");
+    assert_eq!(access["status"], 200);
+    assert_eq!(access["elapsed_ms"], 1.5);
+    assert_eq!(access["instance_id"], "node-1");
+    assert!(access.get("fields").is_none());
+    // アクセスログ以外のイベントの形式は変わらない
+    assert_eq!(lines[1]["fields"]["answer"], 42);
+  }
+
+  #[test]
+  fn pretty_format_selects_pretty_layer() {
+    let out = emit(LogFormat::Pretty);
+    assert!(serde_json::from_str::<serde_json::Value>(out.trim()).is_err());
+    // Prettyはフィールドを複数行に分けて出力する
+    assert!(out.trim_end().lines().count() > 1, "{out}");
+    assert!(out.contains("hello"));
+  }
+
+  #[test]
+  fn compact_format_selects_compact_layer() {
+    let out = emit(LogFormat::Compact);
+    assert!(serde_json::from_str::<serde_json::Value>(out.trim()).is_err());
+    assert_eq!(out.trim_end().lines().count(), 1, "{out}");
+    assert!(out.contains("hello"));
+    assert!(out.contains("answer=42"));
+    assert!(out.contains("INFO"));
+    assert!(!out.contains('\x1b'), "{out:?}");
+  }
+
+  #[test]
+  fn compact_format_initializes_with_stdout() {
+    let subscriber = tracing_subscriber::registry()
+      .with(fmt_layer(LogFormat::Compact, tags(), std::io::stdout))
+      .with(LevelFilter::INFO);
+    tracing::subscriber::with_default(subscriber, || tracing::info!("compact"));
   }
 }
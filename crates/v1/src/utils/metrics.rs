@@ -0,0 +1,195 @@
+//! メトリクス
+//! --------------------------------------------------------------
+//! ・VOの検証で入力を拒否した件数を，対象(target)と理由(reason)のラベルごとに数える
+//! ・`GET /metrics`でPrometheusのテキスト形式で返す（Admin以上のセッションが必要）
+//! ・リクエストの処理中に記録した場合は，そのリクエストIDをexemplarとして保持する
+//!   （AcceptでOpenMetricsを要求された場合のみ出力し，ログ・トレースと突き合わせられるようにする）
+//! ・[metrics].enabledがfalseの場合は数えない（`/metrics`も配置しない）
+//! ・件数はプロセスごとに保持し，再起動で0に戻る
+//! --------------------------------------------------------------
+
+use crate::{config, interfaces::http::middleware::request_id};
+use std::{
+  collections::BTreeMap,
+  fmt::Write,
+  sync::{LazyLock, Mutex, OnceLock},
+};
+
+/// VOの検証で拒否した件数のメトリクス名
+pub const VO_REJECTIONS_TOTAL: &str = "vo_rejections_total";
+
+/// 起動時に設定した記録の有無
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// OpenMetricsのテキスト形式のContent-Type
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+  "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// VOの検証で拒否した件数（(target, reason) → 件数）
+static VO_REJECTIONS: LazyLock<Mutex<BTreeMap<(String, &'static str), Counter>>> =
+  LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// カウンタの値と，最後に加算したリクエストのID
+#[derive(Debug, Default)]
+struct Counter {
+  count: u64,
+  exemplar: Option<request_id::RequestId>,
+}
+
+/// Configの[metrics]に従って記録の有無を設定する。
+/// (2回目以降の呼び出しは無視される)
+pub fn install(config: &config::Metrics) {
+  let _ = ENABLED.set(config.enabled);
+}
+
+/// 記録が有効かを返す。(未設定の場合は無効)
+pub fn enabled() -> bool {
+  ENABLED.get().copied().unwrap_or(false)
+}
+
+/// VOの検証で拒否した件数を1件加算する。
+pub fn record_vo_rejection(target: &str, reason: &'static str) {
+  if !enabled() {
+    return;
+  }
+  let mut counters = VO_REJECTIONS.lock().unwrap_or_else(|e| e.into_inner());
+  let counter = counters.entry((target.to_owned(), reason)).or_default();
+  counter.count += 1;
+  // リクエストの外側で記録した場合は，以前のexemplarを残す
+  if let Some(id) = request_id::current() {
+    counter.exemplar = Some(id);
+  }
+}
+
+/// VOの検証で拒否した件数を返す。
+pub fn vo_rejections(target: &str, reason: &'static str) -> u64 {
+  let counters = VO_REJECTIONS.lock().unwrap_or_else(|e| e.into_inner());
+  counters
+    .get(&(target.to_owned(), reason))
+    .map_or(0, |c| c.count)
+}
+
+/// VOの検証で拒否した件数に付与したexemplar（最後に加算したリクエストのID）を返す。
+pub fn vo_rejection_exemplar(target: &str, reason: &'static str) -> Option<String> {
+  let counters = VO_REJECTIONS.lock().unwrap_or_else(|e| e.into_inner());
+  counters
+    .get(&(target.to_owned(), reason))
+    .and_then(|c| c.exemplar.as_ref())
+    .map(|id| id.as_str().to_owned())
+}
+
+/// 全てのメトリクスをPrometheusのテキスト形式で返す。
+pub fn render() -> String {
+  let counters = VO_REJECTIONS.lock().unwrap_or_else(|e| e.into_inner());
+  let mut out = format!(
+    "# HELP {VO_REJECTIONS_TOTAL} Inputs rejected by value object validation.\n\
+     # TYPE {VO_REJECTIONS_TOTAL} counter\n"
+  );
+  for ((target, reason), counter) in counters.iter() {
+    let _ = writeln!(
+      out,
+      "{VO_REJECTIONS_TOTAL}{{target=\"{}\",reason=\"{}\"}} {}",
+      escape_label(target),
+      escape_label(reason),
+      counter.count
+    );
+  }
+  out
+}
+
+/// 全てのメトリクスをOpenMetricsのテキスト形式で返す。
+/// リクエストIDを記録したカウンタには，exemplarとして付与する。
+pub fn render_openmetrics() -> String {
+  let counters = VO_REJECTIONS.lock().unwrap_or_else(|e| e.into_inner());
+  // OpenMetricsではカウンタのファミリー名に`_total`を含めない
+  let family = VO_REJECTIONS_TOTAL.trim_end_matches("_total");
+  let mut out = format!(
+    "# HELP {family} Inputs rejected by value object validation.\n\
+     # TYPE {family} counter\n"
+  );
+  for ((target, reason), counter) in counters.iter() {
+    let _ = write!(
+      out,
+      "{VO_REJECTIONS_TOTAL}{{target=\"{}\",reason=\"{}\"}} {}",
+      escape_label(target),
+      escape_label(reason),
+      counter.count
+    );
+    if let Some(id) = &counter.exemplar {
+      let _ = write!(out, " # {{request_id=\"{}\"}} 1", escape_label(id.as_str()));
+    }
+    out.push('\n');
+  }
+  out.push_str("# EOF\n");
+  out
+}
+
+/// ラベルの値に使用できない文字をエスケープする。
+fn escape_label(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn render_lists_counters_by_label() {
+    install(&config::Metrics { enabled: true });
+    let before = vo_rejections("metrics_test", "too_long");
+    record_vo_rejection("metrics_test", "too_long");
+    record_vo_rejection("metrics_test", "too_long");
+    let count = vo_rejections("metrics_test", "too_long");
+    assert_eq!(count, before + 2);
+
+    let text = render();
+    assert!(text.contains("# TYPE vo_rejections_total counter"));
+    assert!(text.contains(&format!(
+      "vo_rejections_total{{target=\"metrics_test\",reason=\"too_long\"}} {count}"
+    )));
+  }
+
+  #[tokio::test]
+  async fn sample_recorded_in_request_carries_request_id() {
+    use axum::{Router, body::Body, extract::Request, middleware, routing::get};
+    use tower::ServiceExt;
+
+    install(&config::Metrics { enabled: true });
+    let app = Router::new()
+      .route(
+        "/",
+        get(|| async { record_vo_rejection("metrics_exemplar_test", "too_long") }),
+      )
+      .layer(middleware::from_fn(request_id::assign));
+    let req = Request::get("/")
+      .header(request_id::X_REQUEST_ID, "exemplar-req-1")
+      .body(Body::empty())
+      .unwrap();
+    app.oneshot(req).await.unwrap();
+
+    assert_eq!(
+      vo_rejection_exemplar("metrics_exemplar_test", "too_long").as_deref(),
+      Some("exemplar-req-1")
+    );
+    // リクエストの外側での記録は件数のみ加算し，exemplarを残す
+    record_vo_rejection("metrics_exemplar_test", "too_long");
+    let count = vo_rejections("metrics_exemplar_test", "too_long");
+    let text = render_openmetrics();
+    assert!(text.contains("# TYPE vo_rejections counter"));
+    assert!(text.contains(&format!(
+      "vo_rejections_total{{target=\"metrics_exemplar_test\",reason=\"too_long\"}} {count} \
+       # {{request_id=\"exemplar-req-1\"}} 1\n"
+    )));
+    assert!(text.ends_with("# EOF\n"));
+    // Prometheusのテキスト形式にはexemplarを含めない
+    assert!(!render().contains("exemplar-req-1"));
+  }
+
+  #[test]
+  fn label_values_are_escaped() {
+    assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+  }
+}
@@ -1,6 +1,11 @@
 pub mod hashing;
+pub mod listener;
 pub mod logger;
+pub mod metrics;
 pub mod randomart;
 pub mod regex;
+pub mod self_test;
+pub mod server;
+pub mod shutdown;
 pub mod string;
 pub mod workspace;
@@ -1,29 +1,218 @@
 //! PublicID(nanoid)をハッシュ化して，その値を使用して
 //! Drunken Bishopアルゴリズムでランダムアートを生成する。
 
-use crate::domain::value_obj::public_id::PublicId;
+use crate::{
+  config::{Randomart, RandomartFormat},
+  domain::value_obj::public_id::PublicId,
+  interfaces::http::error::{AppError, AppResult},
+};
+use serde::Serialize;
 use sha3::{Digest, Sha3_384};
+use std::sync::OnceLock;
+
+/// カウント値 → シンボル変換表
+const SYMBOLS: [char; 15] = [
+  ' ', '.', 'o', '+', '=', '*', 'B', 'O', 'X', '@', '%', '&', '#', '/', '^',
+];
+
+/// カウント値からシンボルへの変換方式
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SymbolMapping {
+  /// カウント値nにn番目のシンボルを割り当てる（最後のシンボルで頭打ち）
+  #[default]
+  Linear,
+  /// カウント値が閾値以上になるごとにシンボルを1段階進める
+  Thresholds(Vec<u8>),
+}
+
+/// 起動時に設定した変換方式
+static SYMBOL_MAPPING: OnceLock<SymbolMapping> = OnceLock::new();
+
+/// 任意のデータから生成する場合の入力の最大バイト数の既定値
+const DEFAULT_MAX_INPUT_BYTES: usize = 256;
+
+/// 起動時に設定した入力の最大バイト数
+static MAX_INPUT_BYTES: OnceLock<usize> = OnceLock::new();
+
+impl SymbolMapping {
+  /// 既定値(線形)
+  const DEFAULT: SymbolMapping = SymbolMapping::Linear;
+
+  /// Configの[randomart]から生成する。
+  /// 閾値は0より大きく，狭義単調増加で，シンボルの段階数以内である必要がある。
+  pub fn from_config(config: &Randomart) -> AppResult<Self> {
+    let thresholds = &config.symbol_thresholds;
+    if thresholds.is_empty() {
+      return Ok(Self::Linear);
+    }
+    let increasing =
+      thresholds.first().is_some_and(|&t| t > 0) && thresholds.windows(2).all(|w| w[0] < w[1]);
+    if !increasing || thresholds.len() >= SYMBOLS.len() {
+      return Err(AppError::InternalServerError(Some(format!(
+        "Invalid randomart symbol_thresholds {thresholds:?}: must be strictly increasing, \
+         start above 0 and have at most {} entries",
+        SYMBOLS.len() - 1
+      ))));
+    }
+    Ok(Self::Thresholds(thresholds.clone()))
+  }
+
+  /// アプリケーション全体の変換方式として設定する。
+  /// (2回目以降の呼び出しは無視される)
+  pub fn install(self) {
+    let _ = SYMBOL_MAPPING.set(self);
+  }
+
+  /// 設定済みの変換方式を返す。(未設定の場合は既定値)
+  pub fn current() -> &'static SymbolMapping {
+    SYMBOL_MAPPING.get().unwrap_or(&Self::DEFAULT)
+  }
+
+  /// カウント値に対応するシンボルを返す。
+  fn symbol(&self, cnt: u8) -> char {
+    let idx = match self {
+      Self::Linear => cnt as usize,
+      Self::Thresholds(thresholds) => thresholds.iter().take_while(|&&t| t <= cnt).count(),
+    };
+    SYMBOLS[usize::min(idx, SYMBOLS.len() - 1)]
+  }
+}
 
 /// PublicIDからランダムアート文字列を生成する。
+/// シンボルの変換方式は起動時に設定した値を使用する。
 pub fn generate_randomart(public_id: &PublicId) -> String {
-  let public_id_str = public_id.as_str();
+  generate_randomart_with(public_id, SymbolMapping::current())
+}
+
+/// 指定したシンボルの変換方式でランダムアート文字列を生成する。
+pub fn generate_randomart_with(public_id: &PublicId, mapping: &SymbolMapping) -> String {
+  // 公開IDは長さが固定のため，入力長を検証しない
+  _render_fingerprint(&_fingerprint(public_id), mapping)
+}
 
-  let fingerprint = {
-    // state 再利用
-    let mut hasher = Sha3_384::new();
-    hasher.update(public_id_str.as_bytes());
-    hasher.finalize()
-  };
+/// Configの[randomart].max_input_bytesを，任意のデータから生成する場合の上限として設定する。
+/// (2回目以降の呼び出しは無視される)
+pub fn install_max_input_bytes(config: &Randomart) {
+  let _ = MAX_INPUT_BYTES.set(config.max_input_bytes);
+}
+
+/// 任意のデータから生成する場合の入力の最大バイト数を返す。(未設定の場合は既定値)
+pub fn max_input_bytes() -> usize {
+  MAX_INPUT_BYTES
+    .get()
+    .copied()
+    .unwrap_or(DEFAULT_MAX_INPUT_BYTES)
+}
+
+/// 任意のデータからランダムアート文字列を生成する。
+/// ハッシュ計算の負荷は入力長に比例するため，`max_input_bytes`を超える入力はハッシュ化せずに拒否する。
+pub fn generate_randomart_from_bytes(data: &[u8], mapping: &SymbolMapping) -> AppResult<String> {
+  let max = max_input_bytes();
+  if data.len() > max {
+    return Err(AppError::UnprocessableContent(Some(format!(
+      "ランダムアートの入力は{max}バイト以内である必要があります。"
+    ))));
+  }
+  Ok(_render_fingerprint(&_digest(data), mapping))
+}
 
+/// レスポンスで返すランダムアート
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum RandomartBody {
+  Text(String),
+  Lines(Vec<String>),
+  Grid(RandomartGrid),
+}
+
+/// 枠線を除いたランダムアートのマス目
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RandomartGrid {
+  /// 上辺のラベル（例：`[your_id]`）
+  pub top: String,
+  /// 下辺のラベル（例：`[SHA3-384]`）
+  pub bottom: String,
+  pub width: usize,
+  pub height: usize,
+  /// 行ごとのマス目（1マス1文字）
+  pub cells: Vec<Vec<char>>,
+}
+
+impl RandomartBody {
+  /// 描画済みのランダムアート文字列を指定した形式に変換する。
+  pub fn format(art: String, format: RandomartFormat) -> Self {
+    match format {
+      RandomartFormat::Text => Self::Text(art),
+      RandomartFormat::Lines => Self::Lines(art.lines().map(str::to_owned).collect()),
+      RandomartFormat::Grid => Self::Grid(RandomartGrid::parse(&art)),
+    }
+  }
+}
+
+impl RandomartGrid {
+  /// 描画済みのランダムアート文字列から枠線を取り除いて生成する。
+  fn parse(art: &str) -> Self {
+    let lines: Vec<&str> = art.lines().collect();
+    let label = |line: Option<&&str>| {
+      line
+        .map(|l| l.trim_matches(|c| c == '+' || c == '-'))
+        .unwrap_or_default()
+        .to_owned()
+    };
+    let body = lines
+      .get(1..lines.len().saturating_sub(1))
+      .unwrap_or_default();
+    let cells: Vec<Vec<char>> = body
+      .iter()
+      .map(|l| l.trim_matches('|').chars().collect())
+      .collect();
+    Self {
+      top: label(lines.first()),
+      bottom: label(lines.last()),
+      width: cells.first().map_or(0, Vec::len),
+      height: cells.len(),
+      cells,
+    }
+  }
+}
+
+/// ダイジェストからランダムアートを描画する
+fn _render_fingerprint(fingerprint: &[u8], mapping: &SymbolMapping) -> String {
   // Drunken Bishopグリッドを生成
-  let (grid, start, end) = _generate_drunken_bishop_grid(&fingerprint);
+  let (grid, start, end) = _generate_drunken_bishop_grid(fingerprint);
 
   // 上辺に表示する固定文字列
   let top_msg = "[your_id]";
   // 下辺に表示する固定文字列
   let bottom_msg = "[SHA3-384]";
 
-  _render_drunken_bishop_art(&grid, start, end, top_msg, bottom_msg)
+  _render_drunken_bishop_art(&grid, start, end, top_msg, bottom_msg, mapping)
+}
+
+/// 2つのPublicIDから生成されるランダムアートが一致するか判定する。
+/// (見た目の比較であり，厳密な比較には`randomart_fingerprint_hex`を使用する)
+pub fn randomart_matches(a: &PublicId, b: &PublicId) -> bool {
+  generate_randomart(a) == generate_randomart(b)
+}
+
+/// ランダムアートの元となるSHA3-384ダイジェストを16進文字列で返す。
+pub fn randomart_fingerprint_hex(public_id: &PublicId) -> String {
+  _fingerprint(public_id)
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect()
+}
+
+/// PublicIDのSHA3-384ダイジェストを計算する
+fn _fingerprint(public_id: &PublicId) -> Vec<u8> {
+  _digest(public_id.as_str().as_bytes())
+}
+
+/// SHA3-384ダイジェストを計算する
+fn _digest(data: &[u8]) -> Vec<u8> {
+  let mut hasher = Sha3_384::new();
+  hasher.update(data);
+  hasher.finalize().to_vec()
 }
 
 /// Drunken Bishop のグリッドを生成する
@@ -76,17 +265,13 @@ fn _render_drunken_bishop_art(
   end_position: (usize, usize),
   top_msg: &str,
   bottom_msg: &str,
+  mapping: &SymbolMapping,
 ) -> String {
   let rows = grid.len();
   let cols = if rows > 0 { grid[0].len() } else { 0 };
   let (sr, sc) = start_position;
   let (er, ec) = end_position;
 
-  // カウント値 → シンボル変換表
-  let symbols = [
-    ' ', '.', 'o', '+', '=', '*', 'B', 'O', 'X', '@', '%', '&', '#', '/', '^',
-  ];
-
   let mut lines = Vec::with_capacity(rows + 2);
 
   // 上辺
@@ -103,8 +288,7 @@ fn _render_drunken_bishop_art(
       } else if r == er && c == ec {
         line.push('E');
       } else {
-        let idx = usize::min(cnt as usize, symbols.len() - 1);
-        line.push(symbols[idx]);
+        line.push(mapping.symbol(cnt));
       }
     }
 
@@ -127,4 +311,132 @@ mod tests {
     let art = generate_randomart(&public_id);
     println!("\n{}\n", art);
   }
+
+  #[test]
+  fn identical_ids_match() {
+    let a = PublicId::from_seed(b"randomart-a");
+    let b = PublicId::from_seed(b"randomart-a");
+    assert!(randomart_matches(&a, &b));
+    assert_eq!(randomart_fingerprint_hex(&a), randomart_fingerprint_hex(&b));
+  }
+
+  #[test]
+  fn different_ids_differ_in_fingerprint() {
+    let a = PublicId::from_seed(b"randomart-a");
+    let b = PublicId::from_seed(b"randomart-b");
+    assert_ne!(randomart_fingerprint_hex(&a), randomart_fingerprint_hex(&b));
+    // アートは衝突し得るが，通常は異なる
+    assert!(!randomart_matches(&a, &b));
+  }
+
+  #[test]
+  fn fingerprint_hex_is_sha3_384() {
+    let hex = randomart_fingerprint_hex(&PublicId::new());
+    assert_eq!(hex.len(), 96);
+    assert!(
+      hex
+        .chars()
+        .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase())
+    );
+  }
+
+  fn thresholds(values: &[u8]) -> AppResult<SymbolMapping> {
+    SymbolMapping::from_config(&Randomart {
+      source: Default::default(),
+      symbol_thresholds: values.to_vec(),
+      max_input_bytes: DEFAULT_MAX_INPUT_BYTES,
+      response_format: Default::default(),
+    })
+  }
+
+  /// カウント値0〜15を並べた1行のグリッドを描画し，本体行を返す。
+  fn render_counts(mapping: &SymbolMapping) -> String {
+    let grid = vec![(0..16).collect::<Vec<u8>>()];
+    // 開始・終了位置はグリッドの外に置く
+    let art = _render_drunken_bishop_art(&grid, (1, 0), (1, 0), "", "", mapping);
+    art.lines().nth(1).unwrap().to_owned()
+  }
+
+  #[test]
+  fn linear_and_threshold_mappings_differ_on_crafted_grid() {
+    assert_eq!(render_counts(&SymbolMapping::Linear), "| .o+=*BOX@%&#/^^|");
+    let mapping = thresholds(&[1, 2, 4, 8]).unwrap();
+    assert_eq!(render_counts(&mapping), "| .oo++++========|");
+  }
+
+  #[test]
+  fn empty_thresholds_keep_linear_mapping() {
+    assert_eq!(thresholds(&[]).unwrap(), SymbolMapping::Linear);
+    let id = PublicId::from_seed(b"randomart-a");
+    assert_eq!(
+      generate_randomart(&id),
+      generate_randomart_with(&id, &SymbolMapping::Linear)
+    );
+  }
+
+  #[test]
+  fn non_monotonic_thresholds_are_rejected() {
+    assert!(thresholds(&[1, 4, 2]).is_err());
+    assert!(thresholds(&[1, 1]).is_err());
+    assert!(thresholds(&[0, 1]).is_err());
+    assert!(thresholds(&(1..=15).collect::<Vec<_>>()).is_err());
+    assert!(thresholds(&(1..=14).collect::<Vec<_>>()).is_ok());
+  }
+
+  #[test]
+  fn generic_generator_rejects_over_large_input() {
+    let mapping = SymbolMapping::Linear;
+    let max = max_input_bytes();
+    let err = generate_randomart_from_bytes(&vec![0u8; max + 1], &mapping).unwrap_err();
+    assert!(
+      matches!(err, AppError::UnprocessableContent(Some(m)) if m.contains(&format!("{max}バイト")))
+    );
+
+    // 上限以内の入力は公開IDと同じ方式で生成する
+    let id = PublicId::from_seed(b"randomart-a");
+    assert_eq!(
+      generate_randomart_from_bytes(id.as_str().as_bytes(), &mapping).unwrap(),
+      generate_randomart_with(&id, &mapping)
+    );
+    assert!(generate_randomart_from_bytes(&vec![0u8; max], &mapping).is_ok());
+  }
+
+  #[test]
+  fn max_input_bytes_default_matches_config() {
+    let config = crate::config::AppConfig::new().unwrap().randomart;
+    assert_eq!(config.max_input_bytes, DEFAULT_MAX_INPUT_BYTES);
+  }
+
+  #[test]
+  fn response_formats_serialize_as_documented() {
+    let art = generate_randomart(&PublicId::from_seed(b"randomart-format"));
+    let json = |format| serde_json::to_value(RandomartBody::format(art.clone(), format)).unwrap();
+
+    assert_eq!(json(RandomartFormat::Text), serde_json::json!(art));
+
+    let lines = json(RandomartFormat::Lines);
+    let lines = lines.as_array().unwrap();
+    assert_eq!(lines.len(), 11);
+    assert_eq!(lines[0], "+-------[your_id]-------+");
+    assert_eq!(lines[10], "+------[SHA3-384]-------+");
+
+    let grid = json(RandomartFormat::Grid);
+    assert_eq!(grid["top"], "[your_id]");
+    assert_eq!(grid["bottom"], "[SHA3-384]");
+    assert_eq!(
+      (grid["width"].as_u64(), grid["height"].as_u64()),
+      (Some(23), Some(9))
+    );
+    let cells = grid["cells"].as_array().unwrap();
+    assert_eq!(cells.len(), 9);
+    // 各マスは1文字の文字列で，開始位置は中央
+    assert_eq!(cells[4][11], "S");
+    let row: String = cells[0]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|c| c.as_str().unwrap())
+      .collect();
+    assert_eq!(format!("|{row}|"), art.lines().nth(1).unwrap());
+  }
 }
@@ -11,6 +11,11 @@ const ERROR_MESSAGE: &str = "正規表現のコンパイルに失敗しました
 pub static PHONE_NUMBER_REGEX: Lazy<Regex> =
   Lazy::new(|| Regex::new(r"^0\d{9,10}$").expect(ERROR_MESSAGE));
 
+/// 電話番号正規表現（E.164形式）
+/// 「先頭が+」，「国番号の先頭は0以外」，「+を除いて全体で8〜15桁」を想定する。
+pub static E164_PHONE_NUMBER_REGEX: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"^\+[1-9]\d{7,14}$").expect(ERROR_MESSAGE));
+
 /// eメールアドレス正規表現
 /// ローカルパート：「英数字/_/+/-/.」，「ドットは連続しない」，「先頭末尾にドット禁止」。
 /// ドメインラベル：「英数字/-」，「先頭末尾にハイフン禁止」，「末尾は必ずTLD」。
@@ -71,6 +76,30 @@ mod tests {
     }
   }
 
+  #[test]
+  fn test_e164_phone_number_regex() {
+    for number in ["+819012345678", "+14155552671", "+4420712345"] {
+      assert!(
+        E164_PHONE_NUMBER_REGEX.is_match(number),
+        "Should match: {}",
+        number
+      );
+    }
+    for number in [
+      "09012345678",
+      "+0812345678",
+      "+81-90-1234-5678",
+      "+1234567",
+      "+8190123456789012",
+    ] {
+      assert!(
+        !E164_PHONE_NUMBER_REGEX.is_match(number),
+        "Should not match: {}",
+        number
+      );
+    }
+  }
+
   #[test]
   fn test_email_address_regex_valid() {
     let valid_emails = [
@@ -0,0 +1,152 @@
+//! 起動時の検証ルールの自己診断
+//! --------------------------------------------------------------
+//! ・Configの[validation]から検証ポリシーを組み立てる
+//! ・境界値の入力でVOを生成し，ポリシーどおりに受理・拒否されるか確認する
+//! ・矛盾する設定の場合は，起動前に理由を示して失敗させる
+//! --------------------------------------------------------------
+
+use crate::{
+  config::AppConfig,
+  domain::value_obj::{
+    normalized_string::{NormalizedString, TextPolicy},
+    phone_number::{PhoneFormat, PhoneNumber, PhonePolicy, calling_code},
+    user_full_name::{NamePolicy, UserFullName},
+    user_name::UserNamePolicy,
+  },
+  interfaces::http::error::{AppError, AppResult},
+};
+
+/// Configの検証ルールが矛盾なく適用できるか確認する。
+pub fn run(config: &AppConfig) -> AppResult<()> {
+  UserNamePolicy::from_config(&config.validation)?;
+  let names = NamePolicy::from_config(&config.validation)?;
+  check_names(&names)?;
+  let phones = PhonePolicy::from_config(&config.validation)?;
+  check_phones(&phones)?;
+  check_text(&TextPolicy::from_config(&config.validation))?;
+  Ok(())
+}
+
+fn invalid(message: String) -> AppError {
+  AppError::InternalServerError(Some(format!("Validation self-test failed: {message}")))
+}
+
+/// 氏名の長さの上限・下限が境界値どおりに適用されるか確認する。
+fn check_names(policy: &NamePolicy) -> AppResult<()> {
+  // 姓の確認には，下限を満たす名を組み合わせる
+  let first = "a".repeat(policy.first_min.unwrap_or(1));
+  for (target, is_first, min, max) in [
+    ("name_first", true, policy.first_min, policy.first_max),
+    ("name_last", false, policy.last_min, policy.last_max),
+  ] {
+    if max > UserFullName::MAX_LEN {
+      return Err(invalid(format!(
+        "{target}_max={max} exceeds the column length {}",
+        UserFullName::MAX_LEN
+      )));
+    }
+
+    let name = |len: usize| {
+      let value = "a".repeat(len);
+      if is_first {
+        UserFullName::with_policy(value.as_str(), "", policy)
+      } else {
+        UserFullName::with_policy(first.as_str(), value.as_str(), policy)
+      }
+    };
+    let accepts = |len: usize| name(len).is_ok_and(|n| n.is_some());
+    if !accepts(max) || name(max + 1).is_ok() {
+      return Err(invalid(format!("{target} does not enforce max={max}")));
+    }
+    if let Some(min) = min
+      && (!accepts(min) || (min > 1 && name(min - 1).is_ok()))
+    {
+      return Err(invalid(format!("{target} does not enforce min={min}")));
+    }
+  }
+  Ok(())
+}
+
+/// 使用禁止文字を含む入力が，設定どおりに除去または拒否されるか確認する。
+fn check_text(policy: &TextPolicy) -> AppResult<()> {
+  // 私用領域の文字はNFKC正規化では変化しない使用禁止文字
+  let sample = "a\u{E000}b";
+  let applied = match NormalizedString::with_policy(sample, true, "self_test", None, None, policy) {
+    Ok(Some(text)) => policy.sanitize && text.as_str() == "ab",
+    Ok(None) => false,
+    Err(_) => !policy.sanitize,
+  };
+  if !applied {
+    return Err(invalid(format!(
+      "text policy does not apply sanitize_forbidden_chars={}",
+      policy.sanitize
+    )));
+  }
+  Ok(())
+}
+
+/// 電話番号の形式に合う代表値が受理されるか確認する。
+fn check_phones(policy: &PhonePolicy) -> AppResult<()> {
+  let samples = match policy.format {
+    PhoneFormat::Jp => vec!["09012345678".to_owned()],
+    PhoneFormat::E164 if policy.allowed_countries.is_empty() => vec!["+819012345678".to_owned()],
+    PhoneFormat::E164 => policy
+      .allowed_countries
+      .iter()
+      .filter_map(|c| calling_code(c))
+      .map(|code| format!("+{code}{}", "1".repeat(11 - code.len())))
+      .collect(),
+  };
+  for sample in samples {
+    if !PhoneNumber::with_policy(&sample, true, policy).is_ok_and(|p| p.is_some()) {
+      return Err(invalid(format!(
+        "phone policy rejects the sample '{sample}'"
+      )));
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn default_config_passes() {
+    run(&AppConfig::new().unwrap()).unwrap();
+  }
+
+  #[test]
+  fn min_greater_than_max_is_rejected() {
+    let mut config = AppConfig::new().unwrap();
+    config.validation.name_first_min = 10;
+    config.validation.name_first_max = 5;
+    let err = run(&config).unwrap_err();
+    assert!(matches!(err, AppError::InternalServerError(Some(m)) if m.contains("name_first")));
+  }
+
+  #[test]
+  fn max_over_column_length_is_rejected() {
+    let mut config = AppConfig::new().unwrap();
+    config.validation.name_last_max = 65;
+    let err = run(&config).unwrap_err();
+    assert!(matches!(err, AppError::InternalServerError(Some(m)) if m.contains("column length")));
+  }
+
+  #[test]
+  fn forbidden_char_handling_is_exercised() {
+    let mut config = AppConfig::new().unwrap();
+    for sanitize in [false, true] {
+      config.validation.sanitize_forbidden_chars = sanitize;
+      run(&config).unwrap();
+    }
+  }
+
+  #[test]
+  fn allowed_countries_are_exercised() {
+    let mut config = AppConfig::new().unwrap();
+    config.validation.phone_format = "e164".into();
+    config.validation.phone_allowed_countries = vec!["JP".into(), "HK".into(), "US".into()];
+    run(&config).unwrap();
+  }
+}
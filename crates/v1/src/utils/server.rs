@@ -0,0 +1,213 @@
+//! HTTPサーバーを起動する。
+//! --------------------------------------------------------------
+//! ・受け付けた接続にTCPオプション(nodelay)を適用する
+//! ・`signal`の完了後は新しい接続を受け付けず，処理中のリクエストの完了を待つ
+//! ・待機は[app].shutdown_drain_secsで打ち切る
+//! ・停止後はDBの接続プールを閉じる（打ち切ったリクエストのDB操作は503になる）
+//! --------------------------------------------------------------
+
+use crate::{
+  config::App,
+  interfaces::http::error::{AppError, AppResult},
+  utils::shutdown::{self, ShutdownFlag},
+};
+use axum::{Router, middleware, serve::ListenerExt};
+use sqlx::PgPool;
+use std::{future::Future, net::SocketAddr, time::Duration};
+use tokio::{net::TcpListener, sync::watch};
+use tracing as log;
+
+/// 接続プールを閉じる際に，貸し出し中の接続の返却を待つ上限
+const POOL_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `listener`で`app`を提供し，`signal`の完了後に停止する。
+/// 停止後は`pool`を閉じる。
+pub async fn serve<F>(
+  listener: TcpListener,
+  app: Router,
+  pool: PgPool,
+  config: &App,
+  signal: F,
+) -> AppResult<()>
+where
+  F: Future<Output = ()> + Send + 'static,
+{
+  let tcp_nodelay = config.tcp_nodelay;
+  let listener = listener.tap_io(move |tcp| {
+    // 受け付けた接続にもnodelayを適用する
+    if let Err(e) = tcp.set_nodelay(tcp_nodelay) {
+      log::warn!("Failed to set TCP_NODELAY: {}", e);
+    }
+  });
+
+  // シャットダウンの開始を記録し，待機時間の計測に通知する
+  // (状態はこのサーバーが受け付けたリクエストからのみ参照できる)
+  let flag = ShutdownFlag::default();
+  let app = app.layer(middleware::from_fn_with_state(
+    flag.clone(),
+    shutdown::scope,
+  ));
+  let (started_tx, mut started_rx) = watch::channel(false);
+  let signal = async move {
+    signal.await;
+    flag.begin();
+    let _ = started_tx.send(true);
+  };
+  let drain_timeout = Duration::from_secs(config.shutdown_drain_secs);
+  let drain_deadline = async move {
+    if started_rx.wait_for(|started| *started).await.is_ok() {
+      tokio::time::sleep(drain_timeout).await;
+    } else {
+      // シャットダウンが開始されない場合は打ち切らない
+      std::future::pending::<()>().await;
+    }
+  };
+
+  // 接続元IPアドレスをハンドラから参照できるようにする
+  let server = axum::serve(
+    listener,
+    app.into_make_service_with_connect_info::<SocketAddr>(),
+  )
+  .with_graceful_shutdown(signal);
+
+  let result = tokio::select! {
+    result = server => result.map_err(|e| {
+      AppError::InternalServerError(format!("Failed to start application: {}", e).into())
+    }),
+    _ = drain_deadline => {
+      log::warn!(
+        "Shutdown drain timed out after {:?}; abandoning in-flight requests",
+        drain_timeout
+      );
+      Ok(())
+    }
+  };
+
+  // 新しい接続の取得は直ちに失敗させ，貸し出し中の接続は返却を一定時間だけ待つ
+  if tokio::time::timeout(POOL_CLOSE_TIMEOUT, pool.close())
+    .await
+    .is_err()
+  {
+    log::warn!("Timed out waiting for database connections to be returned");
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{config::AppConfig, interfaces::http::router::build_app};
+  use axum::routing::get;
+  use sqlx::PgPool;
+  use std::{net::Ipv4Addr, sync::Arc};
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::{Notify, oneshot},
+    task::JoinHandle,
+  };
+
+  /// テスト専用ルートのパス
+  const SLOW_PATH: &str = "/__test/slow";
+
+  /// テスト専用の遅いルートを追加したアプリケーションを起動する。
+  /// リクエストの処理開始時に`started`へ通知し，`delay`の経過後に応答する。
+  async fn start(
+    pool: PgPool,
+    drain_secs: u64,
+    delay: Duration,
+    started: Arc<Notify>,
+  ) -> (SocketAddr, oneshot::Sender<()>, JoinHandle<AppResult<()>>) {
+    let mut config = AppConfig::new().unwrap();
+    config.app.shutdown_drain_secs = drain_secs;
+    let app = build_app(&config, pool.clone()).route(
+      SLOW_PATH,
+      get(move || async move {
+        started.notify_one();
+        tokio::time::sleep(delay).await;
+        if shutdown::is_shutting_down() {
+          "slow:shutting_down"
+        } else {
+          "slow"
+        }
+      }),
+    );
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = oneshot::channel::<()>();
+    let handle = tokio::spawn(async move {
+      serve(listener, app, pool, &config.app, async {
+        let _ = rx.await;
+      })
+      .await
+    });
+    (addr, tx, handle)
+  }
+
+  /// HTTP/1.1のGETを送信し，レスポンスの全文を返す。
+  async fn get_raw(addr: SocketAddr, path: &str) -> std::io::Result<String> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let req = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+    stream.write_all(req.as_bytes()).await?;
+    let mut buf = String::new();
+    stream.read_to_string(&mut buf).await?;
+    Ok(buf)
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn in_flight_request_completes_during_shutdown(pool: PgPool) {
+    let started = Arc::new(Notify::new());
+    let (addr, shutdown, server) = start(
+      pool.clone(),
+      30,
+      Duration::from_millis(500),
+      started.clone(),
+    )
+    .await;
+
+    let slow = tokio::spawn(get_raw(addr, SLOW_PATH));
+    started.notified().await;
+    shutdown.send(()).unwrap();
+
+    // 新しい接続は受け付けない
+    let mut refused = false;
+    for _ in 0..50 {
+      if TcpStream::connect(addr).await.is_err() {
+        refused = true;
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert!(refused, "new connections must be refused after shutdown");
+    assert!(!slow.is_finished());
+
+    // 処理中のリクエストは200で完了し，シャットダウン中であることを参照できる
+    let res = slow.await.unwrap().unwrap();
+    assert!(res.starts_with("HTTP/1.1 200"), "{res}");
+    assert!(res.ends_with("slow:shutting_down"), "{res}");
+    // 状態はサーバーごとに保持し，リクエストの外側には漏れない
+    assert!(!shutdown::is_shutting_down());
+
+    // 停止後は接続プールを閉じる
+    server.await.unwrap().unwrap();
+    assert!(pool.is_closed());
+  }
+
+  #[sqlx::test(migrations = "../../migrations")]
+  async fn drain_timeout_abandons_stuck_request(pool: PgPool) {
+    let started = Arc::new(Notify::new());
+    let (addr, shutdown, server) = start(pool, 0, Duration::from_secs(60), started.clone()).await;
+
+    let _stuck = tokio::spawn(get_raw(addr, SLOW_PATH));
+    started.notified().await;
+    shutdown.send(()).unwrap();
+
+    // 処理中のリクエストを待たずに停止する
+    tokio::time::timeout(Duration::from_secs(5), server)
+      .await
+      .expect("server must stop once the drain timeout elapses")
+      .unwrap()
+      .unwrap();
+  }
+}
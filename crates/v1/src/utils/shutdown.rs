@@ -0,0 +1,45 @@
+//! シャットダウン状態の共有
+//! シャットダウンの開始後に発生したエラーを，タイムアウト等と区別するために参照する。
+//! 状態はサーバーごとに保持し，`scope`を適用したリクエストの処理中のみ参照できる。
+
+use axum::{
+  extract::{Request, State},
+  middleware::Next,
+  response::Response,
+};
+use std::sync::{
+  Arc,
+  atomic::{AtomicBool, Ordering},
+};
+
+/// サーバーごとのシャットダウン状態
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownFlag(Arc<AtomicBool>);
+
+impl ShutdownFlag {
+  /// シャットダウンの開始を記録する。
+  pub fn begin(&self) {
+    self.0.store(true, Ordering::SeqCst);
+  }
+
+  /// シャットダウンを開始済みの場合はtrueを返す。
+  pub fn is_set(&self) -> bool {
+    self.0.load(Ordering::SeqCst)
+  }
+}
+
+tokio::task_local! {
+  /// 処理中のリクエストを受け付けたサーバーのシャットダウン状態
+  static CURRENT: ShutdownFlag;
+}
+
+/// リクエストの処理中に，受け付けたサーバーのシャットダウン状態を参照できるようにする。
+pub async fn scope(State(flag): State<ShutdownFlag>, req: Request, next: Next) -> Response {
+  CURRENT.scope(flag, next.run(req)).await
+}
+
+/// 処理中のリクエストを受け付けたサーバーがシャットダウンを開始済みの場合はtrueを返す。
+/// (`scope`の外側では常にfalse)
+pub fn is_shutting_down() -> bool {
+  CURRENT.try_with(ShutdownFlag::is_set).unwrap_or(false)
+}
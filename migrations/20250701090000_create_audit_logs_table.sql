@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS audit_logs (
+    audit_id BIGSERIAL,
+    actor_user_id BIGINT,
+    target_user_id BIGINT,
+    action VARCHAR(64) NOT NULL,
+    detail TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (audit_id),
+    FOREIGN KEY (actor_user_id) REFERENCES users(user_id) ON DELETE SET NULL,
+    FOREIGN KEY (target_user_id) REFERENCES users(user_id) ON DELETE SET NULL
+);
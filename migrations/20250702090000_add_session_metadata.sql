@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE sessions
+    ADD COLUMN IF NOT EXISTS user_agent VARCHAR(512),
+    ADD COLUMN IF NOT EXISTS ip VARCHAR(45);
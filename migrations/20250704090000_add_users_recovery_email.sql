@@ -0,0 +1,3 @@
+-- 予備のメールアドレス（パスワード再設定で主のメールアドレスが使えない場合の送信先）
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS recovery_email VARCHAR(254);
@@ -0,0 +1,7 @@
+-- 初回ログイン時のパスワード変更の強制（管理者が作成・移行したアカウント向け）
+ALTER TABLE user_auths
+    ADD COLUMN IF NOT EXISTS must_change_password BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- セッションの利用範囲（0: 通常，1: パスワード変更のみ）
+ALTER TABLE sessions
+    ADD COLUMN IF NOT EXISTS scope SMALLINT NOT NULL DEFAULT 0;
@@ -0,0 +1,8 @@
+-- 登録フォームの二重送信防止用のワンタイムトークン
+CREATE TABLE IF NOT EXISTS form_nonces (
+    nonce VARCHAR(64) PRIMARY KEY,
+    expires_at TIMESTAMPTZ NOT NULL,
+    consumed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_form_nonces_expires_at ON form_nonces (expires_at);
@@ -0,0 +1,7 @@
+-- 重複判定に使用するメールアドレスの正規形（タグを除いた形など）
+-- 入力された値は email に保持し，一意性は正規形で判定する
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS email_canonical VARCHAR(254);
+UPDATE users SET email_canonical = email WHERE email_canonical IS NULL;
+ALTER TABLE users
+    ADD CONSTRAINT users_email_canonical_key UNIQUE (email_canonical);
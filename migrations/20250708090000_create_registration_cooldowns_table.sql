@@ -0,0 +1,8 @@
+-- 接続元IPごとのアカウント登録のクールダウン
+-- expires_atまでは同じIPからの登録を受け付けない
+CREATE TABLE IF NOT EXISTS registration_cooldowns (
+    ip VARCHAR(45) PRIMARY KEY,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_registration_cooldowns_expires_at ON registration_cooldowns (expires_at);
@@ -0,0 +1,10 @@
+-- メールアドレス確認用のトークン
+-- 使用済みの行は有効期限まで残し，確認リンクの二重クリックを判別できるようにする
+CREATE TABLE IF NOT EXISTS verification_tokens (
+    token VARCHAR(64) PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_verification_tokens_expires_at ON verification_tokens (expires_at);
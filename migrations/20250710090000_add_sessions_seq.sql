@@ -0,0 +1,5 @@
+-- セッション一覧のカーソルに使用する連番
+-- session_idはBearerトークンのため，カーソルやURLに含めない
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS seq BIGINT GENERATED ALWAYS AS IDENTITY;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_created_at_seq ON sessions (created_at, seq);
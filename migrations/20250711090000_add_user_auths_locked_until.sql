@@ -0,0 +1,3 @@
+-- ログイン失敗によるロックの期限（NULLまたは過去の日時の場合はロックしていない）
+ALTER TABLE user_auths
+    ADD COLUMN IF NOT EXISTS locked_until TIMESTAMPTZ;